@@ -8,7 +8,7 @@ pub struct Cli {
     pub verbose: bool,
 
     /// Default: false. Input PNG may have errors. However, in case an error is encountered, the program will proceed by default till the end as long as it does not panic.
-    /// If true, the program will panic on the first error.
+    /// If true, the program will stop on the first error.
     pub fail_fast: bool,
 
     /// Each PNG Chunk has last 4 bytes as a CRC value, which is based on
@@ -18,9 +18,22 @@ pub struct Cli {
     /// Default: `true`. Will be `should_validate_crc` in parser.rs.
     pub validate_crc: bool,
 
-    /// Required. Path to the input PNG file.
+    /// Default: false. Only reads the header and walks the chunks, without decoding the pixels.
+    pub probe: bool,
+
+    /// Default: false. Set by the `optimize` command, which re-encodes the input losslessly
+    /// into the smallest PNG it can find and writes it to the output file.
+    pub optimize: bool,
+
+    /// Default: false. Drops the ancillary chunks that don't change the pixels when optimizing.
+    pub strip: bool,
+
+    /// Required. Path to the input PNG file, or `-` for stdin.
     // todo change to PathBuf
     input_file: Option<String>,
+
+    /// Required for `optimize`. Path the optimized PNG file is written to.
+    output_file: Option<String>,
 }
 
 /// There are better options out there,
@@ -32,7 +45,11 @@ impl Cli {
             verbose: false,
             fail_fast: false,
             validate_crc: true,
+            probe: false,
+            optimize: false,
+            strip: false,
             input_file: None,
+            output_file: None,
         };
     }
 
@@ -40,8 +57,7 @@ impl Cli {
         let args: Vec<String> = std::env::args().collect();
 
         let mut opts = Options::new();
-        opts.optflag("f", "fail-fast", "[Default]: false. Input PNG may have multiple errors. In case an error is encountered, the program will proceed by default till the end as long as it does not panic. If this flag is supplied, the program will stop upon first error. 
-        If true, the program will panic on the first error.");
+        opts.optflag("f", "fail-fast", "[Default]: false. Input PNG may have multiple errors. In case an error is encountered, the program will proceed by default till the end as long as it does not panic. If this flag is supplied, the program will stop upon first error.");
         opts.optflag(
             "v",
             "verbose",
@@ -58,6 +74,22 @@ impl Cli {
             "[Default]: true. Validates crc. Takes more time to finish the program.",
             "false | true",
         );
+        opts.optflag(
+            "p",
+            "probe",
+            "[Default]: false. Prints the header and the chunks of the image without decoding its pixels.",
+        );
+        opts.optflag(
+            "s",
+            "strip",
+            "[Default]: false. With optimize, drops ancillary chunks such as text, gamma and timestamps. tRNS chunk is always kept.",
+        );
+        opts.optopt(
+            "o",
+            "output",
+            "[Required for optimize] Path the optimized PNG file is written to.",
+            "PATH_TO_PNG_FILE",
+        );
         opts.optflag(
             "h",
             "help",
@@ -66,7 +98,7 @@ impl Cli {
         opts.reqopt(
             "i",
             "input",
-            "[Required] Path to the input PNG file.",
+            "[Required] Path to the input PNG file. Use - to read it from stdin.",
             "PATH_TO_PNG_FILE",
         );
 
@@ -97,6 +129,23 @@ impl Cli {
         self.verbose = matches.opt_present("v");
         self.debug = matches.opt_present("d");
         self.fail_fast = matches.opt_present("f");
+        self.probe = matches.opt_present("p");
+        self.strip = matches.opt_present("s");
+        self.optimize = match matches.free.first().map(|command| command.as_str()) {
+            Some("optimize") => true,
+            None => false,
+            Some(command) => {
+                println!("Unknown command: {}", command);
+                self.print_usage(opts);
+                std::process::exit(1);
+            }
+        };
+        self.output_file = matches.opt_str("o");
+        if self.optimize && self.output_file.is_none() {
+            println!("optimize needs an output file: -o PATH_TO_PNG_FILE");
+            self.print_usage(opts);
+            std::process::exit(1);
+        }
         match matches
             .opt_str("validate-crc")
             .unwrap_or("".to_string())
@@ -120,8 +169,15 @@ impl Cli {
             .expect("Input file must be initialized");
     }
 
+    pub fn get_output_file_path(&self) -> &str {
+        return &self
+            .output_file
+            .as_ref()
+            .expect("Output file must be initialized");
+    }
+
     fn print_usage(&self, opts: Options) {
-        let brief = format!("Usage: png-rs [-i|--input PATH_TO_PNG_FILE] [-h|--help] [-v|--verbose] [-d|--debug] [-f|--fail-fast] [--validate-crc false|true]");
+        let brief = format!("Usage: png-rs [-i|--input PATH_TO_PNG_FILE] [-h|--help] [-v|--verbose] [-d|--debug] [-f|--fail-fast] [-p|--probe] [--validate-crc false|true]\n       png-rs optimize [-i|--input PATH_TO_PNG_FILE] [-o|--output PATH_TO_PNG_FILE] [-s|--strip]");
         print!("{}", opts.usage(&brief));
     }
 }
@@ -1,25 +1,100 @@
 mod cli;
 
-use core::{byte_reader, decoder};
+use std::io::Read;
+
+use core::{byte_reader, decoder, errors, optimizer};
 
 fn main() {
     let mut cli = cli::Cli::new();
     cli.init();
+    if cli.optimize {
+        optimize(&cli);
+        return;
+    }
     let input_file_path = cli.get_input_file_path();
-    let mut byte_reader = byte_reader::ByteReader::new(
-        Some(&input_file_path),
-        byte_reader::ByteReaderMode::FILE,
-        None,
-    );
-    byte_reader.read_image();
+    let mut byte_reader = match input_file_path {
+        // read the PNG piped into the program
+        "-" => byte_reader::ByteReader::from_reader(std::io::stdin()),
+        _ => byte_reader::ByteReader::new(
+            Some(&input_file_path),
+            byte_reader::ByteReaderMode::FILE,
+            None,
+        ),
+    };
+    if let Err(code) = byte_reader.read_image() {
+        exit_with_error(code);
+    }
     let decode_options = decoder::PngDecoderOptions {
         fail_fast: cli.fail_fast,
         validate_crc: cli.validate_crc,
+        ..Default::default()
     };
     let mut decoder = decoder::PngDecoder::new(
         &mut byte_reader,
         &decode_options,
     );
-    let decoded_bytes = decoder.run();
+    if cli.probe {
+        let probed_image = decoder
+            .probe_chunks()
+            .unwrap_or_else(|code| exit_with_error(code));
+        println!("{:?}", probed_image.header);
+        for chunk in probed_image.chunks {
+            println!(
+                "{} ({} bytes) at {}",
+                chunk.chunk_type, chunk.length, chunk.byte_pos
+            );
+        }
+        return;
+    }
+    let decoded_image = decoder.run().unwrap_or_else(|code| exit_with_error(code));
     // println!("{:?}", decoded_bytes);
 }
+
+fn optimize(cli: &cli::Cli) {
+    let input_file_path = cli.get_input_file_path();
+    let mut png_bytes = vec![];
+    let read_result = match input_file_path {
+        "-" => std::io::stdin().read_to_end(&mut png_bytes).map(|_| ()),
+        _ => std::fs::File::open(input_file_path)
+            .and_then(|mut file| file.read_to_end(&mut png_bytes).map(|_| ())),
+    };
+    if let Err(err) = read_result {
+        exit_with_error(errors::PngDecodeErrorCode::_34(err.to_string()));
+    }
+
+    let optimizer_options = optimizer::OptimizerOptions {
+        strip_ancillary_chunks: cli.strip,
+    };
+    let optimized_image = optimizer::optimize(&png_bytes, &optimizer_options)
+        .unwrap_or_else(|code| exit_with_error(code));
+    if let Err(err) = std::fs::write(
+        cli.get_output_file_path(),
+        &optimized_image.png_bytes,
+    ) {
+        exit_with_error(errors::PngDecodeErrorCode::_34(err.to_string()));
+    }
+
+    println!(
+        "{} -> {} bytes ({} bytes saved)",
+        optimized_image.original_length,
+        optimized_image.png_bytes.len(),
+        optimized_image.bytes_saved()
+    );
+    if let Some(encoder_options) = optimized_image.encoder_options {
+        println!(
+            "filter strategy: {:?}, compression level: {}, reduced: {}",
+            encoder_options.filter_strategy,
+            encoder_options.compression_level,
+            encoder_options.reduce
+        );
+    }
+}
+
+/// The byte position is left out, because it's already printed along with every error that has been found
+fn exit_with_error(code: errors::PngDecodeErrorCode) -> ! {
+    eprintln!(
+        "{}",
+        errors::PngDecodeError::new(code, 0)
+    );
+    std::process::exit(1);
+}
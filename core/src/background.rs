@@ -0,0 +1,209 @@
+//! bKGD chunk, the color a viewer that can't show transparency should composite the image onto.
+//!
+//! http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.bKGD
+//!
+//! bKGD chunk must come after PLTE chunk and before IDAT chunks, and must not appear more than once.
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{
+    bitmap::{ColorSamples, PixelSamples, PixelType},
+    chunk_types,
+    common::{max_sample_value, scale_sample_to_u16},
+    errors::PngDecodeErrorCode,
+};
+
+/// Whether transparent pixels are composited onto a background color, which leaves every pixel opaque.
+/// See [crate::decoder::PngDecoderOptions::alpha_flattening]
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaFlattening {
+    /// Alpha is written out as it is
+    None,
+    /// Composites onto the color of bKGD chunk,
+    /// or onto [crate::decoder::PngDecoderOptions::background_color] when the image has no valid bKGD chunk
+    ImageBackground,
+    /// Composites onto [crate::decoder::PngDecoderOptions::background_color], even when the image has bKGD chunk
+    BackgroundColor,
+}
+
+/// Value of bKGD chunk, at the bit depth of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackgroundColor {
+    /// Index into the palette, for palette images
+    PaletteIndex(u8),
+    /// For greyscale images, with or without alpha
+    Grey(u16),
+    /// For truecolor images, with or without alpha
+    Rgb(u16, u16, u16),
+}
+
+impl BackgroundColor {
+    /// Opaque samples of the color. `None` when the palette doesn't have the entry
+    pub(crate) fn to_pixel_samples(
+        self,
+        pixel_type: PixelType,
+        palette: Option<&Vec<u8>>,
+    ) -> Option<PixelSamples> {
+        let sample_depth = pixel_type.bit_depth();
+        let (color, sample_depth) = match self {
+            BackgroundColor::PaletteIndex(index) => {
+                let entry = palette?.get(index as usize * 3..index as usize * 3 + 3)?;
+                // palette entries are always 8 bits
+                (
+                    ColorSamples::Rgb(
+                        entry[0] as u16,
+                        entry[1] as u16,
+                        entry[2] as u16,
+                    ),
+                    8,
+                )
+            }
+            BackgroundColor::Grey(grey) => (
+                ColorSamples::Grey(grey),
+                sample_depth,
+            ),
+            BackgroundColor::Rgb(r, g, b) => (
+                ColorSamples::Rgb(r, g, b),
+                sample_depth,
+            ),
+        };
+
+        Some(PixelSamples {
+            color,
+            alpha: max_sample_value(sample_depth),
+            sample_depth,
+        })
+    }
+}
+
+///    Palette index:  1 byte, for color type 3
+///    Grey:           2 bytes, for color types 0 and 4
+///    Red, green, blue: 2 bytes each, for color types 2 and 6
+///
+/// Greyscale and RGB values must fit into the bit depth of the image.
+/// * `palette_entries` - number of entries of PLTE chunk
+pub(crate) fn parse_bkgd_chunk(
+    chunk: &[u8],
+    pixel_type: PixelType,
+    palette_entries: usize,
+) -> Result<BackgroundColor, PngDecodeErrorCode> {
+    let chunk_type = chunk_types::ChunkTypes::bKGD;
+    let expected_length = match pixel_type {
+        _ if pixel_type.is_palette() => 1,
+        _ if pixel_type.is_greyscale() => 2,
+        _ => 6,
+    };
+    if chunk.len() != expected_length {
+        return Err(PngDecodeErrorCode::_47(
+            chunk_type.to_string(),
+            expected_length,
+            chunk.len(),
+        ));
+    }
+
+    if pixel_type.is_palette() {
+        if chunk[0] as usize >= palette_entries {
+            return Err(PngDecodeErrorCode::_51(
+                chunk_type.to_string(),
+                format!(
+                    "palette index {} is out of range of {} palette entries",
+                    chunk[0], palette_entries
+                ),
+            ));
+        }
+        return Ok(BackgroundColor::PaletteIndex(
+            chunk[0],
+        ));
+    }
+
+    let max_value = max_sample_value(pixel_type.bit_depth());
+    let samples: Vec<u16> = chunk
+        .chunks_exact(2)
+        .map(|sample_bytes| u16::from_be_bytes([sample_bytes[0], sample_bytes[1]]))
+        .collect();
+    if let Some(sample) = samples.iter().find(|sample| **sample > max_value) {
+        return Err(PngDecodeErrorCode::_51(
+            chunk_type.to_string(),
+            format!(
+                "{} doesn't fit into {} bits",
+                sample,
+                pixel_type.bit_depth()
+            ),
+        ));
+    }
+
+    Ok(match samples[..] {
+        [grey] => BackgroundColor::Grey(grey),
+        [r, g, b] => BackgroundColor::Rgb(r, g, b),
+        _ => unreachable!("length of bKGD chunk has been checked"),
+    })
+}
+
+/// `0xRRGGBB` as opaque 8-bit samples
+pub(crate) fn rgb_to_pixel_samples(rgb: u32) -> PixelSamples {
+    let [_, r, g, b] = rgb.to_be_bytes();
+    PixelSamples {
+        color: ColorSamples::Rgb(r as u16, g as u16, b as u16),
+        alpha: 255,
+        sample_depth: 8,
+    }
+}
+
+/// Composites `samples` onto the opaque `background`. The result is opaque, and 16 bits per sample
+/// unless both are at the same depth already
+pub(crate) fn composite(samples: PixelSamples, background: &PixelSamples) -> PixelSamples {
+    let max_value = max_sample_value(samples.sample_depth);
+    if samples.alpha == max_value {
+        return samples;
+    }
+
+    let (background, samples) = match background.sample_depth == samples.sample_depth {
+        true => (*background, samples),
+        false => (
+            to_16_bits(*background),
+            to_16_bits(samples),
+        ),
+    };
+    let max_value = max_sample_value(samples.sample_depth) as u32;
+    let alpha = samples.alpha as u32;
+    let blend = |foreground: u16, background: u16| {
+        ((foreground as u32 * alpha + background as u32 * (max_value - alpha) + max_value / 2)
+            / max_value) as u16
+    };
+    let color = match (
+        samples.color,
+        background.color,
+    ) {
+        (ColorSamples::Grey(grey), ColorSamples::Grey(background_grey)) => {
+            ColorSamples::Grey(blend(grey, background_grey))
+        }
+        _ => {
+            let (r, g, b) = samples.rgb();
+            let (background_r, background_g, background_b) = background.rgb();
+            ColorSamples::Rgb(
+                blend(r, background_r),
+                blend(g, background_g),
+                blend(b, background_b),
+            )
+        }
+    };
+
+    PixelSamples {
+        color,
+        alpha: max_value as u16,
+        sample_depth: samples.sample_depth,
+    }
+}
+
+fn to_16_bits(samples: PixelSamples) -> PixelSamples {
+    let scale = |sample: u16| scale_sample_to_u16(sample, samples.sample_depth);
+    PixelSamples {
+        color: match samples.color {
+            ColorSamples::Grey(grey) => ColorSamples::Grey(scale(grey)),
+            ColorSamples::Rgb(r, g, b) => ColorSamples::Rgb(scale(r), scale(g), scale(b)),
+        },
+        alpha: scale(samples.alpha),
+        sample_depth: 16,
+    }
+}
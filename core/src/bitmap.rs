@@ -3,8 +3,10 @@
 /// ##########################################
 use crate::{
     chunk_helpers,
+    common::{max_sample_value, scale_sample_to_u16, scale_sample_to_u8},
+    decoded_image::PixelFormat,
     errors::{self},
-    trns::TransparencyChunk, common::normalize_u16_to_u8,
+    trns::TransparencyChunk,
 };
 
 #[derive(Debug, Copy, Clone)]
@@ -71,257 +73,351 @@ impl PixelType {
 
         Ok(result)
     }
+
+    pub fn is_greyscale(&self) -> bool {
+        matches!(
+            self,
+            PixelType::Grayscale1
+                | PixelType::Grayscale2
+                | PixelType::Grayscale4
+                | PixelType::Grayscale8
+                | PixelType::Grayscale16
+                | PixelType::GrayscaleAlpha8
+                | PixelType::GrayscaleAlpha16
+        )
+    }
+
+    pub fn is_palette(&self) -> bool {
+        matches!(
+            self,
+            PixelType::Palette1 | PixelType::Palette2 | PixelType::Palette4 | PixelType::Palette8
+        )
+    }
+
+    /// Number of bits each sample (or palette index) takes up in the unfiltered data.
+    pub fn bit_depth(&self) -> u8 {
+        match self {
+            PixelType::Grayscale1 | PixelType::Palette1 => 1,
+            PixelType::Grayscale2 | PixelType::Palette2 => 2,
+            PixelType::Grayscale4 | PixelType::Palette4 => 4,
+            PixelType::Grayscale8
+            | PixelType::Rgb8
+            | PixelType::Palette8
+            | PixelType::GrayscaleAlpha8
+            | PixelType::RgbAlpha8 => 8,
+            PixelType::Grayscale16
+            | PixelType::Rgb16
+            | PixelType::GrayscaleAlpha16
+            | PixelType::RgbAlpha16 => 16,
+        }
+    }
 }
 
-pub fn to_rgba_pixel_bytes(
-    pixel_type: PixelType,
-    transparency_chunk: Option<&TransparencyChunk>,
-    palette_chunk: Option<&Vec<u8>>,
-    pixel_start_byte_position: usize,
-    unfiltered_data: &[u8],
-) -> Result<(u8, u8, u8, u8), errors::PngDecodeErrorCode> {
-    let pixel = match pixel_type {
-        PixelType::Grayscale1 => {
-            let byte = unfiltered_data[pixel_start_byte_position / 8];
-            let bit_offset = 7 - pixel_start_byte_position % 8;
-            let grayscale_val = (byte >> bit_offset) & 1;
+/// Color samples of a single pixel. Greyscale pixels keep their single sample,
+/// so that greyscale output formats never have to convert back from r, g, b.
+#[derive(Debug, Clone, Copy)]
+pub enum ColorSamples {
+    Grey(u16),
+    Rgb(u16, u16, u16),
+}
 
-            let alpha = match transparency_chunk {
-                Some(TransparencyChunk::Grayscale(transparent_val))
-                    if grayscale_val == *transparent_val =>
-                {
-                    0
-                }
-                _ => 255,
-            };
+/// Samples of a single pixel, with tRNS and PLTE already applied,
+/// but still at the depth they are stored in the image (see `sample_depth`).
+///
+/// Keeping the original depth around lets the caller decide how the samples are scaled,
+/// instead of always truncating 16-bit samples to 8 bits.
+#[derive(Debug, Clone, Copy)]
+pub struct PixelSamples {
+    pub color: ColorSamples,
+    /// Fully opaque pixels have the maximum value of `sample_depth`
+    pub alpha: u16,
+    /// 1, 2, 4, 8 or 16. Palette entries (and their alpha values from tRNS) are always 8 bits.
+    pub sample_depth: u8,
+}
 
-            let pixel_val = grayscale_val * 255;
+impl PixelSamples {
+    /// Luminance of the pixel, using the Rec. 709 coefficients (the same ones sRGB uses)
+    /// when the pixel is not greyscale already.
+    pub fn grey(self) -> u16 {
+        match self.color {
+            ColorSamples::Grey(grey) => grey,
+            ColorSamples::Rgb(r, g, b) => {
+                ((2126 * r as u32 + 7152 * g as u32 + 722 * b as u32 + 5000) / 10000) as u16
+            }
+        }
+    }
 
-            (
-                pixel_val, pixel_val, pixel_val, alpha,
-            )
+    pub fn rgb(self) -> (u16, u16, u16) {
+        match self.color {
+            ColorSamples::Grey(grey) => (grey, grey, grey),
+            ColorSamples::Rgb(r, g, b) => (r, g, b),
         }
-        PixelType::Grayscale2 => {
-            let byte = unfiltered_data[pixel_start_byte_position / 4];
-            let bit_offset = 6 - ((pixel_start_byte_position % 4) * 2);
-            let grayscale_val = (byte >> bit_offset) & 0b11;
+    }
 
-            let alpha = match transparency_chunk {
-                Some(TransparencyChunk::Grayscale(transparent_val))
-                    if grayscale_val == *transparent_val =>
-                {
-                    0
-                }
-                _ => 255,
-            };
+    fn scale_to_u8(self, sample: u16) -> Result<u8, errors::PngDecodeErrorCode> {
+        scale_sample_to_u8(sample, self.sample_depth)
+    }
 
-            // TODO - use a lookup table
-            let pixel_val = ((grayscale_val as f32 / 3.0) * 255.0) as u8;
+    pub fn to_rgba16(self) -> [u16; 4] {
+        let (r, g, b) = self.rgb();
+        [
+            scale_sample_to_u16(r, self.sample_depth),
+            scale_sample_to_u16(g, self.sample_depth),
+            scale_sample_to_u16(b, self.sample_depth),
+            scale_sample_to_u16(self.alpha, self.sample_depth),
+        ]
+    }
+}
 
-            (
-                pixel_val, pixel_val, pixel_val, alpha,
-            )
-        }
-        PixelType::Grayscale4 => {
-            let byte = unfiltered_data[pixel_start_byte_position / 2];
-            let bit_offset = 4 - ((pixel_start_byte_position % 2) * 4);
-            let grayscale_val = (byte >> bit_offset) & 0b1111;
+/// Reads a 16-bit sample. PNG stores all multi-byte integers in network byte order (big-endian).
+fn read_u16_sample(unfiltered_data: &[u8], byte_offset: usize) -> u16 {
+    u16::from_be_bytes([
+        unfiltered_data[byte_offset],
+        unfiltered_data[byte_offset + 1],
+    ])
+}
 
-            let alpha = match transparency_chunk {
-                Some(TransparencyChunk::Grayscale(transparent_val))
-                    if grayscale_val == *transparent_val =>
-                {
-                    0
-                }
-                _ => 255,
-            };
+/// Reads a 1, 2 or 4-bit sample. Pixels are packed into bytes with the leftmost pixel
+/// in the high-order bits of a byte.
+fn read_packed_sample(unfiltered_data: &[u8], pixel_index: usize, bit_depth: u8) -> u16 {
+    let bit_position = pixel_index * bit_depth as usize;
+    let byte = unfiltered_data[bit_position / 8];
+    let bit_offset = 8 - bit_depth as usize - (bit_position % 8);
+    let mask = (1u8 << bit_depth) - 1;
 
-            // TODO - use a lookup table
-            let pixel_val = ((grayscale_val as f32 / 15.0) * 255.0) as u8;
-            (
-                pixel_val, pixel_val, pixel_val, alpha,
-            )
-        }
-        PixelType::Grayscale8 => {
-            let byte = unfiltered_data[pixel_start_byte_position];
+    ((byte >> bit_offset) & mask) as u16
+}
 
-            let alpha = match transparency_chunk {
-                Some(TransparencyChunk::Grayscale(transparent_val)) if byte == *transparent_val => {
-                    0
-                }
-                _ => 255,
+/// Reads the `pixel_index`th pixel from the scanline `unfiltered_data`.
+/// tRNS and PLTE are applied here, but the samples are not scaled yet.
+pub fn to_pixel_samples(
+    pixel_type: PixelType,
+    transparency_chunk: Option<&TransparencyChunk>,
+    palette_chunk: Option<&Vec<u8>>,
+    pixel_index: usize,
+    unfiltered_data: &[u8],
+) -> Result<PixelSamples, errors::PngDecodeErrorCode> {
+    let sample_depth = pixel_type.bit_depth();
+    let samples = match pixel_type {
+        PixelType::Grayscale1
+        | PixelType::Grayscale2
+        | PixelType::Grayscale4
+        | PixelType::Grayscale8
+        | PixelType::Grayscale16 => {
+            let grayscale_val = match sample_depth {
+                16 => read_u16_sample(
+                    unfiltered_data,
+                    pixel_index * 2,
+                ),
+                8 => unfiltered_data[pixel_index] as u16,
+                _ => read_packed_sample(
+                    unfiltered_data,
+                    pixel_index,
+                    sample_depth,
+                ),
             };
-            (byte, byte, byte, alpha)
-        }
-        PixelType::Grayscale16 => {
-            let offset = pixel_start_byte_position * 2;
-            let pixel_val =
-                normalize_u16_to_u8(u16::from_be_bytes([unfiltered_data[offset], unfiltered_data[offset + 1]]))?; 
 
             let alpha = match transparency_chunk {
                 Some(TransparencyChunk::Grayscale(transparent_val))
-                    if pixel_val == *transparent_val =>
+                    if grayscale_val == *transparent_val =>
                 {
                     0
                 }
-                _ => 255,
+                _ => max_sample_value(sample_depth),
             };
 
-            (
-                pixel_val, pixel_val, pixel_val, alpha,
-            )
+            PixelSamples {
+                color: ColorSamples::Grey(grayscale_val),
+                alpha,
+                sample_depth,
+            }
         }
-        PixelType::Rgb8 => {
-            let offset = pixel_start_byte_position * 3;
-            let r = unfiltered_data[offset];
-            let g = unfiltered_data[offset + 1];
-            let b = unfiltered_data[offset + 2];
-
-            let alpha = match transparency_chunk {
-                Some(TransparencyChunk::Rgb(t_r, t_g, t_b))
-                    if r == *t_r && g == *t_g && b == *t_b =>
-                {
-                    0
+        PixelType::Rgb8 | PixelType::Rgb16 => {
+            let (r, g, b) = match sample_depth {
+                16 => {
+                    let offset = pixel_index * 6;
+                    (
+                        read_u16_sample(unfiltered_data, offset),
+                        read_u16_sample(unfiltered_data, offset + 2),
+                        read_u16_sample(unfiltered_data, offset + 4),
+                    )
+                }
+                _ => {
+                    let offset = pixel_index * 3;
+                    (
+                        unfiltered_data[offset] as u16,
+                        unfiltered_data[offset + 1] as u16,
+                        unfiltered_data[offset + 2] as u16,
+                    )
                 }
-                _ => 255,
             };
 
-            (r, g, b, alpha)
-        }
-        PixelType::Rgb16 => {
-            let offset = pixel_start_byte_position * 6;
-            let r = normalize_u16_to_u8(u16::from_be_bytes([unfiltered_data[offset], unfiltered_data[offset + 1]]))?;
-            let g = normalize_u16_to_u8(u16::from_be_bytes([unfiltered_data[offset + 2], unfiltered_data[offset + 3]]))?;
-            let b = normalize_u16_to_u8(u16::from_be_bytes([unfiltered_data[offset + 4], unfiltered_data[offset + 5]]))?;
-
             let alpha = match transparency_chunk {
                 Some(TransparencyChunk::Rgb(t_r, t_g, t_b))
                     if r == *t_r && g == *t_g && b == *t_b =>
                 {
                     0
                 }
-                _ => 255,
+                _ => max_sample_value(sample_depth),
             };
 
-            (r, g, b, alpha)
+            PixelSamples {
+                color: ColorSamples::Rgb(r, g, b),
+                alpha,
+                sample_depth,
+            }
         }
-        PixelType::Palette1 => {
-            let byte = unfiltered_data[pixel_start_byte_position / 8];
-            let bit_offset = 7 - pixel_start_byte_position % 8;
-            let palette_idx = ((byte >> bit_offset) & 1) as usize;
+        PixelType::Palette1 | PixelType::Palette2 | PixelType::Palette4 | PixelType::Palette8 => {
+            let palette_idx = match sample_depth {
+                8 => unfiltered_data[pixel_index] as usize,
+                _ => read_packed_sample(
+                    unfiltered_data,
+                    pixel_index,
+                    sample_depth,
+                ) as usize,
+            };
 
             let offset = palette_idx * 3;
 
-            let palette = palette_chunk.unwrap();
-            let r = palette[offset];
-            let g = palette[offset + 1];
-            let b = palette[offset + 2];
+            let palette = palette_chunk
+                .ok_or(errors::PngDecodeErrorCode::_16(chunk_helpers::ColorType::IndexedColor))?;
+            let (r, g, b) = match palette.get(offset..offset + 3) {
+                Some(entry) => (entry[0], entry[1], entry[2]),
+                None => {
+                    return Err(
+                        errors::PngDecodeErrorCode::_39(
+                            palette_idx as u8,
+                            palette.len() / 3,
+                        ),
+                    )
+                }
+            };
 
             let alpha: u8 = match transparency_chunk {
                 Some(TransparencyChunk::Palette(data)) => *data.get(palette_idx).unwrap_or(&255),
                 Some(_) | None => 255,
             };
 
-            (r, g, b, alpha)
+            PixelSamples {
+                color: ColorSamples::Rgb(r as u16, g as u16, b as u16),
+                alpha: alpha as u16,
+                // palette entries are always 8 bits regardless of the bit depth of the indices
+                sample_depth: 8,
+            }
         }
-        PixelType::Palette2 => {
-            let byte = unfiltered_data[pixel_start_byte_position / 4];
-            let bit_offset = 6 - ((pixel_start_byte_position % 4) * 2);
-            let palette_idx = ((byte >> bit_offset) & 0b11) as usize;
-
-            let offset = palette_idx * 3;
-
-            let palette = palette_chunk.unwrap();
-            let r = palette[offset];
-            let g = palette[offset + 1];
-            let b = palette[offset + 2];
-
-            let alpha: u8 = match transparency_chunk {
-                Some(TransparencyChunk::Palette(data)) => *data.get(palette_idx).unwrap_or(&255),
-                Some(_) | None => 255,
+        PixelType::GrayscaleAlpha8 | PixelType::GrayscaleAlpha16 => {
+            let (grayscale_val, alpha) = match sample_depth {
+                16 => {
+                    let offset = pixel_index * 4;
+                    (
+                        read_u16_sample(unfiltered_data, offset),
+                        read_u16_sample(unfiltered_data, offset + 2),
+                    )
+                }
+                _ => {
+                    let offset = pixel_index * 2;
+                    (
+                        unfiltered_data[offset] as u16,
+                        unfiltered_data[offset + 1] as u16,
+                    )
+                }
             };
 
-            (r, g, b, alpha)
+            PixelSamples {
+                color: ColorSamples::Grey(grayscale_val),
+                alpha,
+                sample_depth,
+            }
         }
-        PixelType::Palette4 => {
-            let byte = unfiltered_data[pixel_start_byte_position / 2];
-            let bit_offset = 4 - ((pixel_start_byte_position % 2) * 4);
-            let palette_idx = ((byte >> bit_offset) & 0b1111) as usize;
-
-            let offset = palette_idx * 3;
-
-            let palette = palette_chunk.unwrap();
-            let r = palette[offset];
-            let g = palette[offset + 1];
-            let b = palette[offset + 2];
-
-            let alpha: u8 = match transparency_chunk {
-                Some(TransparencyChunk::Palette(data)) => *data.get(palette_idx).unwrap_or(&255),
-                Some(_) | None => 255,
+        PixelType::RgbAlpha8 | PixelType::RgbAlpha16 => {
+            let (r, g, b, a) = match sample_depth {
+                16 => {
+                    let offset = pixel_index * 8;
+                    (
+                        read_u16_sample(unfiltered_data, offset),
+                        read_u16_sample(unfiltered_data, offset + 2),
+                        read_u16_sample(unfiltered_data, offset + 4),
+                        read_u16_sample(unfiltered_data, offset + 6),
+                    )
+                }
+                _ => {
+                    let offset = pixel_index * 4;
+                    (
+                        unfiltered_data[offset] as u16,
+                        unfiltered_data[offset + 1] as u16,
+                        unfiltered_data[offset + 2] as u16,
+                        unfiltered_data[offset + 3] as u16,
+                    )
+                }
             };
 
-            (r, g, b, alpha)
+            PixelSamples {
+                color: ColorSamples::Rgb(r, g, b),
+                alpha: a,
+                sample_depth,
+            }
         }
-        PixelType::Palette8 => {
-            let offset = unfiltered_data[pixel_start_byte_position] as usize * 3;
-
-            let palette = palette_chunk.unwrap();
-            let r = palette[offset];
-            let g = palette[offset + 1];
-            let b = palette[offset + 2];
+    };
 
-            let alpha: u8 = match transparency_chunk {
-                Some(TransparencyChunk::Palette(data)) => *data.get(offset).unwrap_or(&255),
-                Some(_) | None => 255,
-            };
+    return Ok(samples);
+}
 
-            (r, g, b, alpha)
+/// Writes `samples` into `out`, which must be exactly [PixelFormat::bytes_per_pixel] long.
+///
+/// [PixelFormat::Native] is not handled here, because native pixels are copied
+/// from the unfiltered data as they are instead of being converted pixel by pixel.
+pub fn write_pixel(
+    pixel_format: PixelFormat,
+    samples: &PixelSamples,
+    out: &mut [u8],
+) -> Result<(), errors::PngDecodeErrorCode> {
+    match pixel_format {
+        PixelFormat::Gray8 => {
+            out[0] = samples.scale_to_u8(samples.grey())?;
         }
-        PixelType::GrayscaleAlpha8 => {
-            let offset = pixel_start_byte_position * 2;
-            let grayscale_val = unfiltered_data[offset];
-            let alpha = unfiltered_data[offset + 1];
-
-            (
-                grayscale_val,
-                grayscale_val,
-                grayscale_val,
-                alpha,
-            )
+        PixelFormat::GrayAlpha8 => {
+            out[0] = samples.scale_to_u8(samples.grey())?;
+            out[1] = samples.scale_to_u8(samples.alpha)?;
         }
-        PixelType::GrayscaleAlpha16 => {
-            let offset = pixel_start_byte_position * 4;
-            let grayscale_val =
-            normalize_u16_to_u8(u16::from_be_bytes([unfiltered_data[offset], unfiltered_data[offset + 1]]))?;
-            let alpha =
-            normalize_u16_to_u8(u16::from_be_bytes([unfiltered_data[offset + 2], unfiltered_data[offset + 3]]))?;
-
-            (
-                grayscale_val,
-                grayscale_val,
-                grayscale_val,
-                alpha,
-            )
+        PixelFormat::Rgb8 => {
+            let (r, g, b) = samples.rgb();
+            out[0] = samples.scale_to_u8(r)?;
+            out[1] = samples.scale_to_u8(g)?;
+            out[2] = samples.scale_to_u8(b)?;
         }
-        PixelType::RgbAlpha8 => {
-            let offset = pixel_start_byte_position * 4;
-            let r = unfiltered_data[offset];
-            let g = unfiltered_data[offset + 1];
-            let b = unfiltered_data[offset + 2];
-            let a = unfiltered_data[offset + 3];
-
-            (r, g, b, a)
+        PixelFormat::Rgba8 => {
+            let (r, g, b) = samples.rgb();
+            out[0] = samples.scale_to_u8(r)?;
+            out[1] = samples.scale_to_u8(g)?;
+            out[2] = samples.scale_to_u8(b)?;
+            out[3] = samples.scale_to_u8(samples.alpha)?;
         }
-        PixelType::RgbAlpha16 => {
-            let offset = pixel_start_byte_position * 8;
-            let r = normalize_u16_to_u8(u16::from_be_bytes([unfiltered_data[offset], unfiltered_data[offset + 1]]))?;
-            let g = normalize_u16_to_u8(u16::from_be_bytes([unfiltered_data[offset + 2], unfiltered_data[offset + 3]]))?;
-            let b = normalize_u16_to_u8(u16::from_be_bytes([unfiltered_data[offset + 4], unfiltered_data[offset + 5]]))?;
-            let a = normalize_u16_to_u8(u16::from_be_bytes([unfiltered_data[offset + 6], unfiltered_data[offset + 7]]))?;
-
-            (r, g, b, a)
+        PixelFormat::Bgra8 => {
+            let (r, g, b) = samples.rgb();
+            out[0] = samples.scale_to_u8(b)?;
+            out[1] = samples.scale_to_u8(g)?;
+            out[2] = samples.scale_to_u8(r)?;
+            out[3] = samples.scale_to_u8(samples.alpha)?;
         }
-    };
+        PixelFormat::Argb8 => {
+            let (r, g, b) = samples.rgb();
+            out[0] = samples.scale_to_u8(samples.alpha)?;
+            out[1] = samples.scale_to_u8(r)?;
+            out[2] = samples.scale_to_u8(g)?;
+            out[3] = samples.scale_to_u8(b)?;
+        }
+        PixelFormat::Rgba16BigEndian => {
+            for (i, sample) in samples.to_rgba16().iter().enumerate() {
+                out[i * 2..i * 2 + 2].copy_from_slice(&sample.to_be_bytes());
+            }
+        }
+        PixelFormat::Rgba16NativeEndian => {
+            for (i, sample) in samples.to_rgba16().iter().enumerate() {
+                out[i * 2..i * 2 + 2].copy_from_slice(&sample.to_ne_bytes());
+            }
+        }
+        PixelFormat::Native => (),
+    }
 
-    return Ok(pixel);
+    Ok(())
 }
@@ -1,6 +1,12 @@
-use std::{fs::File, io::Read, path::Path};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
 
-/// Byte reading mode. Can either be "file" or "raw"
+use crate::{chunk_helpers, errors::PngDecodeErrorCode};
+
+/// Byte reading mode. Can either be "file", "raw" or "reader"
 /// `raw` means raw encoded Vec<u8> is to be decoded
 #[derive(Eq, PartialEq, Clone, Copy)]
 pub enum ByteReaderMode {
@@ -8,10 +14,26 @@ pub enum ByteReaderMode {
     FILE = 0,
     /// Bytes stored in `Vec<u8>` intead of an iamge file.
     RAW = 1,
+    /// Any [Read], like a socket, an entry of a zip archive or stdin.
+    /// Bytes are pulled from it only as the decoder needs them.
+    READER = 2,
+}
+
+/// [Read] + [Seek] in a single trait, so that it can be used as a trait object.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// The source of [ByteReaderMode::READER].
+enum ReaderSource<'a> {
+    /// Skipping bytes means reading and throwing them away.
+    Sequential(Box<dyn BufRead + 'a>),
+    /// Skipping bytes is done by seeking.
+    Seekable(Box<dyn ReadSeek + 'a>),
 }
 
 pub struct ByteReader<'a> {
-    /// Byte reading mode. Can either be "file" or "raw"
+    /// Byte reading mode. Can either be "file", "raw" or "reader"
     mode: ByteReaderMode,
     /// path to the PNG file read into the parser
     file_path: Option<&'a str>,
@@ -19,6 +41,8 @@ pub struct ByteReader<'a> {
     file: Option<File>,
     /// If file is None, user chooses [ByteReaderMode::RAW] option
     raw_bytes: Vec<u8>,
+    /// Used in [ByteReaderMode::READER] only
+    reader: Option<ReaderSource<'a>>,
     /// Current read position.
     current_byte_pos: usize,
 }
@@ -34,60 +58,188 @@ impl<'a> ByteReader<'a> {
             mode,
             file: None,
             raw_bytes: raw_bytes.unwrap_or(vec![]),
+            reader: None,
+            current_byte_pos: 0,
+        }
+    }
+
+    fn with_reader_source(reader: ReaderSource<'a>) -> ByteReader<'a> {
+        ByteReader {
+            file_path: None,
+            mode: ByteReaderMode::READER,
+            file: None,
+            raw_bytes: vec![],
+            reader: Some(reader),
             current_byte_pos: 0,
         }
     }
 
-    pub fn read_next_n_bytes(&mut self, n: u64) -> Vec<u8> {
-        self.current_byte_pos += n as usize;
-        match (self.mode, self.file_path) {
+    /// Reads from any [Read]. It is wrapped in a [BufReader], because the decoder
+    /// reads a lot of small pieces (4 bytes each for chunk length, chunk type and CRC).
+    /// If `reader` is buffered already, use [ByteReader::from_buf_reader] instead.
+    pub fn from_reader<R: Read + 'a>(reader: R) -> ByteReader<'a> {
+        ByteReader::with_reader_source(ReaderSource::Sequential(
+            Box::new(BufReader::new(reader)),
+        ))
+    }
+
+    /// Reads from a [BufRead] as it is, without adding another buffer on top of it.
+    pub fn from_buf_reader<R: BufRead + 'a>(reader: R) -> ByteReader<'a> {
+        ByteReader::with_reader_source(ReaderSource::Sequential(
+            Box::new(reader),
+        ))
+    }
+
+    /// Reads from a [Read] that can also [Seek]. Chunks that the decoder does not need
+    /// are skipped by seeking over them instead of reading them.
+    ///
+    /// The reader is used as it is, so consider wrapping unbuffered readers in a [BufReader] first.
+    pub fn from_seekable_reader<R: Read + Seek + 'a>(reader: R) -> ByteReader<'a> {
+        ByteReader::with_reader_source(ReaderSource::Seekable(
+            Box::new(reader),
+        ))
+    }
+
+    /// Reads exactly `n` bytes. It is an error if the image ends before that.
+    pub fn read_next_n_bytes(&mut self, n: u64) -> Result<Vec<u8>, PngDecodeErrorCode> {
+        let buffer = match (self.mode, self.file_path) {
             (ByteReaderMode::RAW, None) => {
-                let drained: Vec<u8> = self.raw_bytes.drain(0..n as usize).collect();
+                let available = (n as usize).min(self.raw_bytes.len());
+                let drained: Vec<u8> = self.raw_bytes.drain(0..available).collect();
 
-                return drained;
+                drained
             }
             (ByteReaderMode::FILE, Some(_)) => {
-                let file = self
-                    .file
-                    .as_ref()
-                    .expect("Image file must be provided before reading bytes");
+                let file = self.file.as_ref().ok_or(PngDecodeErrorCode::_37(
+                    "image file must be opened with read_image before reading bytes".to_string(),
+                ))?;
 
                 let mut buffer: Vec<u8> = vec![];
-                match &file.take(n).read_to_end(&mut buffer) {
-                    Err(reason) => {
-                        panic!(
-                            "could not read next {} bytes: {}",
-                            n, reason
-                        )
+                if let Err(reason) = file.take(n).read_to_end(&mut buffer) {
+                    return Err(PngDecodeErrorCode::_34(
+                        reason.to_string(),
+                    ));
+                }
+
+                buffer
+            }
+            (ByteReaderMode::READER, None) => {
+                let reader: &mut dyn Read = match self.reader.as_mut() {
+                    Some(ReaderSource::Sequential(reader)) => reader,
+                    Some(ReaderSource::Seekable(reader)) => reader,
+                    None => {
+                        return Err(PngDecodeErrorCode::_37(
+                            "reader must be provided before reading bytes".to_string(),
+                        ))
                     }
-                    Ok(_) => (),
                 };
 
-                return buffer;
+                let mut buffer: Vec<u8> = vec![];
+                if let Err(reason) = reader.take(n).read_to_end(&mut buffer) {
+                    return Err(PngDecodeErrorCode::_34(
+                        reason.to_string(),
+                    ));
+                }
+
+                buffer
+            }
+            _ => {
+                return Err(PngDecodeErrorCode::_37(
+                    "wrong mode".to_string(),
+                ))
             }
-            _ => panic!("Wrong mode"),
+        };
+        self.current_byte_pos += buffer.len();
+
+        if (buffer.len() as u64) < n {
+            return Err(PngDecodeErrorCode::_35(
+                n,
+                buffer.len() as u64,
+            ));
         }
+
+        Ok(buffer)
     }
 
-    pub fn read_image(&mut self) {
+    /// Moves past the next `n` bytes without keeping them.
+    /// Seeks if the source supports it, which avoids reading the bytes at all.
+    ///
+    /// Seeking past the end is not an error by itself. It is noticed on the next read instead.
+    pub fn skip_next_n_bytes(&mut self, n: u64) -> Result<(), PngDecodeErrorCode> {
+        let skipped = match (self.mode, self.file_path) {
+            (ByteReaderMode::RAW, None) => {
+                let n = (n as usize).min(self.raw_bytes.len());
+                self.raw_bytes.drain(0..n);
+                Ok(())
+            }
+            (ByteReaderMode::FILE, Some(_)) => match self.file.as_mut() {
+                Some(file) => file.seek(SeekFrom::Current(n as i64)).map(|_| ()),
+                None => {
+                    return Err(PngDecodeErrorCode::_37(
+                        "image file must be opened with read_image before reading bytes"
+                            .to_string(),
+                    ))
+                }
+            },
+            (ByteReaderMode::READER, None) => match self.reader.as_mut() {
+                Some(ReaderSource::Sequential(reader)) => io::copy(
+                    &mut reader.take(n),
+                    &mut io::sink(),
+                )
+                .map(|_| ()),
+                Some(ReaderSource::Seekable(reader)) => {
+                    reader.seek(SeekFrom::Current(n as i64)).map(|_| ())
+                }
+                None => {
+                    return Err(PngDecodeErrorCode::_37(
+                        "reader must be provided before reading bytes".to_string(),
+                    ))
+                }
+            },
+            _ => {
+                return Err(PngDecodeErrorCode::_37(
+                    "wrong mode".to_string(),
+                ))
+            }
+        };
+
+        if let Err(reason) = skipped {
+            return Err(PngDecodeErrorCode::_34(
+                reason.to_string(),
+            ));
+        }
+        self.current_byte_pos = self.current_byte_pos.saturating_add(n as usize);
+
+        Ok(())
+    }
+
+    pub fn read_image(&mut self) -> Result<(), PngDecodeErrorCode> {
         if self.mode == ByteReaderMode::RAW && self.file_path.is_none() {
         } else if self.mode == ByteReaderMode::FILE && self.file_path.is_some() {
-            let file_path = self.file_path.expect("File path must exist");
+            let file_path = self.file_path.unwrap_or_default();
             let path = Path::new(&file_path);
             let display = path.display();
 
             let file = match File::open(&path) {
-                Err(why) => panic!(
-                    "couldn't open {}: {}",
-                    display, why
-                ),
+                Err(why) => {
+                    return Err(PngDecodeErrorCode::_37(format!(
+                        "couldn't open {}: {}",
+                        display, why
+                    )))
+                }
                 Ok(file) => file,
             };
 
             self.file = Some(file);
+        } else if self.mode == ByteReaderMode::READER && self.reader.is_some() {
+            // the reader is already open
         } else {
-            panic!("Failed to read image with either options.");
+            return Err(PngDecodeErrorCode::_37(
+                "Failed to read image with either options.".to_string(),
+            ));
         }
+
+        Ok(())
     }
 
     pub fn read_next_u32_num(&self, four_bytes_chunk: &Vec<u8>) -> u32 {
@@ -97,31 +249,22 @@ impl<'a> ByteReader<'a> {
             | four_bytes_chunk[3] as u32;
     }
 
-    pub fn read_next_4bytes(&mut self) -> Vec<u8> {
-        return self.read_next_n_bytes(4);
+    pub fn read_next_4bytes(&mut self) -> Result<Vec<u8>, PngDecodeErrorCode> {
+        self.read_next_n_bytes(4)
     }
 
-    pub fn read_next_4bytes_num(&mut self) -> u32 {
-        let next_4bytes = self.read_next_4bytes();
-        return self.read_next_u32_num(&next_4bytes);
+    pub fn read_next_4bytes_num(&mut self) -> Result<u32, PngDecodeErrorCode> {
+        let next_4bytes = self.read_next_4bytes()?;
+        Ok(self.read_next_u32_num(&next_4bytes))
     }
 
-    pub fn read_next_4bytes_str(&mut self) -> (String, Vec<u8>) {
-        let buffer = self.read_next_4bytes();
-        let str_from_buffer = match std::str::from_utf8(&buffer) {
-            Err(reason) => {
-                panic!(
-                    "error converting next 4 bytes to a string: {}",
-                    reason
-                )
-            }
-            Ok(str_without_error) => (str_without_error),
-        };
+    pub fn read_next_4bytes_str(&mut self) -> Result<(String, Vec<u8>), PngDecodeErrorCode> {
+        let buffer = self.read_next_4bytes()?;
 
-        return (
-            String::from(str_from_buffer),
+        Ok((
+            chunk_helpers::read_chunk_type(&buffer)?,
             buffer,
-        );
+        ))
     }
 
     pub fn get_current_byte_pos(&self) -> usize {
@@ -0,0 +1,251 @@
+//! Edits the text, physical dimension and timestamp chunks of an existing PNG file
+//! without decoding or encoding its pixels.
+//!
+//! Every chunk that is not edited, including IDAT chunks, is copied byte for byte.
+//! Only the chunks written by the editor get a new CRC.
+
+use std::collections::HashSet;
+
+use crate::{
+    byte_reader::{ByteReader, ByteReaderMode},
+    chunk_helpers, chunk_types,
+    decoded_image::{AncillaryChunk, ChunkInfo},
+    decoder::{PngDecoder, PngDecoderOptions},
+    encoder,
+    errors::PngDecodeErrorCode,
+    physical_chunks::{self, ImageOffset, PhysicalDimensions, PhysicalScale},
+    text_chunks::{self, TextEntry},
+    timestamp::{self, Timestamp},
+};
+
+enum TextEdit {
+    Add(TextEntry),
+    Set(TextEntry),
+    Remove(String),
+}
+
+/// Collects edits, then applies all of them to any number of images with [PngChunkEditor::apply].
+///
+/// Keywords are compared as they are, because they are case-sensitive.
+/// An entry or chunk that replaces existing ones takes the place of the first of them,
+/// and other new entries and chunks are written right before the first IDAT chunk.
+#[derive(Default)]
+pub struct PngChunkEditor {
+    /// Applied in the order they have been added
+    text_edits: Vec<TextEdit>,
+    /// Chunks that may appear only once, each replacing the existing chunk of its type
+    chunk_edits: Vec<AncillaryChunk>,
+}
+
+impl PngChunkEditor {
+    pub fn new() -> PngChunkEditor {
+        PngChunkEditor {
+            text_edits: vec![],
+            chunk_edits: vec![],
+        }
+    }
+
+    /// Adds an entry, keeping the entries that have the same keyword. See [TextEntry::new]
+    pub fn add_text(&mut self, text_entry: TextEntry) {
+        self.text_edits.push(TextEdit::Add(text_entry));
+    }
+
+    /// Replaces every entry that has the same keyword, or adds the entry when there is none
+    pub fn set_text(&mut self, text_entry: TextEntry) {
+        self.text_edits.push(TextEdit::Set(text_entry));
+    }
+
+    /// Removes every entry with `keyword`
+    pub fn remove_text(&mut self, keyword: &str) {
+        self.text_edits.push(TextEdit::Remove(
+            keyword.to_string(),
+        ));
+    }
+
+    /// Sets pHYs chunk. See [PhysicalDimensions::from_dpi]
+    pub fn set_physical_dimensions(
+        &mut self,
+        physical_dimensions: &PhysicalDimensions,
+    ) -> Result<(), PngDecodeErrorCode> {
+        let chunk_data = physical_chunks::encode_phys_chunk(physical_dimensions)?;
+        self.set_chunk(
+            chunk_types::ChunkTypes::pHYs,
+            chunk_data,
+        );
+        Ok(())
+    }
+
+    /// Sets oFFs chunk
+    pub fn set_offset(&mut self, image_offset: &ImageOffset) {
+        self.set_chunk(
+            chunk_types::ChunkTypes::oFFs,
+            physical_chunks::encode_offs_chunk(image_offset),
+        );
+    }
+
+    /// Sets sCAL chunk
+    pub fn set_scale(&mut self, physical_scale: &PhysicalScale) -> Result<(), PngDecodeErrorCode> {
+        let chunk_data = physical_chunks::encode_scal_chunk(physical_scale)?;
+        self.set_chunk(
+            chunk_types::ChunkTypes::sCAL,
+            chunk_data,
+        );
+        Ok(())
+    }
+
+    /// Sets tIME chunk. See [Timestamp::from_unix_time]
+    pub fn set_timestamp(&mut self, timestamp: &Timestamp) -> Result<(), PngDecodeErrorCode> {
+        let chunk_data = timestamp::encode_time_chunk(timestamp)?;
+        self.set_chunk(
+            chunk_types::ChunkTypes::tIME,
+            chunk_data,
+        );
+        Ok(())
+    }
+
+    fn set_chunk(&mut self, chunk_type: &str, chunk_data: Vec<u8>) {
+        self.chunk_edits
+            .retain(|chunk| chunk.chunk_type != chunk_type);
+        self.chunk_edits.push(AncillaryChunk {
+            chunk_type: chunk_type.to_string(),
+            data: chunk_data,
+        });
+    }
+
+    /// Returns the bytes of `png_bytes` with the edits applied.
+    /// The input must be a valid image, because its chunks are copied as they are.
+    pub fn apply(&self, png_bytes: &[u8]) -> Result<Vec<u8>, PngDecodeErrorCode> {
+        let decoder_options = PngDecoderOptions {
+            fail_fast: true,
+            validate_crc: true,
+            ..Default::default()
+        };
+        let mut byte_reader = ByteReader::new(
+            None,
+            ByteReaderMode::RAW,
+            Some(png_bytes.to_vec()),
+        );
+        byte_reader.read_image()?;
+        let probed_image = PngDecoder::new(
+            &mut byte_reader,
+            &decoder_options,
+        )
+        .probe_chunks()?;
+
+        // keywords whose existing entries are dropped, and the entries that are written, in the order of the edits
+        let mut removed_keywords: HashSet<&str> = HashSet::new();
+        let mut new_entries: Vec<(&TextEntry, Vec<u8>)> = vec![];
+        for text_edit in &self.text_edits {
+            match text_edit {
+                TextEdit::Add(text_entry) => {
+                    new_entries.push((
+                        text_entry,
+                        text_chunks::encode_text_entry(text_entry)?,
+                    ));
+                }
+                TextEdit::Set(text_entry) => {
+                    removed_keywords.insert(&text_entry.keyword);
+                    new_entries.retain(|(new_entry, _)| new_entry.keyword != text_entry.keyword);
+                    new_entries.push((
+                        text_entry,
+                        text_chunks::encode_text_entry(text_entry)?,
+                    ));
+                }
+                TextEdit::Remove(keyword) => {
+                    removed_keywords.insert(keyword);
+                    new_entries.retain(|(new_entry, _)| new_entry.keyword != *keyword);
+                }
+            }
+        }
+
+        let chunk_bytes = |chunk_info: &ChunkInfo| {
+            // length, chunk type, chunk data and CRC
+            &png_bytes[chunk_info.byte_pos..chunk_info.byte_pos + chunk_info.length as usize + 12]
+        };
+        let chunk_keyword = |chunk_info: &ChunkInfo| match chunk_info.chunk_type.as_str() {
+            chunk_types::ChunkTypes::tEXt
+            | chunk_types::ChunkTypes::zTXt
+            | chunk_types::ChunkTypes::iTXt => {
+                // skips the length and chunk type. The decoder has made sure that the keyword is valid
+                chunk_helpers::read_keyword(&chunk_bytes(chunk_info)[8..])
+                    .ok()
+                    .map(|(keyword, _)| keyword)
+            }
+            _ => None,
+        };
+        // new entries of these keywords take the place of the existing entries
+        let replaced_keywords: HashSet<String> = probed_image
+            .chunks
+            .iter()
+            .filter_map(chunk_keyword)
+            .filter(|keyword| removed_keywords.contains(keyword.as_str()))
+            .collect();
+
+        let has_chunk_type = |chunk_type: &str| {
+            probed_image
+                .chunks
+                .iter()
+                .any(|chunk_info| chunk_info.chunk_type == chunk_type)
+        };
+
+        let mut edited_png_bytes = chunk_helpers::PNG_HEADER.to_vec();
+        let mut written_keywords: HashSet<String> = HashSet::new();
+        let mut written_chunk_types: HashSet<&str> = HashSet::new();
+        let mut has_idat = false;
+        for chunk_info in &probed_image.chunks {
+            if chunk_info.chunk_type == chunk_types::ChunkTypes::IDAT && !has_idat {
+                has_idat = true;
+                for chunk in &self.chunk_edits {
+                    if !has_chunk_type(&chunk.chunk_type) {
+                        encoder::write_chunk(
+                            &mut edited_png_bytes,
+                            &chunk.chunk_type,
+                            &chunk.data,
+                        );
+                    }
+                }
+                for (text_entry, chunk_data) in &new_entries {
+                    if !replaced_keywords.contains(&text_entry.keyword) {
+                        encoder::write_chunk(
+                            &mut edited_png_bytes,
+                            text_entry.chunk_type(),
+                            chunk_data,
+                        );
+                    }
+                }
+            }
+            if let Some(chunk) = self
+                .chunk_edits
+                .iter()
+                .find(|chunk| chunk.chunk_type == chunk_info.chunk_type)
+            {
+                if written_chunk_types.insert(&chunk.chunk_type) {
+                    encoder::write_chunk(
+                        &mut edited_png_bytes,
+                        &chunk.chunk_type,
+                        &chunk.data,
+                    );
+                }
+                continue;
+            }
+            match chunk_keyword(chunk_info) {
+                Some(keyword) if removed_keywords.contains(keyword.as_str()) => {
+                    if written_keywords.insert(keyword.clone()) {
+                        for (text_entry, chunk_data) in &new_entries {
+                            if text_entry.keyword == keyword {
+                                encoder::write_chunk(
+                                    &mut edited_png_bytes,
+                                    text_entry.chunk_type(),
+                                    chunk_data,
+                                );
+                            }
+                        }
+                    }
+                }
+                _ => edited_png_bytes.extend_from_slice(chunk_bytes(chunk_info)),
+            }
+        }
+
+        Ok(edited_png_bytes)
+    }
+}
@@ -2,6 +2,9 @@ use crate::errors;
 
 pub const PNG_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
+/// Width and height are PNG four-byte unsigned integers, which are limited to (2^31)-1
+pub(crate) const MAX_DIMENSION: u32 = (1 << 31) - 1;
+
 /// Color type is a single-byte integer that describes the interpretation of the image data.
 /// Color type codes represent sums of the following values: 1 (palette used), 2 (color used), and 4 (alpha channel used).
 /// Valid values are 0, 2, 3, 4, and 6.
@@ -144,3 +147,91 @@ impl TryFrom<u8> for InterlaceMethod {
         }
     }
 }
+
+/// Chunk type as text. Fails when its bytes are not valid text, which only happens when the image is corrupt
+pub(crate) fn read_chunk_type(
+    chunk_type_bytes: &[u8],
+) -> Result<String, errors::PngDecodeErrorCode> {
+    match std::str::from_utf8(chunk_type_bytes) {
+        Ok(chunk_type) => Ok(chunk_type.to_string()),
+        Err(_) => Err(errors::PngDecodeErrorCode::_36(chunk_type_bytes.to_vec())),
+    }
+}
+
+/// Fails with the expected and actual lengths when `chunk` isn't `expected_length` bytes long
+pub(crate) fn check_length(
+    chunk_type: &str,
+    chunk: &[u8],
+    expected_length: usize,
+) -> Result<(), errors::PngDecodeErrorCode> {
+    if chunk.len() != expected_length {
+        return Err(
+            errors::PngDecodeErrorCode::_47(
+                chunk_type.to_string(),
+                expected_length,
+                chunk.len(),
+            ),
+        );
+    }
+
+    Ok(())
+}
+
+/// Invalid data of an ancillary chunk, which is reported and ignored unless decoding fails fast
+pub(crate) fn invalid_chunk(chunk_type: &str, reason: String) -> errors::PngDecodeErrorCode {
+    errors::PngDecodeErrorCode::_51(chunk_type.to_string(), reason)
+}
+
+/// PNG two-byte unsigned integer at `index`, in network byte order
+pub(crate) fn read_u16(chunk: &[u8], index: usize) -> u16 {
+    u16::from_be_bytes([chunk[index], chunk[index + 1]])
+}
+
+/// PNG four-byte unsigned integer at `index`, in network byte order
+pub(crate) fn read_u32(chunk: &[u8], index: usize) -> u32 {
+    u32::from_be_bytes([
+        chunk[index],
+        chunk[index + 1],
+        chunk[index + 2],
+        chunk[index + 3],
+    ])
+}
+
+/// Keywords of iCCP, tEXt, zTXt and iTXt chunks are 1-79 printable Latin-1 characters (32-126 and 161-255),
+/// without leading, trailing or consecutive spaces, and end with a null separator.
+///
+/// Returns the keyword and the index of its null separator in `chunk`, or why it is invalid.
+pub(crate) fn read_keyword(chunk: &[u8]) -> Result<(String, usize), String> {
+    let null_separator_index = match chunk.iter().position(|byte| *byte == 0) {
+        Some(null_separator_index) => null_separator_index,
+        None => return Err("keyword has no null separator".to_string()),
+    };
+    let keyword_bytes = &chunk[..null_separator_index];
+    if keyword_bytes.is_empty() || keyword_bytes.len() > 79 {
+        return Err(format!(
+            "keyword must be 1-79 bytes, but it is {} bytes",
+            keyword_bytes.len()
+        ));
+    }
+    if let Some(byte) = keyword_bytes
+        .iter()
+        .find(|byte| !matches!(byte, 32..=126 | 161..=255))
+    {
+        return Err(format!(
+            "keyword has a character that is not printable Latin-1: {}",
+            byte
+        ));
+    }
+    if keyword_bytes.starts_with(b" ")
+        || keyword_bytes.ends_with(b" ")
+        || keyword_bytes.windows(2).any(|pair| pair == b"  ")
+    {
+        return Err("keyword has leading, trailing or consecutive spaces".to_string());
+    }
+
+    // every Latin-1 character has the same code point in Unicode
+    Ok((
+        keyword_bytes.iter().map(|byte| *byte as char).collect(),
+        null_separator_index,
+    ))
+}
@@ -1,10 +1,26 @@
 #[derive(Debug, Clone)]
 pub struct ChunkTypes();
 
+#[allow(non_upper_case_globals)]
 impl<'a> ChunkTypes {
     pub const IHDR: &'a str = "IHDR";
     pub const IDAT: &'a str = "IDAT";
     pub const IEND: &'a str = "IEND";
     pub const PLTE: &'a str = "PLTE";
     pub const tRNS: &'a str = "tRNS";
+    pub const gAMA: &'a str = "gAMA";
+    pub const cHRM: &'a str = "cHRM";
+    pub const sRGB: &'a str = "sRGB";
+    pub const iCCP: &'a str = "iCCP";
+    pub const tEXt: &'a str = "tEXt";
+    pub const zTXt: &'a str = "zTXt";
+    pub const iTXt: &'a str = "iTXt";
+    pub const bKGD: &'a str = "bKGD";
+    pub const pHYs: &'a str = "pHYs";
+    pub const oFFs: &'a str = "oFFs";
+    pub const sCAL: &'a str = "sCAL";
+    pub const tIME: &'a str = "tIME";
+    pub const sBIT: &'a str = "sBIT";
+    pub const sPLT: &'a str = "sPLT";
+    pub const hIST: &'a str = "hIST";
 }
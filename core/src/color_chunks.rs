@@ -0,0 +1,201 @@
+//! gAMA, cHRM, sRGB and iCCP chunks, which tell how the samples of the image map to actual colors.
+//!
+//! http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.Additional-chunks
+//!
+//! Each of them must come before PLTE and IDAT chunks, and appear at most once.
+//! sRGB and iCCP chunks must not both appear. When more than one of them is there anyway,
+//! [ColorSpace] follows the precedence the specification gives them.
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{chunk_helpers, chunk_types, errors::PngDecodeErrorCode, zlib};
+
+/// Values of gAMA and cHRM chunks are stored times 100000, as in the chunks themselves
+pub const COLOR_VALUE_SCALE: u32 = 100_000;
+
+/// CIE 1931 x,y chromaticities of the white point and the primaries, from cHRM chunk.
+/// Each value is times 100000, so a white point x of 0.3127 is stored as 31270.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chromaticities {
+    pub white_point_x: u32,
+    pub white_point_y: u32,
+    pub red_x: u32,
+    pub red_y: u32,
+    pub green_x: u32,
+    pub green_y: u32,
+    pub blue_x: u32,
+    pub blue_y: u32,
+}
+
+/// From sRGB chunk. Tells how colors outside the gamut of the output device should be handled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderingIntent {
+    Perceptual = 0,
+    RelativeColorimetric = 1,
+    Saturation = 2,
+    AbsoluteColorimetric = 3,
+}
+
+impl TryFrom<u8> for RenderingIntent {
+    type Error = PngDecodeErrorCode;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(RenderingIntent::Perceptual),
+            1 => Ok(RenderingIntent::RelativeColorimetric),
+            2 => Ok(RenderingIntent::Saturation),
+            3 => Ok(RenderingIntent::AbsoluteColorimetric),
+            _ => Err(PngDecodeErrorCode::_51(
+                chunk_types::ChunkTypes::sRGB.to_string(),
+                format!(
+                    "unknown rendering intent {}",
+                    value
+                ),
+            )),
+        }
+    }
+}
+
+/// Embedded ICC profile from iCCP chunk
+#[derive(Debug, Clone, PartialEq)]
+pub struct IccProfile {
+    /// Name of the profile, for reference only
+    pub name: String,
+    /// The ICC profile itself, already decompressed
+    pub profile: Vec<u8>,
+}
+
+/// How the colors of the image should be interpreted, after the precedence rules of the specification:
+/// iCCP chunk over sRGB chunk, and both of them over gAMA and cHRM chunks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSpace<'a> {
+    IccProfile(&'a IccProfile),
+    Srgb(RenderingIntent),
+    /// From gAMA and cHRM chunks, either of which may be missing
+    Calibrated {
+        gamma: Option<u32>,
+        chromaticities: Option<Chromaticities>,
+    },
+    /// There is no color chunk at all. Most viewers treat such images as sRGB.
+    Unknown,
+}
+
+/// Color space the decoder converts the samples into before they are written out.
+/// See [crate::decoder::PngDecoderOptions::color_conversion]
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorConversion {
+    /// Samples are written out as they are stored in the image
+    None,
+    /// Samples are converted from the color space of the image to sRGB, which is what most displays expect
+    Srgb,
+    /// Samples are converted to linear light with the primaries of sRGB, for blending and resampling.
+    /// Samples are proportional to the light intensity, so dark colors are left with few distinct 8-bit values,
+    /// and a 16-bit [crate::decoded_image::PixelFormat] is usually the better choice
+    Linear,
+    /// Samples are converted to the ICC profile given with [crate::decoder::PngDecoder::set_destination_profile],
+    /// or to sRGB when none has been given
+    DestinationProfile,
+}
+
+/// Gamma:  4 bytes, image gamma times 100000. For example, 45455 for a gamma of 1/2.2
+pub(crate) fn parse_gama_chunk(chunk: &[u8]) -> Result<u32, PngDecodeErrorCode> {
+    chunk_helpers::check_length(
+        chunk_types::ChunkTypes::gAMA,
+        chunk,
+        4,
+    )?;
+    let gamma = chunk_helpers::read_u32(chunk, 0);
+    if gamma == 0 {
+        return Err(PngDecodeErrorCode::_51(
+            chunk_types::ChunkTypes::gAMA.to_string(),
+            "gamma must not be 0".to_string(),
+        ));
+    }
+
+    Ok(gamma)
+}
+
+///    White Point x: 4 bytes
+///    White Point y: 4 bytes
+///    Red x:         4 bytes
+///    Red y:         4 bytes
+///    Green x:       4 bytes
+///    Green y:       4 bytes
+///    Blue x:        4 bytes
+///    Blue y:        4 bytes
+pub(crate) fn parse_chrm_chunk(chunk: &[u8]) -> Result<Chromaticities, PngDecodeErrorCode> {
+    chunk_helpers::check_length(
+        chunk_types::ChunkTypes::cHRM,
+        chunk,
+        32,
+    )?;
+
+    Ok(Chromaticities {
+        white_point_x: chunk_helpers::read_u32(chunk, 0),
+        white_point_y: chunk_helpers::read_u32(chunk, 4),
+        red_x: chunk_helpers::read_u32(chunk, 8),
+        red_y: chunk_helpers::read_u32(chunk, 12),
+        green_x: chunk_helpers::read_u32(chunk, 16),
+        green_y: chunk_helpers::read_u32(chunk, 20),
+        blue_x: chunk_helpers::read_u32(chunk, 24),
+        blue_y: chunk_helpers::read_u32(chunk, 28),
+    })
+}
+
+/// Rendering intent: 1 byte
+pub(crate) fn parse_srgb_chunk(chunk: &[u8]) -> Result<RenderingIntent, PngDecodeErrorCode> {
+    chunk_helpers::check_length(
+        chunk_types::ChunkTypes::sRGB,
+        chunk,
+        1,
+    )?;
+
+    chunk[0].try_into()
+}
+
+///    Profile name:       1-79 bytes (character string)
+///    Null separator:     1 byte
+///    Compression method: 1 byte
+///    Compressed profile: n bytes
+///
+/// * `max_profile_length` - decompression stops with an error once the profile gets longer than this
+pub(crate) fn parse_iccp_chunk(
+    chunk: &[u8],
+    max_profile_length: usize,
+) -> Result<IccProfile, PngDecodeErrorCode> {
+    let invalid_chunk = |reason: String| {
+        PngDecodeErrorCode::_51(
+            chunk_types::ChunkTypes::iCCP.to_string(),
+            reason,
+        )
+    };
+
+    let (name, null_separator_index) = chunk_helpers::read_keyword(chunk).map_err(invalid_chunk)?;
+    let compression_method = match chunk.get(null_separator_index + 1) {
+        Some(compression_method) => *compression_method,
+        None => {
+            return Err(invalid_chunk(
+                "compression method is missing".to_string(),
+            ))
+        }
+    };
+    if compression_method != chunk_helpers::CompressionMethod::Deflate as u8 {
+        return Err(invalid_chunk(format!(
+            "unknown compression method {}",
+            compression_method
+        )));
+    }
+    let profile = zlib::decompress_to_vec(
+        &chunk[null_separator_index + 2..],
+        max_profile_length,
+    )
+    .map_err(|status| {
+        invalid_chunk(format!(
+            "profile can't be decompressed ({:?})",
+            status
+        ))
+    })?;
+
+    Ok(IccProfile { name, profile })
+}
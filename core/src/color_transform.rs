@@ -0,0 +1,531 @@
+//! Conversion of decoded samples from the color space of the image into another one. See [crate::color_chunks::ColorConversion]
+//!
+//! Both color spaces are described as a [ColorProfile]: a tone curve for each channel, which decodes the samples
+//! into linear light, and a matrix from linear RGB to CIE XYZ relative to D50, the profile connection space of ICC.
+//! The color space of the image comes from its iCCP chunk when it has a profile [crate::icc] can read.
+//! Otherwise it is built from sRGB chunk, or gAMA and cHRM chunks, and images without any color chunk are treated as sRGB.
+//!
+//! Samples are decoded into linear light, converted through XYZ with the white point adapted to D50 (Bradford),
+//! and encoded with the tone curves of the destination. Tone curves are computed once for every sample value,
+//! so converting a pixel is a few table lookups, and a matrix when the primaries differ.
+
+use crate::{
+    bitmap::{ColorSamples, PixelSamples, PixelType},
+    color_chunks::{Chromaticities, COLOR_VALUE_SCALE},
+    common::{max_sample_value, scale_sample_to_u16},
+    decoded_image::ImageMetadata,
+};
+
+pub(crate) type Matrix = [[f64; 3]; 3];
+
+/// Primaries and white point (D65) of sRGB, the same values the specification suggests for cHRM chunk of sRGB images
+const SRGB_CHROMATICITIES: Chromaticities = Chromaticities {
+    white_point_x: 31270,
+    white_point_y: 32900,
+    red_x: 64000,
+    red_y: 33000,
+    green_x: 30000,
+    green_y: 60000,
+    blue_x: 15000,
+    blue_y: 6000,
+};
+
+/// CIE XYZ of the D50 white point, as the ICC specification rounds it
+const D50_WHITE_POINT: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+/// From CIE XYZ to the cone responses of the Bradford chromatic adaptation
+const BRADFORD: Matrix = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+/// Decodes a sample from 0.0 to 1.0 into linear light from 0.0 to 1.0
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ToneCurve {
+    /// `sample^gamma`
+    Gamma(f64),
+    /// Linear light at evenly spaced samples, from 0 to 65535. Samples between them are interpolated.
+    /// Has at least 2 entries
+    Table(Vec<u16>),
+    /// One of the five parametric functions of ICC, numbered from 0 to 4, with its parameters g, a, b, c, d, e and f
+    Parametric(u16, [f64; 7]),
+}
+
+impl ToneCurve {
+    fn srgb() -> ToneCurve {
+        ToneCurve::Parametric(
+            3,
+            [
+                2.4,
+                1.0 / 1.055,
+                0.055 / 1.055,
+                1.0 / 12.92,
+                0.04045,
+                0.0,
+                0.0,
+            ],
+        )
+    }
+
+    fn to_linear(&self, sample: f64) -> f64 {
+        match self {
+            ToneCurve::Gamma(gamma) => sample.powf(*gamma),
+            ToneCurve::Table(table) => {
+                let position = sample.clamp(0.0, 1.0) * (table.len() - 1) as f64;
+                let index = (position as usize).min(table.len() - 2);
+                let fraction = position - index as f64;
+                (table[index] as f64 * (1.0 - fraction) + table[index + 1] as f64 * fraction)
+                    / u16::MAX as f64
+            }
+            ToneCurve::Parametric(function_type, [g, a, b, c, d, e, f]) => {
+                let power = |x: f64| (a * x + b).max(0.0).powf(*g);
+                match function_type {
+                    0 => sample.powf(*g),
+                    1 if sample >= -b / a => power(sample),
+                    1 => 0.0,
+                    2 if sample >= -b / a => power(sample) + c,
+                    2 => *c,
+                    3 if sample >= *d => power(sample),
+                    3 => c * sample,
+                    _ if sample >= *d => power(sample) + e,
+                    _ => c * sample + f,
+                }
+            }
+        }
+    }
+
+    /// Inverse of [ToneCurve::to_linear]
+    fn to_sample(&self, linear: f64) -> f64 {
+        let sample = match self {
+            ToneCurve::Gamma(gamma) => linear.powf(1.0 / gamma),
+            ToneCurve::Table(table) => {
+                let value = linear * u16::MAX as f64;
+                // entries are expected to increase, but the curve may be flat in places
+                let index = table.partition_point(|entry| (*entry as f64) < value);
+                match index {
+                    0 => 0.0,
+                    index if index == table.len() => 1.0,
+                    index => {
+                        let (lower, upper) = (
+                            table[index - 1] as f64,
+                            table[index] as f64,
+                        );
+                        let fraction = (value - lower) / (upper - lower);
+                        (index - 1) as f64 / (table.len() - 1) as f64
+                            + fraction / (table.len() - 1) as f64
+                    }
+                }
+            }
+            ToneCurve::Parametric(function_type, [g, a, b, c, d, e, f]) => {
+                let root = |y: f64| (y.max(0.0).powf(1.0 / g) - b) / a;
+                match function_type {
+                    0 => linear.powf(1.0 / g),
+                    1 => root(linear),
+                    2 => root(linear - c),
+                    3 if linear >= c * d => root(linear),
+                    3 => linear / c,
+                    _ if linear >= c * d + f => root(linear - e),
+                    _ => (linear - f) / c,
+                }
+            }
+        };
+
+        match sample.is_finite() {
+            true => sample.clamp(0.0, 1.0),
+            false => 0.0,
+        }
+    }
+}
+
+/// How the samples of an image map to actual colors
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ColorProfile {
+    /// One curve for each channel: three for RGB, one for greyscale
+    pub(crate) tone_curves: Vec<ToneCurve>,
+    /// From linear RGB to CIE XYZ relative to D50. `None` for greyscale, whose linear sample is Y itself
+    pub(crate) to_xyz: Option<Matrix>,
+}
+
+impl ColorProfile {
+    pub(crate) fn srgb() -> ColorProfile {
+        ColorProfile::from_chromaticities(
+            &SRGB_CHROMATICITIES,
+            ToneCurve::srgb(),
+        )
+        .expect("Chromaticities of sRGB must be valid")
+    }
+
+    /// Primaries of sRGB without any tone curve
+    pub(crate) fn linear_srgb() -> ColorProfile {
+        ColorProfile::from_chromaticities(
+            &SRGB_CHROMATICITIES,
+            ToneCurve::Gamma(1.0),
+        )
+        .expect("Chromaticities of sRGB must be valid")
+    }
+
+    pub(crate) fn is_greyscale(&self) -> bool {
+        self.to_xyz.is_none()
+    }
+
+    /// `None` when the chromaticities can't be converted, for example when two primaries are the same
+    fn from_chromaticities(
+        chromaticities: &Chromaticities,
+        tone_curve: ToneCurve,
+    ) -> Option<ColorProfile> {
+        let white_point = chromaticity_to_xyz(
+            chromaticities.white_point_x,
+            chromaticities.white_point_y,
+        )?;
+        let to_xyz = multiply(
+            &calc_adaptation_matrix(white_point)?,
+            &calc_rgb_to_xyz_matrix(chromaticities)?,
+        );
+
+        Some(ColorProfile {
+            tone_curves: vec![tone_curve; 3],
+            to_xyz: Some(to_xyz),
+        })
+    }
+
+    /// Color space of an image described by its color chunks.
+    /// `icc_profile` is the profile parsed from iCCP chunk, which takes precedence over the other color chunks
+    pub(crate) fn from_metadata(
+        metadata: &ImageMetadata,
+        icc_profile: Option<ColorProfile>,
+        pixel_type: PixelType,
+    ) -> ColorProfile {
+        if let Some(icc_profile) = icc_profile {
+            return icc_profile;
+        }
+
+        // sRGB chunk takes precedence over gAMA and cHRM chunks
+        let (tone_curve, chromaticities) = match (
+            metadata.srgb_rendering_intent,
+            metadata.gamma,
+        ) {
+            (None, Some(gamma)) => (
+                ToneCurve::Gamma(COLOR_VALUE_SCALE as f64 / gamma as f64),
+                metadata.chromaticities,
+            ),
+            (None, None) => (
+                ToneCurve::srgb(),
+                metadata.chromaticities,
+            ),
+            (Some(_), _) => (ToneCurve::srgb(), None),
+        };
+        if pixel_type.is_greyscale() {
+            return ColorProfile {
+                tone_curves: vec![tone_curve],
+                to_xyz: None,
+            };
+        }
+
+        // invalid primaries are ignored
+        chromaticities
+            .and_then(|chromaticities| {
+                ColorProfile::from_chromaticities(
+                    &chromaticities,
+                    tone_curve.clone(),
+                )
+            })
+            .unwrap_or_else(|| ColorProfile {
+                tone_curves: vec![tone_curve; 3],
+                ..ColorProfile::srgb()
+            })
+    }
+}
+
+pub(crate) enum ColorTransform {
+    /// Each channel is converted on its own, straight from every sample value to its 16-bit output value.
+    /// One table for greyscale samples, three for RGB
+    PerChannel(Vec<Vec<u16>>),
+    /// Channels are mixed in linear light, when the primaries differ
+    Matrix {
+        /// Every sample value decoded into linear light, for each channel of the image
+        linear_tables: [Vec<f32>; 3],
+        /// From linear light of the image to linear light of the destination. A single row for a greyscale destination
+        rows: Vec<[f32; 3]>,
+        /// Every 16-bit linear value encoded into its output value, for each row
+        encode_tables: Vec<Vec<u16>>,
+    },
+}
+
+impl ColorTransform {
+    /// `None` when the samples are already in the `destination` color space
+    pub(crate) fn new(
+        source: &ColorProfile,
+        destination: &ColorProfile,
+        pixel_type: PixelType,
+    ) -> Option<ColorTransform> {
+        // palette entries are always 8 bits regardless of the bit depth of the indices
+        let sample_depth = match pixel_type.is_palette() {
+            true => 8,
+            false => pixel_type.bit_depth(),
+        };
+
+        let (source_to_xyz, destination_to_xyz) = match (
+            source.to_xyz,
+            destination.to_xyz,
+        ) {
+            (Some(source_to_xyz), Some(destination_to_xyz)) => (
+                source_to_xyz,
+                destination_to_xyz,
+            ),
+            (Some(source_to_xyz), None) => {
+                // only the luminance is left for a greyscale destination
+                let luminance_row = source_to_xyz[1].map(|value| value as f32);
+                return Some(ColorTransform::Matrix {
+                    linear_tables: calc_linear_tables(source, sample_depth),
+                    rows: vec![luminance_row],
+                    encode_tables: vec![calc_encode_table(&destination.tone_curves[0])],
+                });
+            }
+            (None, _) => {
+                // greyscale samples stay greyscale, encoded with the curve of green (or the only curve),
+                // which carries most of the luminance
+                let source_curve = &source.tone_curves[0];
+                let destination_curve = &destination.tone_curves[destination.tone_curves.len() / 2];
+                if source_curve == destination_curve {
+                    return None;
+                }
+                let output_table = sample_values(sample_depth)
+                    .map(|sample| {
+                        encode_to_u16(
+                            destination_curve,
+                            source_curve.to_linear(sample),
+                        )
+                    })
+                    .collect();
+                return Some(ColorTransform::PerChannel(
+                    vec![output_table],
+                ));
+            }
+        };
+
+        let matrix = multiply(
+            &invert(&destination_to_xyz)?,
+            &source_to_xyz,
+        );
+        let is_identity = (0..3).all(|row| {
+            (0..3).all(|col| (matrix[row][col] - if row == col { 1.0 } else { 0.0 }).abs() < 1e-4)
+        });
+        if !is_identity {
+            return Some(ColorTransform::Matrix {
+                linear_tables: calc_linear_tables(source, sample_depth),
+                rows: matrix.map(|row| row.map(|value| value as f32)).to_vec(),
+                encode_tables: destination
+                    .tone_curves
+                    .iter()
+                    .map(calc_encode_table)
+                    .collect(),
+            });
+        }
+        if source.tone_curves == destination.tone_curves {
+            return None;
+        }
+
+        Some(ColorTransform::PerChannel(
+            source
+                .tone_curves
+                .iter()
+                .zip(&destination.tone_curves)
+                .map(
+                    |(source_curve, destination_curve)| {
+                        sample_values(sample_depth)
+                            .map(|sample| {
+                                encode_to_u16(
+                                    destination_curve,
+                                    source_curve.to_linear(sample),
+                                )
+                            })
+                            .collect()
+                    },
+                )
+                .collect(),
+        ))
+    }
+
+    /// Converts the color of `samples`. The result is always 16 bits deep, so that no precision is lost before
+    /// it is scaled to the output format
+    pub(crate) fn apply(&self, samples: PixelSamples) -> PixelSamples {
+        let color = match (self, samples.color) {
+            (ColorTransform::PerChannel(output_tables), ColorSamples::Grey(grey)) => {
+                ColorSamples::Grey(output_tables[0][grey as usize])
+            }
+            (ColorTransform::PerChannel(output_tables), ColorSamples::Rgb(r, g, b)) => {
+                ColorSamples::Rgb(
+                    output_tables[0][r as usize],
+                    output_tables[1][g as usize],
+                    output_tables[2][b as usize],
+                )
+            }
+            (
+                ColorTransform::Matrix {
+                    linear_tables,
+                    rows,
+                    encode_tables,
+                },
+                color,
+            ) => {
+                let (r, g, b) = match color {
+                    ColorSamples::Grey(grey) => (grey, grey, grey),
+                    ColorSamples::Rgb(r, g, b) => (r, g, b),
+                };
+                let linear = [
+                    linear_tables[0][r as usize],
+                    linear_tables[1][g as usize],
+                    linear_tables[2][b as usize],
+                ];
+                let mut converted = rows.iter().zip(encode_tables).map(|(row, encode_table)| {
+                    let value = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+                    // colors outside the gamut of the destination are clipped
+                    encode_table[(value.clamp(0.0, 1.0) * u16::MAX as f32).round() as usize]
+                });
+                match (
+                    converted.next(),
+                    converted.next(),
+                    converted.next(),
+                ) {
+                    (Some(r), Some(g), Some(b)) => ColorSamples::Rgb(r, g, b),
+                    (grey, _, _) => ColorSamples::Grey(grey.unwrap_or(0)),
+                }
+            }
+        };
+
+        PixelSamples {
+            color,
+            alpha: scale_sample_to_u16(
+                samples.alpha,
+                samples.sample_depth,
+            ),
+            sample_depth: 16,
+        }
+    }
+}
+
+/// Every value a sample of `sample_depth` bits can have, from 0.0 to 1.0
+fn sample_values(sample_depth: u8) -> impl Iterator<Item = f64> {
+    let max_sample = max_sample_value(sample_depth);
+    (0..=max_sample).map(move |sample| sample as f64 / max_sample as f64)
+}
+
+/// Every sample value decoded into linear light with the curve of each channel of an RGB `source`
+fn calc_linear_tables(source: &ColorProfile, sample_depth: u8) -> [Vec<f32>; 3] {
+    std::array::from_fn(|channel| {
+        sample_values(sample_depth)
+            .map(|sample| source.tone_curves[channel].to_linear(sample) as f32)
+            .collect()
+    })
+}
+
+/// `linear` is from 0.0 to 1.0
+fn encode_to_u16(tone_curve: &ToneCurve, linear: f64) -> u16 {
+    (tone_curve.to_sample(linear.clamp(0.0, 1.0)) * u16::MAX as f64).round() as u16
+}
+
+/// Every 16-bit linear value encoded with `tone_curve`
+fn calc_encode_table(tone_curve: &ToneCurve) -> Vec<u16> {
+    (0..=u16::MAX)
+        .map(|linear| {
+            encode_to_u16(
+                tone_curve,
+                linear as f64 / u16::MAX as f64,
+            )
+        })
+        .collect()
+}
+
+/// CIE XYZ of a chromaticity, with Y = 1. `None` for y = 0, which no real color has
+fn chromaticity_to_xyz(x: u32, y: u32) -> Option<[f64; 3]> {
+    if y == 0 {
+        return None;
+    }
+    let x = x as f64 / COLOR_VALUE_SCALE as f64;
+    let y = y as f64 / COLOR_VALUE_SCALE as f64;
+
+    Some([x / y, 1.0, (1.0 - x - y) / y])
+}
+
+/// From linear RGB with `chromaticities` to CIE XYZ, scaled so that white has Y = 1
+fn calc_rgb_to_xyz_matrix(chromaticities: &Chromaticities) -> Option<Matrix> {
+    let red = chromaticity_to_xyz(
+        chromaticities.red_x,
+        chromaticities.red_y,
+    )?;
+    let green = chromaticity_to_xyz(
+        chromaticities.green_x,
+        chromaticities.green_y,
+    )?;
+    let blue = chromaticity_to_xyz(
+        chromaticities.blue_x,
+        chromaticities.blue_y,
+    )?;
+    let white = chromaticity_to_xyz(
+        chromaticities.white_point_x,
+        chromaticities.white_point_y,
+    )?;
+    // each primary is a column
+    let primaries = [
+        [red[0], green[0], blue[0]],
+        [red[1], green[1], blue[1]],
+        [red[2], green[2], blue[2]],
+    ];
+    // how much of each primary adds up to white
+    let scale = multiply_vector(&invert(&primaries)?, white);
+
+    Some(primaries.map(|row| [row[0] * scale[0], row[1] * scale[1], row[2] * scale[2]]))
+}
+
+/// Bradford chromatic adaptation from `white_point` to D50
+fn calc_adaptation_matrix(white_point: [f64; 3]) -> Option<Matrix> {
+    let source_cone = multiply_vector(&BRADFORD, white_point);
+    let target_cone = multiply_vector(&BRADFORD, D50_WHITE_POINT);
+    let mut cone_scale = [[0.0; 3]; 3];
+    for i in 0..3 {
+        cone_scale[i][i] = target_cone[i] / source_cone[i];
+    }
+
+    let matrix = multiply(
+        &invert(&BRADFORD)?,
+        &multiply(&cone_scale, &BRADFORD),
+    );
+    match matrix.iter().flatten().all(|value| value.is_finite()) {
+        true => Some(matrix),
+        false => None,
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut product = [[0.0; 3]; 3];
+    for row in 0..3 {
+        for col in 0..3 {
+            product[row][col] = (0..3).map(|i| a[row][i] * b[i][col]).sum();
+        }
+    }
+
+    product
+}
+
+fn multiply_vector(matrix: &Matrix, vector: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+/// `None` for singular matrices
+fn invert(m: &Matrix) -> Option<Matrix> {
+    let cofactor = |row: usize, col: usize| {
+        let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+        let (c0, c1) = ((col + 1) % 3, (col + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let determinant: f64 = (0..3).map(|col| m[0][col] * cofactor(0, col)).sum();
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+
+    // the adjugate is the transpose of the cofactors
+    Some(std::array::from_fn(|row| {
+        std::array::from_fn(|col| cofactor(col, row) / determinant)
+    }))
+}
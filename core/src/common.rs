@@ -18,14 +18,42 @@ const U8_MAX_OUT_SAMPLE: f32 = 255.0;
 // 2**16 - 1
 const U16_MAX_IN_SAMPLE: f32 = 65535.0;
 
-pub(crate) fn normalize_u16_to_u8(
-    num: u16
-) -> Result<u8, errors::PngDecodeErrorCode> {
+pub(crate) fn normalize_u16_to_u8(num: u16) -> Result<u8, errors::PngDecodeErrorCode> {
     let normalized_u8 = ((num as f32 * U8_MAX_OUT_SAMPLE) / U16_MAX_IN_SAMPLE + 0.5).floor();
     if normalized_u8 > U8_MAX_OUT_SAMPLE {
         return Err(errors::PngDecodeErrorCode::_24("u16".to_string(), "u8".to_string()))
     }
 
     // now safe
-    return Ok(normalized_u8 as u8)
-}
\ No newline at end of file
+    return Ok(normalized_u8 as u8);
+}
+
+/// Largest value a sample of `bit_depth` bits can hold. For example, 3 for 2-bit samples.
+pub(crate) fn max_sample_value(bit_depth: u8) -> u16 {
+    ((1u32 << bit_depth) - 1) as u16
+}
+
+/// Scales a sample of `bit_depth` bits to the full 8-bit range.
+///
+/// 1, 2 and 4-bit samples are multiplied by `255 / (2^bit_depth - 1)`, which is always an integer (255, 85 and 17),
+/// so that the maximum value of the original depth maps to 255.
+pub(crate) fn scale_sample_to_u8(
+    sample: u16,
+    bit_depth: u8,
+) -> Result<u8, errors::PngDecodeErrorCode> {
+    match bit_depth {
+        16 => normalize_u16_to_u8(sample),
+        8 => Ok(sample as u8),
+        _ => Ok((sample * (255 / max_sample_value(bit_depth))) as u8),
+    }
+}
+
+/// Scales a sample of `bit_depth` bits to the full 16-bit range.
+///
+/// Just like [scale_sample_to_u8], `65535 / (2^bit_depth - 1)` is always an integer (65535, 21845, 4369 and 257).
+pub(crate) fn scale_sample_to_u16(sample: u16, bit_depth: u8) -> u16 {
+    match bit_depth {
+        16 => sample,
+        _ => sample * (65535 / max_sample_value(bit_depth)),
+    }
+}
@@ -0,0 +1,199 @@
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{
+    background::BackgroundColor,
+    chunk_helpers::{
+        colortype_to_channel, ColorType, CompressionMethod, FilterMethod, InterlaceMethod,
+    },
+    color_chunks::{Chromaticities, ColorSpace, IccProfile, RenderingIntent},
+    common,
+    errors::PngDecodeError,
+    palette_chunks::SuggestedPalette,
+    physical_chunks::{ImageOffset, PhysicalDimensions, PhysicalScale},
+    significant_bits::SignificantBits,
+    text_chunks::TextEntry,
+    timestamp::Timestamp,
+};
+
+/// Layout of each pixel in [DecodedImage::pixels].
+///
+/// Each format is converted to directly from the pixels of the image,
+/// so choosing a smaller format also keeps the memory usage of the decoder smaller.
+/// Alpha is never premultiplied.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PixelFormat {
+    /// 1 byte per pixel. Colored pixels are converted to their luminance (Rec. 709),
+    /// and alpha is dropped.
+    Gray8,
+    /// 2 bytes per pixel, in the order of grey, a.
+    GrayAlpha8,
+    /// 3 bytes per pixel, in the order of r, g, b. Alpha is dropped.
+    Rgb8,
+    /// 4 bytes per pixel, in the order of r, g, b, a.
+    /// 16-bit samples are scaled down to 8 bits.
+    Rgba8,
+    /// 4 bytes per pixel, in the order of b, g, r, a.
+    Bgra8,
+    /// 4 bytes per pixel, in the order of a, r, g, b.
+    Argb8,
+    /// 8 bytes per pixel, in the order of r, g, b, a. Each sample is a big-endian `u16`,
+    /// which is how PNG itself stores 16-bit samples.
+    /// Samples of a smaller bit depth are scaled up to 16 bits.
+    Rgba16BigEndian,
+    /// Same as [PixelFormat::Rgba16BigEndian], but each sample is in the byte order of the machine running the decoder,
+    /// so that `pixels` can be reinterpreted as `&[u16]` directly.
+    Rgba16NativeEndian,
+    /// Unfiltered (and deinterlaced) samples, exactly as they are stored in the image.
+    /// The layout depends on [ImageHeader::color_type] and [ImageHeader::bit_depth]:
+    /// palette indices are not looked up, tRNS is not applied, 16-bit samples stay big-endian,
+    /// and samples smaller than 8 bits stay packed into bytes, with each row starting on a new byte.
+    Native,
+}
+
+impl PixelFormat {
+    /// `None` for [PixelFormat::Native], because the size of a native pixel depends on the image
+    /// and can even be smaller than a byte. Use [DecodedImage::stride] instead.
+    pub fn bytes_per_pixel(&self) -> Option<usize> {
+        match self {
+            PixelFormat::Gray8 => Some(1),
+            PixelFormat::GrayAlpha8 => Some(2),
+            PixelFormat::Rgb8 => Some(3),
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 | PixelFormat::Argb8 => Some(4),
+            PixelFormat::Rgba16BigEndian | PixelFormat::Rgba16NativeEndian => Some(8),
+            PixelFormat::Native => None,
+        }
+    }
+}
+
+/// Values read from the IHDR chunk, after they have been validated by the decoder.
+///
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.IHDR
+#[derive(Debug, Clone)]
+pub struct ImageHeader {
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// Number of bits per sample or per palette index (not per pixel)
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression_method: CompressionMethod,
+    pub filter_method: FilterMethod,
+    pub interlace_method: InterlaceMethod,
+}
+
+/// Values read from the ancillary chunks the decoder understands.
+/// Chunks that are invalid, out of place or repeated are left out, and reported as recoverable errors instead.
+#[derive(Debug, Clone, Default)]
+pub struct ImageMetadata {
+    /// From gAMA chunk, image gamma times 100000. See [crate::color_chunks::COLOR_VALUE_SCALE]
+    pub gamma: Option<u32>,
+    /// From cHRM chunk
+    pub chromaticities: Option<Chromaticities>,
+    /// From sRGB chunk. Set when the image is in the sRGB color space
+    pub srgb_rendering_intent: Option<RenderingIntent>,
+    /// From iCCP chunk
+    pub icc_profile: Option<IccProfile>,
+    /// From tEXt, zTXt and iTXt chunks, in the order they appear
+    pub text_entries: Vec<TextEntry>,
+    /// From bKGD chunk
+    pub background_color: Option<BackgroundColor>,
+    /// From pHYs chunk. See [PhysicalDimensions::dpi]
+    pub physical_dimensions: Option<PhysicalDimensions>,
+    /// From oFFs chunk
+    pub offset: Option<ImageOffset>,
+    /// From sCAL chunk
+    pub scale: Option<PhysicalScale>,
+    /// From tIME chunk
+    pub timestamp: Option<Timestamp>,
+    /// From sBIT chunk
+    pub significant_bits: Option<SignificantBits>,
+    /// From sPLT chunks, in the order they appear
+    pub suggested_palettes: Vec<SuggestedPalette>,
+    /// From hIST chunk, how often each palette entry appears in the image, relative to the others
+    pub palette_histogram: Option<Vec<u16>>,
+}
+
+impl ImageMetadata {
+    /// How the colors of the image should be interpreted. See [ColorSpace]
+    pub fn color_space(&self) -> ColorSpace<'_> {
+        if let Some(icc_profile) = &self.icc_profile {
+            return ColorSpace::IccProfile(icc_profile);
+        }
+        if let Some(rendering_intent) = self.srgb_rendering_intent {
+            return ColorSpace::Srgb(rendering_intent);
+        }
+        if self.gamma.is_some() || self.chromaticities.is_some() {
+            return ColorSpace::Calibrated {
+                gamma: self.gamma,
+                chromaticities: self.chromaticities,
+            };
+        }
+
+        ColorSpace::Unknown
+    }
+}
+
+/// Everything the decoder has found out about an image, returned from [crate::decoder::PngDecoder::run].
+#[derive(Debug, Clone)]
+pub struct DecodedImage {
+    /// Decoded pixels, row by row from the top left. Each pixel is laid out as described by `pixel_format`.
+    pub pixels: Vec<u8>,
+    /// Width of `pixels` in pixels
+    pub width: u32,
+    /// Height of `pixels` in pixels
+    pub height: u32,
+    pub pixel_format: PixelFormat,
+    pub header: ImageHeader,
+    pub metadata: ImageMetadata,
+    /// Recoverable errors found while decoding. An empty vector means the image was decoded without any errors.
+    pub errors: Vec<PngDecodeError>,
+}
+
+impl DecodedImage {
+    /// How many bytes there are per row of `pixels`
+    pub fn stride(&self) -> usize {
+        match self.pixel_format.bytes_per_pixel() {
+            Some(bytes_per_pixel) => self.width as usize * bytes_per_pixel,
+            None => {
+                let (_, bytes_per_line) = common::calc_bytes_per_pixel_and_line(
+                    colortype_to_channel(self.header.color_type),
+                    self.header.bit_depth,
+                    self.width,
+                );
+                bytes_per_line
+            }
+        }
+    }
+}
+
+/// A chunk found by [crate::decoder::PngDecoder::probe_chunks]
+#[derive(Debug, Clone)]
+pub struct ChunkInfo {
+    pub chunk_type: String,
+    /// Length of the chunk data, not including the length, chunk type and CRC
+    pub length: u32,
+    /// Where the chunk starts (at its length), counted from the start of the file
+    pub byte_pos: usize,
+}
+
+/// Type and data of an ancillary chunk (a chunk whose type starts with a lowercase letter),
+/// as it is stored in the image.
+#[derive(Debug, Clone)]
+pub struct AncillaryChunk {
+    pub chunk_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Everything [crate::decoder::PngDecoder::probe_chunks] has found out about an image without decoding its pixels.
+#[derive(Debug, Clone)]
+pub struct ProbedImage {
+    pub header: ImageHeader,
+    /// Every chunk in the order it appears in the image, including IHDR and IEND
+    pub chunks: Vec<ChunkInfo>,
+    pub ancillary_chunks: Vec<AncillaryChunk>,
+    pub metadata: ImageMetadata,
+    /// Recoverable errors found while probing
+    pub errors: Vec<PngDecodeError>,
+}
@@ -2,26 +2,60 @@
 use wasm_bindgen::prelude::wasm_bindgen;
 
 use crate::{
-    bitmap::{self, PixelType},
+    background::{self, AlphaFlattening},
+    bitmap::{self, PixelSamples, PixelType},
     byte_reader,
     chunk_helpers::{self, colortype_to_channel, ColorType, InterlaceMethod},
-    chunk_types, common,
-    deinterlace::{self, ReducedImage},
+    chunk_types,
+    color_chunks::{self, ColorConversion},
+    color_transform::{ColorProfile, ColorTransform},
+    common,
+    decoded_image::{
+        AncillaryChunk, ChunkInfo, DecodedImage, ImageHeader, ImageMetadata, PixelFormat,
+        ProbedImage,
+    },
+    deinterlace::{self, Adam7Pass, ReducedImage},
     errors::{self, PngDecodeErrorCode},
+    icc, palette_chunks, physical_chunks, significant_bits,
+    text_chunks::{self, TextEntry},
+    timestamp,
     trns::TransparencyChunk,
     unfilter, zlib,
 };
 
-pub struct PngDecoderResult {
-    pub bytes: Vec<u8>,
-    pub errors: errors::PngDecodeError,
-}
-
 #[wasm_bindgen]
 #[derive(Clone, Copy)]
 pub struct PngDecoderOptions {
     pub fail_fast: bool,
     pub validate_crc: bool,
+    /// Layout of the decoded pixels. Default: [PixelFormat::Rgba8]
+    pub output_format: PixelFormat,
+    /// Images wider than this are rejected. Default: 1,000,000 pixels
+    pub max_width: u32,
+    /// Images taller than this are rejected. Default: 1,000,000 pixels
+    pub max_height: u32,
+    /// Images whose decoded pixels take more bytes than this in `output_format` are rejected.
+    /// Default: 2^30 bytes, for example 16,384 by 16,384 pixels of [PixelFormat::Rgba8]
+    pub max_output_bytes: u64,
+    /// Decompression stops with an error once the image data inflates to more than this many bytes. Default: 2^32 bytes
+    pub max_inflated_bytes: u64,
+    /// Chunks whose data is longer than this are rejected. Default: 2^31 - 1 bytes, the maximum PNG allows
+    pub max_chunk_length: u32,
+    /// Decoding stops with an error once the data of all ancillary chunks adds up to more than this many bytes.
+    /// Default: 8,000,000 bytes
+    pub max_ancillary_bytes: u64,
+    /// Converts the decoded colors into another color space, using iCCP, sRGB, gAMA and cHRM chunks of the image.
+    /// Not applied to [PixelFormat::Native]. Default: [ColorConversion::None]
+    pub color_conversion: ColorConversion,
+    /// Composites transparent pixels onto a background color, after the colors have been converted.
+    /// Not applied to [PixelFormat::Native]. Default: [AlphaFlattening::None]
+    pub alpha_flattening: AlphaFlattening,
+    /// `0xRRGGBB`, in the color space of the output. See [AlphaFlattening]. Default: `0xFFFFFF` (white)
+    pub background_color: u32,
+    /// Rescales samples with the significant bits from sBIT chunk before anything else is done with them,
+    /// so that, for example, the 5-bit samples of an 8-bit image range from 0 to 255 instead of from 0 to 248.
+    /// Not applied to [PixelFormat::Native]. Default: false
+    pub rescale_significant_bits: bool,
 }
 
 #[wasm_bindgen]
@@ -31,15 +65,41 @@ impl PngDecoderOptions {
         PngDecoderOptions {
             fail_fast,
             validate_crc,
+            output_format: PixelFormat::Rgba8,
+            max_width: 1_000_000,
+            max_height: 1_000_000,
+            max_output_bytes: 1 << 30,
+            max_inflated_bytes: 1 << 32,
+            max_chunk_length: (1 << 31) - 1,
+            max_ancillary_bytes: 8_000_000,
+            color_conversion: ColorConversion::None,
+            alpha_flattening: AlphaFlattening::None,
+            background_color: 0xFFFFFF,
+            rescale_significant_bits: false,
         }
     }
 }
 
-pub struct PngDecoder<'a> {
+impl Default for PngDecoderOptions {
+    fn default() -> Self {
+        PngDecoderOptions::new(false, true)
+    }
+}
+
+/// Called with the pass number and a preview of an interlaced image. See [PngDecoder::set_pass_callback]
+type PassCallback<'a> = Box<dyn FnMut(u8, &[u8]) + 'a>;
+
+/// Decodes the data of one type of chunk. See [PngDecoder::decode_chunk]
+type ChunkDecodingFn<'a, 'r> =
+    fn(&mut PngDecoder<'a, 'r>, &Vec<u8>) -> Result<(), PngDecodeErrorCode>;
+
+/// * `'a` - lifetime of the borrowed byte reader and options
+/// * `'r` - lifetime of whatever the byte reader itself borrows (a file path or a reader)
+pub struct PngDecoder<'a, 'r> {
     /// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.IHDR
-    /// 
+    ///
     /// PNG four-byte unsigned integers are limited to the range 0 to (2^31)-1 to accommodate languages that have difficulty with unsigned four-byte values. Thus it makes sense to use u32 for width and height.
-    /// 
+    ///
     /// Width in pixels from IHDR chunk
     width: Option<u32>,
     /// height in pixels from IHDR chunk
@@ -82,16 +142,29 @@ pub struct PngDecoder<'a> {
     has_alpha_channel: Option<bool>,
     /// alpha information from tRNS chunk if available
     transparency_chunk: Option<TransparencyChunk>,
+    /// values of the ancillary chunks decoded so far
+    metadata: ImageMetadata,
+    /// Prepared from `metadata` once the first IDAT chunk is reached. See [PngDecoderOptions::color_conversion]
+    color_transform: Option<ColorTransform>,
+    /// See [PngDecoder::set_destination_profile]
+    destination_profile: Option<ColorProfile>,
+    /// Prepared once the first IDAT chunk is reached, already converted to the output color space.
+    /// See [PngDecoderOptions::alpha_flattening]
+    background: Option<PixelSamples>,
     pixel_type: Option<PixelType>,
     /// Byte reader. Takes care of reading the raw bytes from the input file/raw pixels.
     /// PngDecoder can do that as well, but separated into a different impl for separation of concerns, so that PngDecoder only focuses on parsing the actual PNG data.
-    byte_reader: &'a mut byte_reader::ByteReader<'a>,
+    ///
+    /// `None` when the bytes are pushed into the decoder by [crate::push_decoder::PngPushDecoder] instead.
+    byte_reader: Option<&'a mut byte_reader::ByteReader<'r>>,
+    /// Position of the last byte pushed into the decoder. Only used when there is no `byte_reader`.
+    pushed_byte_pos: usize,
     /// zlib stream to decompress raw image data
     zlib_decompress_stream: zlib::ZlibDecompressStream,
     // final unfiltered output
     unfiltered_output: Vec<u8>,
     /// cli params
-    decoder_options: &'a PngDecoderOptions,
+    decoder_options: PngDecoderOptions,
     /// the decoder manages errors throughout the program
     /// using ErrorManager.
     multi_errors_manager: errors::MultiErrorsManager,
@@ -101,13 +174,32 @@ pub struct PngDecoder<'a> {
     /// How many bytes are there per line (`bytes_per_line * height = entire image bytes`).
     /// this does NOT include the filter byte, meaning that the decoder will need to account for it when unfiltering the image.
     bytes_per_line: usize,
+    /// Called with a preview of an interlaced image each time a pass has been decompressed.
+    /// See [PngDecoder::set_pass_callback]
+    pass_callback: Option<PassCallback<'a>>,
+    /// Full-size preview of an interlaced image, updated after each pass
+    pass_preview: Vec<u8>,
+    /// How many passes of an interlaced image have been added to `pass_preview`
+    completed_pass_count: u8,
+    /// Total length of the ancillary chunks encountered so far. See [PngDecoderOptions::max_ancillary_bytes]
+    ancillary_byte_count: u64,
 }
 
-impl<'a> PngDecoder<'a> {
+impl<'a, 'r> PngDecoder<'a, 'r> {
     pub fn new(
-        byte_reader: &'a mut byte_reader::ByteReader<'a>,
+        byte_reader: &'a mut byte_reader::ByteReader<'r>,
         decoder_options: &'a PngDecoderOptions,
-    ) -> PngDecoder<'a> {
+    ) -> PngDecoder<'a, 'r> {
+        PngDecoder::with_byte_reader(
+            Some(byte_reader),
+            *decoder_options,
+        )
+    }
+
+    fn with_byte_reader(
+        byte_reader: Option<&'a mut byte_reader::ByteReader<'r>>,
+        decoder_options: PngDecoderOptions,
+    ) -> PngDecoder<'a, 'r> {
         PngDecoder {
             width: None,
             height: None,
@@ -124,9 +216,17 @@ impl<'a> PngDecoder<'a> {
             has_plte: false,
             has_alpha_channel: None,
             transparency_chunk: None,
+            metadata: ImageMetadata::default(),
+            color_transform: None,
+            destination_profile: None,
+            background: None,
             pixel_type: None,
             byte_reader,
-            zlib_decompress_stream: zlib::ZlibDecompressStream::new(None),
+            pushed_byte_pos: 0,
+            zlib_decompress_stream: zlib::ZlibDecompressStream::new(
+                None,
+                decoder_options.max_inflated_bytes,
+            ),
             unfiltered_output: vec![],
             decoder_options,
             multi_errors_manager: errors::MultiErrorsManager::new(
@@ -134,32 +234,256 @@ impl<'a> PngDecoder<'a> {
             ),
             bytes_per_pixel: 0,
             bytes_per_line: 0,
+            pass_callback: None,
+            pass_preview: vec![],
+            completed_pass_count: 0,
+            ancillary_byte_count: 0,
+        }
+    }
+
+    /// Sets a callback for progressive rendering of interlaced (Adam7) images.
+    ///
+    /// Each time one of the seven passes has been decompressed, the callback is called with
+    /// the pass number (1 to 7) and a full-size preview. The pixels that haven't arrived yet are filled by replicating
+    /// the pixels that have, so the preview gets sharper with each pass, and the last preview is the decoded image itself.
+    ///
+    /// The preview is laid out as described by [PngDecoderOptions::output_format],
+    /// except that [PixelFormat::Rgba8] is used instead of [PixelFormat::Native].
+    /// The callback is never called for non-interlaced images.
+    pub fn set_pass_callback(&mut self, pass_callback: impl FnMut(u8, &[u8]) + 'a) {
+        self.pass_callback = Some(Box::new(pass_callback));
+    }
+
+    /// Sets the ICC profile [ColorConversion::DestinationProfile] converts the colors to,
+    /// such as the profile of the display or printer. Only matrix/TRC RGB and greyscale profiles are supported,
+    /// and `_52` is returned for any other profile.
+    /// With a greyscale profile, colored pixels are converted to their luminance.
+    pub fn set_destination_profile(
+        &mut self,
+        icc_profile: &[u8],
+    ) -> Result<(), PngDecodeErrorCode> {
+        let destination_profile =
+            icc::parse_icc_profile(icc_profile).map_err(PngDecodeErrorCode::_52)?;
+        self.destination_profile = Some(destination_profile);
+
+        Ok(())
+    }
+
+    /// Lets the decoder report the right location in errors while bytes are pushed into it.
+    pub(crate) fn set_pushed_byte_pos(&mut self, pushed_byte_pos: usize) {
+        self.pushed_byte_pos = pushed_byte_pos;
+    }
+
+    pub(crate) fn has_ihdr(&self) -> bool {
+        self.has_ihdr
+    }
+
+    pub(crate) fn get_errors(&self) -> &Vec<errors::PngDecodeError> {
+        self.multi_errors_manager.get_errors()
+    }
+
+    pub(crate) fn get_metadata(&self) -> &ImageMetadata {
+        &self.metadata
+    }
+
+    /// For when IDAT chunks are read without [PngDecoder::decode_idat_chunk],
+    /// so that chunks that must come before them can still be checked
+    pub(crate) fn mark_idat_seen(&mut self) -> Result<(), PngDecodeErrorCode> {
+        if !self.has_idat {
+            self.prepare_color_transform()?;
+            self.prepare_background();
+        }
+        self.has_idat = true;
+
+        Ok(())
+    }
+
+    /// Color chunks must come before IDAT chunks, so the metadata they give is complete once IDAT chunks are reached.
+    /// An ICC profile that can't be used is reported, and the other color chunks are used instead
+    fn prepare_color_transform(&mut self) -> Result<(), PngDecodeErrorCode> {
+        let destination = match self.decoder_options.color_conversion {
+            ColorConversion::None => return Ok(()),
+            ColorConversion::Srgb => ColorProfile::srgb(),
+            ColorConversion::Linear => ColorProfile::linear_srgb(),
+            ColorConversion::DestinationProfile => self
+                .destination_profile
+                .clone()
+                .unwrap_or_else(ColorProfile::srgb),
+        };
+        let pixel_type = match self.pixel_type {
+            Some(pixel_type) if self.decoder_options.output_format != PixelFormat::Native => {
+                pixel_type
+            }
+            _ => return Ok(()),
+        };
+
+        let icc_profile = match &self.metadata.icc_profile {
+            Some(icc_profile) => match icc::parse_icc_profile(&icc_profile.profile) {
+                Ok(color_profile) if color_profile.is_greyscale() != pixel_type.is_greyscale() => {
+                    let reason = match pixel_type.is_greyscale() {
+                        true => "an RGB profile doesn't fit a greyscale image",
+                        false => "a greyscale profile doesn't fit a color image",
+                    };
+                    self.create_recoverable_error(PngDecodeErrorCode::_52(
+                        reason.to_string(),
+                    ))?;
+                    None
+                }
+                Ok(color_profile) => Some(color_profile),
+                Err(reason) => {
+                    self.create_recoverable_error(PngDecodeErrorCode::_52(
+                        reason,
+                    ))?;
+                    None
+                }
+            },
+            None => None,
+        };
+        let source = ColorProfile::from_metadata(
+            &self.metadata,
+            icc_profile,
+            pixel_type,
+        );
+        self.color_transform = ColorTransform::new(
+            &source,
+            &destination,
+            pixel_type,
+        );
+
+        Ok(())
+    }
+
+    /// Must be called after [PngDecoder::prepare_color_transform], because the color of bKGD chunk is converted
+    /// just like the pixels are
+    fn prepare_background(&mut self) {
+        let pixel_type = match self.pixel_type {
+            Some(pixel_type) if self.decoder_options.output_format != PixelFormat::Native => {
+                pixel_type
+            }
+            _ => return,
+        };
+        let image_background = self
+            .metadata
+            .background_color
+            .and_then(|background_color| {
+                background_color.to_pixel_samples(
+                    pixel_type,
+                    self.palette.as_ref(),
+                )
+            })
+            .map(
+                |samples| match &self.color_transform {
+                    Some(color_transform) => color_transform.apply(samples),
+                    None => samples,
+                },
+            );
+        let background_color =
+            background::rgb_to_pixel_samples(self.decoder_options.background_color);
+
+        self.background = match self.decoder_options.alpha_flattening {
+            AlphaFlattening::None => None,
+            AlphaFlattening::ImageBackground => Some(image_background.unwrap_or(background_color)),
+            AlphaFlattening::BackgroundColor => Some(background_color),
+        };
+    }
+
+    /// Reads the `col_index`th pixel of `scanline`, rescaled as [PngDecoderOptions::rescale_significant_bits] asks,
+    /// converted as [PngDecoderOptions::color_conversion] asks, and flattened as [PngDecoderOptions::alpha_flattening] asks
+    fn read_pixel_samples(
+        &self,
+        pixel_type: PixelType,
+        col_index: usize,
+        scanline: &[u8],
+    ) -> Result<PixelSamples, PngDecodeErrorCode> {
+        let samples = bitmap::to_pixel_samples(
+            pixel_type,
+            self.transparency_chunk.as_ref(),
+            self.palette.as_ref(),
+            col_index,
+            scanline,
+        )?;
+
+        let samples = match self.metadata.significant_bits {
+            Some(significant_bits) if self.decoder_options.rescale_significant_bits => {
+                significant_bits::rescale(samples, significant_bits)
+            }
+            _ => samples,
+        };
+        let samples = match &self.color_transform {
+            Some(color_transform) => color_transform.apply(samples),
+            None => samples,
+        };
+
+        Ok(match &self.background {
+            Some(background) => background::composite(samples, background),
+            None => samples,
+        })
+    }
+
+    /// Bytes decompressed from the IDAT chunks so far, including filter bytes.
+    /// Once IEND chunk has been decoded, also the zeros that missing rows have been padded with
+    pub(crate) fn get_image_data(&self) -> &[u8] {
+        self.zlib_decompress_stream.get_padded_data()
+    }
+
+    /// How many bytes there are per scanline, not including the filter byte
+    pub(crate) fn get_bytes_per_line(&self) -> usize {
+        self.bytes_per_line
+    }
+
+    /// Creates an [unfilter::UnfilterProcessor] for unfiltering a non-interlaced image row by row
+    pub(crate) fn create_row_unfilter_processor(
+        &self,
+    ) -> Result<unfilter::UnfilterProcessor, PngDecodeErrorCode> {
+        Ok(
+            unfilter::UnfilterProcessor::new(
+                self.height.ok_or(PngDecodeErrorCode::_38)?,
+                self.bytes_per_pixel,
+                self.bytes_per_line,
+            ),
+        )
+    }
+
+    fn get_current_byte_pos(&self) -> usize {
+        match &self.byte_reader {
+            Some(byte_reader) => byte_reader.get_current_byte_pos(),
+            None => self.pushed_byte_pos,
         }
     }
 
-    fn create_recoverable_error(&mut self, code: errors::PngDecodeErrorCode) -> errors::PngDecodeError {
+    /// Records an error that decoding can go on after.
+    /// Returns the error instead when [PngDecoderOptions::fail_fast] is set, so that decoding stops there.
+    pub(crate) fn create_recoverable_error(
+        &mut self,
+        code: errors::PngDecodeErrorCode,
+    ) -> Result<(), PngDecodeErrorCode> {
         let err = errors::PngDecodeError::new(
             code,
-            self.byte_reader.get_current_byte_pos(),
+            self.get_current_byte_pos(),
         );
-        self.multi_errors_manager.handle_err(err.clone());
 
-        return err;
+        self.multi_errors_manager.handle_err(err)
     }
 
     /// Checks if magic header is correct.
     /// https://www.w3.org/TR/PNG-Rationale.html#R.PNG-file-signature
-    fn read_header(&mut self) -> Result<(), errors::PngDecodeError> {
-        let buffer: Vec<u8> = self.byte_reader.read_next_n_bytes(8);
-        return match buffer[0..8]
-            .try_into()
-            .expect("PNG header buffer should have length of 8")
-        {
-            chunk_helpers::PNG_HEADER => Ok(()),
-            invalid_header => {
-                Err(self.create_recoverable_error(errors::PngDecodeErrorCode::_1(invalid_header)))
+    pub(crate) fn read_header(&mut self) -> Result<(), PngDecodeErrorCode> {
+        let buffer: Vec<u8> = self.get_byte_reader()?.read_next_n_bytes(8)?;
+        self.validate_header(&buffer)
+    }
+
+    /// An invalid signature is only a recoverable error
+    pub(crate) fn validate_header(&mut self, buffer: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        match <[u8; 8]>::try_from(buffer) {
+            Ok(chunk_helpers::PNG_HEADER) => Ok(()),
+            Ok(invalid_header) => {
+                self.create_recoverable_error(errors::PngDecodeErrorCode::_1(invalid_header))
             }
-        };
+            Err(_) => Err(PngDecodeErrorCode::_35(
+                8,
+                buffer.len() as u64,
+            )),
+        }
     }
 
     fn finalize_at_iend_chunk(&mut self) -> Result<(), PngDecodeErrorCode> {
@@ -168,7 +492,7 @@ impl<'a> PngDecoder<'a> {
         }
         let color_type = self
             .color_type
-            .expect("Color type must have been obtained already from IHDR chunk");
+            .ok_or(PngDecodeErrorCode::_38)?;
 
         self.has_alpha_channel =
             Some(chunk_helpers::does_colortype_support_alpha_channel(color_type));
@@ -183,18 +507,107 @@ impl<'a> PngDecoder<'a> {
         Ok(())
     }
 
+    /// Rejects dimensions over the limits in [PngDecoderOptions], before anything is allocated for them
+    fn check_dimension_limits(
+        &self,
+        width: u32,
+        height: u32,
+        color_type: chunk_helpers::ColorType,
+        bit_depth: u8,
+    ) -> Result<(), PngDecodeErrorCode> {
+        if width > self.decoder_options.max_width {
+            return Err(PngDecodeErrorCode::_28(
+                width,
+                self.decoder_options.max_width,
+            ));
+        }
+        if height > self.decoder_options.max_height {
+            return Err(PngDecodeErrorCode::_29(
+                height,
+                self.decoder_options.max_height,
+            ));
+        }
+        // native pixels are the unfiltered rows, with as many bytes per row as the image data has
+        let bytes_per_row = match self.decoder_options.output_format.bytes_per_pixel() {
+            Some(bytes_per_pixel) => width as u64 * bytes_per_pixel as u64,
+            None => {
+                common::calc_bytes_per_pixel_and_line(
+                    colortype_to_channel(color_type),
+                    bit_depth,
+                    width,
+                )
+                .1 as u64
+            }
+        };
+        let output_bytes = bytes_per_row * height as u64;
+        if output_bytes > self.decoder_options.max_output_bytes {
+            return Err(PngDecodeErrorCode::_30(
+                output_bytes,
+                self.decoder_options.max_output_bytes,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Rejects a chunk over the limits in [PngDecoderOptions], before its data is read.
+    /// Must be called exactly once for each chunk, because the length of ancillary chunks is added up.
+    pub(crate) fn check_chunk_limits(
+        &mut self,
+        chunk_type: &str,
+        chunk_data_length: u32,
+    ) -> Result<(), PngDecodeErrorCode> {
+        if chunk_data_length > self.decoder_options.max_chunk_length {
+            return Err(PngDecodeErrorCode::_32(
+                chunk_type.to_string(),
+                chunk_data_length,
+                self.decoder_options.max_chunk_length,
+            ));
+        }
+        // the 5th bit of the first byte (lowercase) tells that the chunk is ancillary
+        if chunk_type.starts_with(|c: char| c.is_ascii_lowercase()) {
+            self.ancillary_byte_count += chunk_data_length as u64;
+            if self.ancillary_byte_count > self.decoder_options.max_ancillary_bytes {
+                return Err(PngDecodeErrorCode::_33(
+                    self.decoder_options.max_ancillary_bytes,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the length and type of the next chunk, and checks them against the limits.
+    /// Returns `(chunk data length, chunk type, chunk type bytes)`
+    pub(crate) fn read_chunk_length_and_type(
+        &mut self,
+    ) -> Result<(u32, String, Vec<u8>), PngDecodeErrorCode> {
+        let chunk_data_length = self.get_byte_reader()?.read_next_4bytes_num()?;
+        let (chunk_type, chunk_type_bytes) = self.get_byte_reader()?.read_next_4bytes_str()?;
+        self.check_chunk_limits(&chunk_type, chunk_data_length)?;
+
+        Ok((
+            chunk_data_length,
+            chunk_type,
+            chunk_type_bytes,
+        ))
+    }
+
     /// validates ihdr chunk and returns bit depth, color type, compression method, filter method, interlace method
     /// in order
     fn validate_ihdr_chunk(
         &mut self,
         chunk: &Vec<u8>,
-    ) -> Result<(
-        u8,
-        chunk_helpers::ColorType,
-        chunk_helpers::CompressionMethod,
-        chunk_helpers::FilterMethod,
-        chunk_helpers::InterlaceMethod,
-    ), errors::PngDecodeErrorCode> {
+    ) -> Result<
+        (
+            u8,
+            chunk_helpers::ColorType,
+            chunk_helpers::CompressionMethod,
+            chunk_helpers::FilterMethod,
+            chunk_helpers::InterlaceMethod,
+        ),
+        errors::PngDecodeErrorCode,
+    > {
         let ihdr_chunk_data_length = chunk.len();
         if ihdr_chunk_data_length != 13 {
             return Err(errors::PngDecodeErrorCode::_3(ihdr_chunk_data_length));
@@ -216,7 +629,7 @@ impl<'a> PngDecoder<'a> {
         let compression_method: chunk_helpers::CompressionMethod = match chunk[10].try_into() {
             Ok(compression_method) => compression_method,
             Err(_) => {
-                self.create_recoverable_error(errors::PngDecodeErrorCode::_10(chunk[10]));
+                self.create_recoverable_error(errors::PngDecodeErrorCode::_10(chunk[10]))?;
 
                 chunk_helpers::CompressionMethod::Deflate
             }
@@ -225,7 +638,7 @@ impl<'a> PngDecoder<'a> {
         let filter_method = match chunk[11].try_into() {
             Ok(filter_method) => filter_method,
             Err(_) => {
-                self.create_recoverable_error(errors::PngDecodeErrorCode::_11(chunk[11]));
+                self.create_recoverable_error(errors::PngDecodeErrorCode::_11(chunk[11]))?;
 
                 chunk_helpers::FilterMethod::Adaptive
             }
@@ -258,13 +671,29 @@ impl<'a> PngDecoder<'a> {
     ///
     /// Interlace method:   1 byte
     fn decode_ihdr_chunk(&mut self, chunk: &Vec<u8>) -> Result<(), PngDecodeErrorCode> {
+        if self.has_ihdr {
+            return Err(PngDecodeErrorCode::_40);
+        }
         let (bit_depth, color_type, compression_method, filter_method, interlace_method) =
             self.validate_ihdr_chunk(chunk)?;
 
+        let width = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        let height = u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        if width == 0
+            || height == 0
+            || width > chunk_helpers::MAX_DIMENSION
+            || height > chunk_helpers::MAX_DIMENSION
+        {
+            return Err(PngDecodeErrorCode::_57(
+                width, height,
+            ));
+        }
+        self.check_dimension_limits(
+            width, height, color_type, bit_depth,
+        )?;
+
         self.has_ihdr = true;
-        let width = self.byte_reader.read_next_u32_num(&chunk[0..4].to_vec());
         self.width = Some(width);
-        let height = self.byte_reader.read_next_u32_num(&chunk[4..8].to_vec());
         self.height = Some(height);
         self.bit_depth = Some(bit_depth);
         self.color_type = Some(color_type);
@@ -302,12 +731,11 @@ impl<'a> PngDecoder<'a> {
 
         let color_type = self
             .color_type
-            .expect("Color type must have been obtained from IHDR chunk when parsing PLTE chunk");
+            .ok_or(PngDecodeErrorCode::_38)?;
 
         match color_type {
-            // we're not using the returned value
             chunk_helpers::ColorType::Greyscale | chunk_helpers::ColorType::GreyscaleAlpha => {
-                drop(self.create_recoverable_error(errors::PngDecodeErrorCode::_6(color_type)))
+                self.create_recoverable_error(errors::PngDecodeErrorCode::_6(color_type))?
             }
             _ => (),
         };
@@ -332,6 +760,10 @@ impl<'a> PngDecoder<'a> {
 
         self.has_plte = true;
         self.palette = Some(chunk.to_vec());
+        // bKGD chunk of a truecolor image may only come before PLTE chunk when there is no PLTE chunk at all
+        if self.metadata.background_color.take().is_some() {
+            self.report_misplaced_bkgd_chunk()?;
+        }
 
         Ok(())
     }
@@ -350,10 +782,13 @@ impl<'a> PngDecoder<'a> {
     /// A PNG file in which each IDAT chunk contains only one data byte is valid,
     /// though remarkably wasteful of space.
     /// (For that matter, zero-length IDAT chunks are valid, though even more wasteful.)
-    fn decode_idat_chunk(&mut self, chunk: &Vec<u8>) -> Result<(), errors::PngDecodeErrorCode> {
+    pub(crate) fn decode_idat_chunk(
+        &mut self,
+        chunk: &Vec<u8>,
+    ) -> Result<(), errors::PngDecodeErrorCode> {
         // avoid accessing empty IDAT chunk
         if chunk.len() == 0 {
-            self.has_idat = true;
+            self.mark_idat_seen()?;
             return Ok(());
         }
         if self.zlib_compression_method.is_none() && chunk.len() > 0 {
@@ -365,15 +800,131 @@ impl<'a> PngDecoder<'a> {
             self.additional_flag_after_compression_method = Some(chunk[1]);
         }
 
-        self.zlib_decompress_stream.decompress(&chunk)?;
-        if !self.has_idat {
-            self.has_idat = true
+        match self.zlib_decompress_stream.decompress(&chunk) {
+            // the image data has been decompressed all the same
+            Err(PngDecodeErrorCode::_54) => {
+                self.create_recoverable_error(PngDecodeErrorCode::_54)?
+            }
+            result => result?,
+        }
+        self.mark_idat_seen()?;
+        if self.pass_callback.is_some() {
+            self.preview_completed_passes()?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds every pass of an interlaced image that has been decompressed since the last call to the preview,
+    /// and calls the pass callback for each of them.
+    fn preview_completed_passes(&mut self) -> Result<(), errors::PngDecodeErrorCode> {
+        if !self.has_ihdr {
+            return Ok(());
+        }
+        if let Some(InterlaceMethod::None) = self.interlace_method {
+            return Ok(());
+        }
+        let width = self.width.ok_or(PngDecodeErrorCode::_38)?;
+        let height = self.height.ok_or(PngDecodeErrorCode::_38)?;
+        let pixel_type = self.pixel_type.ok_or(PngDecodeErrorCode::_38)?;
+        let preview_format = match self.decoder_options.output_format {
+            PixelFormat::Native => PixelFormat::Rgba8,
+            output_format => output_format,
+        };
+        let preview_bytes_per_pixel = preview_format
+            .bytes_per_pixel()
+            .ok_or(PngDecodeErrorCode::_55)?;
+        if self.pass_preview.is_empty() {
+            self.pass_preview = vec![0; width as usize * height as usize * preview_bytes_per_pixel];
+        }
+
+        let reduced_images = deinterlace::create_reduced_images(
+            width,
+            height,
+            colortype_to_channel(self.color_type.ok_or(PngDecodeErrorCode::_38)?),
+            self.bit_depth.ok_or(PngDecodeErrorCode::_38)?,
+        );
+        // including the filter bytes. Empty passes have no filter bytes either
+        let filtered_pass_length = |reduced_image: &ReducedImage| match reduced_image.bytes_per_line
+        {
+            0 => 0,
+            bytes_per_line => reduced_image.pixel_height as usize * (bytes_per_line + 1),
+        };
+        let mut pass_start: usize = reduced_images[..self.completed_pass_count as usize]
+            .iter()
+            .map(filtered_pass_length)
+            .sum();
+        let decompressed_data = &self.zlib_decompress_stream.get_out_buffer()
+            [..self.zlib_decompress_stream.get_decompressed_length()];
+        let mut pixel = vec![0u8; preview_bytes_per_pixel];
+
+        while self.completed_pass_count < 7 {
+            let reduced_image = reduced_images[self.completed_pass_count as usize];
+            let nth_pass = Adam7Pass::ALL[self.completed_pass_count as usize];
+            let pass_length = filtered_pass_length(&reduced_image);
+            if decompressed_data.len() < pass_start + pass_length {
+                break;
+            }
+
+            if pass_length > 0 {
+                let mut unfiltered_pass =
+                    vec![0u8; reduced_image.pixel_height as usize * reduced_image.bytes_per_line];
+                unfilter::UnfilterProcessor::new(
+                    reduced_image.pixel_height,
+                    reduced_image.bytes_per_pixel,
+                    reduced_image.bytes_per_line,
+                )
+                .unfilter(
+                    &decompressed_data[pass_start..pass_start + pass_length],
+                    &mut unfiltered_pass,
+                )?;
+
+                for (row_index, scanline) in unfiltered_pass
+                    .chunks_exact(reduced_image.bytes_per_line)
+                    .enumerate()
+                {
+                    for col_index in 0..reduced_image.pixel_width as usize {
+                        let samples = self.read_pixel_samples(
+                            pixel_type, col_index, scanline,
+                        )?;
+                        bitmap::write_pixel(
+                            preview_format,
+                            &samples,
+                            &mut pixel,
+                        )?;
+                        let (x, y) = deinterlace::calc_interlaced_pixel_position(
+                            col_index, row_index, nth_pass,
+                        );
+                        deinterlace::replicate_pixel_over_block(
+                            &mut self.pass_preview,
+                            width,
+                            height,
+                            x,
+                            y,
+                            nth_pass,
+                            &pixel,
+                        );
+                    }
+                }
+            }
+
+            pass_start += pass_length;
+            self.completed_pass_count = nth_pass.number();
+            if let Some(pass_callback) = self.pass_callback.as_mut() {
+                pass_callback(
+                    nth_pass.number(),
+                    &self.pass_preview,
+                );
+            }
         }
 
         Ok(())
     }
 
-    fn validate_trns_chunk(&mut self, chunk: &Vec<u8>) -> Result<(ColorType, PixelType), errors::PngDecodeErrorCode> {
+    fn validate_trns_chunk(
+        &mut self,
+        chunk: &Vec<u8>,
+    ) -> Result<(ColorType, PixelType), errors::PngDecodeErrorCode> {
         let color_type = match self.color_type {
             Some(ct) => ct,
             _ => {
@@ -382,7 +933,7 @@ impl<'a> PngDecoder<'a> {
         };
         let expected_chunk_length = chunk_helpers::colortype_to_alpha_byte_length(color_type);
         if chunk.len() != expected_chunk_length as usize && expected_chunk_length != 0 {
-            self.create_recoverable_error(errors::PngDecodeErrorCode::_21(color_type, chunk.len()));
+            self.create_recoverable_error(errors::PngDecodeErrorCode::_21(color_type, chunk.len()))?;
         }
 
         let pixel_type = match self.pixel_type {
@@ -404,66 +955,472 @@ impl<'a> PngDecoder<'a> {
             }
             _ => {
                 // recoverable error. so don't return error here
-                self.create_recoverable_error(errors::PngDecodeErrorCode::_20);
+                self.create_recoverable_error(errors::PngDecodeErrorCode::_20)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// gAMA, cHRM, sRGB, iCCP and sBIT chunks must come before PLTE and IDAT chunks, and must not appear more than once.
+    /// Returns false (after recording why) when the chunk must be ignored.
+    fn check_color_chunk_position(
+        &mut self,
+        chunk_type: &str,
+        has_appeared: bool,
+    ) -> Result<bool, PngDecodeErrorCode> {
+        if self.has_plte || self.has_idat {
+            self.create_recoverable_error(PngDecodeErrorCode::_48(
+                chunk_type.to_string(),
+                "before PLTE and IDAT chunks".to_string(),
+            ))?;
+            return Ok(false);
+        }
+        if has_appeared {
+            self.create_recoverable_error(PngDecodeErrorCode::_49(
+                chunk_type.to_string(),
+            ))?;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// Records why an ancillary chunk is invalid, so that decoding goes on without it
+    fn ignore_invalid_chunk<T>(
+        &mut self,
+        parsed_chunk: Result<T, PngDecodeErrorCode>,
+    ) -> Result<Option<T>, PngDecodeErrorCode> {
+        match parsed_chunk {
+            Ok(value) => Ok(Some(value)),
+            Err(code) => {
+                self.create_recoverable_error(code)?;
+                Ok(None)
             }
         }
+    }
+
+    fn decode_gama_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        if self.check_color_chunk_position(
+            chunk_types::ChunkTypes::gAMA,
+            self.metadata.gamma.is_some(),
+        )? {
+            self.metadata.gamma =
+                self.ignore_invalid_chunk(color_chunks::parse_gama_chunk(chunk))?;
+        }
+
+        Ok(())
+    }
+
+    fn decode_chrm_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        if self.check_color_chunk_position(
+            chunk_types::ChunkTypes::cHRM,
+            self.metadata.chromaticities.is_some(),
+        )? {
+            self.metadata.chromaticities =
+                self.ignore_invalid_chunk(color_chunks::parse_chrm_chunk(chunk))?;
+        }
+
+        Ok(())
+    }
+
+    fn decode_srgb_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        if !self.check_color_chunk_position(
+            chunk_types::ChunkTypes::sRGB,
+            self.metadata.srgb_rendering_intent.is_some(),
+        )? {
+            return Ok(());
+        }
+        self.metadata.srgb_rendering_intent =
+            self.ignore_invalid_chunk(color_chunks::parse_srgb_chunk(chunk))?;
+        // both are kept, and ImageMetadata::color_space decides which one is used
+        if self.metadata.srgb_rendering_intent.is_some() && self.metadata.icc_profile.is_some() {
+            self.create_recoverable_error(PngDecodeErrorCode::_50)?;
+        }
+
+        Ok(())
+    }
+
+    /// The profile may not be longer than [PngDecoderOptions::max_ancillary_bytes] once it is decompressed
+    fn decode_iccp_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        if !self.check_color_chunk_position(
+            chunk_types::ChunkTypes::iCCP,
+            self.metadata.icc_profile.is_some(),
+        )? {
+            return Ok(());
+        }
+        let max_profile_length =
+            usize::try_from(self.decoder_options.max_ancillary_bytes).unwrap_or(usize::MAX);
+        self.metadata.icc_profile =
+            self.ignore_invalid_chunk(color_chunks::parse_iccp_chunk(chunk, max_profile_length))?;
+        if self.metadata.icc_profile.is_some() && self.metadata.srgb_rendering_intent.is_some() {
+            self.create_recoverable_error(PngDecodeErrorCode::_50)?;
+        }
+
+        Ok(())
+    }
+
+    fn decode_sbit_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        if self.check_color_chunk_position(
+            chunk_types::ChunkTypes::sBIT,
+            self.metadata.significant_bits.is_some(),
+        )? {
+            let pixel_type = self.pixel_type.ok_or(PngDecodeErrorCode::_38)?;
+            self.metadata.significant_bits =
+                self.ignore_invalid_chunk(significant_bits::parse_sbit_chunk(chunk, pixel_type))?;
+        }
+
+        Ok(())
+    }
+
+    /// bKGD chunk must come before IDAT chunks, and must not appear more than once.
+    /// It must come after PLTE chunk whenever there is one, also the suggested palette of truecolor images,
+    /// which is only known once PLTE chunk appears. See [PngDecoder::decode_plte_chunk].
+    /// Its palette index must point to an entry of PLTE chunk
+    fn decode_bkgd_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        let pixel_type = self.pixel_type.ok_or(PngDecodeErrorCode::_38)?;
+        if self.has_idat || (pixel_type.is_palette() && !self.has_plte) {
+            return self.report_misplaced_bkgd_chunk();
+        }
+        if self.metadata.background_color.is_some() {
+            self.create_recoverable_error(PngDecodeErrorCode::_49(
+                chunk_types::ChunkTypes::bKGD.to_string(),
+            ))?;
+            return Ok(());
+        }
+
+        let palette_entries = self.palette.as_ref().map_or(0, |palette| palette.len() / 3);
+        self.metadata.background_color =
+            self.ignore_invalid_chunk(background::parse_bkgd_chunk(
+                chunk,
+                pixel_type,
+                palette_entries,
+            ))?;
+
+        Ok(())
+    }
+
+    fn report_misplaced_bkgd_chunk(&mut self) -> Result<(), PngDecodeErrorCode> {
+        self.create_recoverable_error(PngDecodeErrorCode::_48(
+            chunk_types::ChunkTypes::bKGD.to_string(),
+            "after PLTE chunk and before IDAT chunks".to_string(),
+        ))
+    }
+
+    /// pHYs, oFFs, sCAL and sPLT chunks must come before IDAT chunks, and must not appear more than once
+    /// (except for sPLT chunk, which is checked by its palette name instead).
+    /// Returns false (after recording why) when the chunk must be ignored.
+    fn check_chunk_before_idat(
+        &mut self,
+        chunk_type: &str,
+        has_appeared: bool,
+    ) -> Result<bool, PngDecodeErrorCode> {
+        if self.has_idat {
+            self.create_recoverable_error(PngDecodeErrorCode::_48(
+                chunk_type.to_string(),
+                "before IDAT chunks".to_string(),
+            ))?;
+            return Ok(false);
+        }
+        if has_appeared {
+            self.create_recoverable_error(PngDecodeErrorCode::_49(
+                chunk_type.to_string(),
+            ))?;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    fn decode_phys_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        if self.check_chunk_before_idat(
+            chunk_types::ChunkTypes::pHYs,
+            self.metadata.physical_dimensions.is_some(),
+        )? {
+            self.metadata.physical_dimensions =
+                self.ignore_invalid_chunk(physical_chunks::parse_phys_chunk(chunk))?;
+        }
+
+        Ok(())
+    }
+
+    fn decode_offs_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        if self.check_chunk_before_idat(
+            chunk_types::ChunkTypes::oFFs,
+            self.metadata.offset.is_some(),
+        )? {
+            self.metadata.offset =
+                self.ignore_invalid_chunk(physical_chunks::parse_offs_chunk(chunk))?;
+        }
+
+        Ok(())
+    }
+
+    fn decode_scal_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        if self.check_chunk_before_idat(
+            chunk_types::ChunkTypes::sCAL,
+            self.metadata.scale.is_some(),
+        )? {
+            self.metadata.scale =
+                self.ignore_invalid_chunk(physical_chunks::parse_scal_chunk(chunk))?;
+        }
+
+        Ok(())
+    }
+
+    /// Each sPLT chunk must have a palette name that no earlier sPLT chunk has
+    fn decode_splt_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        if !self.check_chunk_before_idat(
+            chunk_types::ChunkTypes::sPLT,
+            false,
+        )? {
+            return Ok(());
+        }
+        let suggested_palette =
+            match self.ignore_invalid_chunk(palette_chunks::parse_splt_chunk(chunk))? {
+                Some(suggested_palette) => suggested_palette,
+                None => return Ok(()),
+            };
+        if self
+            .metadata
+            .suggested_palettes
+            .iter()
+            .any(|palette| palette.name == suggested_palette.name)
+        {
+            self.create_recoverable_error(PngDecodeErrorCode::_51(
+                chunk_types::ChunkTypes::sPLT.to_string(),
+                format!(
+                    "palette name {:?} is already used by an earlier sPLT chunk",
+                    suggested_palette.name
+                ),
+            ))?;
+            return Ok(());
+        }
+        self.metadata.suggested_palettes.push(suggested_palette);
+
+        Ok(())
+    }
+
+    /// hIST chunk must come after PLTE chunk and before IDAT chunks, and must not appear more than once.
+    /// It must have a frequency for each entry of PLTE chunk
+    fn decode_hist_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        if self.has_idat || !self.has_plte {
+            self.create_recoverable_error(PngDecodeErrorCode::_48(
+                chunk_types::ChunkTypes::hIST.to_string(),
+                "after PLTE chunk and before IDAT chunks".to_string(),
+            ))?;
+            return Ok(());
+        }
+        if self.metadata.palette_histogram.is_some() {
+            self.create_recoverable_error(PngDecodeErrorCode::_49(
+                chunk_types::ChunkTypes::hIST.to_string(),
+            ))?;
+            return Ok(());
+        }
+
+        let palette_entries = self.palette.as_ref().map_or(0, |palette| palette.len() / 3);
+        self.metadata.palette_histogram =
+            self.ignore_invalid_chunk(palette_chunks::parse_hist_chunk(chunk, palette_entries))?;
+
+        Ok(())
+    }
+
+    /// tIME chunk may appear anywhere, but only once
+    fn decode_time_chunk(&mut self, chunk: &[u8]) -> Result<(), PngDecodeErrorCode> {
+        if self.metadata.timestamp.is_some() {
+            self.create_recoverable_error(PngDecodeErrorCode::_49(
+                chunk_types::ChunkTypes::tIME.to_string(),
+            ))?;
+            return Ok(());
+        }
+        self.metadata.timestamp = self.ignore_invalid_chunk(timestamp::parse_time_chunk(
+            chunk,
+        ))?;
+
+        Ok(())
+    }
+
+    /// Text chunks may appear anywhere and any number of times, so they are only checked for their content.
+    /// Compressed text may not be longer than [PngDecoderOptions::max_ancillary_bytes] once it is decompressed
+    fn decode_text_chunk(
+        &mut self,
+        parse_chunk: fn(&[u8], usize) -> Result<TextEntry, PngDecodeErrorCode>,
+        chunk: &[u8],
+    ) -> Result<(), PngDecodeErrorCode> {
+        let max_text_length =
+            usize::try_from(self.decoder_options.max_ancillary_bytes).unwrap_or(usize::MAX);
+        if let Some(text_entry) = self.ignore_invalid_chunk(parse_chunk(
+            chunk,
+            max_text_length,
+        ))? {
+            self.metadata.text_entries.push(text_entry);
+        }
 
         Ok(())
     }
 
     /// Validates CRC. Adds an error when there is a mismatch between
     /// calculated CRC and existing CRC in a PNG chunk.
-    fn validate_crc(&mut self, actual_chunk_crc: u32, chunk_type_and_chunk_data: &[u8]) {
+    fn validate_crc(
+        &mut self,
+        actual_chunk_crc: u32,
+        chunk_type_and_chunk_data: &[u8],
+    ) -> Result<(), PngDecodeErrorCode> {
         let expected_chunk_crc = crc32fast::hash(chunk_type_and_chunk_data);
 
+        self.compare_crc(
+            actual_chunk_crc,
+            expected_chunk_crc,
+        )
+    }
+
+    /// Same as [PngDecoder::validate_crc], for when the CRC has been calculated already
+    /// (for example, piece by piece as the chunk arrives).
+    pub(crate) fn compare_crc(
+        &mut self,
+        actual_chunk_crc: u32,
+        expected_chunk_crc: u32,
+    ) -> Result<(), PngDecodeErrorCode> {
         if actual_chunk_crc != expected_chunk_crc {
             self.create_recoverable_error(
                 errors::PngDecodeErrorCode::_7(
                     expected_chunk_crc,
                     actual_chunk_crc,
                 ),
-            );
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn get_byte_reader(
+        &mut self,
+    ) -> Result<&mut byte_reader::ByteReader<'r>, PngDecodeErrorCode> {
+        self.byte_reader
+            .as_deref_mut()
+            .ok_or(PngDecodeErrorCode::_56)
+    }
+
+    /// Reports an error if a chunk other than IHDR comes first.
+    pub(crate) fn check_first_chunk(&mut self, chunk_type: &str) -> Result<(), PngDecodeErrorCode> {
+        if !self.has_ihdr && chunk_type != chunk_types::ChunkTypes::IHDR {
+            self.create_recoverable_error(errors::PngDecodeErrorCode::_13(chunk_type.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Function that decodes the data of `chunk_type`, or `None` when the decoder doesn't use chunks of that type.
+    fn get_chunk_decoding_fn(chunk_type: &str) -> Option<ChunkDecodingFn<'a, 'r>> {
+        let decoding_fn: ChunkDecodingFn<'a, 'r> = match chunk_type {
+            chunk_types::ChunkTypes::IHDR => |decoder, chunk| decoder.decode_ihdr_chunk(chunk),
+            chunk_types::ChunkTypes::IDAT => |decoder, chunk| decoder.decode_idat_chunk(chunk),
+            chunk_types::ChunkTypes::PLTE => |decoder, chunk| decoder.decode_plte_chunk(chunk),
+            chunk_types::ChunkTypes::tRNS => |decoder, chunk| decoder.decode_trns_chunk(chunk),
+            chunk_types::ChunkTypes::gAMA => |decoder, chunk| decoder.decode_gama_chunk(chunk),
+            chunk_types::ChunkTypes::cHRM => |decoder, chunk| decoder.decode_chrm_chunk(chunk),
+            chunk_types::ChunkTypes::sRGB => |decoder, chunk| decoder.decode_srgb_chunk(chunk),
+            chunk_types::ChunkTypes::iCCP => |decoder, chunk| decoder.decode_iccp_chunk(chunk),
+            chunk_types::ChunkTypes::sBIT => |decoder, chunk| decoder.decode_sbit_chunk(chunk),
+            chunk_types::ChunkTypes::bKGD => |decoder, chunk| decoder.decode_bkgd_chunk(chunk),
+            chunk_types::ChunkTypes::pHYs => |decoder, chunk| decoder.decode_phys_chunk(chunk),
+            chunk_types::ChunkTypes::oFFs => |decoder, chunk| decoder.decode_offs_chunk(chunk),
+            chunk_types::ChunkTypes::sCAL => |decoder, chunk| decoder.decode_scal_chunk(chunk),
+            chunk_types::ChunkTypes::tIME => |decoder, chunk| decoder.decode_time_chunk(chunk),
+            chunk_types::ChunkTypes::sPLT => |decoder, chunk| decoder.decode_splt_chunk(chunk),
+            chunk_types::ChunkTypes::hIST => |decoder, chunk| decoder.decode_hist_chunk(chunk),
+            chunk_types::ChunkTypes::tEXt => |decoder, chunk| {
+                decoder.decode_text_chunk(
+                    |chunk, _| text_chunks::parse_text_chunk(chunk),
+                    chunk,
+                )
+            },
+            chunk_types::ChunkTypes::zTXt => |decoder, chunk| {
+                decoder.decode_text_chunk(
+                    text_chunks::parse_ztxt_chunk,
+                    chunk,
+                )
+            },
+            chunk_types::ChunkTypes::iTXt => |decoder, chunk| {
+                decoder.decode_text_chunk(
+                    text_chunks::parse_itxt_chunk,
+                    chunk,
+                )
+            },
+            chunk_types::ChunkTypes::IEND => |decoder, _| decoder.finalize_at_iend_chunk(),
+            _ => return None,
+        };
+
+        Some(decoding_fn)
+    }
+
+    /// Decodes the data of a single chunk. Returns true when it was the last chunk (IEND).
+    pub(crate) fn decode_chunk(
+        &mut self,
+        chunk_type: &str,
+        chunk_data: &Vec<u8>,
+    ) -> Result<bool, PngDecodeErrorCode> {
+        if let Some(decoding_fn) = Self::get_chunk_decoding_fn(chunk_type) {
+            decoding_fn(self, chunk_data)?;
+        }
+
+        Ok(chunk_type == chunk_types::ChunkTypes::IEND)
+    }
+
+    /// Reads the data and CRC of a chunk whose length and type have just been read, and decodes it.
+    /// Returns true when it was the last chunk (IEND).
+    pub(crate) fn read_and_decode_chunk(
+        &mut self,
+        chunk_data_length: u32,
+        chunk_type: &str,
+        chunk_type_bytes: Vec<u8>,
+    ) -> Result<bool, PngDecodeErrorCode> {
+        // Chunks the decoder does not use don't need to be read at all,
+        // unless their CRC needs to be validated. Seekable sources can skip them quickly.
+        let is_used_by_decoder = Self::get_chunk_decoding_fn(chunk_type).is_some();
+        if !is_used_by_decoder && !self.decoder_options.validate_crc {
+            // chunk data + 4 bytes of CRC
+            self.get_byte_reader()?
+                .skip_next_n_bytes(chunk_data_length as u64 + 4)?;
+            return Ok(false);
+        }
+
+        let chunk_data = self
+            .get_byte_reader()?
+            .read_next_n_bytes(chunk_data_length.into())?;
+
+        let is_last_chunk = self.decode_chunk(chunk_type, &chunk_data)?;
+        let chunk_crc = self.get_byte_reader()?.read_next_4bytes_num()?;
+        if self.decoder_options.validate_crc {
+            // Consume them instead of referencing, cloning, or copying
+            // because we are not going to use these values after this line
+            let chunk_type_and_chunk_data: Vec<u8> = chunk_type_bytes
+                .into_iter()
+                .chain(chunk_data.into_iter())
+                .collect();
+            self.validate_crc(
+                chunk_crc,
+                &chunk_type_and_chunk_data,
+            )?;
         }
+
+        Ok(is_last_chunk)
     }
 
     fn decode_chunks(&mut self) -> Result<(), PngDecodeErrorCode> {
-        let _ = self.read_header();
+        self.read_header()?;
 
         loop {
-            let chunk_data_length = self.byte_reader.read_next_4bytes_num();
-            let (chunk_type, chunk_type_bytes) = self.byte_reader.read_next_4bytes_str();
-            let chunk_data = self.byte_reader.read_next_n_bytes(chunk_data_length.into());
+            let (chunk_data_length, chunk_type, chunk_type_bytes) =
+                self.read_chunk_length_and_type()?;
 
-            if !self.has_ihdr && chunk_type != chunk_types::ChunkTypes::IHDR {
-                self.create_recoverable_error(errors::PngDecodeErrorCode::_13(chunk_type.clone()));
-            }
+            self.check_first_chunk(&chunk_type)?;
 
-            let mut needs_break = false;
-            match chunk_type.as_ref() {
-                chunk_types::ChunkTypes::IHDR => self.decode_ihdr_chunk(&chunk_data),
-                chunk_types::ChunkTypes::IDAT => self.decode_idat_chunk(&chunk_data),
-                chunk_types::ChunkTypes::PLTE => self.decode_plte_chunk(&chunk_data),
-                chunk_types::ChunkTypes::tRNS => self.decode_trns_chunk(&chunk_data),
-                chunk_types::ChunkTypes::IEND => {
-                    needs_break = true;
-                    self.finalize_at_iend_chunk()
-                }
-                _ => Ok(())
-            }?;
-            let chunk_crc = self.byte_reader.read_next_4bytes_num();
-            if self.decoder_options.validate_crc {
-                // Consume them instead of referencing, cloning, or copying
-                // because we are not going to use these values after this line
-                let chunk_type_and_chunk_data: Vec<u8> = chunk_type_bytes
-                    .into_iter()
-                    .chain(chunk_data.into_iter())
-                    .collect();
-                self.validate_crc(
-                    chunk_crc,
-                    &chunk_type_and_chunk_data,
-                );
-            }
-            if needs_break {
+            if self.read_and_decode_chunk(
+                chunk_data_length,
+                &chunk_type,
+                chunk_type_bytes,
+            )? {
                 break;
             }
         }
@@ -471,16 +1428,60 @@ impl<'a> PngDecoder<'a> {
         Ok(())
     }
 
-    fn unfilter_interlaced_image(&mut self) -> Result<Vec<ReducedImage>, errors::PngDecodeErrorCode> {
-        let height = self.height.expect("Height is None");
-        let decompressed_data = self.zlib_decompress_stream.get_out_buffer();
+    /// Image data that ends early is a recoverable error. The missing rows are filled with zeros,
+    /// so that the rows before them can still be shown.
+    /// * `filtered_row_lengths` - how many rows there are of each length (including the filter byte)
+    fn pad_missing_image_data(
+        &mut self,
+        filtered_row_lengths: &[(u32, usize)],
+    ) -> Result<(), PngDecodeErrorCode> {
+        let decompressed_length = self.zlib_decompress_stream.get_decompressed_length();
+        let mut expected_length: usize = 0;
+        let mut expected_row_count: u32 = 0;
+        let mut complete_row_count: u32 = 0;
+        for &(row_count, filtered_row_length) in filtered_row_lengths {
+            let complete_rows_in_this_length =
+                decompressed_length.saturating_sub(expected_length) / filtered_row_length;
+            complete_row_count = complete_row_count
+                .saturating_add(complete_rows_in_this_length.min(row_count as usize) as u32);
+            expected_row_count = expected_row_count.saturating_add(row_count);
+            expected_length =
+                expected_length.saturating_add(row_count as usize * filtered_row_length);
+        }
+
+        if decompressed_length < expected_length {
+            self.create_recoverable_error(PngDecodeErrorCode::_25(
+                expected_row_count,
+                complete_row_count,
+            ))?;
+        }
+        self.zlib_decompress_stream.pad_out_buffer(expected_length);
 
+        Ok(())
+    }
+
+    fn unfilter_interlaced_image(
+        &mut self,
+    ) -> Result<Vec<ReducedImage>, errors::PngDecodeErrorCode> {
+        let height = self.height.ok_or(PngDecodeErrorCode::_38)?;
         let reduced_images = deinterlace::create_reduced_images(
-            self.width.expect("Width is None"),
+            self.width.ok_or(PngDecodeErrorCode::_38)?,
             height,
-            colortype_to_channel(self.color_type.expect("Color type is None")),
-            self.bit_depth.expect("Bit depth is None"),
+            colortype_to_channel(self.color_type.ok_or(PngDecodeErrorCode::_38)?),
+            self.bit_depth.ok_or(PngDecodeErrorCode::_38)?,
         );
+        let filtered_row_lengths: Vec<(u32, usize)> = reduced_images
+            .iter()
+            .filter(|reduced_image| reduced_image.bytes_per_line > 0)
+            .map(|reduced_image| {
+                (
+                    reduced_image.pixel_height,
+                    reduced_image.bytes_per_line + 1,
+                )
+            })
+            .collect();
+        self.pad_missing_image_data(&filtered_row_lengths)?;
+        let decompressed_data = self.zlib_decompress_stream.get_out_buffer();
 
         let mut decompressed_data_cursor: usize = 0;
         let mut unfiltered_output_cursor: usize = 0;
@@ -531,8 +1532,22 @@ impl<'a> PngDecoder<'a> {
         return Ok(reduced_images.to_vec());
     }
 
-    fn unfilter_non_interlaced_image(&mut self) -> Result<Vec<ReducedImage>, errors::PngDecodeErrorCode> {
-        let height = self.height.expect("Height is None");
+    /// See [PngDecoder::pad_missing_image_data]
+    pub(crate) fn pad_missing_non_interlaced_image_data(
+        &mut self,
+    ) -> Result<(), PngDecodeErrorCode> {
+        let height = self.height.ok_or(PngDecodeErrorCode::_38)?;
+        self.pad_missing_image_data(&[(
+            height,
+            self.bytes_per_line + 1,
+        )])
+    }
+
+    fn unfilter_non_interlaced_image(
+        &mut self,
+    ) -> Result<Vec<ReducedImage>, errors::PngDecodeErrorCode> {
+        let height = self.height.ok_or(PngDecodeErrorCode::_38)?;
+        self.pad_missing_non_interlaced_image_data()?;
         let decompressed_data = self.zlib_decompress_stream.get_out_buffer();
 
         let mut unfilter_processor = unfilter::UnfilterProcessor::new(
@@ -541,7 +1556,7 @@ impl<'a> PngDecoder<'a> {
             self.bytes_per_line,
         );
         // expected full image size in bytes (does not include filter bytes)
-        let maximum_possible_byte_width = (self.bytes_per_line * height as usize) as usize;
+        let maximum_possible_byte_width = self.bytes_per_line * height as usize;
         self.unfiltered_output.resize_with(
             maximum_possible_byte_width,
             Default::default,
@@ -552,26 +1567,31 @@ impl<'a> PngDecoder<'a> {
         )?;
 
         return Ok(vec![ReducedImage {
-            pixel_width: self.width.expect("Width is None"),
+            pixel_width: self.width.ok_or(PngDecodeErrorCode::_38)?,
             pixel_height: height,
             bytes_per_line: self.bytes_per_line,
             bytes_per_pixel: self.bytes_per_pixel,
         }]);
     }
-    
-    /// outputs data in rgba (4 bytes) for each pixel
-    fn to_rgba_vec(&self, reduced_images: Vec<ReducedImage>) -> Result<Vec<u8>, PngDecodeErrorCode> {
-        let width = self.width.expect("Width is None");
-        let height = self.height.expect("Height is None");
-        let rgba_data_length = ((width * height) * 4) as usize;
-        let mut rgba_data = vec![0u8; rgba_data_length];
-        let pixel_type = self.pixel_type.expect("Pixel type is None");
-        let interlace_method = self.interlace_method.expect("Interlace method is None");
 
-        let trns = self.transparency_chunk.clone();
+    /// outputs data in `output_format` for each pixel
+    fn to_output_vec(
+        &self,
+        reduced_images: Vec<ReducedImage>,
+        output_format: PixelFormat,
+    ) -> Result<Vec<u8>, PngDecodeErrorCode> {
+        let width = self.width.ok_or(PngDecodeErrorCode::_38)?;
+        let height = self.height.ok_or(PngDecodeErrorCode::_38)?;
+        let output_bytes_per_pixel = output_format
+            .bytes_per_pixel()
+            .ok_or(PngDecodeErrorCode::_55)?;
+        let output_data_length = width as usize * height as usize * output_bytes_per_pixel;
+        let mut output_data = vec![0u8; output_data_length];
+        let pixel_type = self.pixel_type.ok_or(PngDecodeErrorCode::_38)?;
+        let interlace_method = self.interlace_method.ok_or(PngDecodeErrorCode::_38)?;
 
         let mut previous_reduced_image_offset: usize = 0;
-        for (nth_pass, reduced_image) in reduced_images.iter().enumerate() {
+        for (reduced_image, nth_pass) in reduced_images.iter().zip(Adam7Pass::ALL) {
             for row_index in 0..reduced_image.pixel_height {
                 let current_scanline_offset = row_index as usize * reduced_image.bytes_per_line as usize;
                 let current_scanline_start = previous_reduced_image_offset + current_scanline_offset;
@@ -579,54 +1599,285 @@ impl<'a> PngDecoder<'a> {
                 let current_scanline = &self.unfiltered_output[current_scanline_start..current_scanline_end];
 
                 for col_index in 0..reduced_image.pixel_width {
-                    let rgba_data_start_index = match interlace_method {
+                    let output_data_start_index = match interlace_method {
                         InterlaceMethod::Adam7 => deinterlace::calc_interlaced_pixel_index(
                             col_index as usize,
                             row_index as usize,
-                            (nth_pass + 1) as u8,
+                            nth_pass,
                             width,
+                            output_bytes_per_pixel,
                         ),
                         InterlaceMethod::None => {
-                            ((row_index as u64 * width as u64 * 4) + (col_index as u64 * 4))
+                            ((row_index as u64 * width as u64 + col_index as u64)
+                                * output_bytes_per_pixel as u64)
                                 as usize
                         }
                     };
 
-                    let (r, g, b, a) = bitmap::to_rgba_pixel_bytes(
-                        pixel_type,
-                        trns.as_ref(),
-                        self.palette.as_ref(),
-                        col_index as usize,
-                        current_scanline,
-                    )?;
-                    if rgba_data_start_index > rgba_data_length {
+                    let samples = self.read_pixel_samples(pixel_type, col_index as usize, current_scanline)?;
+                    if output_data_start_index >= output_data_length {
                         break;
                     }
 
-                    rgba_data[rgba_data_start_index] = r;
-                    rgba_data[rgba_data_start_index + 1] = g;
-                    rgba_data[rgba_data_start_index + 2] = b;
-                    rgba_data[rgba_data_start_index + 3] = a;                    
+                    bitmap::write_pixel(
+                        output_format,
+                        &samples,
+                        &mut output_data[output_data_start_index
+                            ..output_data_start_index + output_bytes_per_pixel],
+                    )?;
                 }
             }
             previous_reduced_image_offset +=
                 (reduced_image.pixel_height as usize * reduced_image.bytes_per_line) as usize
         }
 
-        return Ok(rgba_data);
+        return Ok(output_data);
     }
 
-    /// returns RGBA vec
-    pub fn run(&mut self) -> Result<Vec<u8>, errors::PngDecodeErrorCode> {
-        self.decode_chunks()?;
+    /// outputs the unfiltered samples as they are, only putting the pixels of interlaced images back in place.
+    /// See [PixelFormat::Native]
+    fn take_native_vec(
+        &mut self,
+        reduced_images: Vec<ReducedImage>,
+    ) -> Result<Vec<u8>, PngDecodeErrorCode> {
+        let height = self.height.ok_or(PngDecodeErrorCode::_38)?;
+        let native_data_length = self.bytes_per_line * height as usize;
+
+        match self.interlace_method.ok_or(PngDecodeErrorCode::_38)? {
+            InterlaceMethod::None => {
+                // non-interlaced unfiltered output is already in the native layout
+                let mut native_data = std::mem::take(&mut self.unfiltered_output);
+                native_data.truncate(native_data_length);
+                return Ok(native_data);
+            }
+            InterlaceMethod::Adam7 => (),
+        }
+
+        let bits_per_pixel = colortype_to_channel(self.color_type.ok_or(PngDecodeErrorCode::_38)?)
+            as usize
+            * self.bit_depth.ok_or(PngDecodeErrorCode::_38)? as usize;
+        let mut native_data = vec![0u8; native_data_length];
+
+        let mut previous_reduced_image_offset: usize = 0;
+        for (reduced_image, nth_pass) in reduced_images.iter().zip(Adam7Pass::ALL) {
+            for row_index in 0..reduced_image.pixel_height as usize {
+                let current_scanline_start =
+                    previous_reduced_image_offset + row_index * reduced_image.bytes_per_line;
+
+                for col_index in 0..reduced_image.pixel_width as usize {
+                    let (x, y) =
+                        deinterlace::calc_interlaced_pixel_position(col_index, row_index, nth_pass);
+                    let native_line_start = y * self.bytes_per_line;
+
+                    if bits_per_pixel >= 8 {
+                        let source_start =
+                            current_scanline_start + col_index * self.bytes_per_pixel;
+                        let target_start = native_line_start + x * self.bytes_per_pixel;
+                        native_data[target_start..target_start + self.bytes_per_pixel]
+                            .copy_from_slice(
+                                &self.unfiltered_output
+                                    [source_start..source_start + self.bytes_per_pixel],
+                            );
+                    } else {
+                        // 1, 2 or 4 bits per pixel. Leftmost pixels are in the high-order bits.
+                        let mask = (1u8 << bits_per_pixel) - 1;
+                        let source_bit = col_index * bits_per_pixel;
+                        let source_shift = 8 - bits_per_pixel - source_bit % 8;
+                        let sample = (self.unfiltered_output
+                            [current_scanline_start + source_bit / 8]
+                            >> source_shift)
+                            & mask;
+
+                        let target_bit = x * bits_per_pixel;
+                        let target_shift = 8 - bits_per_pixel - target_bit % 8;
+                        native_data[native_line_start + target_bit / 8] |= sample << target_shift;
+                    }
+                }
+            }
+            previous_reduced_image_offset +=
+                reduced_image.pixel_height as usize * reduced_image.bytes_per_line;
+        }
 
+        Ok(native_data)
+    }
+
+    /// Collects the IHDR values that have been read so far.
+    pub(crate) fn get_image_header(&self) -> Result<ImageHeader, PngDecodeErrorCode> {
+        Ok(ImageHeader {
+            width: self.width.ok_or(PngDecodeErrorCode::_38)?,
+            height: self.height.ok_or(PngDecodeErrorCode::_38)?,
+            bit_depth: self.bit_depth.ok_or(PngDecodeErrorCode::_38)?,
+            color_type: self.color_type.ok_or(PngDecodeErrorCode::_38)?,
+            compression_method: self
+                .compression_method
+                .clone()
+                .ok_or(PngDecodeErrorCode::_38)?,
+            filter_method: self.filter_method.clone().ok_or(PngDecodeErrorCode::_38)?,
+            interlace_method: self.interlace_method.ok_or(PngDecodeErrorCode::_38)?,
+        })
+    }
+
+    /// Converts a single unfiltered scanline of a non-interlaced image into [PngDecoderOptions::output_format]
+    pub(crate) fn convert_row(&self, unfiltered_row: &[u8]) -> Result<Vec<u8>, PngDecodeErrorCode> {
+        let output_format = self.decoder_options.output_format;
+        let output_bytes_per_pixel = match output_format.bytes_per_pixel() {
+            Some(bytes_per_pixel) => bytes_per_pixel,
+            None => return Ok(unfiltered_row.to_vec()),
+        };
+        let width = self.width.ok_or(PngDecodeErrorCode::_38)? as usize;
+        let pixel_type = self.pixel_type.ok_or(PngDecodeErrorCode::_38)?;
+        let mut output_row = vec![0u8; width * output_bytes_per_pixel];
+
+        for (col_index, output_pixel) in output_row
+            .chunks_exact_mut(output_bytes_per_pixel)
+            .enumerate()
+        {
+            let samples = self.read_pixel_samples(
+                pixel_type,
+                col_index,
+                unfiltered_row,
+            )?;
+            bitmap::write_pixel(
+                output_format,
+                &samples,
+                output_pixel,
+            )?;
+        }
+
+        Ok(output_row)
+    }
+
+    /// Unfilters the decompressed image data, and outputs the pixels in [PngDecoderOptions::output_format].
+    /// Must be called after IEND chunk has been decoded.
+    pub(crate) fn decode_pixels(&mut self) -> Result<Vec<u8>, PngDecodeErrorCode> {
         // length is 1 or 7 based on interlace == 0 or 1
-        let reduced_images = match self.interlace_method.expect("Interlace method is None") {
+        let reduced_images = match self.interlace_method.ok_or(PngDecodeErrorCode::_38)? {
             chunk_helpers::InterlaceMethod::None => self.unfilter_non_interlaced_image(),
             chunk_helpers::InterlaceMethod::Adam7 => self.unfilter_interlaced_image(),
         }?;
-        let rgba_vec = self.to_rgba_vec(reduced_images);
+        let output_format = self.decoder_options.output_format;
+        let pixels = match output_format {
+            PixelFormat::Native => self.take_native_vec(reduced_images)?,
+            _ => self.to_output_vec(reduced_images, output_format)?,
+        };
         self.multi_errors_manager.end(errors::ExitReason::JobDone);
-        return rgba_vec;
+
+        Ok(pixels)
+    }
+
+    /// Reads only the signature and IHDR chunk, and stops there.
+    /// Much cheaper than [PngDecoder::run] when only the dimensions, color type, bit depth or interlace method are needed.
+    pub fn probe(&mut self) -> Result<ImageHeader, errors::PngDecodeErrorCode> {
+        self.read_header()?;
+
+        let (chunk_data_length, chunk_type, chunk_type_bytes) =
+            self.read_chunk_length_and_type()?;
+        if chunk_type != chunk_types::ChunkTypes::IHDR {
+            return Err(PngDecodeErrorCode::_13(
+                chunk_type,
+            ));
+        }
+        self.read_and_decode_chunk(
+            chunk_data_length,
+            &chunk_type,
+            chunk_type_bytes,
+        )?;
+
+        self.get_image_header()
+    }
+
+    /// Walks every chunk until IEND chunk without decompressing the image data.
+    /// IDAT chunks are skipped (by seeking, when the source of the byte reader can seek),
+    /// and the data of ancillary chunks is kept as it is.
+    pub fn probe_chunks(&mut self) -> Result<ProbedImage, errors::PngDecodeErrorCode> {
+        self.read_header()?;
+        let mut chunks: Vec<ChunkInfo> = vec![];
+        let mut ancillary_chunks: Vec<AncillaryChunk> = vec![];
+
+        loop {
+            let byte_pos = self.get_current_byte_pos();
+            let (chunk_data_length, chunk_type, chunk_type_bytes) =
+                self.read_chunk_length_and_type()?;
+            self.check_first_chunk(&chunk_type)?;
+            chunks.push(ChunkInfo {
+                chunk_type: chunk_type.clone(),
+                length: chunk_data_length,
+                byte_pos,
+            });
+
+            if chunk_type == chunk_types::ChunkTypes::IDAT {
+                // chunk data + 4 bytes of CRC
+                self.get_byte_reader()?
+                    .skip_next_n_bytes(chunk_data_length as u64 + 4)?;
+                self.mark_idat_seen()?;
+                continue;
+            }
+            let is_ancillary = chunk_type_bytes[0].is_ascii_lowercase();
+            if !is_ancillary {
+                if self.read_and_decode_chunk(
+                    chunk_data_length,
+                    &chunk_type,
+                    chunk_type_bytes,
+                )? {
+                    break;
+                }
+                continue;
+            }
+
+            let chunk_data = self
+                .get_byte_reader()?
+                .read_next_n_bytes(chunk_data_length.into())?;
+            let chunk_crc = self.get_byte_reader()?.read_next_4bytes_num()?;
+            if self.decoder_options.validate_crc {
+                let chunk_type_and_chunk_data: Vec<u8> = chunk_type_bytes
+                    .iter()
+                    .chain(chunk_data.iter())
+                    .copied()
+                    .collect();
+                self.validate_crc(
+                    chunk_crc,
+                    &chunk_type_and_chunk_data,
+                )?;
+            }
+            self.decode_chunk(&chunk_type, &chunk_data)?;
+            ancillary_chunks.push(AncillaryChunk {
+                chunk_type,
+                data: chunk_data,
+            });
+        }
+
+        Ok(ProbedImage {
+            header: self.get_image_header()?,
+            chunks,
+            ancillary_chunks,
+            metadata: self.metadata.clone(),
+            errors: self.multi_errors_manager.get_errors().clone(),
+        })
+    }
+
+    /// returns the decoded image, with pixels in [PngDecoderOptions::output_format]
+    pub fn run(&mut self) -> Result<DecodedImage, errors::PngDecodeErrorCode> {
+        self.decode_chunks()?;
+        let pixels = self.decode_pixels()?;
+        let output_format = self.decoder_options.output_format;
+
+        let header = self.get_image_header()?;
+        return Ok(DecodedImage {
+            pixels,
+            width: header.width,
+            height: header.height,
+            pixel_format: output_format,
+            header,
+            metadata: self.metadata.clone(),
+            errors: self.multi_errors_manager.get_errors().clone(),
+        });
+    }
+}
+
+impl PngDecoder<'static, 'static> {
+    /// Creates a decoder without a byte reader, for when bytes are pushed into it chunk by chunk
+    /// instead of being pulled. See [crate::push_decoder::PngPushDecoder]
+    pub(crate) fn without_byte_reader(decoder_options: PngDecoderOptions) -> Self {
+        PngDecoder::with_byte_reader(None, decoder_options)
     }
 }
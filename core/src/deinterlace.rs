@@ -1,5 +1,34 @@
 use crate::common;
 
+/// One of the 7 passes of Adam7 interlacing, in the order their reduced images are stored in the image data
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Adam7Pass {
+    First = 1,
+    Second,
+    Third,
+    Fourth,
+    Fifth,
+    Sixth,
+    Seventh,
+}
+
+impl Adam7Pass {
+    pub const ALL: [Adam7Pass; 7] = [
+        Adam7Pass::First,
+        Adam7Pass::Second,
+        Adam7Pass::Third,
+        Adam7Pass::Fourth,
+        Adam7Pass::Fifth,
+        Adam7Pass::Sixth,
+        Adam7Pass::Seventh,
+    ];
+
+    /// 1 to 7
+    pub fn number(self) -> u8 {
+        self as u8
+    }
+}
+
 #[derive(Default, Clone, Copy, Debug)]
 pub struct ReducedImage {
     pub pixel_width: u32,
@@ -53,51 +82,50 @@ pub fn create_reduced_images(
 ) -> [ReducedImage; 7] {
     let mut reduced_images: [ReducedImage; 7] = [ReducedImage::default(); 7];
 
-    for pass in 1..=7 {
+    for (reduced_image, pass) in reduced_images.iter_mut().zip(Adam7Pass::ALL) {
         let (pixel_width, pixel_height) = match pass {
-            1 => {
+            Adam7Pass::First => {
                 let pass_width = (pixel_width + 7) >> 3;
                 let pass_height = (pixel_height + 7) >> 3;
                 (pass_width, pass_height)
             }
-            2 => {
+            Adam7Pass::Second => {
                 let pass_width = (pixel_width >> 3) + ((pixel_width & 7) / 5);
                 let pass_height = (pixel_height + 7) >> 3;
                 (pass_width, pass_height)
             }
-            3 => {
+            Adam7Pass::Third => {
                 let pass_width = ((pixel_width >> 3) << 1) + (((pixel_width & 7) + 3) >> 2);
                 let pass_height = (pixel_height >> 3) + ((pixel_height & 7) / 5);
                 (pass_width, pass_height)
             }
-            4 => {
+            Adam7Pass::Fourth => {
                 let pass_width = ((pixel_width >> 3) << 1) + (((pixel_width & 7) + 1) >> 2);
                 let pass_height = (pixel_height + 3) >> 2;
                 (pass_width, pass_height)
             }
-            5 => {
+            Adam7Pass::Fifth => {
                 let pass_width = (pixel_width >> 1) + (pixel_width & 1);
                 let pass_height = ((pixel_height >> 3) << 1) + (((pixel_height & 7) + 1) >> 2);
                 (pass_width, pass_height)
             }
-            6 => {
+            Adam7Pass::Sixth => {
                 let pass_width = pixel_width >> 1;
                 let pass_height = (pixel_height >> 1) + (pixel_height & 1);
                 (pass_width, pass_height)
             }
-            7 => {
+            Adam7Pass::Seventh => {
                 let pass_width = pixel_width;
                 let pass_height = pixel_height >> 1;
                 (pass_width, pass_height)
             }
-            _ => (0, 0),
         };
         let (bytes_per_pixel, bytes_per_line) = common::calc_bytes_per_pixel_and_line(
             channel,
             bit_depth,
             pixel_width,
         );
-        reduced_images[pass - 1] = ReducedImage {
+        *reduced_image = ReducedImage {
             pixel_width,
             pixel_height,
             bytes_per_pixel,
@@ -108,49 +136,153 @@ pub fn create_reduced_images(
     return reduced_images;
 }
 
-/// Calculates the pixel index to which 4-bytes long RGBA data corresponding to a single pixel
+/// Calculates `(x, y)` of a pixel in the original image,
+/// given that it is the pixel at `nth_col` and `nth_row` of the `nth_pass`th reduced image.
+///
+/// See [calc_interlaced_pixel_index] for an example.
+pub fn calc_interlaced_pixel_position(
+    nth_col: usize,
+    nth_row: usize,
+    nth_pass: Adam7Pass,
+) -> (usize, usize) {
+    match nth_pass {
+        Adam7Pass::First => (nth_col * 8, nth_row * 8),
+        Adam7Pass::Second => (nth_col * 8 + 4, nth_row * 8),
+        Adam7Pass::Third => (nth_col * 4, nth_row * 8 + 4),
+        Adam7Pass::Fourth => (nth_col * 4 + 2, nth_row * 4),
+        Adam7Pass::Fifth => (nth_col * 2, nth_row * 4 + 2),
+        Adam7Pass::Sixth => (nth_col * 2 + 1, nth_row * 2),
+        Adam7Pass::Seventh => (nth_col, nth_row * 2 + 1),
+    }
+}
+
+/// The other direction of deinterlacing, used by the encoder:
+/// copies the pixels of the `nth_pass`th reduced image out of the original image.
+///
+/// * `pixels` - the original image, laid out as described by [crate::decoded_image::PixelFormat::Native]
+/// * `bytes_per_line` - of the original image
+///
+/// Pixels of less than 8 bits are packed into the rows of the reduced image the same way,
+/// with leftmost pixels in the high-order bits and each row starting on a new byte.
+pub fn extract_reduced_image_pixels(
+    pixels: &[u8],
+    bytes_per_line: usize,
+    bits_per_pixel: usize,
+    reduced_image: &ReducedImage,
+    nth_pass: Adam7Pass,
+) -> Vec<u8> {
+    let mut reduced_image_pixels =
+        vec![0u8; reduced_image.bytes_per_line * reduced_image.pixel_height as usize];
+
+    for row_index in 0..reduced_image.pixel_height as usize {
+        let reduced_line_start = row_index * reduced_image.bytes_per_line;
+
+        for col_index in 0..reduced_image.pixel_width as usize {
+            let (x, y) = calc_interlaced_pixel_position(col_index, row_index, nth_pass);
+            let line_start = y * bytes_per_line;
+
+            if bits_per_pixel >= 8 {
+                let source_start = line_start + x * reduced_image.bytes_per_pixel;
+                let target_start = reduced_line_start + col_index * reduced_image.bytes_per_pixel;
+                reduced_image_pixels[target_start..target_start + reduced_image.bytes_per_pixel]
+                    .copy_from_slice(
+                        &pixels[source_start..source_start + reduced_image.bytes_per_pixel],
+                    );
+            } else {
+                // 1, 2 or 4 bits per pixel
+                let mask = (1u8 << bits_per_pixel) - 1;
+                let source_bit = x * bits_per_pixel;
+                let source_shift = 8 - bits_per_pixel - source_bit % 8;
+                let sample = (pixels[line_start + source_bit / 8] >> source_shift) & mask;
+
+                let target_bit = col_index * bits_per_pixel;
+                let target_shift = 8 - bits_per_pixel - target_bit % 8;
+                reduced_image_pixels[reduced_line_start + target_bit / 8] |= sample << target_shift;
+            }
+        }
+    }
+
+    reduced_image_pixels
+}
+
+/// Calculates `(width, height)` of the block of the original image that a pixel of the `nth_pass`th reduced image
+/// stands for, until the passes after it fill the rest of the block.
+///
+/// For example, after the first pass only one in every 8x8 pixels is known,
+/// so a preview replicates each of them over an 8x8 block (the way libpng and browsers do).
+pub fn calc_pass_block_size(nth_pass: Adam7Pass) -> (usize, usize) {
+    match nth_pass {
+        Adam7Pass::First => (8, 8),
+        Adam7Pass::Second => (4, 8),
+        Adam7Pass::Third => (4, 4),
+        Adam7Pass::Fourth => (2, 4),
+        Adam7Pass::Fifth => (2, 2),
+        Adam7Pass::Sixth => (1, 2),
+        Adam7Pass::Seventh => (1, 1),
+    }
+}
+
+/// Fills the block of `preview` that the pixel at `x` and `y` stands for after the `nth_pass`th pass, with `pixel`.
+/// See [calc_pass_block_size]. The block is cut off at the right and bottom edges of the image.
+pub fn replicate_pixel_over_block(
+    preview: &mut [u8],
+    original_image_pixel_width: u32,
+    original_image_pixel_height: u32,
+    x: usize,
+    y: usize,
+    nth_pass: Adam7Pass,
+    pixel: &[u8],
+) {
+    let (block_width, block_height) = calc_pass_block_size(nth_pass);
+    let block_right = (x + block_width).min(original_image_pixel_width as usize);
+    let block_bottom = (y + block_height).min(original_image_pixel_height as usize);
+    let bytes_per_pixel = pixel.len();
+
+    for block_y in y..block_bottom {
+        let line_start = block_y * original_image_pixel_width as usize * bytes_per_pixel;
+        for block_x in x..block_right {
+            let pixel_start = line_start + block_x * bytes_per_pixel;
+            preview[pixel_start..pixel_start + bytes_per_pixel].copy_from_slice(pixel);
+        }
+    }
+}
+
+/// Calculates the pixel index to which the output data (for example, 4-bytes long RGBA data) corresponding to a single pixel
 /// will be started to be inserted from, given that the original image is interlaced.
-/// 
+///
 /// Do not use this for non-interlaced images. You can't.
-/// 
-/// For example, if the return value is 20, 
+///
+/// For example, if the return value is 20 for RGBA output (`output_bytes_per_pixel = 4`),
 /// `r` will be inserted to index 20,
 /// `g` will be inserted to index 21,
 /// `b` will be inserted to index 22,
 ///  and `a` will be inserted to index 23.
-/// 
+///
 /// Here's the logic behind the calculation, with an example.
 /// Let `original_image_pixel_width = 32`.
-/// If it is the first reduced image, it `nth_pass` must be 1.
+/// If it is the first reduced image, `nth_pass` must be `Adam7Pass::First`.
 /// And say, you are at `nth_col = 1` and `nth_row = 2`.
 /// Then `x = nth_col * 8 = 1 * 8 = 8`, and `y = nth_row * 8 = 2 * 8 = 16`.
-/// 
+///
 /// Then `output_index = 16 * 32 * 4 + 8 * 4 = 2080`. So your RGBA data will accommodate 2080th, 2081th, 2082th, and 2083th indices in the output vector.
-/// 
-/// We multiply `4` at the end because each pixel will account for 4 bytes in all cases.
-/// 
+///
+/// We multiply `4` at the end because each pixel will account for 4 bytes in RGBA. Other output formats use their own `output_bytes_per_pixel`.
+///
 /// Essentially, this is undoing the work from `create_reduced_images`. As long as you are iterating through all reduced images correctly in the pass order of 1 to 7, you will be able to fill all parts of the output vector without gaps in between.
-/// 
+///
 /// Also, remember to ignore index (output from this function) that is bigger than the length of output vector. This can happen when there are multiple pixels per byte, where some low-order bits of the last byte of a scanline may go unused (The contents of these unused bits are not specified)
-/// 
+///
 pub fn calc_interlaced_pixel_index(
     nth_col: usize,
     nth_row: usize,
-    nth_pass: u8,
+    nth_pass: Adam7Pass,
     original_image_pixel_width: u32,
+    output_bytes_per_pixel: usize,
 ) -> usize {
-    let (x, y) = match nth_pass {
-        1 => (nth_col * 8, nth_row * 8),
-        2 => (nth_col * 8 + 4, nth_row * 8),
-        3 => (nth_col * 4, nth_row * 8 + 4),
-        4 => (nth_col * 4 + 2, nth_row * 4),
-        5 => (nth_col * 2, nth_row * 4 + 2),
-        6 => (nth_col * 2 + 1, nth_row * 2),
-        7 => (nth_col, nth_row * 2 + 1),
-        _ => panic!("nth_pass must be between 1 and 7"),
-    };
-
-    let output_index = (y as u64 * (original_image_pixel_width as u64) << 2) + ((x as u64) << 2);
+    let (x, y) = calc_interlaced_pixel_position(nth_col, nth_row, nth_pass);
+
+    let output_index =
+        (y as u64 * original_image_pixel_width as u64 + x as u64) * output_bytes_per_pixel as u64;
     let output_index: usize = output_index
         .try_into()
         .expect("Output index does not fit in usize");
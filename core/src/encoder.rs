@@ -0,0 +1,646 @@
+//! Writes PNG files from pixel buffers. The mirror image of [crate::decoder::PngDecoder].
+//!
+//! Pixels are given in the layout of [crate::decoded_image::PixelFormat::Native],
+//! so an image decoded with that format can be encoded again without any conversion,
+//! and decoding the encoded image gives back exactly the same bytes.
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{
+    bitmap::{self, PixelType},
+    chunk_helpers::{self, colortype_to_channel, ColorType},
+    chunk_types, common,
+    decoded_image::{AncillaryChunk, PixelFormat},
+    deinterlace,
+    errors::PngDecodeErrorCode,
+    filter,
+    palette_chunks::{self, SuggestedPalette},
+    physical_chunks::{self, ImageOffset, PhysicalDimensions, PhysicalScale},
+    reduction,
+    timestamp::{self, Timestamp},
+    trns::TransparencyChunk,
+    zlib,
+};
+
+/// IDAT chunks are split at this length, the same size the decoder reads image data in
+const IDAT_CHUNK_LENGTH: usize = 32 * 1024;
+
+/// Ancillary chunks whose data depends on the color type, bit depth or palette.
+/// They would be wrong after the color type is reduced, so images that have them are not reduced.
+/// An ICC profile is either for RGB or for greyscale images, so it doesn't fit after RGB is reduced to greyscale.
+pub(crate) const COLOR_DEPENDENT_CHUNK_TYPES: [&str; 4] = [
+    chunk_types::ChunkTypes::bKGD,
+    chunk_types::ChunkTypes::hIST,
+    chunk_types::ChunkTypes::iCCP,
+    chunk_types::ChunkTypes::sBIT,
+];
+
+/// How the filter type of each row is chosen.
+/// Trades encoding time against the size of the output: from the fastest to the slowest,
+/// a fixed filter type, [FilterStrategy::MinimumSumOfAbsoluteDifferences], [FilterStrategy::Entropy] and [FilterStrategy::BruteForce].
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterStrategy {
+    /// Filter type 0 for every row. Usually the best for palette images and images of less than 8 bits per sample.
+    None,
+    /// Filter type 1 for every row
+    Sub,
+    /// Filter type 2 for every row
+    Up,
+    /// Filter type 3 for every row
+    Average,
+    /// Filter type 4 for every row
+    Paeth,
+    /// Tries every filter type, and picks the one whose filtered bytes add up to the smallest,
+    /// when each byte is seen as a signed difference. This is what libpng does.
+    /// Also like libpng, palette images and images of less than 8 bits per sample use [FilterStrategy::None] instead.
+    MinimumSumOfAbsoluteDifferences,
+    /// Tries every filter type, and picks the one whose filtered bytes have the lowest Shannon entropy
+    Entropy,
+    /// Compresses the row filtered with every filter type, and picks the one that compresses the smallest
+    BruteForce,
+}
+
+/// Where an ancillary chunk is written, relative to the chunks the encoder writes itself.
+/// Some ancillary chunks must come before PLTE chunk (for example gAMA, cHRM, sRGB, iCCP and sBIT),
+/// some after it (bKGD and hIST), and all of them except tEXt, zTXt, iTXt and tIME before IDAT chunks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChunkPosition {
+    /// Right after IHDR chunk
+    BeforePalette,
+    /// After PLTE and tRNS chunks, right before the first IDAT chunk
+    BeforeImageData,
+    /// After the last IDAT chunk, right before IEND chunk
+    AfterImageData,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct PngEncoderOptions {
+    /// From 0 (no compression) to 10 (slowest). Default: 6
+    pub compression_level: u8,
+    /// Default: [FilterStrategy::MinimumSumOfAbsoluteDifferences]
+    pub filter_strategy: FilterStrategy,
+    /// Writes an Adam7 interlaced image, which can be shown progressively while it is downloaded.
+    /// Interlaced images are usually a bit larger. Default: false
+    pub interlace: bool,
+    /// Stores the pixels in the smallest color type and bit depth that keeps every pixel the same.
+    /// For example, opaque images lose their alpha channel, grey images become greyscale,
+    /// and images of 256 or fewer colors get a palette.
+    /// Images of 16 bits per sample, and images with bKGD, hIST or sBIT chunk, are encoded as they are.
+    /// Default: false
+    pub reduce: bool,
+}
+
+#[wasm_bindgen]
+impl PngEncoderOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(compression_level: u8) -> Self {
+        PngEncoderOptions {
+            compression_level,
+            filter_strategy: FilterStrategy::MinimumSumOfAbsoluteDifferences,
+            interlace: false,
+            reduce: false,
+        }
+    }
+}
+
+impl Default for PngEncoderOptions {
+    fn default() -> Self {
+        PngEncoderOptions::new(6)
+    }
+}
+
+pub struct PngEncoder {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: ColorType,
+    /// Data of PLTE chunk, 3 bytes (r, g, b) per entry. Must be set for [ColorType::IndexedColor]
+    palette: Option<Vec<u8>>,
+    /// Data of tRNS chunk, exactly as it is written into the image
+    transparency: Option<Vec<u8>>,
+    /// Written as they are, in the order they have been added
+    ancillary_chunks: Vec<(ChunkPosition, AncillaryChunk)>,
+    encoder_options: PngEncoderOptions,
+}
+
+impl PngEncoder {
+    pub fn new(
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+        bit_depth: u8,
+        encoder_options: &PngEncoderOptions,
+    ) -> PngEncoder {
+        PngEncoder {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            palette: None,
+            transparency: None,
+            ancillary_chunks: vec![],
+            encoder_options: *encoder_options,
+        }
+    }
+
+    /// Sets the data of PLTE chunk, 3 bytes (r, g, b) per entry.
+    /// Required for [ColorType::IndexedColor], optional for [ColorType::Truecolor] and [ColorType::TruecolorAlpha].
+    pub fn set_palette(&mut self, palette: Vec<u8>) {
+        self.palette = Some(palette);
+    }
+
+    /// Sets the data of tRNS chunk:
+    /// alpha values of the palette entries for [ColorType::IndexedColor],
+    /// or a single transparent color for [ColorType::Greyscale] (2 bytes) and [ColorType::Truecolor] (6 bytes),
+    /// with every sample as a big-endian `u16`.
+    pub fn set_transparency(&mut self, transparency: Vec<u8>) {
+        self.transparency = Some(transparency);
+    }
+
+    /// Adds an ancillary chunk, such as a text or timestamp chunk, which is written as it is.
+    /// The encoder doesn't look into `chunk_data`, so it must be valid for the color type and bit depth of the image.
+    /// tRNS chunk can't be added here, use [PngEncoder::set_transparency] instead.
+    pub fn add_ancillary_chunk(
+        &mut self,
+        chunk_type: &str,
+        chunk_data: Vec<u8>,
+        position: ChunkPosition,
+    ) {
+        self.ancillary_chunks.push((
+            position,
+            AncillaryChunk {
+                chunk_type: chunk_type.to_string(),
+                data: chunk_data,
+            },
+        ));
+    }
+
+    /// Writes pHYs chunk, replacing the one set before. See [PhysicalDimensions::from_dpi]
+    pub fn set_physical_dimensions(
+        &mut self,
+        physical_dimensions: &PhysicalDimensions,
+    ) -> Result<(), PngDecodeErrorCode> {
+        let chunk_data = physical_chunks::encode_phys_chunk(physical_dimensions)?;
+        self.set_ancillary_chunk(
+            chunk_types::ChunkTypes::pHYs,
+            chunk_data,
+        );
+        Ok(())
+    }
+
+    /// Writes oFFs chunk, replacing the one set before
+    pub fn set_offset(&mut self, image_offset: &ImageOffset) {
+        self.set_ancillary_chunk(
+            chunk_types::ChunkTypes::oFFs,
+            physical_chunks::encode_offs_chunk(image_offset),
+        );
+    }
+
+    /// Writes sCAL chunk, replacing the one set before
+    pub fn set_scale(&mut self, physical_scale: &PhysicalScale) -> Result<(), PngDecodeErrorCode> {
+        let chunk_data = physical_chunks::encode_scal_chunk(physical_scale)?;
+        self.set_ancillary_chunk(
+            chunk_types::ChunkTypes::sCAL,
+            chunk_data,
+        );
+        Ok(())
+    }
+
+    /// Writes tIME chunk, replacing the one set before. See [Timestamp::from_unix_time]
+    pub fn set_timestamp(&mut self, timestamp: &Timestamp) -> Result<(), PngDecodeErrorCode> {
+        let chunk_data = timestamp::encode_time_chunk(timestamp)?;
+        self.set_ancillary_chunk(
+            chunk_types::ChunkTypes::tIME,
+            chunk_data,
+        );
+        Ok(())
+    }
+
+    /// Writes an sPLT chunk. There may be any number of them, but each must have a different palette name
+    pub fn add_suggested_palette(
+        &mut self,
+        suggested_palette: &SuggestedPalette,
+    ) -> Result<(), PngDecodeErrorCode> {
+        let chunk_data = palette_chunks::encode_splt_chunk(suggested_palette)?;
+        let palette_name = palette_chunks::read_palette_name(&chunk_data);
+        let is_name_used = self.ancillary_chunks.iter().any(|(_, chunk)| {
+            chunk.chunk_type == chunk_types::ChunkTypes::sPLT
+                && palette_chunks::read_palette_name(&chunk.data) == palette_name
+        });
+        if is_name_used {
+            return Err(PngDecodeErrorCode::_53(
+                chunk_types::ChunkTypes::sPLT.to_string(),
+                format!(
+                    "palette name {:?} is already used by another sPLT chunk",
+                    suggested_palette.name
+                ),
+            ));
+        }
+
+        self.add_ancillary_chunk(
+            chunk_types::ChunkTypes::sPLT,
+            chunk_data,
+            ChunkPosition::BeforeImageData,
+        );
+        Ok(())
+    }
+
+    /// Writes hIST chunk, replacing the one set before. It must have a frequency for each entry of the palette
+    /// set with [PngEncoder::set_palette], such as [crate::quantizer::QuantizedImage::histogram].
+    pub fn set_palette_histogram(&mut self, frequencies: &[u16]) -> Result<(), PngDecodeErrorCode> {
+        let palette_entries = self.palette.as_ref().map_or(0, |palette| palette.len() / 3);
+        let chunk_data = palette_chunks::encode_hist_chunk(frequencies, palette_entries)?;
+        self.set_ancillary_chunk(
+            chunk_types::ChunkTypes::hIST,
+            chunk_data,
+        );
+        Ok(())
+    }
+
+    /// For chunks that may appear only once, all of which may come right before IDAT chunks
+    fn set_ancillary_chunk(&mut self, chunk_type: &str, chunk_data: Vec<u8>) {
+        self.ancillary_chunks
+            .retain(|(_, chunk)| chunk.chunk_type != chunk_type);
+        self.add_ancillary_chunk(
+            chunk_type,
+            chunk_data,
+            ChunkPosition::BeforeImageData,
+        );
+    }
+
+    /// Returns the bytes of the PNG file.
+    /// * `pixels` - rows from the top, laid out as described by [crate::decoded_image::PixelFormat::Native]
+    pub fn encode(&self, pixels: &[u8]) -> Result<Vec<u8>, PngDecodeErrorCode> {
+        self.validate_header()?;
+        self.validate_palette()?;
+        self.validate_transparency()?;
+        self.validate_ancillary_chunks()?;
+
+        let (bytes_per_pixel, bytes_per_line) = common::calc_bytes_per_pixel_and_line(
+            colortype_to_channel(self.color_type),
+            self.bit_depth,
+            self.width,
+        );
+        let expected_pixels_length = bytes_per_line * self.height as usize;
+        if pixels.len() != expected_pixels_length {
+            return Err(PngDecodeErrorCode::_41(
+                expected_pixels_length,
+                pixels.len(),
+            ));
+        }
+        let has_color_dependent_chunk = self
+            .ancillary_chunks
+            .iter()
+            .any(|(_, chunk)| COLOR_DEPENDENT_CHUNK_TYPES.contains(&chunk.chunk_type.as_str()));
+        if self.encoder_options.reduce && self.bit_depth <= 8 && !has_color_dependent_chunk {
+            return self.encode_reduced(pixels, bytes_per_line);
+        }
+
+        let filtered_image_data = if self.encoder_options.interlace {
+            self.filter_interlaced_image(pixels, bytes_per_line)
+        } else {
+            filter::FilterProcessor::new(
+                bytes_per_pixel,
+                bytes_per_line,
+                self.get_filter_strategy(),
+                self.encoder_options.compression_level,
+            )
+            .filter(pixels)
+        };
+        let compressed_image_data = zlib::compress(
+            &filtered_image_data,
+            self.encoder_options.compression_level,
+        );
+
+        let mut png_bytes: Vec<u8> = chunk_helpers::PNG_HEADER.to_vec();
+        write_chunk(
+            &mut png_bytes,
+            chunk_types::ChunkTypes::IHDR,
+            &self.create_ihdr_chunk_data(),
+        );
+        self.write_ancillary_chunks(
+            &mut png_bytes,
+            ChunkPosition::BeforePalette,
+        );
+        if let Some(palette) = &self.palette {
+            write_chunk(
+                &mut png_bytes,
+                chunk_types::ChunkTypes::PLTE,
+                palette,
+            );
+        }
+        if let Some(transparency) = &self.transparency {
+            write_chunk(
+                &mut png_bytes,
+                chunk_types::ChunkTypes::tRNS,
+                transparency,
+            );
+        }
+        self.write_ancillary_chunks(
+            &mut png_bytes,
+            ChunkPosition::BeforeImageData,
+        );
+        for idat_chunk_data in compressed_image_data.chunks(IDAT_CHUNK_LENGTH) {
+            write_chunk(
+                &mut png_bytes,
+                chunk_types::ChunkTypes::IDAT,
+                idat_chunk_data,
+            );
+        }
+        self.write_ancillary_chunks(
+            &mut png_bytes,
+            ChunkPosition::AfterImageData,
+        );
+        write_chunk(
+            &mut png_bytes,
+            chunk_types::ChunkTypes::IEND,
+            &[],
+        );
+
+        Ok(png_bytes)
+    }
+
+    fn validate_header(&self) -> Result<(), PngDecodeErrorCode> {
+        if self.width == 0
+            || self.height == 0
+            || self.width > chunk_helpers::MAX_DIMENSION
+            || self.height > chunk_helpers::MAX_DIMENSION
+        {
+            return Err(PngDecodeErrorCode::_42(
+                self.width,
+                self.height,
+            ));
+        }
+        // rejects combinations of color type and bit depth that are not permitted
+        PixelType::new(
+            self.color_type,
+            self.bit_depth,
+        )?;
+
+        Ok(())
+    }
+
+    /// Same rules as the decoder follows for PLTE chunk. See [crate::decoder::PngDecoder]
+    fn validate_palette(&self) -> Result<(), PngDecodeErrorCode> {
+        let palette = match &self.palette {
+            Some(palette) => palette,
+            None if self.color_type == ColorType::IndexedColor => {
+                return Err(PngDecodeErrorCode::_16(
+                    self.color_type,
+                ))
+            }
+            None => return Ok(()),
+        };
+
+        match self.color_type {
+            ColorType::Greyscale | ColorType::GreyscaleAlpha => {
+                return Err(PngDecodeErrorCode::_6(
+                    self.color_type,
+                ))
+            }
+            _ => (),
+        }
+        if palette.len() % 3 != 0 {
+            return Err(PngDecodeErrorCode::_9(
+                palette.len(),
+            ));
+        }
+        // indices can't point past 2^bit_depth entries
+        let max_palette_entries = match self.color_type {
+            ColorType::IndexedColor => 1usize << self.bit_depth,
+            _ => 256,
+        };
+        let palette_entries = palette.len() / 3;
+        if palette_entries == 0 || palette_entries > max_palette_entries {
+            return Err(PngDecodeErrorCode::_43(
+                palette_entries,
+                max_palette_entries,
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn validate_transparency(&self) -> Result<(), PngDecodeErrorCode> {
+        let transparency = match &self.transparency {
+            Some(transparency) => transparency,
+            None => return Ok(()),
+        };
+
+        match self.color_type {
+            ColorType::GreyscaleAlpha | ColorType::TruecolorAlpha => Err(PngDecodeErrorCode::_20),
+            ColorType::Greyscale | ColorType::Truecolor => {
+                if transparency.len()
+                    != chunk_helpers::colortype_to_alpha_byte_length(self.color_type) as usize
+                {
+                    return Err(PngDecodeErrorCode::_21(
+                        self.color_type,
+                        transparency.len(),
+                    ));
+                }
+                Ok(())
+            }
+            ColorType::IndexedColor => {
+                // validate_palette has made sure that there is a palette
+                let palette_entries = self.palette.as_ref().map_or(0, |palette| palette.len() / 3);
+                if transparency.len() > palette_entries {
+                    return Err(PngDecodeErrorCode::_44(
+                        transparency.len(),
+                        palette_entries,
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn validate_ancillary_chunks(&self) -> Result<(), PngDecodeErrorCode> {
+        for (_, chunk) in &self.ancillary_chunks {
+            let chunk_type_bytes = chunk.chunk_type.as_bytes();
+            let is_ancillary = chunk_type_bytes.len() == 4
+                && chunk_type_bytes.iter().all(u8::is_ascii_alphabetic)
+                && chunk_type_bytes[0].is_ascii_lowercase();
+            if !is_ancillary || chunk.chunk_type == chunk_types::ChunkTypes::tRNS {
+                return Err(PngDecodeErrorCode::_45(
+                    chunk.chunk_type.clone(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_ancillary_chunks(&self, png_bytes: &mut Vec<u8>, position: ChunkPosition) {
+        for (chunk_position, chunk) in &self.ancillary_chunks {
+            if *chunk_position == position {
+                write_chunk(
+                    png_bytes,
+                    &chunk.chunk_type,
+                    &chunk.data,
+                );
+            }
+        }
+    }
+
+    /// See [PngEncoderOptions::reduce]
+    fn encode_reduced(
+        &self,
+        pixels: &[u8],
+        bytes_per_line: usize,
+    ) -> Result<Vec<u8>, PngDecodeErrorCode> {
+        let rgba8_pixels = self.to_rgba8(pixels, bytes_per_line)?;
+        let color_reduction = reduction::reduce_rgba8(
+            self.width,
+            self.height,
+            &rgba8_pixels,
+        );
+
+        let reduced_encoder = PngEncoder {
+            width: self.width,
+            height: self.height,
+            bit_depth: color_reduction.bit_depth,
+            color_type: color_reduction.color_type,
+            palette: color_reduction.palette,
+            transparency: color_reduction.transparency,
+            ancillary_chunks: self.ancillary_chunks.clone(),
+            encoder_options: PngEncoderOptions {
+                reduce: false,
+                ..self.encoder_options
+            },
+        };
+        reduced_encoder.encode(&color_reduction.pixels)
+    }
+
+    /// Converts pixels of 8 bits per sample or less to [PixelFormat::Rgba8], applying PLTE and tRNS
+    fn to_rgba8(
+        &self,
+        pixels: &[u8],
+        bytes_per_line: usize,
+    ) -> Result<Vec<u8>, PngDecodeErrorCode> {
+        let pixel_type = PixelType::new(
+            self.color_type,
+            self.bit_depth,
+        )?;
+        let transparency_chunk = match &self.transparency {
+            Some(transparency) => TransparencyChunk::new(
+                transparency.clone(),
+                pixel_type,
+            )?,
+            None => None,
+        };
+
+        let mut rgba8_pixels = vec![0u8; self.width as usize * self.height as usize * 4];
+        for (row, rgba8_row) in pixels
+            .chunks_exact(bytes_per_line)
+            .zip(rgba8_pixels.chunks_exact_mut(self.width as usize * 4))
+        {
+            for (col_index, rgba8_pixel) in rgba8_row.chunks_exact_mut(4).enumerate() {
+                let samples = bitmap::to_pixel_samples(
+                    pixel_type,
+                    transparency_chunk.as_ref(),
+                    self.palette.as_ref(),
+                    col_index,
+                    row,
+                )?;
+                bitmap::write_pixel(
+                    PixelFormat::Rgba8,
+                    &samples,
+                    rgba8_pixel,
+                )?;
+            }
+        }
+
+        Ok(rgba8_pixels)
+    }
+
+    /// Splits the image into the seven reduced images, and filters each of them on its own,
+    /// as if it were a separate image. The filtered passes are stored one after another.
+    /// See [crate::deinterlace::create_reduced_images]
+    fn filter_interlaced_image(&self, pixels: &[u8], bytes_per_line: usize) -> Vec<u8> {
+        let channel = colortype_to_channel(self.color_type);
+        let bits_per_pixel = channel as usize * self.bit_depth as usize;
+        let reduced_images = deinterlace::create_reduced_images(
+            self.width,
+            self.height,
+            channel,
+            self.bit_depth,
+        );
+
+        let mut filtered_image_data = vec![];
+        for (reduced_image, nth_pass) in reduced_images.iter().zip(deinterlace::Adam7Pass::ALL) {
+            // an empty pass has no rows at all, not even filter type bytes
+            if reduced_image.pixel_width == 0 || reduced_image.pixel_height == 0 {
+                continue;
+            }
+            let reduced_image_pixels = deinterlace::extract_reduced_image_pixels(
+                pixels,
+                bytes_per_line,
+                bits_per_pixel,
+                reduced_image,
+                nth_pass,
+            );
+            let filtered_pass = filter::FilterProcessor::new(
+                reduced_image.bytes_per_pixel,
+                reduced_image.bytes_per_line,
+                self.get_filter_strategy(),
+                self.encoder_options.compression_level,
+            )
+            .filter(&reduced_image_pixels);
+            filtered_image_data.extend_from_slice(&filtered_pass);
+        }
+
+        filtered_image_data
+    }
+
+    /// See [FilterStrategy::MinimumSumOfAbsoluteDifferences]
+    fn get_filter_strategy(&self) -> FilterStrategy {
+        match self.encoder_options.filter_strategy {
+            FilterStrategy::MinimumSumOfAbsoluteDifferences
+                if self.color_type == ColorType::IndexedColor || self.bit_depth < 8 =>
+            {
+                FilterStrategy::None
+            }
+            filter_strategy => filter_strategy,
+        }
+    }
+
+    ///    Width:              4 bytes
+    ///    Height:             4 bytes
+    ///    Bit depth:          1 byte
+    ///    Color type:         1 byte
+    ///    Compression method: 1 byte
+    ///    Filter method:      1 byte
+    ///    Interlace method:   1 byte
+    fn create_ihdr_chunk_data(&self) -> Vec<u8> {
+        let mut chunk_data = Vec::with_capacity(13);
+        chunk_data.extend_from_slice(&self.width.to_be_bytes());
+        chunk_data.extend_from_slice(&self.height.to_be_bytes());
+        chunk_data.push(self.bit_depth);
+        chunk_data.push(self.color_type as u8);
+        chunk_data.push(chunk_helpers::CompressionMethod::Deflate as u8);
+        chunk_data.push(chunk_helpers::FilterMethod::Adaptive as u8);
+        chunk_data.push(
+            match self.encoder_options.interlace {
+                true => chunk_helpers::InterlaceMethod::Adam7 as u8,
+                false => chunk_helpers::InterlaceMethod::None as u8,
+            },
+        );
+
+        chunk_data
+    }
+}
+
+/// Appends a chunk: length, chunk type, chunk data, and the CRC of chunk type and chunk data.
+pub(crate) fn write_chunk(png_bytes: &mut Vec<u8>, chunk_type: &str, chunk_data: &[u8]) {
+    png_bytes.extend_from_slice(&(chunk_data.len() as u32).to_be_bytes());
+
+    let mut crc_hasher = crc32fast::Hasher::new();
+    crc_hasher.update(chunk_type.as_bytes());
+    crc_hasher.update(chunk_data);
+
+    png_bytes.extend_from_slice(chunk_type.as_bytes());
+    png_bytes.extend_from_slice(chunk_data);
+    png_bytes.extend_from_slice(&crc_hasher.finalize().to_be_bytes());
+}
@@ -61,6 +61,72 @@ pub enum PngDecodeErrorCode {
     _23,
     /// Number type cast error `(from, to)`
     _24(String, String),
+    /// Image data has ended before all rows of the image `(expected_rows, actual_rows)`
+    _25(u32, u32),
+    /// Interlaced images can't be decoded row by row, because every row is spread over several passes
+    _26,
+    /// IDAT chunk has appeared before IHDR chunk
+    _27,
+    /// Image width exceeds the limit `(width, max_width)`
+    _28(u32, u32),
+    /// Image height exceeds the limit `(height, max_height)`
+    _29(u32, u32),
+    /// Decoded pixels take more bytes than the limit `(output_bytes, max_output_bytes)`
+    _30(u64, u64),
+    /// Image data inflates to more bytes than the limit `(max_inflated_bytes)`
+    _31(u64),
+    /// Chunk data length exceeds the limit `(chunk_type, chunk_data_length, max_chunk_length)`
+    _32(String, u32, u32),
+    /// Total length of ancillary chunks exceeds the limit `(max_ancillary_bytes)`
+    _33(u64),
+    /// I/O error while reading the image `(reason)`
+    _34(String),
+    /// Image has ended unexpectedly `(expected_bytes, actual_bytes)`
+    _35(u64, u64),
+    /// Chunk type is not valid text `(chunk_type_bytes)`
+    _36(Vec<u8>),
+    /// Byte reader can't read the image `(reason)`
+    _37(String),
+    /// IHDR chunk has not been decoded yet, but a value from it is needed
+    _38,
+    /// Palette index is out of range `(index, number_of_palette_entries)`
+    _39(u8, usize),
+    /// IHDR chunk appears more than once
+    _40,
+    /// Pixel buffer given to the encoder has a wrong length `(expected_length, actual_length)`
+    _41(usize, usize),
+    /// Image dimensions can't be encoded `(width, height)`
+    _42(u32, u32),
+    /// Number of palette entries is out of range `(number_of_palette_entries, max_palette_entries)`
+    _43(usize, usize),
+    /// tRNS chunk has more alpha values than there are palette entries `(number_of_alpha_values, number_of_palette_entries)`
+    _44(usize, usize),
+    /// Chunk can't be added by the encoder, because its type is not the type of an ancillary chunk `(chunk_type)`
+    _45(String),
+    /// Optimized image doesn't decode to the same pixels as the original image
+    _46,
+    /// Chunk data has a wrong length `(chunk_type, expected_length, actual_length)`
+    _47(String, usize, usize),
+    /// Chunk is out of place and has been ignored `(chunk_type, where_it_must_be)`
+    _48(String, String),
+    /// Chunk that may appear only once appears again, and has been ignored `(chunk_type)`
+    _49(String),
+    /// Both sRGB and iCCP chunks appear, although only one of them may
+    _50,
+    /// Chunk data is invalid, and the chunk has been ignored `(chunk_type, reason)`
+    _51(String, String),
+    /// ICC profile can't be used for color conversion `(reason)`
+    _52(String),
+    /// Ancillary chunk can't be written, because the values given for it are invalid `(chunk_type, reason)`
+    _53(String, String),
+    /// Adler-32 checksum at the end of the image data doesn't match the decompressed image data
+    _54,
+    /// Pixels can't be converted to the native pixel format, which has no fixed number of bytes per pixel
+    _55,
+    /// Decoder has no byte reader to pull bytes from, because its bytes are pushed to it
+    _56,
+    /// Width or height in IHDR chunk is 0 or over 2^31 - 1 `(width, height)`
+    _57(u32, u32),
 }
 
 /// For specific errors that are not in line with the PNG specification and can't really be generalized, meaning that they probably have unique error messages.
@@ -80,6 +146,10 @@ impl PngDecodeError {
             approx_byte_location,
         }
     }
+
+    pub fn get_approx_byte_location(&self) -> usize {
+        self.approx_byte_location
+    }
 }
 
 impl Error for PngDecodeError {}
@@ -111,6 +181,39 @@ impl fmt::Display for PngDecodeError {
       PngDecodeErrorCode::_22(color_type, bit_depth) => write!(fmt, "Combination of color type of {:?} and bit depth of {} is not permitted.", color_type, bit_depth),
       PngDecodeErrorCode::_23 => write!(fmt, "Pixel type has not been defined yet. Probably tRNS chunk has been encountered before IHDR chunk."),
       PngDecodeErrorCode::_24(from, to) => write!(fmt, "Failed to convert {} to {}", from, to),
+      PngDecodeErrorCode::_25(expected_rows, actual_rows) => write!(fmt, "Image data has ended after {} rows, but the image has {} rows", actual_rows, expected_rows),
+      PngDecodeErrorCode::_26 => write!(fmt, "Interlaced images can't be decoded row by row. Decode the entire image instead."),
+      PngDecodeErrorCode::_27 => write!(fmt, "IDAT chunk has appeared before IHDR chunk."),
+      PngDecodeErrorCode::_28(width, max_width) => write!(fmt, "Image width {} exceeds the limit of {}", width, max_width),
+      PngDecodeErrorCode::_29(height, max_height) => write!(fmt, "Image height {} exceeds the limit of {}", height, max_height),
+      PngDecodeErrorCode::_30(output_bytes, max_output_bytes) => write!(fmt, "Decoded pixels take {} bytes, which exceeds the limit of {} bytes", output_bytes, max_output_bytes),
+      PngDecodeErrorCode::_31(max_inflated_bytes) => write!(fmt, "Image data inflates to more than the limit of {} bytes", max_inflated_bytes),
+      PngDecodeErrorCode::_32(chunk_type, chunk_data_length, max_chunk_length) => write!(fmt, "{} chunk has {} bytes of data, which exceeds the limit of {}", chunk_type, chunk_data_length, max_chunk_length),
+      PngDecodeErrorCode::_33(max_ancillary_bytes) => write!(fmt, "Ancillary chunks add up to more than the limit of {} bytes", max_ancillary_bytes),
+      PngDecodeErrorCode::_34(reason) => write!(fmt, "Failed to read the image. Reason: {}", reason),
+      PngDecodeErrorCode::_35(expected_bytes, actual_bytes) => write!(fmt, "The image has ended unexpectedly. Expected {} more bytes, but only {} bytes were left.", expected_bytes, actual_bytes),
+      PngDecodeErrorCode::_36(chunk_type_bytes) => write!(fmt, "Chunk type {:?} is not valid text. The image is likely to be corrupt.", chunk_type_bytes),
+      PngDecodeErrorCode::_37(reason) => write!(fmt, "Byte reader can't read the image. Reason: {}", reason),
+      PngDecodeErrorCode::_38 => write!(fmt, "IHDR chunk has not been decoded yet, but a value from it is needed. To fix this error, make IHDR chunk appear first."),
+      PngDecodeErrorCode::_39(index, palette_entries) => write!(fmt, "Palette index {} is out of range, because PLTE chunk only has {} entries.", index, palette_entries),
+      PngDecodeErrorCode::_40 => write!(fmt, "IHDR chunk has appeared more than once. There must be only one IHDR chunk."),
+      PngDecodeErrorCode::_41(expected_length, actual_length) => write!(fmt, "The image needs {} bytes of pixels, but {} bytes have been given.", expected_length, actual_length),
+      PngDecodeErrorCode::_42(width, height) => write!(fmt, "An image of {}x{} pixels can't be encoded. Width and height must be between 1 and 2^31 - 1.", width, height),
+      PngDecodeErrorCode::_43(palette_entries, max_palette_entries) => write!(fmt, "Palette has {} entries, but it must have from 1 to {} entries.", palette_entries, max_palette_entries),
+      PngDecodeErrorCode::_44(alpha_values, palette_entries) => write!(fmt, "tRNS chunk has {} alpha values, but there are only {} palette entries.", alpha_values, palette_entries),
+      PngDecodeErrorCode::_45(chunk_type) => write!(fmt, "{:?} can't be added as an ancillary chunk. Chunk type must be 4 ASCII letters starting with a lowercase letter, and tRNS chunk is set with the transparency of the encoder.", chunk_type),
+      PngDecodeErrorCode::_46 => write!(fmt, "Optimized image doesn't decode to the same pixels as the original image, so it has been thrown away."),
+      PngDecodeErrorCode::_47(chunk_type, expected_length, actual_length) => write!(fmt, "Data of {} chunk must be {} bytes, but it is {} bytes. The chunk has been ignored.", chunk_type, expected_length, actual_length),
+      PngDecodeErrorCode::_48(chunk_type, where_it_must_be) => write!(fmt, "{} chunk must come {}. The chunk has been ignored.", chunk_type, where_it_must_be),
+      PngDecodeErrorCode::_49(chunk_type) => write!(fmt, "{} chunk must not appear more than once. The chunk has been ignored.", chunk_type),
+      PngDecodeErrorCode::_50 => write!(fmt, "sRGB and iCCP chunks must not both appear. iCCP chunk takes precedence."),
+      PngDecodeErrorCode::_51(chunk_type, reason) => write!(fmt, "{} chunk is invalid: {}. The chunk has been ignored.", chunk_type, reason),
+      PngDecodeErrorCode::_52(reason) => write!(fmt, "ICC profile can't be used for color conversion: {}.", reason),
+      PngDecodeErrorCode::_53(chunk_type, reason) => write!(fmt, "{} chunk can't be written: {}.", chunk_type, reason),
+      PngDecodeErrorCode::_54 => write!(fmt, "Adler-32 checksum of the image data doesn't match. The image data may be corrupt, but it has been decoded as it is."),
+      PngDecodeErrorCode::_55 => write!(fmt, "Pixels can't be converted to the native pixel format, because it has no fixed number of bytes per pixel. Native pixels are copied as they are instead."),
+      PngDecodeErrorCode::_56 => write!(fmt, "Decoder has no byte reader to pull bytes from. Bytes of a push decoder must be pushed to it."),
+      PngDecodeErrorCode::_57(width, height) => write!(fmt, "IHDR chunk gives a size of {}x{} pixels, but width and height must be between 1 and 2^31 - 1.", width, height),
     }
     }
 }
@@ -159,20 +262,26 @@ impl MultiErrorsManager {
         }
     }
 
-    // Add an error.
-    pub fn handle_err(&mut self, err: PngDecodeError) -> PngDecodeError {
+    pub fn get_errors(&self) -> &Vec<PngDecodeError> {
+        &self.errors
+    }
+
+    /// Add an error. When `fail_fast` is set, the error is returned so that decoding stops right there.
+    pub fn handle_err(&mut self, err: PngDecodeError) -> Result<(), PngDecodeErrorCode> {
         self.errors.push(err.clone());
         self.print_all_errors();
         if self.fail_fast {
             self.end(ExitReason::FailFast);
+            return Err(err.code);
         }
-        return err;
+
+        Ok(())
     }
 
     pub fn end(&self, end_reason: ExitReason) {
         match end_reason {
-            ExitReason::FailFast => panic!(
-                "Ending program because fail_fast is set to true and first error is encountered."
+            ExitReason::FailFast => println!(
+                "Ending because fail_fast is set to true and first error is encountered."
             ),
             ExitReason::JobDone => {
                 if self.errors.len() > 0 {
@@ -0,0 +1,183 @@
+//! The other direction of [crate::unfilter]: turns rows of pixels into filtered rows for the encoder.
+//!
+//! Any filter type can be used for any row, so which one to use is up to a heuristic (see [FilterStrategy]).
+//! The filter type that makes a row compress the smallest can't be known without compressing it,
+//! so the heuristics other than [FilterStrategy::BruteForce] only guess it from the filtered bytes.
+
+use crate::{encoder::FilterStrategy, unfilter::paeth_predictor, zlib};
+
+/// Filter types in the order of their filter type byte
+const FILTER_TYPES: [u8; 5] = [0, 1, 2, 3, 4];
+
+/// `None` for the strategies that choose a filter type for each row
+fn get_fixed_filter_type(filter_strategy: FilterStrategy) -> Option<u8> {
+    match filter_strategy {
+        FilterStrategy::None => Some(0),
+        FilterStrategy::Sub => Some(1),
+        FilterStrategy::Up => Some(2),
+        FilterStrategy::Average => Some(3),
+        FilterStrategy::Paeth => Some(4),
+        FilterStrategy::MinimumSumOfAbsoluteDifferences
+        | FilterStrategy::Entropy
+        | FilterStrategy::BruteForce => None,
+    }
+}
+
+/// Variable names follow [crate::unfilter::UnfilterProcessor].
+/// `previous_row` of the first row is all zeros, which is what the filters assume for the row above the image.
+pub(crate) struct FilterProcessor {
+    bytes_per_pixel: usize,
+    bytes_per_line: usize,
+    filter_strategy: FilterStrategy,
+    /// Only used by [FilterStrategy::BruteForce]
+    compression_level: u8,
+}
+
+impl FilterProcessor {
+    pub fn new(
+        bytes_per_pixel: usize,
+        bytes_per_line: usize,
+        filter_strategy: FilterStrategy,
+        compression_level: u8,
+    ) -> Self {
+        Self {
+            bytes_per_pixel,
+            bytes_per_line,
+            filter_strategy,
+            compression_level,
+        }
+    }
+
+    /// Filters every row of `pixels` (`bytes_per_line` bytes each),
+    /// and returns them with the filter type byte in front of each, ready to be compressed.
+    pub fn filter(&self, pixels: &[u8]) -> Vec<u8> {
+        if self.bytes_per_line == 0 {
+            return vec![];
+        }
+        let row_count = pixels.len() / self.bytes_per_line;
+        let mut filtered_image_data = Vec::with_capacity((self.bytes_per_line + 1) * row_count);
+        let zero_row = vec![0u8; self.bytes_per_line];
+        // one filtered row per filter type, with the filter type byte in front
+        let mut candidate_rows = vec![vec![0u8; self.bytes_per_line + 1]; FILTER_TYPES.len()];
+
+        let mut previous_row: &[u8] = &zero_row;
+        for row in pixels.chunks_exact(self.bytes_per_line) {
+            let chosen_filter_type = match get_fixed_filter_type(self.filter_strategy) {
+                Some(filter_type) => {
+                    self.filter_row(
+                        filter_type,
+                        previous_row,
+                        row,
+                        &mut candidate_rows[filter_type as usize],
+                    );
+                    filter_type
+                }
+                None => {
+                    for filter_type in FILTER_TYPES {
+                        self.filter_row(
+                            filter_type,
+                            previous_row,
+                            row,
+                            &mut candidate_rows[filter_type as usize],
+                        );
+                    }
+                    self.choose_filter_type(&candidate_rows)
+                }
+            };
+            let chosen_row = &candidate_rows[chosen_filter_type as usize];
+            filtered_image_data.extend_from_slice(chosen_row);
+            previous_row = row;
+        }
+
+        filtered_image_data
+    }
+
+    /// Writes the filter type byte, followed by `row` filtered with `filter_type`, into `out_row`
+    fn filter_row(&self, filter_type: u8, previous_row: &[u8], row: &[u8], out_row: &mut [u8]) {
+        out_row[0] = filter_type;
+        let out_row = &mut out_row[1..];
+
+        for i in 0..self.bytes_per_line {
+            let left = if i >= self.bytes_per_pixel {
+                row[i - self.bytes_per_pixel]
+            } else {
+                0
+            };
+            let up = previous_row[i];
+            let up_left = if i >= self.bytes_per_pixel {
+                previous_row[i - self.bytes_per_pixel]
+            } else {
+                0
+            };
+
+            let prediction = match filter_type {
+                1 => left,
+                2 => up,
+                // same as unfiltering, the sum must not overflow before it is halved
+                3 => ((left as u16 + up as u16) >> 1) as u8,
+                4 => paeth_predictor(
+                    left as i16,
+                    up as i16,
+                    up_left as i16,
+                ),
+                _ => 0,
+            };
+            out_row[i] = row[i].wrapping_sub(prediction);
+        }
+    }
+
+    /// Picks the filter type whose row scores the lowest, preferring the lower filter type on a tie
+    fn choose_filter_type(&self, candidate_rows: &[Vec<u8>]) -> u8 {
+        let mut chosen_filter_type = 0;
+        let mut lowest_score = f64::MAX;
+        for filter_type in FILTER_TYPES {
+            let score = self.score_row(&candidate_rows[filter_type as usize]);
+            if score < lowest_score {
+                lowest_score = score;
+                chosen_filter_type = filter_type;
+            }
+        }
+
+        chosen_filter_type
+    }
+
+    /// Lower is expected to compress better
+    /// * `filtered_row` - including the filter type byte
+    fn score_row(&self, filtered_row: &[u8]) -> f64 {
+        match self.filter_strategy {
+            // libpng's heuristic. Bytes are seen as signed, so that small negative differences count as small as well
+            FilterStrategy::MinimumSumOfAbsoluteDifferences => filtered_row[1..]
+                .iter()
+                .map(|byte| (*byte as i8).unsigned_abs() as u64)
+                .sum::<u64>() as f64,
+            // Shannon entropy of the bytes in bits, multiplied by the number of bytes
+            FilterStrategy::Entropy => {
+                let mut byte_counts = [0u32; 256];
+                for byte in &filtered_row[1..] {
+                    byte_counts[*byte as usize] += 1;
+                }
+                let byte_count = (filtered_row.len() - 1) as f64;
+                byte_counts
+                    .iter()
+                    .filter(|count| **count > 0)
+                    .map(|count| {
+                        let count = *count as f64;
+                        -count * (count / byte_count).log2()
+                    })
+                    .sum()
+            }
+            // each row is compressed on its own, so matches with the rows above it are not taken into account
+            FilterStrategy::BruteForce => zlib::compress(
+                filtered_row,
+                self.compression_level,
+            )
+            .len() as f64,
+            // never scored, see get_fixed_filter_type
+            FilterStrategy::None
+            | FilterStrategy::Sub
+            | FilterStrategy::Up
+            | FilterStrategy::Average
+            | FilterStrategy::Paeth => 0.0,
+        }
+    }
+}
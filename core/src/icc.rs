@@ -0,0 +1,174 @@
+//! Reads matrix/TRC ICC profiles, the kind that describes RGB and greyscale displays and working spaces
+//! such as Adobe RGB and Display P3.
+//!
+//! https://www.color.org/specification/ICC.1-2022-05.pdf
+//!
+//! Such a profile decodes each channel into linear light with a tone reproduction curve (rTRC, gTRC and bTRC tags,
+//! or kTRC for greyscale), and converts linear RGB into CIE XYZ relative to D50 with the colorants
+//! of the primaries as a matrix (rXYZ, gXYZ and bXYZ tags).
+//! Profiles built only from lookup tables (A2B0 tag), and profiles connecting through CIELAB, are not supported.
+
+use crate::color_transform::{ColorProfile, ToneCurve};
+
+/// Size of the profile header, which the tag table follows
+const HEADER_LENGTH: usize = 128;
+
+/// Number of parameters of each parametric curve function type
+const PARAMETRIC_CURVE_PARAMETER_COUNTS: [usize; 5] = [1, 3, 4, 5, 7];
+
+fn read_u16(data: &[u8], index: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(index..index + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], index: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(index..index + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Fixed point number with 16 fractional bits
+fn read_s15_fixed16(data: &[u8], index: usize) -> Option<f64> {
+    Some(read_u32(data, index)? as i32 as f64 / 65536.0)
+}
+
+/// Data of the tag with `signature`, or `None` when the profile doesn't have it
+fn find_tag<'a>(profile: &'a [u8], signature: &[u8; 4]) -> Result<Option<&'a [u8]>, String> {
+    let tag_count = read_u32(profile, HEADER_LENGTH).ok_or("the tag table is missing")?;
+    for index in 0..tag_count as usize {
+        let entry_start = HEADER_LENGTH + 4 + index * 12;
+        let entry = profile
+            .get(entry_start..entry_start + 12)
+            .ok_or("the tag table is cut off")?;
+        if &entry[0..4] != signature {
+            continue;
+        }
+
+        let offset = read_u32(entry, 4).unwrap_or(0) as usize;
+        let length = read_u32(entry, 8).unwrap_or(0) as usize;
+        let tag_data = offset
+            .checked_add(length)
+            .and_then(|end| profile.get(offset..end))
+            .ok_or_else(|| {
+                format!(
+                    "{} tag is outside the profile",
+                    String::from_utf8_lossy(signature)
+                )
+            })?;
+        return Ok(Some(tag_data));
+    }
+
+    Ok(None)
+}
+
+fn find_required_tag<'a>(profile: &'a [u8], signature: &[u8; 4]) -> Result<&'a [u8], String> {
+    find_tag(profile, signature)?.ok_or_else(|| {
+        format!(
+            "{} tag is missing, and only matrix/TRC profiles are supported",
+            String::from_utf8_lossy(signature)
+        )
+    })
+}
+
+/// XYZ of a colorant, from a tag of `XYZ ` type
+fn read_xyz_tag(profile: &[u8], signature: &[u8; 4]) -> Result<[f64; 3], String> {
+    let tag_data = find_required_tag(profile, signature)?;
+    let read_xyz = || {
+        Some([
+            read_s15_fixed16(tag_data, 8)?,
+            read_s15_fixed16(tag_data, 12)?,
+            read_s15_fixed16(tag_data, 16)?,
+        ])
+    };
+    let xyz = match &tag_data[..tag_data.len().min(4)] {
+        b"XYZ " => read_xyz(),
+        _ => None,
+    };
+
+    xyz.ok_or_else(|| {
+        format!(
+            "{} tag is not a valid XYZ tag",
+            String::from_utf8_lossy(signature)
+        )
+    })
+}
+
+/// Tone curve from a tag of `curv` or `para` type
+fn read_curve_tag(profile: &[u8], signature: &[u8; 4]) -> Result<ToneCurve, String> {
+    let tag_data = find_required_tag(profile, signature)?;
+    let tone_curve = match &tag_data[..tag_data.len().min(4)] {
+        b"curv" => read_u32(tag_data, 8).and_then(
+            |entry_count| match entry_count {
+                0 => Some(ToneCurve::Gamma(1.0)),
+                // u8Fixed8Number
+                1 => Some(ToneCurve::Gamma(
+                    read_u16(tag_data, 12)? as f64 / 256.0,
+                )),
+                entry_count => (0..entry_count as usize)
+                    .map(|index| read_u16(tag_data, 12 + index * 2))
+                    .collect::<Option<Vec<u16>>>()
+                    .map(ToneCurve::Table),
+            },
+        ),
+        b"para" => read_u16(tag_data, 8).and_then(|function_type| {
+            let parameter_count = *PARAMETRIC_CURVE_PARAMETER_COUNTS.get(function_type as usize)?;
+            let mut parameters = [0.0; 7];
+            for (index, parameter) in parameters.iter_mut().take(parameter_count).enumerate() {
+                *parameter = read_s15_fixed16(tag_data, 12 + index * 4)?;
+            }
+            Some(ToneCurve::Parametric(
+                function_type,
+                parameters,
+            ))
+        }),
+        _ => None,
+    };
+
+    tone_curve.ok_or_else(|| {
+        format!(
+            "{} tag is not a valid curve",
+            String::from_utf8_lossy(signature)
+        )
+    })
+}
+
+/// Reads the tone curves and the colorants of a matrix/TRC profile.
+/// Returns the reason when the profile is invalid or not supported
+pub(crate) fn parse_icc_profile(profile: &[u8]) -> Result<ColorProfile, String> {
+    if profile.len() < HEADER_LENGTH + 4 || &profile[36..40] != b"acsp" {
+        return Err("the profile has no valid header".to_string());
+    }
+    if &profile[20..24] != b"XYZ " {
+        return Err("only profiles connecting through CIE XYZ are supported".to_string());
+    }
+
+    match &profile[16..20] {
+        b"RGB " => {
+            let red = read_xyz_tag(profile, b"rXYZ")?;
+            let green = read_xyz_tag(profile, b"gXYZ")?;
+            let blue = read_xyz_tag(profile, b"bXYZ")?;
+            Ok(ColorProfile {
+                tone_curves: vec![
+                    read_curve_tag(profile, b"rTRC")?,
+                    read_curve_tag(profile, b"gTRC")?,
+                    read_curve_tag(profile, b"bTRC")?,
+                ],
+                // each colorant is a column
+                to_xyz: Some([
+                    [red[0], green[0], blue[0]],
+                    [red[1], green[1], blue[1]],
+                    [red[2], green[2], blue[2]],
+                ]),
+            })
+        }
+        b"GRAY" => Ok(ColorProfile {
+            tone_curves: vec![read_curve_tag(profile, b"kTRC")?],
+            to_xyz: None,
+        }),
+        color_space => Err(format!(
+            "{} color space is not supported",
+            String::from_utf8_lossy(color_space).trim_end()
+        )),
+    }
+}
@@ -1,11 +1,29 @@
+pub mod background;
 mod bitmap;
 pub mod byte_reader;
-mod chunk_helpers;
+pub mod chunk_editor;
+pub mod chunk_helpers;
 mod chunk_types;
+pub mod color_chunks;
+mod color_transform;
 mod common;
+pub mod decoded_image;
 pub mod decoder;
 mod deinterlace;
-mod errors;
+pub mod encoder;
+pub mod errors;
+mod filter;
+mod icc;
+pub mod optimizer;
+pub mod palette_chunks;
+pub mod physical_chunks;
+pub mod push_decoder;
+pub mod quantizer;
+mod reduction;
+pub mod row_decoder;
+pub mod significant_bits;
+pub mod text_chunks;
+pub mod timestamp;
 mod trns;
 mod unfilter;
 mod zlib;
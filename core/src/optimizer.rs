@@ -0,0 +1,321 @@
+//! Lossless recompression of existing PNG files, in the spirit of oxipng.
+//!
+//! The image is decoded, then encoded again with every combination of [FilterStrategy], compression level
+//! and color type reduction (see [crate::encoder::PngEncoderOptions::reduce]), and the smallest result is kept.
+//! Before it is returned, the result is decoded and compared with the original pixels.
+//!
+//! The deflate window size is not tried, because miniz_oxide always compresses with the largest window of 32 KiB,
+//! and a smaller window never makes the image data smaller.
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{
+    byte_reader::{ByteReader, ByteReaderMode},
+    chunk_helpers::{ColorType, InterlaceMethod},
+    chunk_types,
+    decoded_image::{AncillaryChunk, DecodedImage, PixelFormat, ProbedImage},
+    decoder::{PngDecoder, PngDecoderOptions},
+    encoder::{
+        ChunkPosition, FilterStrategy, PngEncoder, PngEncoderOptions, COLOR_DEPENDENT_CHUNK_TYPES,
+    },
+    errors::PngDecodeErrorCode,
+};
+
+const FILTER_STRATEGIES: [FilterStrategy; 8] = [
+    FilterStrategy::None,
+    FilterStrategy::Sub,
+    FilterStrategy::Up,
+    FilterStrategy::Average,
+    FilterStrategy::Paeth,
+    FilterStrategy::MinimumSumOfAbsoluteDifferences,
+    FilterStrategy::Entropy,
+    FilterStrategy::BruteForce,
+];
+
+/// Default level of miniz_oxide, and its slowest level, which is sometimes larger
+const COMPRESSION_LEVELS: [u8; 2] = [9, 10];
+
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct OptimizerOptions {
+    /// Drops every ancillary chunk that doesn't change the pixels (everything except tRNS chunk),
+    /// such as text, gamma, color profile and timestamp chunks, along with the suggested palette
+    /// of truecolor images. Default: false
+    pub strip_ancillary_chunks: bool,
+}
+
+#[wasm_bindgen]
+impl OptimizerOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(strip_ancillary_chunks: bool) -> Self {
+        OptimizerOptions {
+            strip_ancillary_chunks,
+        }
+    }
+}
+
+impl Default for OptimizerOptions {
+    fn default() -> Self {
+        OptimizerOptions::new(false)
+    }
+}
+
+pub struct OptimizedImage {
+    /// Bytes of the smallest PNG file found. The original bytes, when nothing has made it smaller
+    pub png_bytes: Vec<u8>,
+    pub original_length: usize,
+    /// Options of the encoder that has written `png_bytes`. `None` when the original bytes have been kept
+    pub encoder_options: Option<PngEncoderOptions>,
+}
+
+impl OptimizedImage {
+    pub fn bytes_saved(&self) -> usize {
+        self.original_length.saturating_sub(self.png_bytes.len())
+    }
+}
+
+/// Everything about the original image that is written into every trial
+struct OriginalImage {
+    decoded_image: DecodedImage,
+    palette: Option<Vec<u8>>,
+    transparency: Option<Vec<u8>>,
+    ancillary_chunks: Vec<(ChunkPosition, AncillaryChunk)>,
+}
+
+/// Returns the smallest lossless encoding of `png_bytes` that has been found.
+/// The input must be a valid image, because the optimizer can't know which pixels a broken image should have.
+pub fn optimize(
+    png_bytes: &[u8],
+    optimizer_options: &OptimizerOptions,
+) -> Result<OptimizedImage, PngDecodeErrorCode> {
+    let decoder_options = PngDecoderOptions {
+        fail_fast: true,
+        output_format: PixelFormat::Native,
+        ..Default::default()
+    };
+    let mut byte_reader = ByteReader::new(
+        None,
+        ByteReaderMode::RAW,
+        Some(png_bytes.to_vec()),
+    );
+    byte_reader.read_image()?;
+    let probed_image = PngDecoder::new(
+        &mut byte_reader,
+        &decoder_options,
+    )
+    .probe_chunks()?;
+    let original_image = read_original_image(
+        png_bytes,
+        &probed_image,
+        decoder_options,
+        optimizer_options,
+    )?;
+    let header = &original_image.decoded_image.header;
+
+    // 16-bit images are never reduced, so there is nothing to gain from trying
+    let can_reduce = header.bit_depth <= 8
+        && original_image
+            .ancillary_chunks
+            .iter()
+            .all(|(_, chunk)| !COLOR_DEPENDENT_CHUNK_TYPES.contains(&chunk.chunk_type.as_str()))
+        // a suggested palette is dropped by reduction
+        && (header.color_type == ColorType::IndexedColor || original_image.palette.is_none());
+    let reduce_options: &[bool] = match can_reduce {
+        true => &[false, true],
+        false => &[false],
+    };
+
+    let mut smallest: Option<(Vec<u8>, PngEncoderOptions)> = None;
+    for &reduce in reduce_options {
+        for compression_level in COMPRESSION_LEVELS {
+            for filter_strategy in FILTER_STRATEGIES {
+                let encoder_options = PngEncoderOptions {
+                    compression_level,
+                    filter_strategy,
+                    interlace: matches!(
+                        header.interlace_method,
+                        InterlaceMethod::Adam7
+                    ),
+                    reduce,
+                };
+                let encoded_bytes = encode(
+                    &original_image,
+                    &encoder_options,
+                )?;
+                if smallest
+                    .as_ref()
+                    .is_none_or(|(png_bytes, _)| encoded_bytes.len() < png_bytes.len())
+                {
+                    smallest = Some((encoded_bytes, encoder_options));
+                }
+            }
+        }
+    }
+
+    // the original bytes have every ancillary chunk, so they can only be kept when there is nothing to strip
+    let has_strippable_chunks = probed_image
+        .ancillary_chunks
+        .iter()
+        .any(|chunk| chunk.chunk_type != chunk_types::ChunkTypes::tRNS)
+        || (header.color_type != ColorType::IndexedColor && has_palette_chunk(&probed_image));
+    let can_keep_original = !optimizer_options.strip_ancillary_chunks || !has_strippable_chunks;
+    let (optimized_bytes, encoder_options) = match smallest {
+        Some((encoded_bytes, encoder_options))
+            if !can_keep_original || encoded_bytes.len() < png_bytes.len() =>
+        {
+            verify_pixels(png_bytes, &encoded_bytes)?;
+            (
+                encoded_bytes,
+                Some(encoder_options),
+            )
+        }
+        _ => (png_bytes.to_vec(), None),
+    };
+
+    Ok(OptimizedImage {
+        png_bytes: optimized_bytes,
+        original_length: png_bytes.len(),
+        encoder_options,
+    })
+}
+
+fn has_palette_chunk(probed_image: &ProbedImage) -> bool {
+    probed_image
+        .chunks
+        .iter()
+        .any(|chunk| chunk.chunk_type == chunk_types::ChunkTypes::PLTE)
+}
+
+fn read_original_image(
+    png_bytes: &[u8],
+    probed_image: &ProbedImage,
+    decoder_options: PngDecoderOptions,
+    optimizer_options: &OptimizerOptions,
+) -> Result<OriginalImage, PngDecodeErrorCode> {
+    let decoded_image = decode(png_bytes, decoder_options)?;
+
+    let palette = probed_image
+        .chunks
+        .iter()
+        .find(|chunk| chunk.chunk_type == chunk_types::ChunkTypes::PLTE)
+        .map(|chunk| {
+            // skips the length and chunk type
+            let chunk_data_start = chunk.byte_pos + 8;
+            png_bytes[chunk_data_start..chunk_data_start + chunk.length as usize].to_vec()
+        })
+        .filter(|_| {
+            !optimizer_options.strip_ancillary_chunks
+                || decoded_image.header.color_type == ColorType::IndexedColor
+        });
+    let transparency = probed_image
+        .ancillary_chunks
+        .iter()
+        .find(|chunk| chunk.chunk_type == chunk_types::ChunkTypes::tRNS)
+        .map(|chunk| chunk.data.clone());
+
+    let mut ancillary_chunks = vec![];
+    if !optimizer_options.strip_ancillary_chunks {
+        // ancillary chunks of the probed image are in the same order as the chunks starting with a lowercase letter
+        let mut position = ChunkPosition::BeforePalette;
+        let mut ancillary_chunk_iter = probed_image.ancillary_chunks.iter();
+        for chunk_info in &probed_image.chunks {
+            match chunk_info.chunk_type.as_str() {
+                chunk_types::ChunkTypes::PLTE => position = ChunkPosition::BeforeImageData,
+                chunk_types::ChunkTypes::IDAT => position = ChunkPosition::AfterImageData,
+                chunk_type if chunk_type.as_bytes()[0].is_ascii_lowercase() => {
+                    let chunk = match ancillary_chunk_iter.next() {
+                        Some(chunk) => chunk,
+                        None => break,
+                    };
+                    // written by the encoder from the transparency, right before the chunks that follow it here
+                    if chunk.chunk_type == chunk_types::ChunkTypes::tRNS {
+                        position = ChunkPosition::BeforeImageData;
+                    } else {
+                        ancillary_chunks.push((position, chunk.clone()));
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    Ok(OriginalImage {
+        decoded_image,
+        palette,
+        transparency,
+        ancillary_chunks,
+    })
+}
+
+fn encode(
+    original_image: &OriginalImage,
+    encoder_options: &PngEncoderOptions,
+) -> Result<Vec<u8>, PngDecodeErrorCode> {
+    let header = &original_image.decoded_image.header;
+    let mut encoder = PngEncoder::new(
+        header.width,
+        header.height,
+        header.color_type,
+        header.bit_depth,
+        encoder_options,
+    );
+    if let Some(palette) = &original_image.palette {
+        encoder.set_palette(palette.clone());
+    }
+    if let Some(transparency) = &original_image.transparency {
+        encoder.set_transparency(transparency.clone());
+    }
+    for (position, chunk) in &original_image.ancillary_chunks {
+        encoder.add_ancillary_chunk(
+            &chunk.chunk_type,
+            chunk.data.clone(),
+            *position,
+        );
+    }
+
+    encoder.encode(&original_image.decoded_image.pixels)
+}
+
+fn decode(
+    png_bytes: &[u8],
+    decoder_options: PngDecoderOptions,
+) -> Result<DecodedImage, PngDecodeErrorCode> {
+    let mut byte_reader = ByteReader::new(
+        None,
+        ByteReaderMode::RAW,
+        Some(png_bytes.to_vec()),
+    );
+    byte_reader.read_image()?;
+    let decoded_image = PngDecoder::new(
+        &mut byte_reader,
+        &decoder_options,
+    )
+    .run()?;
+
+    Ok(decoded_image)
+}
+
+/// Compares the pixels with their largest samples, so that reduced color types and bit depths can be compared as well
+fn verify_pixels(
+    original_png_bytes: &[u8],
+    optimized_png_bytes: &[u8],
+) -> Result<(), PngDecodeErrorCode> {
+    let decoder_options = PngDecoderOptions {
+        fail_fast: true,
+        output_format: PixelFormat::Rgba16BigEndian,
+        ..Default::default()
+    };
+    let original_image = decode(
+        original_png_bytes,
+        decoder_options,
+    )?;
+    let optimized_image = decode(
+        optimized_png_bytes,
+        decoder_options,
+    )?;
+    if original_image.pixels != optimized_image.pixels {
+        return Err(PngDecodeErrorCode::_46);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+//! sPLT and hIST chunks, which help viewers that can show only a limited number of colors
+//! to choose a palette for the image.
+//!
+//! http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.sPLT
+//!
+//! sPLT chunks must come before IDAT chunks, and each of them must have a different palette name.
+//! hIST chunk must come after PLTE chunk and before IDAT chunks, and must not appear more than once.
+
+use crate::{chunk_helpers, chunk_types, errors::PngDecodeErrorCode, text_chunks};
+
+/// A color of sPLT chunk, with samples at [SuggestedPalette::sample_depth]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SuggestedPaletteEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    /// 0 is fully transparent, and the largest value of the sample depth is fully opaque
+    pub alpha: u16,
+    /// How often the color appears in the image, relative to the other entries. 0 when it is unknown
+    pub frequency: u16,
+}
+
+/// Palette that the encoder suggests for viewers with a limited number of colors, from sPLT chunk.
+/// Unlike PLTE chunk, it may be given for any color type, and may have any number of entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuggestedPalette {
+    /// 1-79 printable Latin-1 characters, with the same rules as keywords of text chunks
+    pub name: String,
+    /// 8 or 16
+    pub sample_depth: u8,
+    pub entries: Vec<SuggestedPaletteEntry>,
+}
+
+/// Scales how many pixels each palette entry has to frequencies of hIST or sPLT chunk,
+/// so that the most frequent entry becomes 65535. Entries with any pixels never become 0
+pub fn scale_frequencies(pixel_counts: &[u32]) -> Vec<u16> {
+    let max_count = pixel_counts.iter().copied().max().unwrap_or(0) as u64;
+    pixel_counts
+        .iter()
+        .map(|count| match *count {
+            0 => 0,
+            count => (count as u64 * u16::MAX as u64 / max_count).max(1) as u16,
+        })
+        .collect()
+}
+
+///    Palette name:     1-79 bytes (character string)
+///    Null separator:   1 byte
+///    Sample depth:     1 byte (8 or 16)
+///    Entries of 6 bytes at sample depth 8, or 10 bytes at sample depth 16:
+///        Red, green, blue and alpha: 1 or 2 bytes each
+///        Frequency:                  2 bytes
+pub(crate) fn parse_splt_chunk(chunk: &[u8]) -> Result<SuggestedPalette, PngDecodeErrorCode> {
+    let chunk_type = chunk_types::ChunkTypes::sPLT;
+    let (name, null_separator_index) = chunk_helpers::read_keyword(chunk)
+        .map_err(|reason| chunk_helpers::invalid_chunk(chunk_type, reason))?;
+    let sample_depth = match chunk.get(null_separator_index + 1) {
+        Some(sample_depth @ (8 | 16)) => *sample_depth,
+        Some(sample_depth) => {
+            return Err(chunk_helpers::invalid_chunk(
+                chunk_type,
+                format!(
+                    "sample depth must be 8 or 16, but it is {}",
+                    sample_depth
+                ),
+            ))
+        }
+        None => {
+            return Err(chunk_helpers::invalid_chunk(
+                chunk_type,
+                "sample depth is missing".to_string(),
+            ))
+        }
+    };
+    let entry_bytes = &chunk[null_separator_index + 2..];
+    let entry_size = match sample_depth {
+        8 => 6,
+        _ => 10,
+    };
+    if !entry_bytes.len().is_multiple_of(entry_size) {
+        return Err(chunk_helpers::invalid_chunk(
+            chunk_type,
+            format!(
+                "entries are {} bytes, which is not a multiple of {} bytes",
+                entry_bytes.len(),
+                entry_size
+            ),
+        ));
+    }
+
+    let entries = entry_bytes
+        .chunks_exact(entry_size)
+        .map(|entry| {
+            let samples: Vec<u16> = match sample_depth {
+                8 => entry[..4].iter().map(|sample| *sample as u16).collect(),
+                _ => (0..4)
+                    .map(|channel| chunk_helpers::read_u16(entry, channel * 2))
+                    .collect(),
+            };
+            SuggestedPaletteEntry {
+                red: samples[0],
+                green: samples[1],
+                blue: samples[2],
+                alpha: samples[3],
+                frequency: chunk_helpers::read_u16(entry, entry_size - 2),
+            }
+        })
+        .collect();
+
+    Ok(SuggestedPalette {
+        name,
+        sample_depth,
+        entries,
+    })
+}
+
+///    Frequency: 2 bytes for each entry of PLTE chunk
+/// * `palette_entries` - number of entries of PLTE chunk
+pub(crate) fn parse_hist_chunk(
+    chunk: &[u8],
+    palette_entries: usize,
+) -> Result<Vec<u16>, PngDecodeErrorCode> {
+    if chunk.len() != palette_entries * 2 {
+        return Err(PngDecodeErrorCode::_47(
+            chunk_types::ChunkTypes::hIST.to_string(),
+            palette_entries * 2,
+            chunk.len(),
+        ));
+    }
+
+    Ok(chunk
+        .chunks_exact(2)
+        .map(|frequency| chunk_helpers::read_u16(frequency, 0))
+        .collect())
+}
+
+/// Palette name of sPLT chunk data that has been written by [encode_splt_chunk]
+pub(crate) fn read_palette_name(chunk: &[u8]) -> &[u8] {
+    chunk.split(|byte| *byte == 0).next().unwrap_or(chunk)
+}
+
+/// Data of sPLT chunk. Fails when the name is not a valid keyword, the sample depth is not 8 or 16,
+/// or a sample doesn't fit into the sample depth
+pub(crate) fn encode_splt_chunk(
+    suggested_palette: &SuggestedPalette,
+) -> Result<Vec<u8>, PngDecodeErrorCode> {
+    let invalid_palette = |reason: &str| {
+        PngDecodeErrorCode::_53(
+            chunk_types::ChunkTypes::sPLT.to_string(),
+            reason.to_string(),
+        )
+    };
+
+    let mut chunk_data = text_chunks::string_to_latin1(&suggested_palette.name)
+        .ok_or_else(|| invalid_palette("palette name is not Latin-1"))?;
+    chunk_data.push(0);
+    let (_, null_separator_index) =
+        chunk_helpers::read_keyword(&chunk_data).map_err(|reason| invalid_palette(&reason))?;
+    if null_separator_index != chunk_data.len() - 1 {
+        return Err(invalid_palette(
+            "palette name must not contain a null character",
+        ));
+    }
+    if !matches!(
+        suggested_palette.sample_depth,
+        8 | 16
+    ) {
+        return Err(invalid_palette(
+            "sample depth must be 8 or 16",
+        ));
+    }
+
+    chunk_data.push(suggested_palette.sample_depth);
+    for entry in &suggested_palette.entries {
+        let samples = [entry.red, entry.green, entry.blue, entry.alpha];
+        match suggested_palette.sample_depth {
+            8 => {
+                for sample in samples {
+                    chunk_data.push(
+                        u8::try_from(sample)
+                            .map_err(|_| invalid_palette("a sample doesn't fit into 8 bits"))?,
+                    );
+                }
+            }
+            _ => samples
+                .iter()
+                .for_each(|sample| chunk_data.extend_from_slice(&sample.to_be_bytes())),
+        }
+        chunk_data.extend_from_slice(&entry.frequency.to_be_bytes());
+    }
+
+    Ok(chunk_data)
+}
+
+/// Data of hIST chunk. Fails when there isn't exactly one frequency for each of `palette_entries`
+pub(crate) fn encode_hist_chunk(
+    frequencies: &[u16],
+    palette_entries: usize,
+) -> Result<Vec<u8>, PngDecodeErrorCode> {
+    if palette_entries == 0 || frequencies.len() != palette_entries {
+        return Err(PngDecodeErrorCode::_53(
+            chunk_types::ChunkTypes::hIST.to_string(),
+            format!(
+                "it has {} frequencies, but the palette has {} entries",
+                frequencies.len(),
+                palette_entries
+            ),
+        ));
+    }
+
+    Ok(frequencies
+        .iter()
+        .flat_map(|frequency| frequency.to_be_bytes())
+        .collect())
+}
@@ -0,0 +1,273 @@
+//! pHYs, oFFs and sCAL chunks, which tell how large the image is meant to be in the physical world,
+//! such as the resolution it should be printed at.
+//!
+//! http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.pHYs
+//! and the extensions at http://www.libpng.org/pub/png/spec/register/pngext-1.5.0.html
+//!
+//! Each of them must come before IDAT chunks, and appear at most once.
+
+use crate::{chunk_helpers, chunk_types, errors::PngDecodeErrorCode};
+
+/// PNG four-byte unsigned integers are limited to (2^31)-1
+const MAX_PIXELS_PER_UNIT: u32 = (1 << 31) - 1;
+
+/// Meters per inch, for converting pixels per meter to dots per inch
+const METERS_PER_INCH: f64 = 0.0254;
+
+/// Unit of pHYs chunk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PhysicalUnit {
+    /// Only the aspect ratio of the pixels is known
+    Unknown = 0,
+    Meter = 1,
+}
+
+/// Intended pixel size or aspect ratio, from pHYs chunk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalDimensions {
+    pub pixels_per_unit_x: u32,
+    pub pixels_per_unit_y: u32,
+    pub unit: PhysicalUnit,
+}
+
+impl PhysicalDimensions {
+    /// Pixels per meter that are the closest to `dpi_x` and `dpi_y` dots per inch
+    pub fn from_dpi(dpi_x: f64, dpi_y: f64) -> PhysicalDimensions {
+        PhysicalDimensions {
+            pixels_per_unit_x: (dpi_x / METERS_PER_INCH).round() as u32,
+            pixels_per_unit_y: (dpi_y / METERS_PER_INCH).round() as u32,
+            unit: PhysicalUnit::Meter,
+        }
+    }
+
+    /// Horizontal and vertical dots per inch, or `None` when the unit is unknown.
+    /// For example, 2835 pixels per meter is 72.009 dots per inch, which is usually rounded to 72.
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            PhysicalUnit::Meter => Some((
+                self.pixels_per_unit_x as f64 * METERS_PER_INCH,
+                self.pixels_per_unit_y as f64 * METERS_PER_INCH,
+            )),
+            PhysicalUnit::Unknown => None,
+        }
+    }
+}
+
+/// Unit of oFFs chunk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OffsetUnit {
+    Pixel = 0,
+    Micrometer = 1,
+}
+
+/// Position of the image on a page, from oFFs chunk.
+/// The offsets are from the left and top edges of the page to the left and top edges of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageOffset {
+    pub x: i32,
+    pub y: i32,
+    pub unit: OffsetUnit,
+}
+
+/// Unit of sCAL chunk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScaleUnit {
+    Meter = 1,
+    Radian = 2,
+}
+
+/// Physical size of each pixel of the image, from sCAL chunk.
+/// For example, the ground area a pixel of an aerial photograph covers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalScale {
+    pub pixel_width: f64,
+    pub pixel_height: f64,
+    pub unit: ScaleUnit,
+}
+
+///    Pixels per unit, X axis: 4 bytes (unsigned integer)
+///    Pixels per unit, Y axis: 4 bytes (unsigned integer)
+///    Unit specifier:          1 byte
+pub(crate) fn parse_phys_chunk(chunk: &[u8]) -> Result<PhysicalDimensions, PngDecodeErrorCode> {
+    let chunk_type = chunk_types::ChunkTypes::pHYs;
+    chunk_helpers::check_length(chunk_type, chunk, 9)?;
+    let pixels_per_unit_x = chunk_helpers::read_u32(chunk, 0);
+    let pixels_per_unit_y = chunk_helpers::read_u32(chunk, 4);
+    if let Some(pixels_per_unit) = [pixels_per_unit_x, pixels_per_unit_y]
+        .into_iter()
+        .find(|pixels_per_unit| !(1..=MAX_PIXELS_PER_UNIT).contains(pixels_per_unit))
+    {
+        return Err(PngDecodeErrorCode::_51(
+            chunk_type.to_string(),
+            format!(
+                "pixels per unit must be from 1 to 2^31 - 1, but it is {}",
+                pixels_per_unit
+            ),
+        ));
+    }
+    let unit = match chunk[8] {
+        0 => PhysicalUnit::Unknown,
+        1 => PhysicalUnit::Meter,
+        unit => {
+            return Err(PngDecodeErrorCode::_51(
+                chunk_type.to_string(),
+                format!(
+                    "unknown unit specifier {}",
+                    unit
+                ),
+            ))
+        }
+    };
+
+    Ok(PhysicalDimensions {
+        pixels_per_unit_x,
+        pixels_per_unit_y,
+        unit,
+    })
+}
+
+///    Image position, x axis: 4 bytes (signed integer)
+///    Image position, y axis: 4 bytes (signed integer)
+///    Unit specifier:         1 byte
+pub(crate) fn parse_offs_chunk(chunk: &[u8]) -> Result<ImageOffset, PngDecodeErrorCode> {
+    let chunk_type = chunk_types::ChunkTypes::oFFs;
+    chunk_helpers::check_length(chunk_type, chunk, 9)?;
+    let unit = match chunk[8] {
+        0 => OffsetUnit::Pixel,
+        1 => OffsetUnit::Micrometer,
+        unit => {
+            return Err(PngDecodeErrorCode::_51(
+                chunk_type.to_string(),
+                format!(
+                    "unknown unit specifier {}",
+                    unit
+                ),
+            ))
+        }
+    };
+
+    Ok(ImageOffset {
+        x: chunk_helpers::read_u32(chunk, 0) as i32,
+        y: chunk_helpers::read_u32(chunk, 4) as i32,
+        unit,
+    })
+}
+
+/// Reads a positive floating-point number written as ASCII text, such as "1.5" or "2.5E-3"
+fn parse_positive_float(text: &[u8]) -> Option<f64> {
+    let is_float_text = !text.is_empty()
+        && text.iter().all(|byte| {
+            byte.is_ascii_digit()
+                || matches!(
+                    byte,
+                    b'.' | b'e' | b'E' | b'+' | b'-'
+                )
+        });
+    if !is_float_text {
+        return None;
+    }
+    let value: f64 = std::str::from_utf8(text).ok()?.parse().ok()?;
+
+    (value > 0.0 && value.is_finite()).then_some(value)
+}
+
+///    Unit specifier: 1 byte
+///    Pixel width:    1 or more bytes (ASCII floating-point)
+///    Null separator: 1 byte
+///    Pixel height:   1 or more bytes (ASCII floating-point)
+pub(crate) fn parse_scal_chunk(chunk: &[u8]) -> Result<PhysicalScale, PngDecodeErrorCode> {
+    let chunk_type = chunk_types::ChunkTypes::sCAL;
+    let invalid_chunk = |reason: String| PngDecodeErrorCode::_51(chunk_type.to_string(), reason);
+    let unit = match chunk.first() {
+        Some(1) => ScaleUnit::Meter,
+        Some(2) => ScaleUnit::Radian,
+        Some(unit) => {
+            return Err(invalid_chunk(format!(
+                "unknown unit specifier {}",
+                unit
+            )))
+        }
+        None => {
+            return Err(invalid_chunk(
+                "unit specifier is missing".to_string(),
+            ))
+        }
+    };
+    let (pixel_width, pixel_height) = match chunk[1..].iter().position(|byte| *byte == 0) {
+        Some(null_separator_index) => (
+            &chunk[1..1 + null_separator_index],
+            &chunk[2 + null_separator_index..],
+        ),
+        None => {
+            return Err(invalid_chunk(
+                "pixel width has no null separator".to_string(),
+            ))
+        }
+    };
+
+    Ok(PhysicalScale {
+        pixel_width: parse_positive_float(pixel_width)
+            .ok_or_else(|| invalid_chunk("pixel width must be a positive number".to_string()))?,
+        pixel_height: parse_positive_float(pixel_height)
+            .ok_or_else(|| invalid_chunk("pixel height must be a positive number".to_string()))?,
+        unit,
+    })
+}
+
+/// Data of pHYs chunk. Fails when pixels per unit are out of range
+pub(crate) fn encode_phys_chunk(
+    physical_dimensions: &PhysicalDimensions,
+) -> Result<Vec<u8>, PngDecodeErrorCode> {
+    let pixels_per_unit = [
+        physical_dimensions.pixels_per_unit_x,
+        physical_dimensions.pixels_per_unit_y,
+    ];
+    if pixels_per_unit
+        .iter()
+        .any(|pixels_per_unit| !(1..=MAX_PIXELS_PER_UNIT).contains(pixels_per_unit))
+    {
+        return Err(PngDecodeErrorCode::_53(
+            chunk_types::ChunkTypes::pHYs.to_string(),
+            "pixels per unit must be from 1 to 2^31 - 1".to_string(),
+        ));
+    }
+
+    let mut chunk_data = Vec::with_capacity(9);
+    chunk_data.extend_from_slice(&physical_dimensions.pixels_per_unit_x.to_be_bytes());
+    chunk_data.extend_from_slice(&physical_dimensions.pixels_per_unit_y.to_be_bytes());
+    chunk_data.push(physical_dimensions.unit as u8);
+
+    Ok(chunk_data)
+}
+
+/// Data of oFFs chunk
+pub(crate) fn encode_offs_chunk(image_offset: &ImageOffset) -> Vec<u8> {
+    let mut chunk_data = Vec::with_capacity(9);
+    chunk_data.extend_from_slice(&image_offset.x.to_be_bytes());
+    chunk_data.extend_from_slice(&image_offset.y.to_be_bytes());
+    chunk_data.push(image_offset.unit as u8);
+
+    chunk_data
+}
+
+/// Data of sCAL chunk. Fails when the width or height is not a positive number
+pub(crate) fn encode_scal_chunk(
+    physical_scale: &PhysicalScale,
+) -> Result<Vec<u8>, PngDecodeErrorCode> {
+    let sizes = [physical_scale.pixel_width, physical_scale.pixel_height];
+    if sizes.iter().any(|size| !(*size > 0.0 && size.is_finite())) {
+        return Err(PngDecodeErrorCode::_53(
+            chunk_types::ChunkTypes::sCAL.to_string(),
+            "pixel width and height must be positive numbers".to_string(),
+        ));
+    }
+
+    // Display of f64 never uses an exponent, and gives the shortest text that reads back as the same value
+    Ok([
+        &[physical_scale.unit as u8][..],
+        physical_scale.pixel_width.to_string().as_bytes(),
+        &[0],
+        physical_scale.pixel_height.to_string().as_bytes(),
+    ]
+    .concat())
+}
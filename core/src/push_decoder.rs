@@ -0,0 +1,363 @@
+//! Decodes a PNG whose bytes arrive in pieces (for example, from a network),
+//! without collecting the entire file first.
+//!
+//! Unlike [crate::decoder::PngDecoder], which pulls bytes from a [crate::byte_reader::ByteReader],
+//! bytes are pushed into [PngPushDecoder::feed] as they come in.
+//! A piece can end anywhere, even in the middle of a chunk length or CRC.
+
+use std::collections::VecDeque;
+
+use crate::{
+    chunk_helpers::{self, InterlaceMethod},
+    chunk_types,
+    decoded_image::{ImageHeader, ImageMetadata},
+    decoder::{PngDecoder, PngDecoderOptions},
+    errors::{self, PngDecodeErrorCode},
+    unfilter,
+};
+
+/// What the decoder has got to after [PngPushDecoder::feed]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PushStatus {
+    /// Nothing new can be decoded until more bytes are fed
+    NeedsMoreData,
+    /// IHDR chunk has been decoded, and [PngPushDecoder::header] is available.
+    /// No rows are waiting yet.
+    HeaderAvailable,
+    /// This many rows are waiting to be taken with [PngPushDecoder::next_row]
+    RowsAvailable(usize),
+    /// IEND chunk has been decoded. Any bytes fed after this are ignored.
+    /// Rows may still be waiting to be taken with [PngPushDecoder::next_row].
+    Done,
+}
+
+/// Which part of the PNG datastream the next byte belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FramingState {
+    Signature,
+    ChunkLength,
+    ChunkType,
+    ChunkData,
+    ChunkCrc,
+    Done,
+}
+
+pub struct PngPushDecoder {
+    /// Does the actual decoding, chunk by chunk
+    decoder: PngDecoder<'static, 'static>,
+    decoder_options: PngDecoderOptions,
+    framing_state: FramingState,
+    /// Bytes of the signature, chunk length, chunk type or CRC that have arrived so far.
+    /// Also holds the data of chunks other than IDAT until the entire chunk has arrived.
+    pending_bytes: Vec<u8>,
+    chunk_length: u32,
+    chunk_type: String,
+    /// Bytes of the current chunk data that haven't arrived yet
+    remaining_chunk_data_length: usize,
+    /// CRC is calculated piece by piece as the chunk arrives
+    crc_hasher: crc32fast::Hasher,
+    /// true if the current chunk is IEND
+    is_last_chunk: bool,
+    /// How many bytes have been fed so far
+    fed_byte_count: usize,
+    header: Option<ImageHeader>,
+    /// Only used for non-interlaced images, which can be unfiltered row by row
+    row_unfilter_processor: Option<unfilter::UnfilterProcessor>,
+    /// How many bytes of the decompressed image data have been unfiltered
+    unfiltered_byte_count: usize,
+    /// How many rows have been decoded
+    decoded_row_count: u32,
+    /// Decoded rows that haven't been taken by [PngPushDecoder::next_row] yet
+    decoded_rows: VecDeque<Vec<u8>>,
+}
+
+impl PngPushDecoder {
+    pub fn new(decoder_options: &PngDecoderOptions) -> Self {
+        PngPushDecoder {
+            decoder: PngDecoder::without_byte_reader(*decoder_options),
+            decoder_options: *decoder_options,
+            framing_state: FramingState::Signature,
+            pending_bytes: vec![],
+            chunk_length: 0,
+            chunk_type: String::new(),
+            remaining_chunk_data_length: 0,
+            crc_hasher: crc32fast::Hasher::new(),
+            is_last_chunk: false,
+            fed_byte_count: 0,
+            header: None,
+            row_unfilter_processor: None,
+            unfiltered_byte_count: 0,
+            decoded_row_count: 0,
+            decoded_rows: VecDeque::new(),
+        }
+    }
+
+    /// Values from IHDR chunk. `None` until IHDR chunk has been decoded.
+    pub fn header(&self) -> Option<&ImageHeader> {
+        self.header.as_ref()
+    }
+
+    /// Takes the next decoded row from the top. Each row is laid out as described by [PngDecoderOptions::output_format].
+    ///
+    /// Rows of a non-interlaced image become available as soon as their image data arrives.
+    /// Rows of an interlaced (Adam7) image only become available after IEND chunk,
+    /// because every row is spread over several passes.
+    pub fn next_row(&mut self) -> Option<Vec<u8>> {
+        self.decoded_rows.pop_front()
+    }
+
+    /// See [PngDecoder::set_pass_callback]. Previews of an interlaced image are made while its image data
+    /// is still arriving, long before any rows are available.
+    pub fn set_pass_callback(&mut self, pass_callback: impl FnMut(u8, &[u8]) + 'static) {
+        self.decoder.set_pass_callback(pass_callback);
+    }
+
+    /// See [PngDecoder::set_destination_profile]. Must be called before the first IDAT chunk is fed
+    pub fn set_destination_profile(
+        &mut self,
+        icc_profile: &[u8],
+    ) -> Result<(), PngDecodeErrorCode> {
+        self.decoder.set_destination_profile(icc_profile)
+    }
+
+    /// Recoverable errors found so far
+    pub fn get_errors(&self) -> &Vec<errors::PngDecodeError> {
+        self.decoder.get_errors()
+    }
+
+    /// Values of the ancillary chunks decoded so far.
+    /// Chunks after the image data are only there once the last row has been decoded.
+    pub fn get_metadata(&self) -> &ImageMetadata {
+        self.decoder.get_metadata()
+    }
+
+    /// Decodes as much as possible from `bytes`, and remembers whatever is left incomplete
+    /// until the next call.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<PushStatus, PngDecodeErrorCode> {
+        let mut cursor: usize = 0;
+
+        while cursor < bytes.len() && self.framing_state != FramingState::Done {
+            self.decoder
+                .set_pushed_byte_pos(self.fed_byte_count + cursor);
+
+            match self.framing_state {
+                FramingState::Signature => {
+                    cursor += self.take_pending_bytes(&bytes[cursor..], 8);
+                    if self.pending_bytes.len() == 8 {
+                        // same as the pulling decoder, an invalid signature is only reported
+                        self.decoder.validate_header(&self.pending_bytes)?;
+                        self.pending_bytes.clear();
+                        self.framing_state = FramingState::ChunkLength;
+                    }
+                }
+                FramingState::ChunkLength => {
+                    cursor += self.take_pending_bytes(&bytes[cursor..], 4);
+                    if self.pending_bytes.len() == 4 {
+                        self.chunk_length = u32::from_be_bytes([
+                            self.pending_bytes[0],
+                            self.pending_bytes[1],
+                            self.pending_bytes[2],
+                            self.pending_bytes[3],
+                        ]);
+                        self.pending_bytes.clear();
+                        self.framing_state = FramingState::ChunkType;
+                    }
+                }
+                FramingState::ChunkType => {
+                    cursor += self.take_pending_bytes(&bytes[cursor..], 4);
+                    if self.pending_bytes.len() == 4 {
+                        self.start_chunk_data()?;
+                    }
+                }
+                FramingState::ChunkData => {
+                    let piece_length = self.remaining_chunk_data_length.min(bytes.len() - cursor);
+                    let piece = &bytes[cursor..cursor + piece_length];
+                    self.crc_hasher.update(piece);
+                    if self.chunk_type == chunk_types::ChunkTypes::IDAT {
+                        // image data is decompressed as it comes in,
+                        // because IDAT chunk boundaries have no meaning anyway
+                        self.decoder.decode_idat_chunk(&piece.to_vec())?;
+                        self.unfilter_available_rows()?;
+                    } else {
+                        self.pending_bytes.extend_from_slice(piece);
+                    }
+                    cursor += piece_length;
+                    self.remaining_chunk_data_length -= piece_length;
+
+                    if self.remaining_chunk_data_length == 0 {
+                        self.finish_chunk_data()?;
+                    }
+                }
+                FramingState::ChunkCrc => {
+                    cursor += self.take_pending_bytes(&bytes[cursor..], 4);
+                    if self.pending_bytes.len() == 4 {
+                        self.finish_chunk()?;
+                    }
+                }
+                FramingState::Done => (),
+            }
+        }
+        self.fed_byte_count += cursor;
+
+        Ok(self.get_status())
+    }
+
+    fn get_status(&self) -> PushStatus {
+        if self.framing_state == FramingState::Done {
+            PushStatus::Done
+        } else if !self.decoded_rows.is_empty() {
+            PushStatus::RowsAvailable(self.decoded_rows.len())
+        } else if self.header.is_some() {
+            PushStatus::HeaderAvailable
+        } else {
+            PushStatus::NeedsMoreData
+        }
+    }
+
+    /// Moves bytes into `self.pending_bytes` until it has `target_length` bytes.
+    /// Returns how many bytes have been moved.
+    fn take_pending_bytes(&mut self, bytes: &[u8], target_length: usize) -> usize {
+        let taken_length = (target_length - self.pending_bytes.len()).min(bytes.len());
+        self.pending_bytes.extend_from_slice(&bytes[..taken_length]);
+
+        taken_length
+    }
+
+    /// Called once the chunk type in `self.pending_bytes` is complete
+    fn start_chunk_data(&mut self) -> Result<(), PngDecodeErrorCode> {
+        self.chunk_type = chunk_helpers::read_chunk_type(&self.pending_bytes)?;
+        self.crc_hasher = crc32fast::Hasher::new();
+        self.crc_hasher.update(&self.pending_bytes);
+        self.pending_bytes.clear();
+        self.decoder.check_first_chunk(&self.chunk_type)?;
+        self.decoder.check_chunk_limits(
+            &self.chunk_type,
+            self.chunk_length,
+        )?;
+
+        self.remaining_chunk_data_length = self.chunk_length as usize;
+        self.framing_state = FramingState::ChunkData;
+        if self.remaining_chunk_data_length == 0 {
+            self.finish_chunk_data()?;
+        }
+
+        Ok(())
+    }
+
+    /// Called once all data of the current chunk has arrived
+    fn finish_chunk_data(&mut self) -> Result<(), PngDecodeErrorCode> {
+        if self.chunk_type == chunk_types::ChunkTypes::IDAT {
+            if self.chunk_length == 0 {
+                self.decoder.decode_idat_chunk(&vec![])?;
+            }
+        } else {
+            let chunk_data = std::mem::take(&mut self.pending_bytes);
+            self.is_last_chunk = self.decoder.decode_chunk(&self.chunk_type, &chunk_data)?;
+        }
+        if self.header.is_none() && self.decoder.has_ihdr() {
+            self.header = Some(self.decoder.get_image_header()?);
+        }
+        self.framing_state = FramingState::ChunkCrc;
+
+        Ok(())
+    }
+
+    /// Called once the CRC in `self.pending_bytes` is complete
+    fn finish_chunk(&mut self) -> Result<(), PngDecodeErrorCode> {
+        let chunk_crc = u32::from_be_bytes([
+            self.pending_bytes[0],
+            self.pending_bytes[1],
+            self.pending_bytes[2],
+            self.pending_bytes[3],
+        ]);
+        self.pending_bytes.clear();
+        if self.decoder_options.validate_crc {
+            let crc_hasher = std::mem::replace(
+                &mut self.crc_hasher,
+                crc32fast::Hasher::new(),
+            );
+            self.decoder.compare_crc(
+                chunk_crc,
+                crc_hasher.finalize(),
+            )?;
+        }
+
+        if self.is_last_chunk {
+            self.finish_image()?;
+            self.framing_state = FramingState::Done;
+        } else {
+            self.framing_state = FramingState::ChunkLength;
+        }
+
+        Ok(())
+    }
+
+    /// Unfilters and converts every complete row of a non-interlaced image that has been decompressed so far
+    fn unfilter_available_rows(&mut self) -> Result<(), PngDecodeErrorCode> {
+        let header = match &self.header {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+        if let InterlaceMethod::Adam7 = header.interlace_method {
+            return Ok(());
+        }
+        let height = header.height;
+        let bytes_per_line = self.decoder.get_bytes_per_line();
+        // including the filter byte
+        let filtered_row_length = bytes_per_line + 1;
+        let decoder = &self.decoder;
+        if self.row_unfilter_processor.is_none() {
+            self.row_unfilter_processor = Some(decoder.create_row_unfilter_processor()?);
+        }
+        let row_unfilter_processor = match self.row_unfilter_processor.as_mut() {
+            Some(row_unfilter_processor) => row_unfilter_processor,
+            None => return Ok(()),
+        };
+
+        while self.decoded_row_count < height
+            && decoder.get_image_data().len() >= self.unfiltered_byte_count + filtered_row_length
+        {
+            let filtered_row = &decoder.get_image_data()
+                [self.unfiltered_byte_count..self.unfiltered_byte_count + filtered_row_length];
+            let mut unfiltered_row = vec![0u8; bytes_per_line];
+            row_unfilter_processor.unfilter_next_row(
+                filtered_row,
+                &mut unfiltered_row,
+            )?;
+            self.decoded_rows
+                .push_back(decoder.convert_row(&unfiltered_row)?);
+
+            self.unfiltered_byte_count += filtered_row_length;
+            self.decoded_row_count += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Called after IEND chunk
+    fn finish_image(&mut self) -> Result<(), PngDecodeErrorCode> {
+        let header = self.header.clone().ok_or(PngDecodeErrorCode::_15)?;
+
+        match header.interlace_method {
+            InterlaceMethod::None => {
+                // same as the pulling decoder, missing rows are only reported, and filled with zeros
+                self.decoder.pad_missing_non_interlaced_image_data()?;
+                self.unfilter_available_rows()?;
+            }
+            InterlaceMethod::Adam7 => {
+                let pixels = self.decoder.decode_pixels()?;
+                let stride = match self.decoder_options.output_format.bytes_per_pixel() {
+                    Some(bytes_per_pixel) => header.width as usize * bytes_per_pixel,
+                    None => self.decoder.get_bytes_per_line(),
+                };
+                if stride > 0 {
+                    self.decoded_rows
+                        .extend(pixels.chunks_exact(stride).map(|row| row.to_vec()));
+                }
+                self.decoded_row_count = header.height;
+            }
+        }
+
+        Ok(())
+    }
+}
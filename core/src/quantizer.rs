@@ -0,0 +1,343 @@
+//! Lossy palette quantization for the encoder.
+//!
+//! Reduces 8-bit RGBA pixels to a palette of at most [QuantizerOptions::max_colors] colors with median cut,
+//! optionally with Floyd–Steinberg dithering. The result can be given straight to [crate::encoder::PngEncoder]
+//! as a [crate::chunk_helpers::ColorType::IndexedColor] image, with [QuantizedImage::palette] as its PLTE chunk
+//! and [QuantizedImage::transparency] as its tRNS chunk. [QuantizedImage::histogram] can be written as its hIST chunk,
+//! and [QuantizedImage::suggested_palette] as an sPLT chunk of the original image.
+
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{
+    chunk_helpers::{colortype_to_channel, ColorType},
+    common,
+    errors::PngDecodeErrorCode,
+    palette_chunks::{self, SuggestedPalette, SuggestedPaletteEntry},
+    reduction,
+};
+
+/// PLTE chunk can't have more entries than a 8-bit index can address
+const MAX_PALETTE_ENTRIES: u16 = 256;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct QuantizerOptions {
+    /// From 1 to 256. Default: 256
+    pub max_colors: u16,
+    /// Spreads the difference between each pixel and its palette color over the pixels next to it,
+    /// which hides banding in gradients, but usually makes the image compress worse. Default: false
+    pub dithering: bool,
+}
+
+#[wasm_bindgen]
+impl QuantizerOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(max_colors: u16) -> Self {
+        QuantizerOptions {
+            max_colors,
+            dithering: false,
+        }
+    }
+}
+
+impl Default for QuantizerOptions {
+    fn default() -> Self {
+        QuantizerOptions::new(MAX_PALETTE_ENTRIES)
+    }
+}
+
+/// Everything the encoder needs to write the quantized image
+pub struct QuantizedImage {
+    /// Smallest bit depth that can address every palette entry
+    pub bit_depth: u8,
+    /// Palette indices, laid out as described by [crate::decoded_image::PixelFormat::Native]
+    pub pixels: Vec<u8>,
+    /// Data of PLTE chunk
+    pub palette: Vec<u8>,
+    /// Data of tRNS chunk. `None` when every palette entry is opaque
+    pub transparency: Option<Vec<u8>>,
+    /// How often each palette entry is used, scaled so that the most used one is 65535. See [palette_chunks::scale_frequencies]
+    pub histogram: Vec<u16>,
+}
+
+impl QuantizedImage {
+    /// The palette as sPLT chunk of 8 bits per sample, with the most used colors first
+    pub fn suggested_palette(&self, name: &str) -> SuggestedPalette {
+        let transparency = self.transparency.as_deref().unwrap_or(&[]);
+        let mut entries: Vec<SuggestedPaletteEntry> = self
+            .palette
+            .chunks_exact(3)
+            .zip(&self.histogram)
+            .enumerate()
+            .map(
+                |(index, (rgb, frequency))| SuggestedPaletteEntry {
+                    red: rgb[0] as u16,
+                    green: rgb[1] as u16,
+                    blue: rgb[2] as u16,
+                    alpha: transparency.get(index).map_or(255, |alpha| *alpha as u16),
+                    frequency: *frequency,
+                },
+            )
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.frequency));
+
+        SuggestedPalette {
+            name: name.to_string(),
+            sample_depth: 8,
+            entries,
+        }
+    }
+}
+
+/// Colors of one median cut box, with the number of pixels of each color
+type ColorBox = Vec<([u8; 4], u32)>;
+
+/// Reduces `rgba8_pixels` to a palette of at most `quantizer_options.max_colors` colors.
+/// Images that already have few enough colors keep every pixel the same.
+pub fn quantize(
+    width: u32,
+    height: u32,
+    rgba8_pixels: &[u8],
+    quantizer_options: &QuantizerOptions,
+) -> Result<QuantizedImage, PngDecodeErrorCode> {
+    let max_colors = quantizer_options.max_colors;
+    if max_colors == 0 || max_colors > MAX_PALETTE_ENTRIES {
+        return Err(PngDecodeErrorCode::_43(
+            max_colors as usize,
+            MAX_PALETTE_ENTRIES as usize,
+        ));
+    }
+    let expected_length = width as usize * height as usize * 4;
+    if rgba8_pixels.len() != expected_length {
+        return Err(PngDecodeErrorCode::_41(
+            expected_length,
+            rgba8_pixels.len(),
+        ));
+    }
+
+    let histogram = count_colors(rgba8_pixels);
+    let mut colors: Vec<[u8; 4]> = match histogram.len() <= max_colors as usize {
+        true => histogram.iter().map(|(color, _)| *color).collect(),
+        false => median_cut(histogram, max_colors as usize),
+    };
+    // colors that are not fully opaque come first, so that tRNS chunk is as short as it can be
+    colors.sort_by_key(|color| color[3] == 255);
+
+    let indices = match quantizer_options.dithering {
+        true => map_colors_dithered(width, rgba8_pixels, &colors),
+        false => map_colors(rgba8_pixels, &colors),
+    };
+
+    let bit_depth = [1, 2, 4, 8]
+        .into_iter()
+        .find(|bit_depth| colors.len() <= 1 << bit_depth)
+        .unwrap_or(8);
+    let (_, bytes_per_line) = common::calc_bytes_per_pixel_and_line(
+        colortype_to_channel(ColorType::IndexedColor),
+        bit_depth,
+        width,
+    );
+    let mut pixels = Vec::with_capacity(bytes_per_line * height as usize);
+    if width > 0 {
+        for row in indices.chunks_exact(width as usize) {
+            reduction::pack_samples(
+                row.iter().copied(),
+                bit_depth,
+                bytes_per_line,
+                &mut pixels,
+            );
+        }
+    }
+
+    let mut pixel_counts = vec![0u32; colors.len()];
+    indices
+        .iter()
+        .for_each(|index| pixel_counts[*index as usize] += 1);
+
+    let palette: Vec<u8> = colors
+        .iter()
+        .flat_map(|color| [color[0], color[1], color[2]])
+        .collect();
+    let transparency: Vec<u8> = colors
+        .iter()
+        .take_while(|color| color[3] != 255)
+        .map(|color| color[3])
+        .collect();
+
+    Ok(QuantizedImage {
+        bit_depth,
+        pixels,
+        palette,
+        transparency: match transparency.is_empty() {
+            true => None,
+            false => Some(transparency),
+        },
+        histogram: palette_chunks::scale_frequencies(&pixel_counts),
+    })
+}
+
+/// Unique colors with the number of pixels of each, sorted by color so that the result never depends on hashing
+fn count_colors(rgba8_pixels: &[u8]) -> ColorBox {
+    let mut counts: HashMap<[u8; 4], u32> = HashMap::new();
+    for pixel in rgba8_pixels.chunks_exact(4) {
+        *counts
+            .entry([pixel[0], pixel[1], pixel[2], pixel[3]])
+            .or_insert(0) += 1;
+    }
+
+    let mut histogram: ColorBox = counts.into_iter().collect();
+    histogram.sort_unstable_by_key(|(color, _)| *color);
+    histogram
+}
+
+/// Splits the colors into `max_colors` boxes, each time cutting the box with the widest channel
+/// at the median pixel of that channel, and returns the average color of each box
+fn median_cut(histogram: ColorBox, max_colors: usize) -> Vec<[u8; 4]> {
+    let mut boxes: Vec<ColorBox> = vec![histogram];
+
+    while boxes.len() < max_colors {
+        let widest_box = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, color_box)| color_box.len() > 1)
+            .map(|(index, color_box)| {
+                let (channel, range) = find_widest_channel(color_box);
+                (index, channel, range)
+            })
+            .max_by_key(|(_, _, range)| *range);
+        let (box_index, channel) = match widest_box {
+            Some((box_index, channel, _)) => (box_index, channel),
+            // every box has a single color
+            None => break,
+        };
+
+        let mut lower_half = boxes.swap_remove(box_index);
+        lower_half.sort_by_key(|(color, _)| color[channel]);
+        let pixel_count: u64 = lower_half.iter().map(|(_, count)| *count as u64).sum();
+        let mut seen_pixel_count = 0;
+        let mut split_index = lower_half.len() / 2;
+        for (index, (_, count)) in lower_half.iter().enumerate() {
+            seen_pixel_count += *count as u64;
+            if seen_pixel_count * 2 >= pixel_count {
+                split_index = index + 1;
+                break;
+            }
+        }
+        // both halves must keep at least one color
+        let split_index = split_index.clamp(1, lower_half.len() - 1);
+        let upper_half = lower_half.split_off(split_index);
+        boxes.push(lower_half);
+        boxes.push(upper_half);
+    }
+
+    boxes.iter().map(average_color).collect()
+}
+
+/// `(channel, max - min)` of the channel whose values are spread the widest
+fn find_widest_channel(color_box: &ColorBox) -> (usize, u8) {
+    (0..4)
+        .map(|channel| {
+            let values = color_box.iter().map(|(color, _)| color[channel]);
+            let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+            (channel, range)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+/// Average of the colors, weighted by their number of pixels
+fn average_color(color_box: &ColorBox) -> [u8; 4] {
+    let pixel_count: u64 = color_box.iter().map(|(_, count)| *count as u64).sum();
+    let mut sums = [0u64; 4];
+    for (color, count) in color_box {
+        for channel in 0..4 {
+            sums[channel] += color[channel] as u64 * *count as u64;
+        }
+    }
+
+    sums.map(|sum| ((sum + pixel_count / 2) / pixel_count.max(1)) as u8)
+}
+
+/// Index of the palette color closest to `color`, measured by squared distance over all four channels
+fn find_nearest_color(colors: &[[u8; 4]], color: [i32; 4]) -> u8 {
+    let mut nearest_index = 0;
+    let mut nearest_distance = i32::MAX;
+    for (index, palette_color) in colors.iter().enumerate() {
+        let distance: i32 = (0..4)
+            .map(|channel| {
+                let difference = color[channel] - palette_color[channel] as i32;
+                difference * difference
+            })
+            .sum();
+        if distance < nearest_distance {
+            nearest_index = index;
+            nearest_distance = distance;
+        }
+    }
+
+    nearest_index as u8
+}
+
+/// Replaces every pixel with the index of its closest palette color
+fn map_colors(rgba8_pixels: &[u8], colors: &[[u8; 4]]) -> Vec<u8> {
+    let mut nearest_indices: HashMap<[u8; 4], u8> = HashMap::new();
+    rgba8_pixels
+        .chunks_exact(4)
+        .map(|pixel| {
+            let rgba = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            *nearest_indices.entry(rgba).or_insert_with(|| {
+                find_nearest_color(
+                    colors,
+                    rgba.map(|sample| sample as i32),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Same as [map_colors], but the difference between each pixel and its palette color is spread over
+/// the pixels to the right and below with Floyd–Steinberg dithering: 7/16 to the right,
+/// and 3/16, 5/16 and 1/16 to the bottom left, bottom and bottom right.
+fn map_colors_dithered(width: u32, rgba8_pixels: &[u8], colors: &[[u8; 4]]) -> Vec<u8> {
+    let width = width as usize;
+    let mut indices = Vec::with_capacity(rgba8_pixels.len() / 4);
+    if width == 0 {
+        return indices;
+    }
+
+    // errors in 1/16ths, with one extra pixel on both sides so that the edges need no special case
+    let mut current_errors = vec![[0i32; 4]; width + 2];
+    let mut next_errors = vec![[0i32; 4]; width + 2];
+    for row in rgba8_pixels.chunks_exact(width * 4) {
+        for (col_index, pixel) in row.chunks_exact(4).enumerate() {
+            let position = col_index + 1;
+            let mut color = [0i32; 4];
+            for channel in 0..4 {
+                color[channel] =
+                    (pixel[channel] as i32 + current_errors[position][channel] / 16).clamp(0, 255);
+            }
+
+            let index = find_nearest_color(colors, color);
+            indices.push(index);
+
+            let palette_color = colors[index as usize];
+            for channel in 0..4 {
+                let error = color[channel] - palette_color[channel] as i32;
+                current_errors[position + 1][channel] += error * 7;
+                next_errors[position - 1][channel] += error * 3;
+                next_errors[position][channel] += error * 5;
+                next_errors[position + 1][channel] += error;
+            }
+        }
+
+        std::mem::swap(
+            &mut current_errors,
+            &mut next_errors,
+        );
+        next_errors.iter_mut().for_each(|error| *error = [0; 4]);
+    }
+
+    indices
+}
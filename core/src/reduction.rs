@@ -0,0 +1,366 @@
+//! Lossless color type and bit depth reduction for the encoder.
+//!
+//! Looks at 8-bit RGBA pixels, finds out which smaller forms can store them without changing a single pixel,
+//! and picks the one with the fewest bytes. For example, an image with only opaque grey pixels
+//! becomes [ColorType::Greyscale], and one with 16 or fewer colors becomes a 4-bit [ColorType::IndexedColor] image.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    chunk_helpers::{
+        colortype_to_alpha_byte_length, colortype_to_channel,
+        get_supported_color_type_to_bit_depths, ColorType,
+    },
+    common,
+};
+
+/// Candidates in the order they are preferred in, when they take up the same number of bytes
+const COLOR_TYPES: [ColorType; 5] = [
+    ColorType::Greyscale,
+    ColorType::IndexedColor,
+    ColorType::GreyscaleAlpha,
+    ColorType::Truecolor,
+    ColorType::TruecolorAlpha,
+];
+
+/// Bytes that every extra chunk adds: length, chunk type and CRC
+const CHUNK_OVERHEAD: usize = 12;
+
+/// Everything the encoder needs to write the reduced image
+pub struct ColorReduction {
+    pub color_type: ColorType,
+    pub bit_depth: u8,
+    /// Laid out as described by [crate::decoded_image::PixelFormat::Native]
+    pub pixels: Vec<u8>,
+    /// Data of PLTE chunk
+    pub palette: Option<Vec<u8>>,
+    /// Data of tRNS chunk
+    pub transparency: Option<Vec<u8>>,
+}
+
+/// What has been found out about the pixels
+struct PixelAnalysis {
+    /// Every pixel has r == g == b
+    is_grey: bool,
+    /// Every pixel has an alpha of 255
+    is_opaque: bool,
+    /// Set when the only transparent pixels are fully transparent pixels of this one color,
+    /// and no opaque pixel has this color. A tRNS chunk with this color keeps them transparent.
+    transparent_color: Option<[u8; 3]>,
+    /// Fewest bits that every grey value (including `transparent_color`) fits in. Only used when `is_grey`
+    grey_bit_depth: u8,
+    /// Unique colors, in the order they appear. `None` when there are more than 256
+    unique_colors: Option<Vec<[u8; 4]>>,
+}
+
+/// Picks the smallest legal color type and bit depth for `rgba8_pixels`,
+/// and converts them to it. Decoding the result as [crate::decoded_image::PixelFormat::Rgba8] gives back `rgba8_pixels`.
+pub fn reduce_rgba8(width: u32, height: u32, rgba8_pixels: &[u8]) -> ColorReduction {
+    let pixel_analysis = analyze_pixels(rgba8_pixels);
+
+    let mut chosen: (ColorType, u8) = (ColorType::TruecolorAlpha, 8);
+    let mut smallest_length = usize::MAX;
+    for color_type in COLOR_TYPES {
+        for bit_depth in get_supported_color_type_to_bit_depths(color_type) {
+            if !can_store(
+                &pixel_analysis,
+                color_type,
+                bit_depth,
+            ) {
+                continue;
+            }
+            let length = estimate_length(
+                &pixel_analysis,
+                width,
+                height,
+                color_type,
+                bit_depth,
+            );
+            if length < smallest_length {
+                smallest_length = length;
+                chosen = (color_type, bit_depth);
+            }
+        }
+    }
+
+    convert_pixels(
+        &pixel_analysis,
+        width,
+        rgba8_pixels,
+        chosen.0,
+        chosen.1,
+    )
+}
+
+fn analyze_pixels(rgba8_pixels: &[u8]) -> PixelAnalysis {
+    let mut is_grey = true;
+    let mut is_opaque = true;
+    // every alpha is either 0 or 255, and every fully transparent pixel has the same color
+    let mut has_single_transparent_color = true;
+    let mut transparent_color: Option<[u8; 3]> = None;
+    let mut seen_colors: HashSet<[u8; 4]> = HashSet::new();
+    let mut unique_colors: Option<Vec<[u8; 4]>> = Some(vec![]);
+
+    for pixel in rgba8_pixels.chunks_exact(4) {
+        let rgba = [pixel[0], pixel[1], pixel[2], pixel[3]];
+        is_grey &= rgba[0] == rgba[1] && rgba[1] == rgba[2];
+        is_opaque &= rgba[3] == 255;
+        match rgba[3] {
+            255 => (),
+            0 => match transparent_color {
+                Some(color) => has_single_transparent_color &= color == [rgba[0], rgba[1], rgba[2]],
+                None => transparent_color = Some([rgba[0], rgba[1], rgba[2]]),
+            },
+            _ => has_single_transparent_color = false,
+        }
+
+        if let Some(colors) = unique_colors.as_mut() {
+            if !seen_colors.contains(&rgba) {
+                if colors.len() == 256 {
+                    unique_colors = None;
+                    continue;
+                }
+                seen_colors.insert(rgba);
+                colors.push(rgba);
+            }
+        }
+    }
+
+    if !has_single_transparent_color {
+        transparent_color = None;
+    }
+    if let Some(color) = transparent_color {
+        // an opaque pixel of the same color would become transparent as well
+        let is_color_also_opaque = rgba8_pixels
+            .chunks_exact(4)
+            .any(|pixel| pixel[3] == 255 && pixel[0..3] == color);
+        if is_color_also_opaque {
+            transparent_color = None;
+        }
+    }
+
+    let grey_bit_depth = match is_grey {
+        true => rgba8_pixels
+            .chunks_exact(4)
+            .map(|pixel| pixel[0])
+            .chain(transparent_color.map(|color| color[0]))
+            .map(calc_grey_bit_depth)
+            .max()
+            .unwrap_or(1),
+        false => 8,
+    };
+
+    PixelAnalysis {
+        is_grey,
+        is_opaque,
+        transparent_color,
+        grey_bit_depth,
+        unique_colors,
+    }
+}
+
+/// Fewest bits that `grey` can be stored in, so that scaling it back to 8 bits gives `grey` again
+fn calc_grey_bit_depth(grey: u8) -> u8 {
+    for bit_depth in [1, 2, 4] {
+        if common::scale_sample_to_u8(
+            (grey >> (8 - bit_depth)) as u16,
+            bit_depth,
+        )
+        .ok()
+            == Some(grey)
+        {
+            return bit_depth;
+        }
+    }
+
+    8
+}
+
+fn can_store(pixel_analysis: &PixelAnalysis, color_type: ColorType, bit_depth: u8) -> bool {
+    // every pixel is 8 bits per sample, so 16 bits never makes an image smaller
+    if bit_depth == 16 {
+        return false;
+    }
+    let has_simple_transparency =
+        pixel_analysis.is_opaque || pixel_analysis.transparent_color.is_some();
+
+    match color_type {
+        ColorType::Greyscale => {
+            pixel_analysis.is_grey
+                && has_simple_transparency
+                && bit_depth >= pixel_analysis.grey_bit_depth
+        }
+        ColorType::GreyscaleAlpha => pixel_analysis.is_grey,
+        ColorType::Truecolor => has_simple_transparency,
+        ColorType::TruecolorAlpha => true,
+        ColorType::IndexedColor => match &pixel_analysis.unique_colors {
+            Some(colors) => colors.len() <= 1 << bit_depth,
+            None => false,
+        },
+    }
+}
+
+/// Bytes of the image data before compression, plus the PLTE and tRNS chunks
+fn estimate_length(
+    pixel_analysis: &PixelAnalysis,
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: u8,
+) -> usize {
+    let (_, bytes_per_line) = common::calc_bytes_per_pixel_and_line(
+        colortype_to_channel(color_type),
+        bit_depth,
+        width,
+    );
+    // including the filter type bytes
+    let image_data_length = (bytes_per_line + 1) * height as usize;
+
+    let chunks_length = match color_type {
+        ColorType::IndexedColor => {
+            let palette = create_palette(pixel_analysis);
+            let transparency_length = match &palette.1 {
+                Some(transparency) => CHUNK_OVERHEAD + transparency.len(),
+                None => 0,
+            };
+            CHUNK_OVERHEAD + palette.0.len() + transparency_length
+        }
+        ColorType::Greyscale | ColorType::Truecolor if !pixel_analysis.is_opaque => {
+            CHUNK_OVERHEAD + colortype_to_alpha_byte_length(color_type) as usize
+        }
+        _ => 0,
+    };
+
+    image_data_length + chunks_length
+}
+
+/// Data of PLTE and tRNS chunks. Colors that are not fully opaque come first, so that tRNS chunk is as short as it can be
+fn create_palette(pixel_analysis: &PixelAnalysis) -> (Vec<u8>, Option<Vec<u8>>) {
+    let mut colors = pixel_analysis.unique_colors.clone().unwrap_or_default();
+    // stable, so that the colors keep the order they appear in otherwise
+    colors.sort_by_key(|color| color[3] == 255);
+
+    let palette: Vec<u8> = colors
+        .iter()
+        .flat_map(|color| [color[0], color[1], color[2]])
+        .collect();
+    let transparency: Vec<u8> = colors
+        .iter()
+        .take_while(|color| color[3] != 255)
+        .map(|color| color[3])
+        .collect();
+
+    match transparency.is_empty() {
+        true => (palette, None),
+        false => (palette, Some(transparency)),
+    }
+}
+
+fn convert_pixels(
+    pixel_analysis: &PixelAnalysis,
+    width: u32,
+    rgba8_pixels: &[u8],
+    color_type: ColorType,
+    bit_depth: u8,
+) -> ColorReduction {
+    let (_, bytes_per_line) = common::calc_bytes_per_pixel_and_line(
+        colortype_to_channel(color_type),
+        bit_depth,
+        width,
+    );
+    let mut palette = None;
+    let mut transparency = None;
+    let mut pixels = vec![];
+
+    match color_type {
+        ColorType::Greyscale => {
+            let shift = 8 - bit_depth;
+            for row in rgba8_pixels.chunks_exact(width as usize * 4) {
+                pack_samples(
+                    row.chunks_exact(4).map(|pixel| pixel[0] >> shift),
+                    bit_depth,
+                    bytes_per_line,
+                    &mut pixels,
+                );
+            }
+            transparency = pixel_analysis
+                .transparent_color
+                .map(|color| vec![0, color[0] >> shift]);
+        }
+        ColorType::IndexedColor => {
+            let (palette_data, transparency_data) = create_palette(pixel_analysis);
+            let color_indices: HashMap<[u8; 4], u8> = palette_data
+                .chunks_exact(3)
+                .enumerate()
+                .map(|(index, rgb)| {
+                    let alpha = transparency_data
+                        .as_ref()
+                        .and_then(|alpha| alpha.get(index))
+                        .copied()
+                        .unwrap_or(255);
+                    (
+                        [rgb[0], rgb[1], rgb[2], alpha],
+                        index as u8,
+                    )
+                })
+                .collect();
+            for row in rgba8_pixels.chunks_exact(width as usize * 4) {
+                pack_samples(
+                    row.chunks_exact(4)
+                        .map(|pixel| color_indices[&[pixel[0], pixel[1], pixel[2], pixel[3]]]),
+                    bit_depth,
+                    bytes_per_line,
+                    &mut pixels,
+                );
+            }
+            palette = Some(palette_data);
+            transparency = transparency_data;
+        }
+        ColorType::GreyscaleAlpha => {
+            pixels = rgba8_pixels
+                .chunks_exact(4)
+                .flat_map(|pixel| [pixel[0], pixel[3]])
+                .collect();
+        }
+        ColorType::Truecolor => {
+            pixels = rgba8_pixels
+                .chunks_exact(4)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+                .collect();
+            transparency = pixel_analysis
+                .transparent_color
+                .map(|color| vec![0, color[0], 0, color[1], 0, color[2]]);
+        }
+        ColorType::TruecolorAlpha => {
+            pixels = rgba8_pixels.to_vec();
+        }
+    }
+
+    ColorReduction {
+        color_type,
+        bit_depth,
+        pixels,
+        palette,
+        transparency: match pixel_analysis.is_opaque {
+            true => None,
+            false => transparency,
+        },
+    }
+}
+
+/// Appends a row of samples of `bit_depth` bits, with leftmost samples in the high-order bits
+pub(crate) fn pack_samples(
+    samples: impl Iterator<Item = u8>,
+    bit_depth: u8,
+    bytes_per_line: usize,
+    out: &mut Vec<u8>,
+) {
+    let row_start = out.len();
+    out.resize(row_start + bytes_per_line, 0);
+
+    for (index, sample) in samples.enumerate() {
+        let bit_position = index * bit_depth as usize;
+        let shift = 8 - bit_depth as usize - bit_position % 8;
+        out[row_start + bit_position / 8] |= sample << shift;
+    }
+}
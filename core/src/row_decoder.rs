@@ -0,0 +1,277 @@
+//! Decodes a non-interlaced PNG one row at a time, with bounded memory.
+//!
+//! [crate::decoder::PngDecoder::run] keeps all decompressed image data, all unfiltered image data
+//! and all output pixels in memory at once. [PngRowDecoder] instead only decompresses enough image data for the next row,
+//! unfilters it against the previous row, and converts it right away.
+//! Apart from the byte reader, the memory used is about two rows plus the 32KiB zlib window.
+
+use crate::{
+    byte_reader,
+    chunk_helpers::InterlaceMethod,
+    chunk_types,
+    decoded_image::{ImageHeader, ImageMetadata},
+    decoder::{PngDecoder, PngDecoderOptions},
+    errors::{self, PngDecodeErrorCode},
+    unfilter, zlib,
+};
+
+/// How many bytes of an IDAT chunk are read from the byte reader at a time
+const COMPRESSED_PIECE_SIZE: usize = 32 * 1024;
+
+/// * `'a` - lifetime of the borrowed byte reader
+/// * `'r` - lifetime of whatever the byte reader itself borrows (a file path or a reader)
+pub struct PngRowDecoder<'a, 'r> {
+    /// Decodes the chunks other than IDAT
+    decoder: PngDecoder<'a, 'r>,
+    decoder_options: PngDecoderOptions,
+    header: Option<ImageHeader>,
+    zlib_inflate_stream: zlib::ZlibInflateStream,
+    /// Part of an IDAT chunk that has been read from the byte reader
+    compressed_piece: Vec<u8>,
+    /// How many bytes of `compressed_piece` have been decompressed
+    compressed_piece_pos: usize,
+    /// Bytes of the current IDAT chunk that haven't been read from the byte reader yet
+    remaining_idat_chunk_length: usize,
+    /// CRC of the current IDAT chunk is calculated piece by piece
+    crc_hasher: crc32fast::Hasher,
+    /// true once a chunk other than IDAT has been read after IDAT chunks
+    has_idat_ended: bool,
+    /// true once IEND chunk has been decoded
+    has_iend: bool,
+    row_unfilter_processor: Option<unfilter::UnfilterProcessor>,
+    /// The next row as it is being decompressed, including the filter byte
+    filtered_row: Vec<u8>,
+    /// How many bytes of `filtered_row` have been decompressed
+    filtered_row_pos: usize,
+    /// true once the image data has ended before the last row. The rest of the rows are zeros
+    is_image_data_truncated: bool,
+    unfiltered_row: Vec<u8>,
+    /// How many rows have been decoded
+    decoded_row_count: u32,
+}
+
+impl<'a, 'r> PngRowDecoder<'a, 'r> {
+    pub fn new(
+        byte_reader: &'a mut byte_reader::ByteReader<'r>,
+        decoder_options: &'a PngDecoderOptions,
+    ) -> PngRowDecoder<'a, 'r> {
+        PngRowDecoder {
+            decoder: PngDecoder::new(byte_reader, decoder_options),
+            decoder_options: *decoder_options,
+            header: None,
+            zlib_inflate_stream: zlib::ZlibInflateStream::new(decoder_options.max_inflated_bytes),
+            compressed_piece: vec![],
+            compressed_piece_pos: 0,
+            remaining_idat_chunk_length: 0,
+            crc_hasher: crc32fast::Hasher::new(),
+            has_idat_ended: false,
+            has_iend: false,
+            row_unfilter_processor: None,
+            filtered_row: vec![],
+            filtered_row_pos: 0,
+            is_image_data_truncated: false,
+            unfiltered_row: vec![],
+            decoded_row_count: 0,
+        }
+    }
+
+    /// Decodes every chunk before the first IDAT chunk, and returns the values from IHDR chunk.
+    /// Called by [PngRowDecoder::next_row] if it hasn't been called yet.
+    pub fn read_header(&mut self) -> Result<ImageHeader, PngDecodeErrorCode> {
+        if let Some(header) = &self.header {
+            return Ok(header.clone());
+        }
+        // same as PngDecoder, an invalid signature is only reported
+        self.decoder.read_header()?;
+
+        loop {
+            let (chunk_data_length, chunk_type, chunk_type_bytes) =
+                self.decoder.read_chunk_length_and_type()?;
+            self.decoder.check_first_chunk(&chunk_type)?;
+
+            if chunk_type == chunk_types::ChunkTypes::IDAT {
+                self.start_idat_chunk(
+                    chunk_data_length,
+                    &chunk_type_bytes,
+                )?;
+                break;
+            }
+            if self.decoder.read_and_decode_chunk(
+                chunk_data_length,
+                &chunk_type,
+                chunk_type_bytes,
+            )? {
+                // IEND without any IDAT chunk
+                self.has_iend = true;
+                self.has_idat_ended = true;
+                break;
+            }
+        }
+
+        if !self.decoder.has_ihdr() {
+            return Err(PngDecodeErrorCode::_27);
+        }
+        let header = self.decoder.get_image_header()?;
+        if let InterlaceMethod::Adam7 = header.interlace_method {
+            return Err(PngDecodeErrorCode::_26);
+        }
+
+        let bytes_per_line = self.decoder.get_bytes_per_line();
+        self.row_unfilter_processor = Some(self.decoder.create_row_unfilter_processor()?);
+        self.filtered_row = vec![0; bytes_per_line + 1];
+        self.unfiltered_row = vec![0; bytes_per_line];
+        self.header = Some(header.clone());
+
+        Ok(header)
+    }
+
+    /// Decodes the next row from the top, laid out as described by [PngDecoderOptions::output_format].
+    /// Returns `None` after the last row, once the chunks after the image data have been decoded as well.
+    pub fn next_row(&mut self) -> Result<Option<Vec<u8>>, PngDecodeErrorCode> {
+        let height = match &self.header {
+            Some(header) => header.height,
+            None => self.read_header()?.height,
+        };
+        if self.decoded_row_count == height {
+            self.finish()?;
+            return Ok(None);
+        }
+
+        while self.filtered_row_pos < self.filtered_row.len() && !self.is_image_data_truncated {
+            let (bytes_read, bytes_written) = self.zlib_inflate_stream.inflate(
+                &self.compressed_piece[self.compressed_piece_pos..],
+                &mut self.filtered_row[self.filtered_row_pos..],
+            )?;
+            self.compressed_piece_pos += bytes_read;
+            self.filtered_row_pos += bytes_written;
+
+            if bytes_read == 0 && bytes_written == 0 {
+                let is_piece_exhausted = self.compressed_piece_pos == self.compressed_piece.len();
+                if !is_piece_exhausted || !self.read_next_compressed_piece()? {
+                    // same as the pulling decoder, missing rows are only reported, and filled with zeros
+                    self.is_image_data_truncated = true;
+                    self.decoder
+                        .create_recoverable_error(PngDecodeErrorCode::_25(
+                            height,
+                            self.decoded_row_count,
+                        ))?;
+                }
+            }
+        }
+        if self.is_image_data_truncated {
+            self.filtered_row[self.filtered_row_pos..].fill(0);
+        }
+
+        self.row_unfilter_processor
+            .as_mut()
+            .ok_or(PngDecodeErrorCode::_38)?
+            .unfilter_next_row(
+                &self.filtered_row,
+                &mut self.unfiltered_row,
+            )?;
+        self.filtered_row_pos = 0;
+        self.decoded_row_count += 1;
+
+        Ok(Some(
+            self.decoder.convert_row(&self.unfiltered_row)?,
+        ))
+    }
+
+    /// See [PngDecoder::set_destination_profile]. Must be called before [PngRowDecoder::read_header]
+    pub fn set_destination_profile(
+        &mut self,
+        icc_profile: &[u8],
+    ) -> Result<(), PngDecodeErrorCode> {
+        self.decoder.set_destination_profile(icc_profile)
+    }
+
+    /// Recoverable errors found so far
+    pub fn get_errors(&self) -> &Vec<errors::PngDecodeError> {
+        self.decoder.get_errors()
+    }
+
+    /// Values of the ancillary chunks decoded so far.
+    /// Chunks after the image data are only there once the last row has been decoded.
+    pub fn get_metadata(&self) -> &ImageMetadata {
+        self.decoder.get_metadata()
+    }
+
+    fn start_idat_chunk(
+        &mut self,
+        chunk_data_length: u32,
+        chunk_type_bytes: &[u8],
+    ) -> Result<(), PngDecodeErrorCode> {
+        self.decoder.mark_idat_seen()?;
+        self.remaining_idat_chunk_length = chunk_data_length as usize;
+        self.crc_hasher = crc32fast::Hasher::new();
+        self.crc_hasher.update(chunk_type_bytes);
+
+        Ok(())
+    }
+
+    /// Reads the next part of the image data into `self.compressed_piece`, moving on to the next IDAT chunk if needed.
+    /// Returns false when there are no more IDAT chunks.
+    fn read_next_compressed_piece(&mut self) -> Result<bool, PngDecodeErrorCode> {
+        while !self.has_idat_ended {
+            if self.remaining_idat_chunk_length > 0 {
+                let piece_length = self.remaining_idat_chunk_length.min(COMPRESSED_PIECE_SIZE);
+                self.compressed_piece = self
+                    .decoder
+                    .get_byte_reader()?
+                    .read_next_n_bytes(piece_length as u64)?;
+                self.crc_hasher.update(&self.compressed_piece);
+                self.compressed_piece_pos = 0;
+                self.remaining_idat_chunk_length -= piece_length;
+                return Ok(true);
+            }
+
+            // the current IDAT chunk has ended
+            let chunk_crc = self.decoder.get_byte_reader()?.read_next_4bytes_num()?;
+            if self.decoder_options.validate_crc {
+                let crc_hasher = std::mem::replace(
+                    &mut self.crc_hasher,
+                    crc32fast::Hasher::new(),
+                );
+                self.decoder.compare_crc(
+                    chunk_crc,
+                    crc_hasher.finalize(),
+                )?;
+            }
+
+            let (chunk_data_length, chunk_type, chunk_type_bytes) =
+                self.decoder.read_chunk_length_and_type()?;
+            if chunk_type == chunk_types::ChunkTypes::IDAT {
+                self.start_idat_chunk(
+                    chunk_data_length,
+                    &chunk_type_bytes,
+                )?;
+            } else {
+                self.has_idat_ended = true;
+                self.has_iend = self.decoder.read_and_decode_chunk(
+                    chunk_data_length,
+                    &chunk_type,
+                    chunk_type_bytes,
+                )?;
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Reads whatever is left of the image data, and decodes the chunks after it until IEND chunk.
+    fn finish(&mut self) -> Result<(), PngDecodeErrorCode> {
+        while self.read_next_compressed_piece()? {}
+
+        while !self.has_iend {
+            let (chunk_data_length, chunk_type, chunk_type_bytes) =
+                self.decoder.read_chunk_length_and_type()?;
+            self.has_iend = self.decoder.read_and_decode_chunk(
+                chunk_data_length,
+                &chunk_type,
+                chunk_type_bytes,
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+//! sBIT chunk, which tells how many bits of each sample are significant,
+//! for images whose source had fewer bits per sample than PNG can store (such as 5 bits per channel).
+//!
+//! http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.sBIT
+//!
+//! sBIT chunk must come before PLTE and IDAT chunks, and must not appear more than once.
+
+use crate::{
+    bitmap::{ColorSamples, PixelSamples, PixelType},
+    chunk_types,
+    common::max_sample_value,
+    errors::PngDecodeErrorCode,
+};
+
+/// Significant bits of each channel, from sBIT chunk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignificantBits {
+    /// For greyscale images
+    Grey(u8),
+    /// For greyscale images with alpha: grey and alpha
+    GreyAlpha(u8, u8),
+    /// For truecolor and palette images: red, green and blue
+    Rgb(u8, u8, u8),
+    /// For truecolor images with alpha: red, green, blue and alpha
+    RgbAlpha(u8, u8, u8, u8),
+}
+
+impl SignificantBits {
+    fn color_bits(self) -> (u8, u8, u8) {
+        match self {
+            SignificantBits::Grey(grey) | SignificantBits::GreyAlpha(grey, _) => (grey, grey, grey),
+            SignificantBits::Rgb(r, g, b) | SignificantBits::RgbAlpha(r, g, b, _) => (r, g, b),
+        }
+    }
+
+    fn alpha_bits(self) -> Option<u8> {
+        match self {
+            SignificantBits::GreyAlpha(_, alpha) | SignificantBits::RgbAlpha(_, _, _, alpha) => {
+                Some(alpha)
+            }
+            SignificantBits::Grey(_) | SignificantBits::Rgb(_, _, _) => None,
+        }
+    }
+}
+
+///    Color type 0: 1 byte, significant bits of grey
+///    Color type 2 and 3: 3 bytes, significant bits of red, green and blue
+///    Color type 4: 2 bytes, significant bits of grey and alpha
+///    Color type 6: 4 bytes, significant bits of red, green, blue and alpha
+///
+/// Each value must be from 1 to the bit depth of the image, which is 8 for palette images
+pub(crate) fn parse_sbit_chunk(
+    chunk: &[u8],
+    pixel_type: PixelType,
+) -> Result<SignificantBits, PngDecodeErrorCode> {
+    let chunk_type = chunk_types::ChunkTypes::sBIT;
+    let sample_depth = match pixel_type.is_palette() {
+        true => 8,
+        false => pixel_type.bit_depth(),
+    };
+    let has_alpha = matches!(
+        pixel_type,
+        PixelType::GrayscaleAlpha8
+            | PixelType::GrayscaleAlpha16
+            | PixelType::RgbAlpha8
+            | PixelType::RgbAlpha16
+    );
+    let expected_length = match (
+        pixel_type.is_greyscale(),
+        has_alpha,
+    ) {
+        (true, false) => 1,
+        (true, true) => 2,
+        (false, false) => 3,
+        (false, true) => 4,
+    };
+    if chunk.len() != expected_length {
+        return Err(PngDecodeErrorCode::_47(
+            chunk_type.to_string(),
+            expected_length,
+            chunk.len(),
+        ));
+    }
+    if let Some(bits) = chunk
+        .iter()
+        .find(|bits| !(1..=sample_depth).contains(*bits))
+    {
+        return Err(PngDecodeErrorCode::_51(
+            chunk_type.to_string(),
+            format!(
+                "significant bits must be from 1 to {}, but it is {}",
+                sample_depth, bits
+            ),
+        ));
+    }
+
+    Ok(match *chunk {
+        [grey] => SignificantBits::Grey(grey),
+        [grey, alpha] => SignificantBits::GreyAlpha(grey, alpha),
+        [r, g, b] => SignificantBits::Rgb(r, g, b),
+        [r, g, b, alpha] => SignificantBits::RgbAlpha(r, g, b, alpha),
+        _ => unreachable!("length of sBIT chunk has been checked"),
+    })
+}
+
+/// Drops the bits of `sample` that are not significant, and scales the rest back up to the full range of `sample_depth`,
+/// so that the largest significant value becomes the largest value of the sample depth
+fn rescale_sample(sample: u16, sample_depth: u8, significant_bits: u8) -> u16 {
+    if significant_bits >= sample_depth {
+        return sample;
+    }
+    let significant_sample = (sample >> (sample_depth - significant_bits)) as u32;
+    let max_significant_value = max_sample_value(significant_bits) as u32;
+
+    ((significant_sample * max_sample_value(sample_depth) as u32 + max_significant_value / 2)
+        / max_significant_value) as u16
+}
+
+/// Rescales each sample with its significant bits. See [crate::decoder::PngDecoderOptions::rescale_significant_bits]
+pub(crate) fn rescale(samples: PixelSamples, significant_bits: SignificantBits) -> PixelSamples {
+    let sample_depth = samples.sample_depth;
+    let (r_bits, g_bits, b_bits) = significant_bits.color_bits();
+    let color = match samples.color {
+        ColorSamples::Grey(grey) => ColorSamples::Grey(rescale_sample(
+            grey,
+            sample_depth,
+            r_bits,
+        )),
+        ColorSamples::Rgb(r, g, b) => ColorSamples::Rgb(
+            rescale_sample(r, sample_depth, r_bits),
+            rescale_sample(g, sample_depth, g_bits),
+            rescale_sample(b, sample_depth, b_bits),
+        ),
+    };
+    let alpha = match significant_bits.alpha_bits() {
+        Some(alpha_bits) => rescale_sample(
+            samples.alpha,
+            sample_depth,
+            alpha_bits,
+        ),
+        None => samples.alpha,
+    };
+
+    PixelSamples {
+        color,
+        alpha,
+        sample_depth,
+    }
+}
@@ -0,0 +1,360 @@
+//! tEXt, zTXt and iTXt chunks, which hold textual information as keyword/text pairs,
+//! such as the author, a description, the software that created the image, or XMP metadata.
+//!
+//! http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.Anc-text
+//!
+//! All of them may appear any number of times, anywhere between IHDR and IEND chunks.
+
+use crate::{chunk_helpers, chunk_types, errors::PngDecodeErrorCode, zlib};
+
+/// Text longer than this many bytes is compressed by [TextEntry::new]. Shorter text rarely gets any smaller
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Text is written once and read many times, so it is compressed with the slowest level of miniz_oxide
+const TEXT_COMPRESSION_LEVEL: u8 = 10;
+
+/// Language tag and translated keyword of iTXt chunk
+#[derive(Debug, Clone, PartialEq)]
+pub struct InternationalText {
+    /// Language of the text, such as `en-us` or `cn`. Empty when the language is unknown
+    pub language_tag: String,
+    /// Keyword translated into the language of the text. May be empty
+    pub translated_keyword: String,
+}
+
+/// A keyword and its text, from tEXt, zTXt or iTXt chunk
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEntry {
+    /// Such as `Title`, `Author`, `Description`, `Software` or `XML:com.adobe.xmp`
+    pub keyword: String,
+    /// Already decompressed. Lines may be separated by a single line feed
+    pub text: String,
+    /// true for zTXt chunk, and for iTXt chunk with its compression flag set
+    pub compressed: bool,
+    /// `Some` for iTXt chunk, whose text is UTF-8 instead of Latin-1
+    pub international: Option<InternationalText>,
+}
+
+impl TextEntry {
+    /// Entry that is stored in tEXt chunk when every character of `text` is Latin-1, and in iTXt chunk
+    /// (without a language tag) otherwise. Text longer than [COMPRESSION_THRESHOLD] bytes is compressed,
+    /// which turns tEXt chunk into zTXt chunk.
+    pub fn new(keyword: &str, text: &str) -> TextEntry {
+        let is_latin1 = text.chars().all(|character| (character as u32) <= 0xff);
+        TextEntry {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            compressed: text.len() > COMPRESSION_THRESHOLD,
+            international: match is_latin1 {
+                true => None,
+                false => Some(InternationalText {
+                    language_tag: String::new(),
+                    translated_keyword: String::new(),
+                }),
+            },
+        }
+    }
+
+    /// tEXt, zTXt or iTXt, whichever chunk the entry is stored in
+    pub fn chunk_type(&self) -> &'static str {
+        match (
+            &self.international,
+            self.compressed,
+        ) {
+            (Some(_), _) => chunk_types::ChunkTypes::iTXt,
+            (None, true) => chunk_types::ChunkTypes::zTXt,
+            (None, false) => chunk_types::ChunkTypes::tEXt,
+        }
+    }
+}
+
+fn latin1_to_string(bytes: &[u8]) -> String {
+    // every Latin-1 character has the same code point in Unicode
+    bytes.iter().map(|byte| *byte as char).collect()
+}
+
+/// `None` when `string` has a character that is not Latin-1
+pub(crate) fn string_to_latin1(string: &str) -> Option<Vec<u8>> {
+    string
+        .chars()
+        .map(|character| u8::try_from(character).ok())
+        .collect()
+}
+
+/// Hyphen-separated words of 1-8 alphanumeric characters, such as "en-us", or empty for an unknown language
+fn is_valid_language_tag(language_tag: &[u8]) -> bool {
+    language_tag.is_empty()
+        || language_tag
+            .split(|byte| *byte == b'-')
+            .all(|word| (1..=8).contains(&word.len()) && word.iter().all(u8::is_ascii_alphanumeric))
+}
+
+/// Reads the compression method at `index` of `chunk`, which must be 0 (deflate)
+fn check_compression_method(
+    chunk_type: &str,
+    chunk: &[u8],
+    index: usize,
+) -> Result<(), PngDecodeErrorCode> {
+    match chunk.get(index) {
+        Some(compression_method)
+            if *compression_method == chunk_helpers::CompressionMethod::Deflate as u8 =>
+        {
+            Ok(())
+        }
+        Some(compression_method) => Err(chunk_helpers::invalid_chunk(
+            chunk_type,
+            format!(
+                "unknown compression method {}",
+                compression_method
+            ),
+        )),
+        None => Err(chunk_helpers::invalid_chunk(
+            chunk_type,
+            "compression method is missing".to_string(),
+        )),
+    }
+}
+
+fn decompress_text(
+    chunk_type: &str,
+    compressed_text: &[u8],
+    max_text_length: usize,
+) -> Result<Vec<u8>, PngDecodeErrorCode> {
+    zlib::decompress_to_vec(
+        compressed_text,
+        max_text_length,
+    )
+    .map_err(|status| {
+        chunk_helpers::invalid_chunk(
+            chunk_type,
+            format!(
+                "text can't be decompressed ({:?})",
+                status
+            ),
+        )
+    })
+}
+
+///    Keyword:        1-79 bytes (character string)
+///    Null separator: 1 byte
+///    Text:           n bytes (character string)
+pub(crate) fn parse_text_chunk(chunk: &[u8]) -> Result<TextEntry, PngDecodeErrorCode> {
+    let chunk_type = chunk_types::ChunkTypes::tEXt;
+    let (keyword, null_separator_index) = chunk_helpers::read_keyword(chunk)
+        .map_err(|reason| chunk_helpers::invalid_chunk(chunk_type, reason))?;
+    let text = &chunk[null_separator_index + 1..];
+    if text.contains(&0) {
+        return Err(chunk_helpers::invalid_chunk(
+            chunk_type,
+            "text must not contain a null character".to_string(),
+        ));
+    }
+
+    Ok(TextEntry {
+        keyword,
+        text: latin1_to_string(text),
+        compressed: false,
+        international: None,
+    })
+}
+
+///    Keyword:            1-79 bytes (character string)
+///    Null separator:     1 byte
+///    Compression method: 1 byte
+///    Compressed text:    n bytes
+///
+/// * `max_text_length` - decompression stops with an error once the text gets longer than this
+pub(crate) fn parse_ztxt_chunk(
+    chunk: &[u8],
+    max_text_length: usize,
+) -> Result<TextEntry, PngDecodeErrorCode> {
+    let chunk_type = chunk_types::ChunkTypes::zTXt;
+    let (keyword, null_separator_index) = chunk_helpers::read_keyword(chunk)
+        .map_err(|reason| chunk_helpers::invalid_chunk(chunk_type, reason))?;
+    check_compression_method(
+        chunk_type,
+        chunk,
+        null_separator_index + 1,
+    )?;
+    let text = decompress_text(
+        chunk_type,
+        &chunk[null_separator_index + 2..],
+        max_text_length,
+    )?;
+
+    Ok(TextEntry {
+        keyword,
+        text: latin1_to_string(&text),
+        compressed: true,
+        international: None,
+    })
+}
+
+///    Keyword:             1-79 bytes (character string)
+///    Null separator:      1 byte
+///    Compression flag:    1 byte
+///    Compression method:  1 byte
+///    Language tag:        0 or more bytes (character string)
+///    Null separator:      1 byte
+///    Translated keyword:  0 or more bytes
+///    Null separator:      1 byte
+///    Text:                0 or more bytes
+///
+/// * `max_text_length` - decompression stops with an error once the text gets longer than this
+pub(crate) fn parse_itxt_chunk(
+    chunk: &[u8],
+    max_text_length: usize,
+) -> Result<TextEntry, PngDecodeErrorCode> {
+    let chunk_type = chunk_types::ChunkTypes::iTXt;
+    let (keyword, null_separator_index) = chunk_helpers::read_keyword(chunk)
+        .map_err(|reason| chunk_helpers::invalid_chunk(chunk_type, reason))?;
+    let compressed = match chunk.get(null_separator_index + 1) {
+        Some(0) => false,
+        Some(1) => true,
+        Some(compression_flag) => {
+            return Err(chunk_helpers::invalid_chunk(
+                chunk_type,
+                format!(
+                    "compression flag must be 0 or 1, but it is {}",
+                    compression_flag
+                ),
+            ))
+        }
+        None => {
+            return Err(chunk_helpers::invalid_chunk(
+                chunk_type,
+                "compression flag is missing".to_string(),
+            ))
+        }
+    };
+    // the compression method must be 0 even when the text is not compressed
+    check_compression_method(
+        chunk_type,
+        chunk,
+        null_separator_index + 2,
+    )?;
+
+    let mut fields = chunk[null_separator_index + 3..].splitn(3, |byte| *byte == 0);
+    let (language_tag, translated_keyword, text) = match (
+        fields.next(),
+        fields.next(),
+        fields.next(),
+    ) {
+        (Some(language_tag), Some(translated_keyword), Some(text)) => (
+            language_tag,
+            translated_keyword,
+            text,
+        ),
+        _ => {
+            return Err(chunk_helpers::invalid_chunk(
+                chunk_type,
+                "language tag or translated keyword has no null separator".to_string(),
+            ))
+        }
+    };
+    if !is_valid_language_tag(language_tag) {
+        return Err(chunk_helpers::invalid_chunk(
+            chunk_type,
+            "language tag is invalid".to_string(),
+        ));
+    }
+    let translated_keyword = String::from_utf8(translated_keyword.to_vec()).map_err(|_| {
+        chunk_helpers::invalid_chunk(
+            chunk_type,
+            "translated keyword is not valid UTF-8".to_string(),
+        )
+    })?;
+    let text = match compressed {
+        true => decompress_text(
+            chunk_type,
+            text,
+            max_text_length,
+        )?,
+        false => text.to_vec(),
+    };
+    let text = String::from_utf8(text).map_err(|_| {
+        chunk_helpers::invalid_chunk(
+            chunk_type,
+            "text is not valid UTF-8".to_string(),
+        )
+    })?;
+
+    Ok(TextEntry {
+        keyword,
+        text,
+        compressed,
+        international: Some(InternationalText {
+            language_tag: latin1_to_string(language_tag),
+            translated_keyword,
+        }),
+    })
+}
+
+/// Data of the chunk that [TextEntry::chunk_type] tells, with the text compressed when [TextEntry::compressed] is set.
+/// Fails when the keyword, language tag or text can't be stored in that chunk
+pub(crate) fn encode_text_entry(text_entry: &TextEntry) -> Result<Vec<u8>, PngDecodeErrorCode> {
+    let chunk_type = text_entry.chunk_type();
+    let invalid_entry = |reason: &str| {
+        PngDecodeErrorCode::_53(
+            chunk_type.to_string(),
+            reason.to_string(),
+        )
+    };
+    let mut chunk_data = string_to_latin1(&text_entry.keyword)
+        .ok_or_else(|| invalid_entry("keyword is not Latin-1"))?;
+    chunk_data.push(0);
+    let (_, null_separator_index) =
+        chunk_helpers::read_keyword(&chunk_data).map_err(|reason| invalid_entry(&reason))?;
+    if null_separator_index != chunk_data.len() - 1 {
+        return Err(invalid_entry(
+            "keyword must not contain a null character",
+        ));
+    }
+    if text_entry.text.contains('\0') {
+        return Err(invalid_entry(
+            "text must not contain a null character",
+        ));
+    }
+
+    let text = match &text_entry.international {
+        None => {
+            let text = string_to_latin1(&text_entry.text).ok_or_else(|| {
+                invalid_entry("text is not Latin-1, so it must be stored in iTXt chunk")
+            })?;
+            // zTXt chunk
+            if text_entry.compressed {
+                chunk_data.push(chunk_helpers::CompressionMethod::Deflate as u8);
+            }
+            text
+        }
+        Some(international) => {
+            if !is_valid_language_tag(international.language_tag.as_bytes()) {
+                return Err(invalid_entry(
+                    "language tag is invalid",
+                ));
+            }
+            if international.translated_keyword.contains('\0') {
+                return Err(invalid_entry(
+                    "translated keyword must not contain a null character",
+                ));
+            }
+            chunk_data.push(text_entry.compressed as u8);
+            // the compression method is written even when the text is not compressed
+            chunk_data.push(chunk_helpers::CompressionMethod::Deflate as u8);
+            chunk_data.extend_from_slice(international.language_tag.as_bytes());
+            chunk_data.push(0);
+            chunk_data.extend_from_slice(international.translated_keyword.as_bytes());
+            chunk_data.push(0);
+            text_entry.text.as_bytes().to_vec()
+        }
+    };
+    match text_entry.compressed {
+        true => chunk_data.extend_from_slice(&zlib::compress(
+            &text,
+            TEXT_COMPRESSION_LEVEL,
+        )),
+        false => chunk_data.extend_from_slice(&text),
+    }
+
+    Ok(chunk_data)
+}
@@ -0,0 +1,142 @@
+//! tIME chunk, the time of the last modification of the image (not of its creation).
+//!
+//! http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.tIME
+//!
+//! tIME chunk may appear anywhere between IHDR and IEND chunks, but at most once.
+
+use crate::{chunk_helpers, chunk_types, errors::PngDecodeErrorCode};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Time of the last modification, from tIME chunk. Always in UTC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timestamp {
+    /// Complete year, such as 1995. Not 95
+    pub year: u16,
+    /// 1-12
+    pub month: u8,
+    /// 1-31
+    pub day: u8,
+    /// 0-23
+    pub hour: u8,
+    /// 0-59
+    pub minute: u8,
+    /// 0-60, for leap seconds
+    pub second: u8,
+}
+
+impl Timestamp {
+    /// Time that is `unix_time` seconds after 1970-01-01 00:00:00 UTC, such as the modification time of a file.
+    /// `None` after the year 65535
+    pub fn from_unix_time(unix_time: u64) -> Option<Timestamp> {
+        let (days, seconds_of_day) = (
+            unix_time / SECONDS_PER_DAY,
+            unix_time % SECONDS_PER_DAY,
+        );
+
+        // converts days since 1970-01-01 into a date of the proleptic Gregorian calendar,
+        // counting in eras of 400 years that start on March 1st, so that leap days come at the end of a year
+        let days = days + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days % 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + (month <= 2) as u64;
+
+        Some(Timestamp {
+            year: u16::try_from(year).ok()?,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds_of_day / 3600) as u8,
+            minute: (seconds_of_day / 60 % 60) as u8,
+            second: (seconds_of_day % 60) as u8,
+        })
+    }
+
+    /// Why the timestamp is not a valid date and time, or `None` when it is
+    fn find_invalid_field(&self) -> Option<String> {
+        let is_leap_year = self.year.is_multiple_of(4)
+            && (!self.year.is_multiple_of(100) || self.year.is_multiple_of(400));
+        let days_in_month = match self.month {
+            2 if is_leap_year => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        let invalid_field = if !(1..=12).contains(&self.month) {
+            ("month", self.month)
+        } else if !(1..=days_in_month).contains(&self.day) {
+            ("day", self.day)
+        } else if self.hour > 23 {
+            ("hour", self.hour)
+        } else if self.minute > 59 {
+            ("minute", self.minute)
+        } else if self.second > 60 {
+            ("second", self.second)
+        } else {
+            return None;
+        };
+
+        Some(format!(
+            "{} {} is out of range",
+            invalid_field.0, invalid_field.1
+        ))
+    }
+}
+
+///    Year:   2 bytes (complete; for example, 1995, not 95)
+///    Month:  1 byte (1-12)
+///    Day:    1 byte (1-31)
+///    Hour:   1 byte (0-23)
+///    Minute: 1 byte (0-59)
+///    Second: 1 byte (0-60)    (yes, 60, for leap seconds; not 61, a common error)
+pub(crate) fn parse_time_chunk(chunk: &[u8]) -> Result<Timestamp, PngDecodeErrorCode> {
+    let chunk_type = chunk_types::ChunkTypes::tIME;
+    chunk_helpers::check_length(chunk_type, chunk, 7)?;
+    let timestamp = Timestamp {
+        year: u16::from_be_bytes([chunk[0], chunk[1]]),
+        month: chunk[2],
+        day: chunk[3],
+        hour: chunk[4],
+        minute: chunk[5],
+        second: chunk[6],
+    };
+    if let Some(reason) = timestamp.find_invalid_field() {
+        return Err(PngDecodeErrorCode::_51(
+            chunk_type.to_string(),
+            reason,
+        ));
+    }
+
+    Ok(timestamp)
+}
+
+/// Data of tIME chunk. Fails when the timestamp is not a valid date and time
+pub(crate) fn encode_time_chunk(timestamp: &Timestamp) -> Result<Vec<u8>, PngDecodeErrorCode> {
+    if let Some(reason) = timestamp.find_invalid_field() {
+        return Err(PngDecodeErrorCode::_53(
+            chunk_types::ChunkTypes::tIME.to_string(),
+            reason,
+        ));
+    }
+
+    Ok([
+        &timestamp.year.to_be_bytes()[..],
+        &[
+            timestamp.month,
+            timestamp.day,
+            timestamp.hour,
+            timestamp.minute,
+            timestamp.second,
+        ],
+    ]
+    .concat())
+}
@@ -1,45 +1,64 @@
-use crate::{bitmap, common::{normalize_u16_to_u8}, errors};
+use crate::{bitmap, errors};
 
+/// Values from the tRNS chunk.
+///
+/// Greyscale and RGB values are kept as they are stored in the image (not scaled to 8 bits),
+/// so that they can be compared against samples of the same bit depth.
 #[derive(Clone)]
 pub enum TransparencyChunk {
     Palette(Vec<u8>),
-    Grayscale(u8),
-    Rgb(u8, u8, u8),
+    Grayscale(u16),
+    Rgb(u16, u16, u16),
 }
 
 impl TransparencyChunk {
-    pub fn new(chunk: Vec<u8>, pixel_type: bitmap::PixelType) -> Result<Option<Self>, errors::PngDecodeErrorCode> {
+    /// Returns `None` for a greyscale or RGB tRNS chunk that is too short.
+    /// The wrong length has already been reported while validating the chunk.
+    pub fn new(
+        chunk: Vec<u8>,
+        pixel_type: bitmap::PixelType,
+    ) -> Result<Option<Self>, errors::PngDecodeErrorCode> {
+        let min_chunk_length = match pixel_type {
+            bitmap::PixelType::Grayscale1
+            | bitmap::PixelType::Grayscale2
+            | bitmap::PixelType::Grayscale4
+            | bitmap::PixelType::Grayscale8
+            | bitmap::PixelType::Grayscale16 => 2,
+            bitmap::PixelType::Rgb8 | bitmap::PixelType::Rgb16 => 6,
+            _ => 0,
+        };
+        if chunk.len() < min_chunk_length {
+            return Ok(None);
+        }
+
         match pixel_type {
             bitmap::PixelType::Grayscale1 => Ok(Some(TransparencyChunk::Grayscale(
-                chunk[1] & 0b1,
+                (chunk[1] & 0b1) as u16,
             ))),
             bitmap::PixelType::Grayscale2 => Ok(Some(TransparencyChunk::Grayscale(
-                chunk[1] & 0b11,
+                (chunk[1] & 0b11) as u16,
             ))),
             bitmap::PixelType::Grayscale4 => Ok(Some(TransparencyChunk::Grayscale(
-                chunk[1] & 0b1111,
+                (chunk[1] & 0b1111) as u16,
             ))),
             bitmap::PixelType::Grayscale8 => Ok(Some(TransparencyChunk::Grayscale(
-                chunk[1],
+                chunk[1] as u16,
+            ))),
+            bitmap::PixelType::Grayscale16 => Ok(Some(TransparencyChunk::Grayscale(
+                u16::from_be_bytes([chunk[0], chunk[1]]),
             ))),
-            bitmap::PixelType::Grayscale16 => {
-                let val = normalize_u16_to_u8(u16::from_be_bytes([chunk[0], chunk[1]]))?;
-                Ok(Some(TransparencyChunk::Grayscale(
-                    val,
-                )))
-            }
             bitmap::PixelType::Rgb8 => {
-                let r = chunk[1];
-                let g = chunk[3];
-                let b = chunk[5];
+                let r = chunk[1] as u16;
+                let g = chunk[3] as u16;
+                let b = chunk[5] as u16;
                 Ok(Some(TransparencyChunk::Rgb(
                     r, g, b,
                 )))
             }
             bitmap::PixelType::Rgb16 => {
-                let r =  normalize_u16_to_u8(u16::from_be_bytes([chunk[0], chunk[1]]))?;
-                let g = normalize_u16_to_u8(u16::from_be_bytes([chunk[2], chunk[3]]))?;
-                let b = normalize_u16_to_u8(u16::from_be_bytes([chunk[4], chunk[5]]))?;
+                let r = u16::from_be_bytes([chunk[0], chunk[1]]);
+                let g = u16::from_be_bytes([chunk[2], chunk[3]]);
+                let b = u16::from_be_bytes([chunk[4], chunk[5]]);
                 Ok(Some(TransparencyChunk::Rgb(
                     r,
                     g,
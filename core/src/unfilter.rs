@@ -16,6 +16,26 @@
 
 use crate::errors;
 
+/// | up_left pixel | up pixel |
+/// |-|-|
+/// | left pixel | current pixel |
+///
+/// Shared with [crate::filter], since filtering has to predict exactly the same value.
+pub(crate) fn paeth_predictor(left: i16, up: i16, up_left: i16) -> u8 {
+    let paeth = left + up - up_left;
+    let position_left = ((paeth - left) as f32).abs() as u8;
+    let position_up = ((paeth - up) as f32).abs() as u8;
+    let position_up_left = ((paeth - up_left) as f32).abs() as u8;
+
+    if position_left <= position_up && position_left <= position_up_left {
+        return left as u8;
+    }
+    if position_up <= position_up_left {
+        return up as u8;
+    }
+    return up_left as u8;
+}
+
 /// Variable names in unfilter processor are as elaborate as possible to avoid confusion. Instead, lines have become a bit longer.
 /// However short variable names often cause confusion especially in detailed bytewise ops, so let's keep it this way
 pub struct UnfilterProcessor {
@@ -25,6 +45,11 @@ pub struct UnfilterProcessor {
     bytes_per_pixel: usize,
     /// How many bytes are there per line (`bytes_per_line * height = entire image bytes`).
     bytes_per_line: usize,
+    /// Only used by [UnfilterProcessor::unfilter_next_row].
+    /// The first half holds the previously unfiltered row, and the second half the row being unfiltered.
+    previous_and_current_row: Vec<u8>,
+    /// How many rows have been unfiltered by [UnfilterProcessor::unfilter_next_row]
+    unfiltered_row_count: u32,
 }
 
 impl UnfilterProcessor {
@@ -33,6 +58,8 @@ impl UnfilterProcessor {
             height,
             bytes_per_pixel,
             bytes_per_line,
+            previous_and_current_row: vec![],
+            unfiltered_row_count: 0,
         }
     }
 
@@ -200,24 +227,6 @@ impl UnfilterProcessor {
                 in_buffer[in_buffer_start_index + i].wrapping_add(rhs);
         }
     }
-    /// | up_left pixel | up pixel |
-    /// |-|-|
-    /// | left pixel | current pixel |
-    fn paeth_predictor(&self, left: i16, up: i16, up_left: i16) -> u8 {
-        let paeth = left + up - up_left;
-        let position_left = ((paeth - left) as f32).abs() as u8;
-        let position_up = ((paeth - up) as f32).abs() as u8;
-        let position_up_left = ((paeth - up_left) as f32).abs() as u8;
-
-        if position_left <= position_up && position_left <= position_up_left {
-            return left as u8;
-        }
-        if position_up <= position_up_left {
-            return up as u8;
-        }
-        return up_left as u8;
-    }
-
     fn unfilter_paeth(
         &mut self,
         out_buffer_start_index: usize,
@@ -247,20 +256,86 @@ impl UnfilterProcessor {
                 as i16;
 
             out_buffer[out_buffer_start_index + i] = in_buffer[in_buffer_start_index + i]
-                .wrapping_add(self.paeth_predictor(left, up, up_left));
+                .wrapping_add(paeth_predictor(left, up, up_left));
         }
     }
 
     /// unfilters scanlines with possibly varying filter types.
     /// * `in_buffer` - the bytes decompressed by zlib
-    pub fn unfilter(&mut self, in_buffer: &[u8], out_buffer: &mut [u8]) -> Result<(), errors::PngDecodeErrorCode> {
+    pub fn unfilter(
+        &mut self,
+        in_buffer: &[u8],
+        out_buffer: &mut [u8],
+    ) -> Result<(), errors::PngDecodeErrorCode> {
         let mut filter_byte_index: usize = 0;
 
-        for line_number in 0..self.height.try_into().expect("Height doesn't fit in usize") {
+        for line_number in 0..self.height as usize {
             let filter_type = in_buffer[filter_byte_index];
             let out_buffer_start_index = self.get_out_buffer_index(line_number, 0);
             let in_buffer_start_index = filter_byte_index + 1;
 
+            self.unfilter_line(
+                filter_type,
+                out_buffer_start_index,
+                out_buffer,
+                in_buffer_start_index,
+                in_buffer,
+            )?;
+            filter_byte_index += self.bytes_per_line + 1;
+        }
+
+        Ok(())
+    }
+
+    /// Unfilters a single scanline, unlike [UnfilterProcessor::unfilter] which needs every scanline at once.
+    /// The previous scanline is kept inside the processor, so that only two scanlines are in memory at a time.
+    /// * `filtered_row` - a scanline decompressed by zlib, starting with its filter type byte
+    /// * `out_row` - receives the unfiltered scanline, which is `bytes_per_line` long
+    pub fn unfilter_next_row(
+        &mut self,
+        filtered_row: &[u8],
+        out_row: &mut [u8],
+    ) -> Result<(), errors::PngDecodeErrorCode> {
+        if self.previous_and_current_row.is_empty() {
+            self.previous_and_current_row = vec![0; self.bytes_per_line * 2];
+        }
+        let filter_type = filtered_row[0];
+        // the filters treat a scanline starting from index 0 of the out buffer as the first one,
+        // which has no scanline above it.
+        let out_buffer_start_index = match self.unfiltered_row_count {
+            0 => 0,
+            _ => self.bytes_per_line,
+        };
+
+        let mut previous_and_current_row = std::mem::take(&mut self.previous_and_current_row);
+        let unfiltered = self.unfilter_line(
+            filter_type,
+            out_buffer_start_index,
+            &mut previous_and_current_row,
+            1,
+            filtered_row,
+        );
+        self.previous_and_current_row = previous_and_current_row;
+        unfiltered?;
+
+        let current_row = out_buffer_start_index..out_buffer_start_index + self.bytes_per_line;
+        out_row.copy_from_slice(&self.previous_and_current_row[current_row.clone()]);
+        // the current row becomes the previous row of the next one
+        self.previous_and_current_row.copy_within(current_row, 0);
+        self.unfiltered_row_count += 1;
+
+        Ok(())
+    }
+
+    fn unfilter_line(
+        &mut self,
+        filter_type: u8,
+        out_buffer_start_index: usize,
+        out_buffer: &mut [u8],
+        in_buffer_start_index: usize,
+        in_buffer: &[u8],
+    ) -> Result<(), errors::PngDecodeErrorCode> {
+        {
             match filter_type {
                 0 => self.unfilter_none(
                     out_buffer_start_index,
@@ -298,7 +373,6 @@ impl UnfilterProcessor {
                     ))
                 }
             }
-            filter_byte_index += self.bytes_per_line + 1;
         }
 
         Ok(())
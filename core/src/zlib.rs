@@ -1,12 +1,18 @@
 use std::vec::Vec;
 
-use miniz_oxide::inflate::{
-    core::{
-        decompress,
-        inflate_flags::{self, TINFL_FLAG_PARSE_ZLIB_HEADER},
-        DecompressorOxide,
+use miniz_oxide::{
+    deflate::compress_to_vec_zlib,
+    inflate::{
+        core::{
+            decompress,
+            inflate_flags::{self, TINFL_FLAG_PARSE_ZLIB_HEADER},
+            DecompressorOxide,
+        },
+        decompress_to_vec_zlib_with_limit,
+        stream::{inflate, InflateState},
+        TINFLStatus,
     },
-    TINFLStatus,
+    DataFormat, MZError, MZFlush,
 };
 
 use crate::errors::{PngDecodeError, PngDecodeErrorCode};
@@ -20,6 +26,10 @@ pub struct ZlibDecompressStream {
     out_buffer_byte_pos: usize,
     // Stores the state of miniz_oxide's decompress function
     decompressor_state: Box<DecompressorOxide>,
+    // Decompressing more than this many bytes is an error. `self.out_buffer` never grows much larger than this
+    max_decompressed_length: u64,
+    // Length that `self.out_buffer` has been padded to with zeros, when the image data ends early
+    padded_length: usize,
 }
 
 const DEFAULT_ZLIB_STREAM_BUFFER_SIZE: usize = 32 * 1024;
@@ -29,20 +39,25 @@ const BASE_FLAGS: u32 = inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER
     | inflate_flags::TINFL_FLAG_HAS_MORE_INPUT;
 
 impl ZlibDecompressStream {
-    pub fn new(buffer_size: Option<usize>) -> ZlibDecompressStream {
+    pub fn new(buffer_size: Option<usize>, max_decompressed_length: u64) -> ZlibDecompressStream {
         let effective_buffer_size = buffer_size.unwrap_or(DEFAULT_ZLIB_STREAM_BUFFER_SIZE);
 
         ZlibDecompressStream {
             out_buffer: vec![0; 2 * effective_buffer_size],
             decompressor_state: Box::new(DecompressorOxide::new()),
             out_buffer_byte_pos: 0,
+            max_decompressed_length,
+            padded_length: 0,
         }
     }
 
-    /// `self.out_buffer` size is increased to avoid index out of range when `self.out_buffer_byte_pos` * 2 is greater
+    /// `self.out_buffer` size is increased to avoid index out of range when `self.out_buffer_byte_pos` * 2 is greater.
+    /// It grows up to one byte more than `self.max_decompressed_length`, so that going over the limit can be noticed.
     fn resize_out_buffer_if_needed(&mut self) {
         if self.out_buffer.len() <= self.out_buffer_byte_pos * 2 {
-            self.out_buffer.resize(self.out_buffer.len() * 2, 0u8);
+            let max_buffer_length = usize::try_from(self.max_decompressed_length.saturating_add(1)).unwrap_or(usize::MAX);
+            let new_buffer_length = (self.out_buffer.len() * 2).min(max_buffer_length).max(self.out_buffer.len());
+            self.out_buffer.resize(new_buffer_length, 0u8);
         }
     }
 
@@ -50,6 +65,25 @@ impl ZlibDecompressStream {
         &self.out_buffer
     }
 
+    /// Makes `self.out_buffer` at least `length` bytes long. Bytes that haven't been decompressed are zeros.
+    pub fn pad_out_buffer(&mut self, length: usize) {
+        if self.out_buffer.len() < length {
+            self.out_buffer.resize(length, 0u8);
+        }
+        self.padded_length = self.padded_length.max(length);
+    }
+
+    /// Bytes decompressed so far, followed by the zeros they have been padded with by [ZlibDecompressStream::pad_out_buffer]
+    pub fn get_padded_data(&self) -> &[u8] {
+        &self.out_buffer[..self.out_buffer_byte_pos.max(self.padded_length)]
+    }
+
+    /// How many bytes have been decompressed so far. `self.out_buffer` is longer than this,
+    /// because it is allocated ahead of time.
+    pub fn get_decompressed_length(&self) -> usize {
+        self.out_buffer_byte_pos
+    }
+
     /// Decompresses image bytes as they come in.
     /// * `raw_image_bytes` - this is the vector of u8 image data from an IDAT chunk. Favorably should be possible to receive more than a single IDAT chunk or a part of an IDAT chunk because the size of an IDAT chunk varies greatly. But for now we are just sticking to a single IDAT chunk.
     pub fn decompress(&mut self, raw_image_bytes: &Vec<u8>) -> Result<(), PngDecodeErrorCode> {
@@ -67,15 +101,98 @@ impl ZlibDecompressStream {
 
             in_buffer_byte_pos += num_bytes_read;
             self.out_buffer_byte_pos += num_bytes_written;
+            if self.out_buffer_byte_pos as u64 > self.max_decompressed_length {
+                return Err(PngDecodeErrorCode::_31(self.max_decompressed_length));
+            }
 
             match current_TINFL_status {
                 TINFLStatus::BadParam
                 | TINFLStatus::Failed
                 | TINFLStatus::FailedCannotMakeProgress => return Err(PngDecodeErrorCode::_14(current_TINFL_status)),
+                // the zlib stream has ended. Any bytes left over can't be decompressed.
+                TINFLStatus::Done => break,
+                // a wrong checksum doesn't change the bytes already decompressed, so they are kept,
+                // and the caller may go on with them
+                TINFLStatus::Adler32Mismatch => return Err(PngDecodeErrorCode::_54),
                 _ => (),
             }
+            if num_bytes_read == 0 && num_bytes_written == 0 {
+                return Err(PngDecodeErrorCode::_14(TINFLStatus::FailedCannotMakeProgress));
+            }
         }
 
         Ok(())
     }
 }
+
+/// Compresses filtered image data into a zlib stream, which is what the data of IDAT chunks adds up to.
+/// * `compression_level` - from 0 (no compression) to 10 (slowest). Larger values are treated as 10.
+pub fn compress(filtered_image_data: &[u8], compression_level: u8) -> Vec<u8> {
+    compress_to_vec_zlib(
+        filtered_image_data,
+        compression_level,
+    )
+}
+
+/// Decompresses an entire zlib stream at once, as in iCCP and zTXt chunks.
+/// * `max_length` - decompression stops with [TINFLStatus::HasMoreOutput] once the output gets longer than this
+pub fn decompress_to_vec(
+    compressed_data: &[u8],
+    max_length: usize,
+) -> Result<Vec<u8>, TINFLStatus> {
+    decompress_to_vec_zlib_with_limit(compressed_data, max_length)
+}
+
+/// Unlike [ZlibDecompressStream], does not keep everything it has decompressed.
+/// Only the last 32KiB (the zlib window) is kept, and the caller decides how much to decompress at a time.
+pub struct ZlibInflateStream {
+    // Stores the state of miniz_oxide's inflate function, including the zlib window
+    inflate_state: Box<InflateState>,
+    // How many bytes have been inflated so far
+    inflated_length: u64,
+    // Inflating more than this many bytes is an error, as in ZlibDecompressStream
+    max_inflated_length: u64,
+}
+
+impl ZlibInflateStream {
+    pub fn new(max_inflated_length: u64) -> ZlibInflateStream {
+        ZlibInflateStream {
+            inflate_state: InflateState::new_boxed(DataFormat::Zlib),
+            inflated_length: 0,
+            max_inflated_length,
+        }
+    }
+
+    /// Decompresses as much of `compressed_bytes` as fits in `out_buffer`.
+    /// Returns `(number of bytes read, number of bytes written)`.
+    /// Both are zero when no progress can be made until more compressed bytes arrive.
+    pub fn inflate(
+        &mut self,
+        compressed_bytes: &[u8],
+        out_buffer: &mut [u8],
+    ) -> Result<(usize, usize), PngDecodeErrorCode> {
+        let result = inflate(
+            &mut self.inflate_state,
+            compressed_bytes,
+            out_buffer,
+            MZFlush::None,
+        );
+
+        self.inflated_length += result.bytes_written as u64;
+        if self.inflated_length > self.max_inflated_length {
+            return Err(PngDecodeErrorCode::_31(
+                self.max_inflated_length,
+            ));
+        }
+
+        match result.status {
+            Ok(_) | Err(MZError::Buf) => Ok((
+                result.bytes_consumed,
+                result.bytes_written,
+            )),
+            Err(_) => Err(PngDecodeErrorCode::_14(
+                self.inflate_state.last_status(),
+            )),
+        }
+    }
+}
@@ -208,13 +208,15 @@ mod tests {
             byte_reader::ByteReaderMode::FILE,
             None,
         );
-        byte_reader.read_image();
+        byte_reader.read_image().unwrap();
+        let decoder_options = core::decoder::PngDecoderOptions {
+            fail_fast: false,
+            validate_crc: true,
+            ..Default::default()
+        };
         let mut decoder = core::decoder::PngDecoder::new(
             byte_reader,
-            &core::decoder::PngDecoderOptions {
-                fail_fast: false,
-                validate_crc: true,
-            },
+            &decoder_options,
         );
 
         let decoder_result = decoder.run().unwrap();
@@ -223,8 +225,864 @@ mod tests {
         let image_rs_rgba8 = image_rs_output.to_rgba8();
         let image_rs_bytes = image_rs_rgba8.as_bytes();
 
-        assert_eq!(decoder_result.len(), image_rs_bytes.len());
-        assert_eq!(&decoder_result[..], &image_rs_bytes[..]);
+        assert_eq!(
+            decoder_result.width,
+            image_rs_rgba8.width()
+        );
+        assert_eq!(
+            decoder_result.height,
+            image_rs_rgba8.height()
+        );
+        assert_eq!(
+            decoder_result.pixels.len(),
+            image_rs_bytes.len()
+        );
+        assert_eq!(
+            &decoder_result.pixels[..],
+            &image_rs_bytes[..]
+        );
+    }
+
+    #[test_case("../test/png/official/basn0g16.png";"Decoding ../test/png/official/basn0g16.png) to RGBA16 should work")]
+    #[test_case("../test/png/official/basn2c16.png";"Decoding ../test/png/official/basn2c16.png) to RGBA16 should work")]
+    #[test_case("../test/png/official/basn4a16.png";"Decoding ../test/png/official/basn4a16.png) to RGBA16 should work")]
+    #[test_case("../test/png/official/basn6a16.png";"Decoding ../test/png/official/basn6a16.png) to RGBA16 should work")]
+    #[test_case("../test/png/official/basi6a16.png";"Decoding ../test/png/official/basi6a16.png) to RGBA16 should work")]
+    #[test_case("../test/png/official/tbbn2c16.png";"Decoding ../test/png/official/tbbn2c16.png) to RGBA16 should work")]
+    #[test_case("../test/png/official/tbgn2c16.png";"Decoding ../test/png/official/tbgn2c16.png) to RGBA16 should work")]
+    #[test_case("../test/png/official/basn0g02.png";"Decoding ../test/png/official/basn0g02.png) to RGBA16 should work")]
+    #[test_case("../test/png/official/basn3p04.png";"Decoding ../test/png/official/basn3p04.png) to RGBA16 should work")]
+    #[test_case("../test/png/official/basn6a08.png";"Decoding ../test/png/official/basn6a08.png) to RGBA16 should work")]
+    fn decoding_rgba16_test(png_path: &str) {
+        let image_rs_output = image::open(png_path).unwrap();
+        let image_rs_rgba16 = image_rs_output.to_rgba16();
+        let image_rs_samples: Vec<u16> = image_rs_rgba16.as_raw().to_vec();
+
+        for output_format in [
+            core::decoded_image::PixelFormat::Rgba16BigEndian,
+            core::decoded_image::PixelFormat::Rgba16NativeEndian,
+        ] {
+            let byte_reader = &mut byte_reader::ByteReader::new(
+                Some(&png_path),
+                byte_reader::ByteReaderMode::FILE,
+                None,
+            );
+            byte_reader.read_image().unwrap();
+            let decoder_options = core::decoder::PngDecoderOptions {
+                output_format,
+                ..Default::default()
+            };
+            let mut decoder = core::decoder::PngDecoder::new(byte_reader, &decoder_options);
+            let decoder_result = decoder.run().unwrap();
+            assert_eq!(
+                decoder_result.pixel_format,
+                output_format
+            );
+
+            let decoder_samples: Vec<u16> = decoder_result
+                .pixels
+                .chunks_exact(2)
+                .map(|sample| match output_format {
+                    core::decoded_image::PixelFormat::Rgba16BigEndian => {
+                        u16::from_be_bytes([sample[0], sample[1]])
+                    }
+                    _ => u16::from_ne_bytes([sample[0], sample[1]]),
+                })
+                .collect();
+            assert_eq!(
+                decoder_samples,
+                image_rs_samples
+            );
+        }
+    }
+
+    fn decode_with_output_format(
+        png_path: &str,
+        output_format: core::decoded_image::PixelFormat,
+    ) -> core::decoded_image::DecodedImage {
+        let byte_reader = &mut byte_reader::ByteReader::new(
+            Some(&png_path),
+            byte_reader::ByteReaderMode::FILE,
+            None,
+        );
+        byte_reader.read_image().unwrap();
+        let decoder_options = core::decoder::PngDecoderOptions {
+            output_format,
+            ..Default::default()
+        };
+        let mut decoder = core::decoder::PngDecoder::new(byte_reader, &decoder_options);
+
+        decoder.run().unwrap()
+    }
+
+    #[test_case("../test/png/official/basn2c08.png";"Decoding ../test/png/official/basn2c08.png) to RGB8, BGRA8 and ARGB8 should work")]
+    #[test_case("../test/png/official/basi3p04.png";"Decoding ../test/png/official/basi3p04.png) to RGB8, BGRA8 and ARGB8 should work")]
+    #[test_case("../test/png/official/basn6a16.png";"Decoding ../test/png/official/basn6a16.png) to RGB8, BGRA8 and ARGB8 should work")]
+    #[test_case("../test/png/official/tbrn2c08.png";"Decoding ../test/png/official/tbrn2c08.png) to RGB8, BGRA8 and ARGB8 should work")]
+    fn decoding_color_output_formats_test(png_path: &str) {
+        let image_rs_rgba8 = image::open(png_path).unwrap().to_rgba8();
+        let image_rs_pixels: Vec<&[u8]> = image_rs_rgba8.as_bytes().chunks_exact(4).collect();
+
+        let rgb8 = decode_with_output_format(
+            png_path,
+            core::decoded_image::PixelFormat::Rgb8,
+        );
+        let bgra8 = decode_with_output_format(
+            png_path,
+            core::decoded_image::PixelFormat::Bgra8,
+        );
+        let argb8 = decode_with_output_format(
+            png_path,
+            core::decoded_image::PixelFormat::Argb8,
+        );
+        assert_eq!(
+            rgb8.stride(),
+            rgb8.width as usize * 3
+        );
+
+        for (i, rgba) in image_rs_pixels.iter().enumerate() {
+            assert_eq!(
+                &rgb8.pixels[i * 3..i * 3 + 3],
+                &[rgba[0], rgba[1], rgba[2]]
+            );
+            assert_eq!(
+                &bgra8.pixels[i * 4..i * 4 + 4],
+                &[rgba[2], rgba[1], rgba[0], rgba[3]]
+            );
+            assert_eq!(
+                &argb8.pixels[i * 4..i * 4 + 4],
+                &[rgba[3], rgba[0], rgba[1], rgba[2]]
+            );
+        }
+    }
+
+    #[test_case("../test/png/official/basn0g01.png";"Decoding ../test/png/official/basn0g01.png) to Gray8 and GrayAlpha8 should work")]
+    #[test_case("../test/png/official/basi0g04.png";"Decoding ../test/png/official/basi0g04.png) to Gray8 and GrayAlpha8 should work")]
+    #[test_case("../test/png/official/basn0g16.png";"Decoding ../test/png/official/basn0g16.png) to Gray8 and GrayAlpha8 should work")]
+    #[test_case("../test/png/official/basn4a08.png";"Decoding ../test/png/official/basn4a08.png) to Gray8 and GrayAlpha8 should work")]
+    #[test_case("../test/png/official/tbbn0g04.png";"Decoding ../test/png/official/tbbn0g04.png) to Gray8 and GrayAlpha8 should work")]
+    fn decoding_grey_output_formats_test(png_path: &str) {
+        let image_rs_output = image::open(png_path).unwrap();
+
+        let gray8 = decode_with_output_format(
+            png_path,
+            core::decoded_image::PixelFormat::Gray8,
+        );
+        let gray_alpha8 = decode_with_output_format(
+            png_path,
+            core::decoded_image::PixelFormat::GrayAlpha8,
+        );
+
+        assert_eq!(
+            gray8.pixels,
+            image_rs_output.to_luma8().as_bytes()
+        );
+        assert_eq!(
+            gray_alpha8.pixels,
+            image_rs_output.to_luma_alpha8().as_bytes()
+        );
+    }
+
+    // interlaced and non-interlaced images of the same pixels must have the same native layout
+    #[test_case("../test/png/official/basi0g01.png", "../test/png/official/basn0g01.png";"Decoding ../test/png/official/basi0g01.png) to native layout should work")]
+    #[test_case("../test/png/official/basi0g02.png", "../test/png/official/basn0g02.png";"Decoding ../test/png/official/basi0g02.png) to native layout should work")]
+    #[test_case("../test/png/official/basi3p04.png", "../test/png/official/basn3p04.png";"Decoding ../test/png/official/basi3p04.png) to native layout should work")]
+    #[test_case("../test/png/official/basi2c16.png", "../test/png/official/basn2c16.png";"Decoding ../test/png/official/basi2c16.png) to native layout should work")]
+    #[test_case("../test/png/official/basi6a08.png", "../test/png/official/basn6a08.png";"Decoding ../test/png/official/basi6a08.png) to native layout should work")]
+    fn decoding_native_output_format_test(
+        interlaced_png_path: &str,
+        non_interlaced_png_path: &str,
+    ) {
+        let interlaced = decode_with_output_format(
+            interlaced_png_path,
+            core::decoded_image::PixelFormat::Native,
+        );
+        let non_interlaced = decode_with_output_format(
+            non_interlaced_png_path,
+            core::decoded_image::PixelFormat::Native,
+        );
+
+        assert_eq!(
+            interlaced.pixels.len(),
+            interlaced.stride() * interlaced.height as usize
+        );
+        assert_eq!(
+            interlaced.pixels,
+            non_interlaced.pixels
+        );
+    }
+
+    /// Hands out at most one byte per read, like a slow socket would
+    struct OneByteAtATimeReader<R: std::io::Read>(R);
+
+    impl<R: std::io::Read> std::io::Read for OneByteAtATimeReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test_case("../test/png/official/basn6a08.png";"Decoding ../test/png/official/basn6a08.png) from a reader should work")]
+    #[test_case("../test/png/official/basi3p02.png";"Decoding ../test/png/official/basi3p02.png) from a reader should work")]
+    #[test_case("../test/png/official/ccwn2c08.png";"Decoding ../test/png/official/ccwn2c08.png) from a reader should work")]
+    #[test_case("../test/png/official/ct1n0g04.png";"Decoding ../test/png/official/ct1n0g04.png) from a reader should work")]
+    fn decoding_from_reader_test(png_path: &str) {
+        let image_rs_rgba8 = image::open(png_path).unwrap().to_rgba8();
+        let png_bytes = std::fs::read(png_path).unwrap();
+
+        for validate_crc in [true, false] {
+            let byte_readers = [
+                byte_reader::ByteReader::from_reader(std::fs::File::open(png_path).unwrap()),
+                byte_reader::ByteReader::from_seekable_reader(std::io::Cursor::new(
+                    &png_bytes,
+                )),
+                byte_reader::ByteReader::from_buf_reader(
+                    std::io::BufReader::with_capacity(
+                        3,
+                        OneByteAtATimeReader(&png_bytes[..]),
+                    ),
+                ),
+            ];
+
+            for mut byte_reader in byte_readers {
+                byte_reader.read_image().unwrap();
+                let decoder_options = core::decoder::PngDecoderOptions {
+                    validate_crc,
+                    ..Default::default()
+                };
+                let mut decoder = core::decoder::PngDecoder::new(
+                    &mut byte_reader,
+                    &decoder_options,
+                );
+                let decoder_result = decoder.run().unwrap();
+
+                assert_eq!(
+                    &decoder_result.pixels[..],
+                    image_rs_rgba8.as_bytes()
+                );
+            }
+        }
+    }
+
+    #[test_case("../test/png/official/basn0g01.png";"Pushing ../test/png/official/basn0g01.png) in pieces should work")]
+    #[test_case("../test/png/official/basn3p04.png";"Pushing ../test/png/official/basn3p04.png) in pieces should work")]
+    #[test_case("../test/png/official/basn6a16.png";"Pushing ../test/png/official/basn6a16.png) in pieces should work")]
+    #[test_case("../test/png/official/oi9n2c16.png";"Pushing ../test/png/official/oi9n2c16.png) in pieces should work")]
+    #[test_case("../test/png/official/tbbn3p08.png";"Pushing ../test/png/official/tbbn3p08.png) in pieces should work")]
+    #[test_case("../test/png/official/basi3p02.png";"Pushing ../test/png/official/basi3p02.png) in pieces should work")]
+    #[test_case("../test/png/official/basi6a16.png";"Pushing ../test/png/official/basi6a16.png) in pieces should work")]
+    fn push_decoding_test(png_path: &str) {
+        let image_rs_rgba8 = image::open(png_path).unwrap().to_rgba8();
+        let png_bytes = std::fs::read(png_path).unwrap();
+
+        for piece_length in [1, 7, png_bytes.len()] {
+            let decoder_options = core::decoder::PngDecoderOptions::default();
+            let mut push_decoder = core::push_decoder::PngPushDecoder::new(&decoder_options);
+            let mut pixels: Vec<u8> = vec![];
+            let mut last_status = core::push_decoder::PushStatus::NeedsMoreData;
+
+            for piece in png_bytes.chunks(piece_length) {
+                last_status = push_decoder.feed(piece).unwrap();
+                while let Some(row) = push_decoder.next_row() {
+                    pixels.extend(row);
+                }
+            }
+
+            assert_eq!(
+                last_status,
+                core::push_decoder::PushStatus::Done
+            );
+            assert_eq!(
+                push_decoder.header().unwrap().width,
+                image_rs_rgba8.width()
+            );
+            assert_eq!(
+                &pixels[..],
+                image_rs_rgba8.as_bytes()
+            );
+            assert!(push_decoder.get_errors().is_empty());
+        }
+    }
+
+    #[test]
+    fn push_decoding_status_test() {
+        use core::push_decoder::PushStatus;
+
+        let png_bytes = std::fs::read("../test/png/official/basn2c08.png").unwrap();
+        let decoder_options = core::decoder::PngDecoderOptions::default();
+        let mut push_decoder = core::push_decoder::PngPushDecoder::new(&decoder_options);
+
+        // signature, then IHDR chunk except for the last byte of its data
+        assert_eq!(
+            push_decoder.feed(&png_bytes[..8]).unwrap(),
+            PushStatus::NeedsMoreData
+        );
+        assert_eq!(
+            push_decoder.feed(&png_bytes[8..28]).unwrap(),
+            PushStatus::NeedsMoreData
+        );
+        assert_eq!(
+            push_decoder.feed(&png_bytes[28..33]).unwrap(),
+            PushStatus::HeaderAvailable
+        );
+        assert_eq!(
+            push_decoder.header().unwrap().height,
+            32
+        );
+
+        match push_decoder
+            .feed(&png_bytes[33..png_bytes.len() - 1])
+            .unwrap()
+        {
+            PushStatus::RowsAvailable(row_count) => assert_eq!(row_count, 32),
+            status => panic!(
+                "Expected rows to be available, got {:?}",
+                status
+            ),
+        }
+        assert_eq!(
+            push_decoder.next_row().unwrap().len(),
+            32 * 4
+        );
+        assert_eq!(
+            push_decoder
+                .feed(&png_bytes[png_bytes.len() - 1..])
+                .unwrap(),
+            PushStatus::Done
+        );
+    }
+
+    #[test]
+    fn push_decoding_corrupt_image_test() {
+        use core::{chunk_helpers::ColorType, encoder::PngEncoder, errors::PngDecodeErrorCode};
+
+        let push_decode = |png_bytes: &[u8]| {
+            let mut push_decoder = core::push_decoder::PngPushDecoder::new(&Default::default());
+            let mut pixels: Vec<u8> = vec![];
+            for piece in png_bytes.chunks(7) {
+                push_decoder.feed(piece)?;
+                while let Some(row) = push_decoder.next_row() {
+                    pixels.extend(row);
+                }
+            }
+            let codes: Vec<String> = push_decoder
+                .get_errors()
+                .iter()
+                .map(|error| format!("{:?}", error.code))
+                .collect();
+            Ok::<_, PngDecodeErrorCode>((pixels, codes))
+        };
+
+        // image data of 2 rows, in an image of 4 rows
+        let mut png_bytes = PngEncoder::new(
+            2,
+            2,
+            ColorType::Greyscale,
+            8,
+            &Default::default(),
+        )
+        .encode(&[10, 20, 30, 40])
+        .unwrap();
+        let ihdr_range = find_chunks(&png_bytes, "IHDR").pop().unwrap();
+        png_bytes[ihdr_range.start + 12..ihdr_range.start + 16]
+            .copy_from_slice(&4u32.to_be_bytes());
+        let crc = crc32fast::hash(&png_bytes[ihdr_range.start + 4..ihdr_range.end - 4]);
+        png_bytes[ihdr_range.end - 4..ihdr_range.end].copy_from_slice(&crc.to_be_bytes());
+
+        let decoded_image = decode_with_limits(
+            png_bytes.clone(),
+            Default::default(),
+        )
+        .unwrap();
+        let codes: Vec<String> = decoded_image
+            .errors
+            .iter()
+            .map(|error| format!("{:?}", error.code))
+            .collect();
+        assert_eq!(codes, ["_25(4, 2)"]);
+        // missing rows are filled with zeros
+        let expected_pixels: Vec<u8> = [10, 20, 30, 40, 0, 0, 0, 0]
+            .iter()
+            .flat_map(|grey| [*grey, *grey, *grey, 255])
+            .collect();
+        assert_eq!(
+            decoded_image.pixels,
+            expected_pixels
+        );
+        assert_eq!(
+            push_decode(&png_bytes).unwrap(),
+            (expected_pixels, codes)
+        );
+
+        // the row decoder pads the missing rows in the same way
+        let mut byte_reader = byte_reader::ByteReader::new(
+            None,
+            byte_reader::ByteReaderMode::RAW,
+            Some(png_bytes.clone()),
+        );
+        byte_reader.read_image().unwrap();
+        let decoder_options = core::decoder::PngDecoderOptions::default();
+        let mut row_decoder = core::row_decoder::PngRowDecoder::new(
+            &mut byte_reader,
+            &decoder_options,
+        );
+        let mut pixels: Vec<u8> = vec![];
+        while let Some(row) = row_decoder.next_row().unwrap() {
+            pixels.extend(row);
+        }
+        let codes: Vec<String> = row_decoder
+            .get_errors()
+            .iter()
+            .map(|error| format!("{:?}", error.code))
+            .collect();
+        assert_eq!(codes, ["_25(4, 2)"]);
+        assert_eq!(pixels, decoded_image.pixels);
+
+        // chunk type that is not valid text
+        let ihdr_end = find_chunks(&png_bytes, "IHDR")[0].end;
+        let chunk_type_bytes = [0xff, 0xfe, 0xfd, 0xfc];
+        let corrupt_bytes = [
+            &png_bytes[..ihdr_end],
+            &[0, 0, 0, 0],
+            &chunk_type_bytes,
+            &[0, 0, 0, 0],
+            &png_bytes[ihdr_end..],
+        ]
+        .concat();
+        assert!(
+            matches!(decode_with_limits(corrupt_bytes.clone(), Default::default()), Err(PngDecodeErrorCode::_36(bytes)) if bytes == chunk_type_bytes)
+        );
+        assert!(
+            matches!(push_decode(&corrupt_bytes), Err(PngDecodeErrorCode::_36(bytes)) if bytes == chunk_type_bytes)
+        );
+    }
+
+    #[test_case("../test/png/official/basn0g01.png";"Decoding ../test/png/official/basn0g01.png) row by row should work")]
+    #[test_case("../test/png/official/basn3p04.png";"Decoding ../test/png/official/basn3p04.png) row by row should work")]
+    #[test_case("../test/png/official/basn6a16.png";"Decoding ../test/png/official/basn6a16.png) row by row should work")]
+    #[test_case("../test/png/official/oi9n2c16.png";"Decoding ../test/png/official/oi9n2c16.png) row by row should work")]
+    #[test_case("../test/png/official/z09n2c08.png";"Decoding ../test/png/official/z09n2c08.png) row by row should work")]
+    #[test_case("../test/png/official/f04n2c08.png";"Decoding ../test/png/official/f04n2c08.png) row by row should work")]
+    #[test_case("../test/png/official/tbbn3p08.png";"Decoding ../test/png/official/tbbn3p08.png) row by row should work")]
+    fn row_decoding_test(png_path: &str) {
+        let image_rs_rgba8 = image::open(png_path).unwrap().to_rgba8();
+
+        for validate_crc in [true, false] {
+            let mut byte_reader =
+                byte_reader::ByteReader::from_reader(std::fs::File::open(png_path).unwrap());
+            byte_reader.read_image().unwrap();
+            let decoder_options = core::decoder::PngDecoderOptions {
+                validate_crc,
+                ..Default::default()
+            };
+            let mut row_decoder = core::row_decoder::PngRowDecoder::new(
+                &mut byte_reader,
+                &decoder_options,
+            );
+            let header = row_decoder.read_header().unwrap();
+            let mut pixels: Vec<u8> = vec![];
+            let mut row_count = 0;
+
+            while let Some(row) = row_decoder.next_row().unwrap() {
+                assert_eq!(
+                    row.len(),
+                    header.width as usize * 4
+                );
+                pixels.extend(row);
+                row_count += 1;
+            }
+
+            assert_eq!(row_count, header.height);
+            assert_eq!(
+                &pixels[..],
+                image_rs_rgba8.as_bytes()
+            );
+            assert!(row_decoder.get_errors().is_empty());
+        }
+    }
+
+    #[test]
+    fn row_decoding_interlaced_image_test() {
+        let mut byte_reader = byte_reader::ByteReader::new(
+            Some("../test/png/official/basi0g08.png"),
+            byte_reader::ByteReaderMode::FILE,
+            None,
+        );
+        byte_reader.read_image().unwrap();
+        let decoder_options = core::decoder::PngDecoderOptions::default();
+        let mut row_decoder = core::row_decoder::PngRowDecoder::new(
+            &mut byte_reader,
+            &decoder_options,
+        );
+
+        assert!(matches!(
+            row_decoder.next_row(),
+            Err(core::errors::PngDecodeErrorCode::_26)
+        ));
+    }
+
+    #[test_case("../test/png/official/basi0g01.png";"Previewing ../test/png/official/basi0g01.png) for each pass should work")]
+    #[test_case("../test/png/official/basi3p04.png";"Previewing ../test/png/official/basi3p04.png) for each pass should work")]
+    #[test_case("../test/png/official/basi6a16.png";"Previewing ../test/png/official/basi6a16.png) for each pass should work")]
+    #[test_case("../test/png/official/s03i3p01.png";"Previewing ../test/png/official/s03i3p01.png) for each pass should work")]
+    #[test_case("../test/png/official/s09i3p02.png";"Previewing ../test/png/official/s09i3p02.png) for each pass should work")]
+    fn pass_preview_test(png_path: &str) {
+        let previews = std::rc::Rc::new(std::cell::RefCell::new(
+            Vec::<(u8, Vec<u8>)>::new(),
+        ));
+        let mut byte_reader = byte_reader::ByteReader::new(
+            Some(&png_path),
+            byte_reader::ByteReaderMode::FILE,
+            None,
+        );
+        byte_reader.read_image().unwrap();
+        let decoder_options = core::decoder::PngDecoderOptions::default();
+        let mut decoder = core::decoder::PngDecoder::new(
+            &mut byte_reader,
+            &decoder_options,
+        );
+        let previews_in_callback = previews.clone();
+        decoder.set_pass_callback(move |nth_pass, preview| {
+            previews_in_callback
+                .borrow_mut()
+                .push((nth_pass, preview.to_vec()));
+        });
+        let decoded_image = decoder.run().unwrap();
+        let width = decoded_image.width as usize;
+        let previews = previews.borrow();
+
+        assert_eq!(
+            previews
+                .iter()
+                .map(|(nth_pass, _)| *nth_pass)
+                .collect::<Vec<u8>>(),
+            vec![1, 2, 3, 4, 5, 6, 7]
+        );
+        // after the first pass, every pixel is a copy of the top left pixel of its 8x8 block
+        let (_, first_preview) = &previews[0];
+        for y in 0..decoded_image.height as usize {
+            for x in 0..width {
+                let preview_index = (y * width + x) * 4;
+                let decoded_index = ((y & !7) * width + (x & !7)) * 4;
+                assert_eq!(
+                    first_preview[preview_index..preview_index + 4],
+                    decoded_image.pixels[decoded_index..decoded_index + 4]
+                );
+            }
+        }
+        let (_, last_preview) = &previews[6];
+        assert_eq!(
+            last_preview,
+            &decoded_image.pixels
+        );
+    }
+
+    #[test]
+    fn push_pass_preview_test() {
+        let png_bytes = std::fs::read("../test/png/official/basi2c08.png").unwrap();
+        let passes = std::rc::Rc::new(std::cell::RefCell::new(
+            Vec::<u8>::new(),
+        ));
+        let decoder_options = core::decoder::PngDecoderOptions::default();
+        let mut push_decoder = core::push_decoder::PngPushDecoder::new(&decoder_options);
+        let passes_in_callback = passes.clone();
+        push_decoder.set_pass_callback(move |nth_pass, preview| {
+            assert_eq!(preview.len(), 32 * 32 * 4);
+            passes_in_callback.borrow_mut().push(nth_pass);
+        });
+
+        // everything but IEND chunk
+        for byte in &png_bytes[..png_bytes.len() - 12] {
+            push_decoder.feed(&[*byte]).unwrap();
+            if passes.borrow().len() == 1 {
+                // the first pass is done long before rows of an interlaced image are available
+                assert!(push_decoder.next_row().is_none());
+            }
+        }
+
+        assert_eq!(
+            *passes.borrow(),
+            vec![1, 2, 3, 4, 5, 6, 7]
+        );
+    }
+
+    #[test_case("../test/png/official/basn0g01.png";"Probing ../test/png/official/basn0g01.png) should work")]
+    #[test_case("../test/png/official/basi3p04.png";"Probing ../test/png/official/basi3p04.png) should work")]
+    #[test_case("../test/png/official/basn6a16.png";"Probing ../test/png/official/basn6a16.png) should work")]
+    #[test_case("../test/png/official/s39i3p04.png";"Probing ../test/png/official/s39i3p04.png) should work")]
+    fn probe_test(png_path: &str) {
+        let image_rs_image = image::open(png_path).unwrap();
+        let mut byte_reader = byte_reader::ByteReader::new(
+            Some(&png_path),
+            byte_reader::ByteReaderMode::FILE,
+            None,
+        );
+        byte_reader.read_image().unwrap();
+        let decoder_options = core::decoder::PngDecoderOptions::default();
+        let header = core::decoder::PngDecoder::new(
+            &mut byte_reader,
+            &decoder_options,
+        )
+        .probe()
+        .unwrap();
+
+        assert_eq!(
+            header.width,
+            image_rs_image.width()
+        );
+        assert_eq!(
+            header.height,
+            image_rs_image.height()
+        );
+        // signature + IHDR chunk
+        assert_eq!(
+            byte_reader.get_current_byte_pos(),
+            8 + 12 + 13
+        );
+    }
+
+    #[test_case("../test/png/official/ccwn2c08.png", &["IHDR", "gAMA", "cHRM", "IDAT", "IEND"];"Probing chunks of ../test/png/official/ccwn2c08.png) should work")]
+    #[test_case("../test/png/official/oi4n2c16.png", &["IHDR", "gAMA", "IDAT", "IDAT", "IDAT", "IDAT", "IEND"];"Probing chunks of ../test/png/official/oi4n2c16.png) should work")]
+    #[test_case("../test/png/official/tbbn3p08.png", &["IHDR", "gAMA", "PLTE", "tRNS", "bKGD", "IDAT", "IEND"];"Probing chunks of ../test/png/official/tbbn3p08.png) should work")]
+    fn probe_chunks_test(png_path: &str, expected_chunk_types: &[&str]) {
+        let png_bytes = std::fs::read(png_path).unwrap();
+        let byte_readers = [
+            byte_reader::ByteReader::new(
+                Some(&png_path),
+                byte_reader::ByteReaderMode::FILE,
+                None,
+            ),
+            byte_reader::ByteReader::from_reader(&png_bytes[..]),
+            byte_reader::ByteReader::from_seekable_reader(std::io::Cursor::new(
+                &png_bytes,
+            )),
+        ];
+
+        for mut byte_reader in byte_readers {
+            byte_reader.read_image().unwrap();
+            let decoder_options = core::decoder::PngDecoderOptions::default();
+            let mut decoder = core::decoder::PngDecoder::new(
+                &mut byte_reader,
+                &decoder_options,
+            );
+            let probed_image = decoder.probe_chunks().unwrap();
+
+            assert_eq!(
+                probed_image
+                    .chunks
+                    .iter()
+                    .map(|chunk| chunk.chunk_type.as_str())
+                    .collect::<Vec<&str>>(),
+                expected_chunk_types
+            );
+            let last_chunk = probed_image.chunks.last().unwrap();
+            assert_eq!(
+                last_chunk.byte_pos + 12,
+                png_bytes.len()
+            );
+            assert_eq!(
+                probed_image.ancillary_chunks[0].chunk_type,
+                "gAMA"
+            );
+            assert_eq!(
+                probed_image.ancillary_chunks[0].data.len(),
+                4
+            );
+            assert!(probed_image.errors.is_empty());
+        }
+    }
+
+    fn decode_with_limits(
+        png_bytes: Vec<u8>,
+        decoder_options: core::decoder::PngDecoderOptions,
+    ) -> Result<core::decoded_image::DecodedImage, core::errors::PngDecodeErrorCode> {
+        let mut byte_reader = byte_reader::ByteReader::new(
+            None,
+            byte_reader::ByteReaderMode::RAW,
+            Some(png_bytes),
+        );
+        byte_reader.read_image().unwrap();
+        let mut decoder = core::decoder::PngDecoder::new(
+            &mut byte_reader,
+            &decoder_options,
+        );
+        decoder.run()
+    }
+
+    #[test]
+    fn decoding_limits_test() {
+        use core::errors::PngDecodeErrorCode;
+
+        // 32x32, IDAT chunk with 65 bytes of data, gAMA chunk with 4 bytes of data
+        let png_bytes = std::fs::read("../test/png/official/basn0g08.png").unwrap();
+        let default_options = core::decoder::PngDecoderOptions::default();
+
+        assert!(decode_with_limits(
+            png_bytes.clone(),
+            default_options
+        )
+        .is_ok());
+        assert!(matches!(
+            decode_with_limits(
+                png_bytes.clone(),
+                core::decoder::PngDecoderOptions {
+                    max_width: 31,
+                    ..default_options
+                }
+            ),
+            Err(PngDecodeErrorCode::_28(
+                32, 31
+            ))
+        ));
+        assert!(matches!(
+            decode_with_limits(
+                png_bytes.clone(),
+                core::decoder::PngDecoderOptions {
+                    max_height: 16,
+                    ..default_options
+                }
+            ),
+            Err(PngDecodeErrorCode::_29(
+                32, 16
+            ))
+        ));
+        assert!(matches!(
+            decode_with_limits(
+                png_bytes.clone(),
+                core::decoder::PngDecoderOptions {
+                    max_output_bytes: 4095,
+                    ..default_options
+                }
+            ),
+            Err(PngDecodeErrorCode::_30(
+                4096, 4095
+            ))
+        ));
+        // 1 byte per pixel in the native format of an 8-bit greyscale image
+        assert!(decode_with_limits(
+            png_bytes.clone(),
+            core::decoder::PngDecoderOptions {
+                output_format: core::decoded_image::PixelFormat::Native,
+                max_output_bytes: 1024,
+                ..default_options
+            }
+        )
+        .is_ok());
+        assert!(matches!(
+            decode_with_limits(
+                png_bytes.clone(),
+                core::decoder::PngDecoderOptions {
+                    output_format: core::decoded_image::PixelFormat::Rgba16BigEndian,
+                    max_output_bytes: 8191,
+                    ..default_options
+                }
+            ),
+            Err(PngDecodeErrorCode::_30(
+                8192, 8191
+            ))
+        ));
+        // 32 rows of 1 filter byte + 32 bytes
+        assert!(decode_with_limits(
+            png_bytes.clone(),
+            core::decoder::PngDecoderOptions {
+                max_inflated_bytes: 32 * 33,
+                ..default_options
+            }
+        )
+        .is_ok());
+        assert!(matches!(
+            decode_with_limits(
+                png_bytes.clone(),
+                core::decoder::PngDecoderOptions {
+                    max_inflated_bytes: 32 * 33 - 1,
+                    ..default_options
+                }
+            ),
+            Err(PngDecodeErrorCode::_31(1055))
+        ));
+        assert!(matches!(
+            decode_with_limits(png_bytes.clone(), core::decoder::PngDecoderOptions { max_chunk_length: 50, ..default_options }),
+            Err(PngDecodeErrorCode::_32(chunk_type, 65, 50)) if chunk_type == "IDAT"
+        ));
+        assert!(matches!(
+            decode_with_limits(
+                png_bytes.clone(),
+                core::decoder::PngDecoderOptions {
+                    max_ancillary_bytes: 3,
+                    ..default_options
+                }
+            ),
+            Err(PngDecodeErrorCode::_33(3))
+        ));
+
+        let mut push_decoder = core::push_decoder::PngPushDecoder::new(
+            &core::decoder::PngDecoderOptions {
+                max_chunk_length: 50,
+                ..default_options
+            },
+        );
+        assert!(matches!(
+            push_decoder.feed(&png_bytes),
+            Err(PngDecodeErrorCode::_32(chunk_type, 65, 50)) if chunk_type == "IDAT"
+        ));
+
+        // the row decoder counts inflated bytes as well, and the last row goes over the limit
+        let mut byte_reader = byte_reader::ByteReader::new(
+            None,
+            byte_reader::ByteReaderMode::RAW,
+            Some(png_bytes),
+        );
+        byte_reader.read_image().unwrap();
+        let row_decoder_options = core::decoder::PngDecoderOptions {
+            max_inflated_bytes: 32 * 33 - 1,
+            ..default_options
+        };
+        let mut row_decoder = core::row_decoder::PngRowDecoder::new(
+            &mut byte_reader,
+            &row_decoder_options,
+        );
+        for _ in 0..31 {
+            assert!(row_decoder.next_row().unwrap().is_some());
+        }
+        assert!(matches!(
+            row_decoder.next_row(),
+            Err(PngDecodeErrorCode::_31(1055))
+        ));
+    }
+
+    #[test]
+    fn decoding_huge_dimensions_test() {
+        let mut png_bytes = std::fs::read("../test/png/official/basn0g08.png").unwrap();
+        // IHDR chunk data starts at 16. Declare 2^31 - 1 by 2^31 - 1 pixels
+        png_bytes[16..24].copy_from_slice(&[0x7f, 0xff, 0xff, 0xff, 0x7f, 0xff, 0xff, 0xff]);
+
+        assert!(matches!(
+            decode_with_limits(
+                png_bytes,
+                core::decoder::PngDecoderOptions::default()
+            ),
+            Err(core::errors::PngDecodeErrorCode::_28(0x7fffffff, 1_000_000))
+        ));
+    }
+
+    #[test_case(0, 32;"Decoding an IHDR chunk of zero width should fail")]
+    #[test_case(32, 0;"Decoding an IHDR chunk of zero height should fail")]
+    #[test_case(0, 0;"Decoding an IHDR chunk of zero width and height should fail")]
+    #[test_case(0x8000_0000, 32;"Decoding an IHDR chunk of a width over 2^31 - 1 should fail")]
+    fn decoding_zero_dimensions_test(width: u32, height: u32) {
+        let mut png_bytes = std::fs::read("../test/png/official/basn0g08.png").unwrap();
+        // IHDR chunk data starts at 16
+        png_bytes[16..20].copy_from_slice(&width.to_be_bytes());
+        png_bytes[20..24].copy_from_slice(&height.to_be_bytes());
+
+        assert!(matches!(
+            decode_with_limits(
+                png_bytes,
+                core::decoder::PngDecoderOptions::default()
+            ),
+            Err(core::errors::PngDecodeErrorCode::_57(actual_width, actual_height))
+                if actual_width == width && actual_height == height
+        ));
     }
 
     fn decode_corrupt_image(corrupt_png_path: &str) {
@@ -233,15 +1091,20 @@ mod tests {
             byte_reader::ByteReaderMode::FILE,
             None,
         );
-        byte_reader.read_image();
+        byte_reader.read_image().unwrap();
+        let decoder_options = core::decoder::PngDecoderOptions {
+            fail_fast: false,
+            validate_crc: true,
+            ..Default::default()
+        };
         let mut decoder = core::decoder::PngDecoder::new(
             byte_reader,
-            &core::decoder::PngDecoderOptions {
-                fail_fast: false,
-                validate_crc: true,
-            },
+            &decoder_options,
         );
-        decoder.run();
+        // the error is either recoverable and reported, or stops decoding
+        if let Ok(decoded_image) = decoder.run() {
+            assert!(!decoded_image.errors.is_empty());
+        }
     }
 
     // recoverable errors
@@ -281,4 +1144,4184 @@ mod tests {
     fn decode_unrecoverable_corrupt_image(path: &str) {
         decode_corrupt_image(path)
     }
+
+    #[test]
+    fn fail_fast_returns_error_test() {
+        let png_bytes = std::fs::read("../test/png/official/xs2n0g01.png").unwrap();
+        let decoder_options = core::decoder::PngDecoderOptions {
+            fail_fast: true,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            decode_with_limits(
+                png_bytes.clone(),
+                decoder_options
+            ),
+            Err(core::errors::PngDecodeErrorCode::_1(_))
+        ));
+        let mut push_decoder = core::push_decoder::PngPushDecoder::new(&decoder_options);
+        assert!(matches!(
+            push_decoder.feed(&png_bytes),
+            Err(core::errors::PngDecodeErrorCode::_1(_))
+        ));
+    }
+
+    #[test]
+    fn adler32_mismatch_test() {
+        use core::{
+            decoded_image::PixelFormat, errors::PngDecodeErrorCode, push_decoder::PngPushDecoder,
+        };
+
+        let png_bytes = std::fs::read("../test/png/official/basn0g08.png").unwrap();
+        // the last byte of the image data is the last byte of its Adler-32 checksum.
+        // The CRC is written again, so that only the checksum is wrong
+        let mut corrupt_bytes = png_bytes.clone();
+        let idat_range = find_chunks(&png_bytes, "IDAT").pop().unwrap();
+        corrupt_bytes[idat_range.end - 5] ^= 1;
+        let crc = crc32fast::hash(&corrupt_bytes[idat_range.start + 4..idat_range.end - 4]);
+        corrupt_bytes[idat_range.end - 4..idat_range.end].copy_from_slice(&crc.to_be_bytes());
+
+        let decoded_image = decode_with_limits(
+            corrupt_bytes.clone(),
+            Default::default(),
+        )
+        .unwrap();
+        assert!(
+            matches!(decoded_image.errors[..], [ref error] if matches!(error.code, PngDecodeErrorCode::_54))
+        );
+        assert_eq!(
+            decoded_image.pixels,
+            decode_bytes(png_bytes, PixelFormat::Rgba8).pixels
+        );
+
+        let fail_fast_options = core::decoder::PngDecoderOptions {
+            fail_fast: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            decode_with_limits(
+                corrupt_bytes.clone(),
+                fail_fast_options
+            ),
+            Err(PngDecodeErrorCode::_54)
+        ));
+        let mut push_decoder = PngPushDecoder::new(&Default::default());
+        push_decoder.feed(&corrupt_bytes).unwrap();
+        assert!(
+            matches!(push_decoder.get_errors()[..], [ref error] if matches!(error.code, PngDecodeErrorCode::_54))
+        );
+        assert!(matches!(
+            PngPushDecoder::new(&fail_fast_options).feed(&corrupt_bytes),
+            Err(PngDecodeErrorCode::_54)
+        ));
+    }
+
+    #[test]
+    fn truncated_image_test() {
+        let png_bytes = std::fs::read("../test/png/official/basn0g08.png").unwrap();
+
+        for length in [0, 7, 8, 20, 33, 60, 100, png_bytes.len() - 1] {
+            assert!(decode_with_limits(
+                png_bytes[..length].to_vec(),
+                Default::default()
+            )
+            .is_err());
+        }
+    }
+
+    /// Small xorshift generator, so that the same mutations are tried on every run
+    fn next_random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    /// Decodes `png_bytes` in every way that the decoder supports. Only checks that nothing panics.
+    fn decode_in_every_way(png_bytes: &[u8], decoder_options: &core::decoder::PngDecoderOptions) {
+        let _ = decode_with_limits(
+            png_bytes.to_vec(),
+            *decoder_options,
+        );
+        let _ = decode_with_limits(
+            png_bytes.to_vec(),
+            core::decoder::PngDecoderOptions {
+                output_format: core::decoded_image::PixelFormat::Native,
+                ..*decoder_options
+            },
+        );
+
+        let mut byte_reader = byte_reader::ByteReader::new(
+            None,
+            byte_reader::ByteReaderMode::RAW,
+            Some(png_bytes.to_vec()),
+        );
+        let _ = core::decoder::PngDecoder::new(
+            &mut byte_reader,
+            decoder_options,
+        )
+        .probe_chunks();
+
+        let mut byte_reader = byte_reader::ByteReader::new(
+            None,
+            byte_reader::ByteReaderMode::RAW,
+            Some(png_bytes.to_vec()),
+        );
+        let mut row_decoder = core::row_decoder::PngRowDecoder::new(
+            &mut byte_reader,
+            decoder_options,
+        );
+        while let Ok(Some(_)) = row_decoder.next_row() {}
+
+        let mut push_decoder = core::push_decoder::PngPushDecoder::new(decoder_options);
+        push_decoder.set_pass_callback(|_, _| {});
+        for piece in png_bytes.chunks(61) {
+            if push_decoder.feed(piece).is_err() {
+                break;
+            }
+            while push_decoder.next_row().is_some() {}
+        }
+    }
+
+    #[test_case("../test/png/official/basn0g01.png";"Mutating ../test/png/official/basn0g01.png should not panic")]
+    #[test_case("../test/png/official/basn2c16.png";"Mutating ../test/png/official/basn2c16.png should not panic")]
+    #[test_case("../test/png/official/basn3p04.png";"Mutating ../test/png/official/basn3p04.png should not panic")]
+    #[test_case("../test/png/official/basi3p08.png";"Mutating ../test/png/official/basi3p08.png should not panic")]
+    #[test_case("../test/png/official/basi6a16.png";"Mutating ../test/png/official/basi6a16.png should not panic")]
+    #[test_case("../test/png/official/tbrn2c08.png";"Mutating ../test/png/official/tbrn2c08.png should not panic")]
+    #[test_case("../test/png/official/tp1n3p08.png";"Mutating ../test/png/official/tp1n3p08.png should not panic")]
+    #[test_case("../test/png/official/s01i3p01.png";"Mutating ../test/png/official/s01i3p01.png should not panic")]
+    #[test_case("../test/png/official/xlfn0g04.png";"Mutating ../test/png/official/xlfn0g04.png should not panic")]
+    #[test_case("../test/png/official/xdtn0g01.png";"Mutating ../test/png/official/xdtn0g01.png should not panic")]
+    fn mutated_image_does_not_panic_test(png_path: &str) {
+        let png_bytes = std::fs::read(png_path).unwrap();
+        // keeps corrupt dimensions from allocating too much
+        let decoder_options = core::decoder::PngDecoderOptions {
+            max_output_bytes: 1 << 18,
+            max_inflated_bytes: 1 << 20,
+            ..Default::default()
+        };
+        let fail_fast_options = core::decoder::PngDecoderOptions {
+            fail_fast: true,
+            ..decoder_options
+        };
+        let mut random_state: u64 = 0x2545f4914f6cdd1d;
+
+        for length in 0..png_bytes.len() {
+            decode_in_every_way(
+                &png_bytes[..length],
+                &decoder_options,
+            );
+        }
+        for _ in 0..300 {
+            let mut mutated_bytes = png_bytes.clone();
+            for _ in 0..1 + next_random(&mut random_state) % 4 {
+                let byte_index =
+                    (next_random(&mut random_state) % mutated_bytes.len() as u64) as usize;
+                mutated_bytes[byte_index] = next_random(&mut random_state) as u8;
+            }
+            decode_in_every_way(
+                &mutated_bytes,
+                &decoder_options,
+            );
+            decode_in_every_way(
+                &mutated_bytes,
+                &fail_fast_options,
+            );
+        }
+    }
+
+    /// Where every chunk of `chunk_type` is, from its length to the end of its CRC,
+    /// found by walking the chunks of `png_bytes`
+    fn find_chunks(png_bytes: &[u8], chunk_type: &str) -> Vec<std::ops::Range<usize>> {
+        let mut chunk_ranges = vec![];
+        let mut chunk_start = 8;
+        while chunk_start + 8 <= png_bytes.len() {
+            let chunk_data_length =
+                u32::from_be_bytes(png_bytes[chunk_start..chunk_start + 4].try_into().unwrap())
+                    as usize;
+            let chunk_end = chunk_start + chunk_data_length + 12;
+            if &png_bytes[chunk_start + 4..chunk_start + 8] == chunk_type.as_bytes() {
+                chunk_ranges.push(chunk_start..chunk_end);
+            }
+            chunk_start = chunk_end;
+        }
+
+        chunk_ranges
+    }
+
+    /// Data of the first chunk of `chunk_type`
+    fn read_chunk_data(png_bytes: &[u8], chunk_type: &str) -> Option<Vec<u8>> {
+        let chunk_range = find_chunks(png_bytes, chunk_type).into_iter().next()?;
+        Some(png_bytes[chunk_range.start + 8..chunk_range.end - 4].to_vec())
+    }
+
+    /// Bytes of every chunk of `chunk_type`, including their length and CRC
+    fn chunk_bytes(png_bytes: &[u8], chunk_type: &str) -> Vec<u8> {
+        find_chunks(png_bytes, chunk_type)
+            .into_iter()
+            .flat_map(|chunk_range| png_bytes[chunk_range].to_vec())
+            .collect()
+    }
+
+    fn decode_bytes(
+        png_bytes: Vec<u8>,
+        output_format: core::decoded_image::PixelFormat,
+    ) -> core::decoded_image::DecodedImage {
+        let decoder_options = core::decoder::PngDecoderOptions {
+            fail_fast: true,
+            output_format,
+            ..Default::default()
+        };
+        decode_with_limits(png_bytes, decoder_options).unwrap()
+    }
+
+    #[test_case("../test/png/official/basn0g01.png";"Encoding ../test/png/official/basn0g01.png should round-trip")]
+    #[test_case("../test/png/official/basn0g02.png";"Encoding ../test/png/official/basn0g02.png should round-trip")]
+    #[test_case("../test/png/official/basn0g04.png";"Encoding ../test/png/official/basn0g04.png should round-trip")]
+    #[test_case("../test/png/official/basn0g08.png";"Encoding ../test/png/official/basn0g08.png should round-trip")]
+    #[test_case("../test/png/official/basn0g16.png";"Encoding ../test/png/official/basn0g16.png should round-trip")]
+    #[test_case("../test/png/official/basn2c08.png";"Encoding ../test/png/official/basn2c08.png should round-trip")]
+    #[test_case("../test/png/official/basn2c16.png";"Encoding ../test/png/official/basn2c16.png should round-trip")]
+    #[test_case("../test/png/official/basn3p01.png";"Encoding ../test/png/official/basn3p01.png should round-trip")]
+    #[test_case("../test/png/official/basn3p02.png";"Encoding ../test/png/official/basn3p02.png should round-trip")]
+    #[test_case("../test/png/official/basn3p04.png";"Encoding ../test/png/official/basn3p04.png should round-trip")]
+    #[test_case("../test/png/official/basn3p08.png";"Encoding ../test/png/official/basn3p08.png should round-trip")]
+    #[test_case("../test/png/official/basn4a08.png";"Encoding ../test/png/official/basn4a08.png should round-trip")]
+    #[test_case("../test/png/official/basn4a16.png";"Encoding ../test/png/official/basn4a16.png should round-trip")]
+    #[test_case("../test/png/official/basn6a08.png";"Encoding ../test/png/official/basn6a08.png should round-trip")]
+    #[test_case("../test/png/official/basn6a16.png";"Encoding ../test/png/official/basn6a16.png should round-trip")]
+    #[test_case("../test/png/official/basi2c16.png";"Encoding ../test/png/official/basi2c16.png should round-trip")]
+    #[test_case("../test/png/official/s35n3p04.png";"Encoding ../test/png/official/s35n3p04.png should round-trip")]
+    #[test_case("../test/png/official/tbbn3p08.png";"Encoding ../test/png/official/tbbn3p08.png should round-trip")]
+    #[test_case("../test/png/official/tbrn2c08.png";"Encoding ../test/png/official/tbrn2c08.png should round-trip")]
+    #[test_case("../test/png/official/tbgn2c16.png";"Encoding ../test/png/official/tbgn2c16.png should round-trip")]
+    fn encoding_round_trip_test(png_path: &str) {
+        use core::decoded_image::PixelFormat;
+
+        let png_bytes = std::fs::read(png_path).unwrap();
+        let native_image = decode_bytes(
+            png_bytes.clone(),
+            PixelFormat::Native,
+        );
+
+        let mut encoder = core::encoder::PngEncoder::new(
+            native_image.width,
+            native_image.height,
+            native_image.header.color_type,
+            native_image.header.bit_depth,
+            &core::encoder::PngEncoderOptions::default(),
+        );
+        if let Some(palette) = read_chunk_data(&png_bytes, "PLTE") {
+            encoder.set_palette(palette);
+        }
+        if let Some(transparency) = read_chunk_data(&png_bytes, "tRNS") {
+            encoder.set_transparency(transparency);
+        }
+        let encoded_bytes = encoder.encode(&native_image.pixels).unwrap();
+
+        let encoded_native_image = decode_bytes(
+            encoded_bytes.clone(),
+            PixelFormat::Native,
+        );
+        assert!(encoded_native_image.errors.is_empty());
+        assert_eq!(
+            encoded_native_image.pixels,
+            native_image.pixels
+        );
+        assert_eq!(
+            decode_bytes(
+                encoded_bytes,
+                PixelFormat::Rgba16BigEndian
+            )
+            .pixels,
+            decode_bytes(
+                png_bytes,
+                PixelFormat::Rgba16BigEndian
+            )
+            .pixels
+        );
+    }
+
+    #[test]
+    fn encoding_invalid_image_test() {
+        use core::{chunk_helpers::ColorType, encoder, errors::PngDecodeErrorCode};
+
+        let encoder_options = encoder::PngEncoderOptions::default();
+
+        assert!(matches!(
+            encoder::PngEncoder::new(
+                2,
+                2,
+                ColorType::Truecolor,
+                8,
+                &encoder_options
+            )
+            .encode(&[0; 11]),
+            Err(PngDecodeErrorCode::_41(
+                12, 11
+            ))
+        ));
+        assert!(matches!(
+            encoder::PngEncoder::new(
+                0,
+                2,
+                ColorType::Greyscale,
+                8,
+                &encoder_options
+            )
+            .encode(&[]),
+            Err(PngDecodeErrorCode::_42(0, 2))
+        ));
+        assert!(matches!(
+            encoder::PngEncoder::new(
+                2,
+                2,
+                ColorType::IndexedColor,
+                16,
+                &encoder_options
+            )
+            .encode(&[0; 8]),
+            Err(PngDecodeErrorCode::_22(
+                ColorType::IndexedColor,
+                16
+            ))
+        ));
+        assert!(matches!(
+            encoder::PngEncoder::new(
+                2,
+                2,
+                ColorType::IndexedColor,
+                8,
+                &encoder_options
+            )
+            .encode(&[0; 4]),
+            Err(PngDecodeErrorCode::_16(
+                ColorType::IndexedColor
+            ))
+        ));
+
+        let mut indexed_encoder = encoder::PngEncoder::new(
+            2,
+            2,
+            ColorType::IndexedColor,
+            1,
+            &encoder_options,
+        );
+        indexed_encoder.set_palette(vec![0; 9]);
+        assert!(matches!(
+            indexed_encoder.encode(&[0; 2]),
+            Err(PngDecodeErrorCode::_43(3, 2))
+        ));
+        indexed_encoder.set_palette(vec![0; 6]);
+        indexed_encoder.set_transparency(vec![0; 3]);
+        assert!(matches!(
+            indexed_encoder.encode(&[0; 2]),
+            Err(PngDecodeErrorCode::_44(3, 2))
+        ));
+        indexed_encoder.set_transparency(vec![0]);
+        assert!(indexed_encoder.encode(&[0b0100_0000, 0b1000_0000]).is_ok());
+
+        let mut greyscale_alpha_encoder = encoder::PngEncoder::new(
+            1,
+            1,
+            ColorType::GreyscaleAlpha,
+            8,
+            &encoder_options,
+        );
+        greyscale_alpha_encoder.set_transparency(vec![0, 0]);
+        assert!(matches!(
+            greyscale_alpha_encoder.encode(&[0; 2]),
+            Err(PngDecodeErrorCode::_20)
+        ));
+    }
+
+    fn encode_with_filter_strategy(
+        png_path: &str,
+        filter_strategy: core::encoder::FilterStrategy,
+    ) -> (Vec<u8>, Vec<u8>) {
+        let png_bytes = std::fs::read(png_path).unwrap();
+        let native_image = decode_bytes(
+            png_bytes.clone(),
+            core::decoded_image::PixelFormat::Native,
+        );
+        let encoder_options = core::encoder::PngEncoderOptions {
+            filter_strategy,
+            ..Default::default()
+        };
+        let mut encoder = core::encoder::PngEncoder::new(
+            native_image.width,
+            native_image.height,
+            native_image.header.color_type,
+            native_image.header.bit_depth,
+            &encoder_options,
+        );
+        if let Some(palette) = read_chunk_data(&png_bytes, "PLTE") {
+            encoder.set_palette(palette);
+        }
+
+        (
+            encoder.encode(&native_image.pixels).unwrap(),
+            native_image.pixels,
+        )
+    }
+
+    #[test_case(core::encoder::FilterStrategy::None;"Encoding with FilterStrategy::None should round-trip")]
+    #[test_case(core::encoder::FilterStrategy::Sub;"Encoding with FilterStrategy::Sub should round-trip")]
+    #[test_case(core::encoder::FilterStrategy::Up;"Encoding with FilterStrategy::Up should round-trip")]
+    #[test_case(core::encoder::FilterStrategy::Average;"Encoding with FilterStrategy::Average should round-trip")]
+    #[test_case(core::encoder::FilterStrategy::Paeth;"Encoding with FilterStrategy::Paeth should round-trip")]
+    #[test_case(core::encoder::FilterStrategy::MinimumSumOfAbsoluteDifferences;"Encoding with FilterStrategy::MinimumSumOfAbsoluteDifferences should round-trip")]
+    #[test_case(core::encoder::FilterStrategy::Entropy;"Encoding with FilterStrategy::Entropy should round-trip")]
+    #[test_case(core::encoder::FilterStrategy::BruteForce;"Encoding with FilterStrategy::BruteForce should round-trip")]
+    fn filter_strategy_round_trip_test(filter_strategy: core::encoder::FilterStrategy) {
+        for png_path in [
+            "../test/png/official/basn0g02.png",
+            "../test/png/official/basn2c08.png",
+            "../test/png/official/basn3p04.png",
+            "../test/png/official/basn6a16.png",
+            "../test/png/official/s03n3p01.png",
+            "../test/png/samples/3.png",
+        ] {
+            let (encoded_bytes, native_pixels) =
+                encode_with_filter_strategy(png_path, filter_strategy);
+            assert_eq!(
+                decode_bytes(
+                    encoded_bytes,
+                    core::decoded_image::PixelFormat::Native
+                )
+                .pixels,
+                native_pixels
+            );
+        }
+    }
+
+    #[test]
+    fn filter_strategy_size_test() {
+        use core::encoder::FilterStrategy;
+
+        // a smooth gradient compresses much better once it is filtered
+        let png_path = "../test/png/official/basn2c08.png";
+        let unfiltered_length = encode_with_filter_strategy(png_path, FilterStrategy::None)
+            .0
+            .len();
+        for filter_strategy in [
+            FilterStrategy::MinimumSumOfAbsoluteDifferences,
+            FilterStrategy::Entropy,
+            FilterStrategy::BruteForce,
+        ] {
+            assert!(
+                encode_with_filter_strategy(png_path, filter_strategy)
+                    .0
+                    .len()
+                    * 2
+                    < unfiltered_length
+            );
+        }
+
+        // palette images are left unfiltered by default
+        let png_path = "../test/png/samples/3.png";
+        assert_eq!(
+            encode_with_filter_strategy(
+                png_path,
+                FilterStrategy::MinimumSumOfAbsoluteDifferences
+            )
+            .0,
+            encode_with_filter_strategy(png_path, FilterStrategy::None).0
+        );
+    }
+
+    #[test_case("../test/png/official/basn0g01.png";"Interlaced encoding of ../test/png/official/basn0g01.png should round-trip")]
+    #[test_case("../test/png/official/basn0g04.png";"Interlaced encoding of ../test/png/official/basn0g04.png should round-trip")]
+    #[test_case("../test/png/official/basn0g16.png";"Interlaced encoding of ../test/png/official/basn0g16.png should round-trip")]
+    #[test_case("../test/png/official/basn2c08.png";"Interlaced encoding of ../test/png/official/basn2c08.png should round-trip")]
+    #[test_case("../test/png/official/basn4a08.png";"Interlaced encoding of ../test/png/official/basn4a08.png should round-trip")]
+    #[test_case("../test/png/official/basn6a16.png";"Interlaced encoding of ../test/png/official/basn6a16.png should round-trip")]
+    #[test_case("../test/png/official/s01n3p01.png";"Interlaced encoding of ../test/png/official/s01n3p01.png should round-trip")]
+    #[test_case("../test/png/official/s02n3p01.png";"Interlaced encoding of ../test/png/official/s02n3p01.png should round-trip")]
+    #[test_case("../test/png/official/s05n3p02.png";"Interlaced encoding of ../test/png/official/s05n3p02.png should round-trip")]
+    #[test_case("../test/png/official/s07n3p02.png";"Interlaced encoding of ../test/png/official/s07n3p02.png should round-trip")]
+    #[test_case("../test/png/official/s09n3p02.png";"Interlaced encoding of ../test/png/official/s09n3p02.png should round-trip")]
+    #[test_case("../test/png/official/s35n3p04.png";"Interlaced encoding of ../test/png/official/s35n3p04.png should round-trip")]
+    #[test_case("../test/png/official/s39n3p04.png";"Interlaced encoding of ../test/png/official/s39n3p04.png should round-trip")]
+    fn interlaced_encoding_round_trip_test(png_path: &str) {
+        use core::decoded_image::PixelFormat;
+
+        let png_bytes = std::fs::read(png_path).unwrap();
+        let native_image = decode_bytes(
+            png_bytes.clone(),
+            PixelFormat::Native,
+        );
+        let encoder_options = core::encoder::PngEncoderOptions {
+            interlace: true,
+            ..Default::default()
+        };
+        let mut encoder = core::encoder::PngEncoder::new(
+            native_image.width,
+            native_image.height,
+            native_image.header.color_type,
+            native_image.header.bit_depth,
+            &encoder_options,
+        );
+        if let Some(palette) = read_chunk_data(&png_bytes, "PLTE") {
+            encoder.set_palette(palette);
+        }
+        let encoded_bytes = encoder.encode(&native_image.pixels).unwrap();
+
+        let encoded_native_image = decode_bytes(
+            encoded_bytes.clone(),
+            PixelFormat::Native,
+        );
+        assert!(matches!(
+            encoded_native_image.header.interlace_method,
+            core::chunk_helpers::InterlaceMethod::Adam7
+        ));
+        assert_eq!(
+            encoded_native_image.pixels,
+            native_image.pixels
+        );
+        // checked against another decoder as well
+        assert_eq!(
+            image::load_from_memory(&encoded_bytes)
+                .unwrap()
+                .to_rgba16()
+                .as_raw(),
+            image::load_from_memory(&png_bytes)
+                .unwrap()
+                .to_rgba16()
+                .as_raw()
+        );
+    }
+
+    fn encode_rgba8_reduced(width: u32, height: u32, rgba8_pixels: &[u8]) -> Vec<u8> {
+        let encoder_options = core::encoder::PngEncoderOptions {
+            reduce: true,
+            ..Default::default()
+        };
+        core::encoder::PngEncoder::new(
+            width,
+            height,
+            core::chunk_helpers::ColorType::TruecolorAlpha,
+            8,
+            &encoder_options,
+        )
+        .encode(rgba8_pixels)
+        .unwrap()
+    }
+
+    #[test]
+    fn color_reduction_test() {
+        use core::{chunk_helpers::ColorType, decoded_image::PixelFormat};
+
+        let width = 16;
+        let height = 16;
+        let pixel_count = (width * height) as usize;
+        // (rgba8 pixels, expected color type, expected bit depth)
+        let cases: Vec<(Vec<u8>, ColorType, u8)> = vec![
+            // black and white
+            (
+                (0..pixel_count)
+                    .flat_map(|i| {
+                        if i % 3 == 0 {
+                            [255, 255, 255, 255]
+                        } else {
+                            [0, 0, 0, 255]
+                        }
+                    })
+                    .collect(),
+                ColorType::Greyscale,
+                1,
+            ),
+            // multiples of 85
+            (
+                (0..pixel_count)
+                    .flat_map(|i| {
+                        let v = (i % 4) as u8 * 85;
+                        [v, v, v, 255]
+                    })
+                    .collect(),
+                ColorType::Greyscale,
+                2,
+            ),
+            // multiples of 17
+            (
+                (0..pixel_count)
+                    .flat_map(|i| {
+                        let v = (i % 16) as u8 * 17;
+                        [v, v, v, 255]
+                    })
+                    .collect(),
+                ColorType::Greyscale,
+                4,
+            ),
+            // every grey value
+            (
+                (0..pixel_count)
+                    .flat_map(|i| {
+                        let v = i as u8;
+                        [v, v, v, 255]
+                    })
+                    .collect(),
+                ColorType::Greyscale,
+                8,
+            ),
+            // grey with a single fully transparent color
+            (
+                (0..pixel_count)
+                    .flat_map(|i| {
+                        let v = i as u8;
+                        if v == 7 {
+                            [v, v, v, 0]
+                        } else {
+                            [v, v, v, 255]
+                        }
+                    })
+                    .collect(),
+                ColorType::Greyscale,
+                8,
+            ),
+            // grey with partial transparency
+            (
+                (0..pixel_count)
+                    .flat_map(|i| {
+                        let v = i as u8;
+                        [v, v, v, v]
+                    })
+                    .collect(),
+                ColorType::GreyscaleAlpha,
+                8,
+            ),
+            // 3 colors, one of them semi-transparent
+            (
+                (0..pixel_count)
+                    .flat_map(|i| [[255, 0, 0, 255], [0, 255, 0, 128], [0, 0, 255, 255]][i % 3])
+                    .collect(),
+                ColorType::IndexedColor,
+                2,
+            ),
+            // 16 colors
+            (
+                (0..pixel_count)
+                    .flat_map(|i| [(i % 16) as u8 * 16, 0, 100, 255])
+                    .collect(),
+                ColorType::IndexedColor,
+                4,
+            ),
+            // more than 256 opaque colors
+            (
+                (0..pixel_count)
+                    .flat_map(|i| [i as u8, (i / 2) as u8, (i / 3) as u8, 255])
+                    .collect(),
+                ColorType::Truecolor,
+                8,
+            ),
+            // more than 256 colors, with all kinds of alpha
+            (
+                (0..pixel_count)
+                    .flat_map(|i| [i as u8, (i / 2) as u8, (i / 3) as u8, (i * 7) as u8])
+                    .collect(),
+                ColorType::TruecolorAlpha,
+                8,
+            ),
+        ];
+
+        for (rgba8_pixels, expected_color_type, expected_bit_depth) in cases {
+            let encoded_bytes = encode_rgba8_reduced(width, height, &rgba8_pixels);
+            let decoded_image = decode_bytes(
+                encoded_bytes,
+                PixelFormat::Rgba8,
+            );
+
+            assert_eq!(
+                decoded_image.header.color_type,
+                expected_color_type
+            );
+            assert_eq!(
+                decoded_image.header.bit_depth,
+                expected_bit_depth
+            );
+            assert_eq!(
+                decoded_image.pixels,
+                rgba8_pixels
+            );
+        }
+    }
+
+    #[test_case("../test/png/official/basn0g01.png";"Reducing ../test/png/official/basn0g01.png should keep the pixels")]
+    #[test_case("../test/png/official/basn2c08.png";"Reducing ../test/png/official/basn2c08.png should keep the pixels")]
+    #[test_case("../test/png/official/basn3p02.png";"Reducing ../test/png/official/basn3p02.png should keep the pixels")]
+    #[test_case("../test/png/official/basn4a08.png";"Reducing ../test/png/official/basn4a08.png should keep the pixels")]
+    #[test_case("../test/png/official/basn6a08.png";"Reducing ../test/png/official/basn6a08.png should keep the pixels")]
+    #[test_case("../test/png/official/tbbn3p08.png";"Reducing ../test/png/official/tbbn3p08.png should keep the pixels")]
+    #[test_case("../test/png/official/tbrn2c08.png";"Reducing ../test/png/official/tbrn2c08.png should keep the pixels")]
+    #[test_case("../test/png/official/tp0n2c08.png";"Reducing ../test/png/official/tp0n2c08.png should keep the pixels")]
+    #[test_case("../test/png/samples/3.png";"Reducing ../test/png/samples/3.png should keep the pixels")]
+    fn color_reduction_round_trip_test(png_path: &str) {
+        use core::decoded_image::PixelFormat;
+
+        let png_bytes = std::fs::read(png_path).unwrap();
+        let rgba8_image = decode_bytes(png_bytes, PixelFormat::Rgba8);
+        let unreduced_bytes = core::encoder::PngEncoder::new(
+            rgba8_image.width,
+            rgba8_image.height,
+            core::chunk_helpers::ColorType::TruecolorAlpha,
+            8,
+            &Default::default(),
+        )
+        .encode(&rgba8_image.pixels)
+        .unwrap();
+        let reduced_bytes = encode_rgba8_reduced(
+            rgba8_image.width,
+            rgba8_image.height,
+            &rgba8_image.pixels,
+        );
+
+        assert!(reduced_bytes.len() <= unreduced_bytes.len());
+        assert_eq!(
+            decode_bytes(
+                reduced_bytes,
+                PixelFormat::Rgba8
+            )
+            .pixels,
+            rgba8_image.pixels
+        );
+    }
+
+    #[test]
+    fn color_dependent_chunks_reduction_test() {
+        use core::{
+            background::BackgroundColor,
+            chunk_helpers::ColorType,
+            decoded_image::PixelFormat,
+            encoder::{ChunkPosition, PngEncoder, PngEncoderOptions},
+            significant_bits::SignificantBits,
+        };
+
+        let encoder_options = PngEncoderOptions {
+            reduce: true,
+            ..Default::default()
+        };
+        // 2 colors, which would be reduced to a palette of 1 bit
+        let rgb8_pixels: Vec<u8> = (0..256)
+            .flat_map(|i| [[255, 0, 0], [0, 0, 255]][i % 2])
+            .collect();
+        let mut encoder = PngEncoder::new(
+            16,
+            16,
+            ColorType::Truecolor,
+            8,
+            &encoder_options,
+        );
+        let decoded_image = decode_bytes(
+            encoder.encode(&rgb8_pixels).unwrap(),
+            PixelFormat::Rgb8,
+        );
+        assert_eq!(
+            decoded_image.header.color_type,
+            ColorType::IndexedColor
+        );
+
+        // bKGD and sBIT chunks are only valid for the color type they have been written for, so the image is kept as it is
+        encoder.add_ancillary_chunk(
+            "bKGD",
+            vec![0, 10, 0, 20, 0, 30],
+            ChunkPosition::BeforeImageData,
+        );
+        encoder.add_ancillary_chunk(
+            "sBIT",
+            vec![5, 6, 5],
+            ChunkPosition::BeforePalette,
+        );
+        let decoded_image = decode_bytes(
+            encoder.encode(&rgb8_pixels).unwrap(),
+            PixelFormat::Rgb8,
+        );
+        assert!(decoded_image.errors.is_empty());
+        assert_eq!(
+            decoded_image.header.color_type,
+            ColorType::Truecolor
+        );
+        assert_eq!(
+            decoded_image.pixels,
+            rgb8_pixels
+        );
+        assert_eq!(
+            decoded_image.metadata.background_color,
+            Some(BackgroundColor::Rgb(
+                10, 20, 30
+            ))
+        );
+        assert_eq!(
+            decoded_image.metadata.significant_bits,
+            Some(SignificantBits::Rgb(5, 6, 5))
+        );
+    }
+
+    fn encode_quantized(
+        width: u32,
+        height: u32,
+        rgba8_pixels: &[u8],
+        quantizer_options: &core::quantizer::QuantizerOptions,
+    ) -> Vec<u8> {
+        let quantized_image = core::quantizer::quantize(
+            width,
+            height,
+            rgba8_pixels,
+            quantizer_options,
+        )
+        .unwrap();
+        let mut encoder = core::encoder::PngEncoder::new(
+            width,
+            height,
+            core::chunk_helpers::ColorType::IndexedColor,
+            quantized_image.bit_depth,
+            &Default::default(),
+        );
+        encoder.set_palette(quantized_image.palette);
+        if let Some(transparency) = quantized_image.transparency {
+            encoder.set_transparency(transparency);
+        }
+        encoder.encode(&quantized_image.pixels).unwrap()
+    }
+
+    fn mean_absolute_difference(pixels: &[u8], other_pixels: &[u8]) -> f64 {
+        let sum: u64 = pixels
+            .iter()
+            .zip(other_pixels)
+            .map(|(a, b)| a.abs_diff(*b) as u64)
+            .sum();
+        sum as f64 / pixels.len() as f64
+    }
+
+    #[test_case("../test/png/official/basn2c08.png", 16, 10.0;"Quantizing ../test/png/official/basn2c08.png to 16 colors should stay close to the original")]
+    #[test_case("../test/png/official/basn6a08.png", 4, 55.0;"Quantizing ../test/png/official/basn6a08.png to 4 colors should stay close to the original")]
+    #[test_case("../test/png/official/basn6a08.png", 64, 9.0;"Quantizing ../test/png/official/basn6a08.png to 64 colors should stay close to the original")]
+    #[test_case("../test/png/official/basn2c16.png", 2, 35.0;"Quantizing ../test/png/official/basn2c16.png to 2 colors should stay close to the original")]
+    #[test_case("../test/png/official/basn4a16.png", 256, 0.0;"Quantizing ../test/png/official/basn4a16.png to 256 colors should stay close to the original")]
+    #[test_case("../test/png/samples/3.png", 32, 5.0;"Quantizing ../test/png/samples/3.png to 32 colors should stay close to the original")]
+    fn quantization_test(png_path: &str, max_colors: u16, max_mean_absolute_difference: f64) {
+        use core::{chunk_helpers::ColorType, decoded_image::PixelFormat};
+
+        let png_bytes = std::fs::read(png_path).unwrap();
+        let rgba8_image = decode_bytes(png_bytes, PixelFormat::Rgba8);
+        for dithering in [false, true] {
+            let quantizer_options = core::quantizer::QuantizerOptions {
+                max_colors,
+                dithering,
+            };
+            let encoded_bytes = encode_quantized(
+                rgba8_image.width,
+                rgba8_image.height,
+                &rgba8_image.pixels,
+                &quantizer_options,
+            );
+
+            let palette = read_chunk_data(&encoded_bytes, "PLTE").unwrap();
+            assert!(palette.len() / 3 <= max_colors as usize);
+            let decoded_image = decode_bytes(
+                encoded_bytes,
+                PixelFormat::Rgba8,
+            );
+            assert_eq!(
+                decoded_image.header.color_type,
+                ColorType::IndexedColor
+            );
+
+            // dithered pixels are further off one by one, so only the closest colors are checked
+            if !dithering {
+                let difference = mean_absolute_difference(
+                    &decoded_image.pixels,
+                    &rgba8_image.pixels,
+                );
+                assert!(
+                    difference <= max_mean_absolute_difference,
+                    "mean absolute difference: {}",
+                    difference
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quantization_dithering_test() {
+        use core::decoded_image::PixelFormat;
+
+        // horizontal grey gradient, quantized to black and white
+        let width = 64;
+        let height = 64;
+        let rgba8_pixels: Vec<u8> = (0..width * height)
+            .flat_map(|index| {
+                let grey = ((index % width) * 255 / (width - 1)) as u8;
+                [grey, grey, grey, 255]
+            })
+            .collect();
+
+        let mut column_differences = vec![];
+        for dithering in [false, true] {
+            let quantizer_options = core::quantizer::QuantizerOptions {
+                max_colors: 2,
+                dithering,
+            };
+            let encoded_bytes = encode_quantized(
+                width,
+                height,
+                &rgba8_pixels,
+                &quantizer_options,
+            );
+            let decoded_image = decode_bytes(
+                encoded_bytes,
+                PixelFormat::Rgba8,
+            );
+
+            // how far the average of each column is from the grey of the gradient. Median cut puts the two colors
+            // at about a quarter and three quarters of the gradient, so only the columns between them can be matched
+            let columns = width as usize / 4..width as usize * 3 / 4;
+            let column_count = columns.len() as f64;
+            let column_difference: f64 = columns
+                .map(|column| {
+                    let column_sum: u32 = (0..height as usize)
+                        .map(|row| decoded_image.pixels[(row * width as usize + column) * 4] as u32)
+                        .sum();
+                    (column_sum as f64 / height as f64 - rgba8_pixels[column * 4] as f64).abs()
+                })
+                .sum::<f64>()
+                / column_count;
+            column_differences.push(column_difference);
+        }
+
+        // without dithering, every column is a single color. With it, the mix of black and white follows the gradient
+        assert!(
+            column_differences[1] * 4.0 < column_differences[0],
+            "column differences: {:?}",
+            column_differences
+        );
+    }
+
+    #[test_case("../test/png/official/basn3p08.png";"Quantizing ../test/png/official/basn3p08.png should keep every pixel")]
+    #[test_case("../test/png/official/tbbn3p08.png";"Quantizing ../test/png/official/tbbn3p08.png should keep every pixel")]
+    #[test_case("../test/png/official/basn0g04.png";"Quantizing ../test/png/official/basn0g04.png should keep every pixel")]
+    fn quantization_lossless_test(png_path: &str) {
+        use core::decoded_image::PixelFormat;
+
+        let png_bytes = std::fs::read(png_path).unwrap();
+        let rgba8_image = decode_bytes(png_bytes, PixelFormat::Rgba8);
+        for dithering in [false, true] {
+            let quantizer_options = core::quantizer::QuantizerOptions {
+                dithering,
+                ..Default::default()
+            };
+            let encoded_bytes = encode_quantized(
+                rgba8_image.width,
+                rgba8_image.height,
+                &rgba8_image.pixels,
+                &quantizer_options,
+            );
+            assert_eq!(
+                decode_bytes(
+                    encoded_bytes,
+                    PixelFormat::Rgba8
+                )
+                .pixels,
+                rgba8_image.pixels
+            );
+        }
+    }
+
+    #[test]
+    fn quantization_invalid_options_test() {
+        let rgba8_pixels = vec![0u8; 4 * 4 * 4];
+        for max_colors in [0, 257] {
+            let quantizer_options = core::quantizer::QuantizerOptions::new(max_colors);
+            assert!(matches!(
+                core::quantizer::quantize(
+                    4,
+                    4,
+                    &rgba8_pixels,
+                    &quantizer_options
+                ),
+                Err(core::errors::PngDecodeErrorCode::_43(_, 256))
+            ));
+        }
+        assert!(matches!(
+            core::quantizer::quantize(
+                4,
+                5,
+                &rgba8_pixels,
+                &Default::default()
+            ),
+            Err(core::errors::PngDecodeErrorCode::_41(80, 64))
+        ));
+    }
+
+    /// Types of every chunk in `png_bytes`, in the order they appear
+    fn read_chunk_types(png_bytes: &[u8]) -> Vec<String> {
+        let mut chunk_types = vec![];
+        let mut chunk_start = 8;
+        while chunk_start + 8 <= png_bytes.len() {
+            let chunk_data_length =
+                u32::from_be_bytes(png_bytes[chunk_start..chunk_start + 4].try_into().unwrap())
+                    as usize;
+            chunk_types.push(
+                String::from_utf8_lossy(&png_bytes[chunk_start + 4..chunk_start + 8]).to_string(),
+            );
+            chunk_start += 8 + chunk_data_length + 4;
+        }
+
+        chunk_types
+    }
+
+    fn read_ancillary_chunk_types(png_bytes: &[u8]) -> Vec<String> {
+        read_chunk_types(png_bytes)
+            .into_iter()
+            .filter(|chunk_type| chunk_type.as_bytes()[0].is_ascii_lowercase())
+            .collect()
+    }
+
+    #[test_case("../test/png/official/basn0g01.png";"Optimizing ../test/png/official/basn0g01.png should keep the pixels")]
+    #[test_case("../test/png/official/basn2c08.png";"Optimizing ../test/png/official/basn2c08.png should keep the pixels")]
+    #[test_case("../test/png/official/basn3p02.png";"Optimizing ../test/png/official/basn3p02.png should keep the pixels")]
+    #[test_case("../test/png/official/basn4a08.png";"Optimizing ../test/png/official/basn4a08.png should keep the pixels")]
+    #[test_case("../test/png/official/basn6a16.png";"Optimizing ../test/png/official/basn6a16.png should keep the pixels")]
+    #[test_case("../test/png/official/basi0g08.png";"Optimizing ../test/png/official/basi0g08.png should keep the pixels")]
+    #[test_case("../test/png/official/tbbn3p08.png";"Optimizing ../test/png/official/tbbn3p08.png should keep the pixels")]
+    #[test_case("../test/png/official/tbrn2c08.png";"Optimizing ../test/png/official/tbrn2c08.png should keep the pixels")]
+    #[test_case("../test/png/official/bgai4a08.png";"Optimizing ../test/png/official/bgai4a08.png should keep the pixels")]
+    #[test_case("../test/png/official/ccwn3p08.png";"Optimizing ../test/png/official/ccwn3p08.png should keep the pixels")]
+    #[test_case("../test/png/official/ch1n3p04.png";"Optimizing ../test/png/official/ch1n3p04.png should keep the pixels")]
+    #[test_case("../test/png/official/cs3n2c16.png";"Optimizing ../test/png/official/cs3n2c16.png should keep the pixels")]
+    #[test_case("../test/png/official/ctzn0g04.png";"Optimizing ../test/png/official/ctzn0g04.png should keep the pixels")]
+    #[test_case("../test/png/official/ps1n2c16.png";"Optimizing ../test/png/official/ps1n2c16.png should keep the pixels")]
+    #[test_case("../test/png/official/exif2c08.png";"Optimizing ../test/png/official/exif2c08.png should keep the pixels")]
+    fn optimizer_test(png_path: &str) {
+        use core::{decoded_image::PixelFormat, optimizer};
+
+        let png_bytes = std::fs::read(png_path).unwrap();
+        let original_image = decode_bytes(
+            png_bytes.clone(),
+            PixelFormat::Rgba16BigEndian,
+        );
+
+        let optimized_image = optimizer::optimize(
+            &png_bytes,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            optimized_image.original_length,
+            png_bytes.len()
+        );
+        assert!(optimized_image.png_bytes.len() <= png_bytes.len());
+        assert_eq!(
+            optimized_image.bytes_saved(),
+            png_bytes.len() - optimized_image.png_bytes.len()
+        );
+        assert_eq!(
+            read_ancillary_chunk_types(&optimized_image.png_bytes),
+            read_ancillary_chunk_types(&png_bytes)
+        );
+        let decoded_image = decode_bytes(
+            optimized_image.png_bytes,
+            PixelFormat::Rgba16BigEndian,
+        );
+        assert_eq!(
+            format!(
+                "{:?}",
+                decoded_image.header.interlace_method
+            ),
+            format!(
+                "{:?}",
+                original_image.header.interlace_method
+            )
+        );
+        assert_eq!(
+            decoded_image.pixels,
+            original_image.pixels
+        );
+
+        let strip_options = optimizer::OptimizerOptions {
+            strip_ancillary_chunks: true,
+        };
+        let stripped_image = optimizer::optimize(&png_bytes, &strip_options).unwrap();
+        assert!(stripped_image.png_bytes.len() <= optimized_image.original_length);
+        assert!(
+            read_ancillary_chunk_types(&stripped_image.png_bytes)
+                .iter()
+                .all(|chunk_type| chunk_type == "tRNS")
+        );
+        assert_eq!(
+            decode_bytes(
+                stripped_image.png_bytes,
+                PixelFormat::Rgba16BigEndian
+            )
+            .pixels,
+            original_image.pixels
+        );
+    }
+
+    #[test]
+    fn optimizer_invalid_image_test() {
+        let png_bytes = std::fs::read("../test/png/official/xcrn0g04.png").unwrap();
+        assert!(core::optimizer::optimize(
+            &png_bytes,
+            &Default::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn optimizer_icc_profile_test() {
+        use core::{
+            chunk_helpers::ColorType,
+            color_chunks::ColorConversion,
+            decoded_image::PixelFormat,
+            decoder::PngDecoderOptions,
+            encoder::{ChunkPosition, PngEncoder},
+            optimizer,
+        };
+
+        // only grey pixels, with an RGB profile that doesn't fit a greyscale image
+        let rgb8_pixels: Vec<u8> = (0..=255).flat_map(|i| [i, i, i]).collect();
+        let mut encoder = PngEncoder::new(
+            16,
+            16,
+            ColorType::Truecolor,
+            8,
+            &Default::default(),
+        );
+        let iccp_chunk = [
+            &b"Profile\0\0"[..],
+            &compress_stored(&display_p3_icc_profile()),
+        ]
+        .concat();
+        encoder.add_ancillary_chunk(
+            "iCCP",
+            iccp_chunk,
+            ChunkPosition::BeforePalette,
+        );
+        let png_bytes = encoder.encode(&rgb8_pixels).unwrap();
+
+        let optimized_image = optimizer::optimize(
+            &png_bytes,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            read_ancillary_chunk_types(&optimized_image.png_bytes),
+            ["iCCP"]
+        );
+        let decoded_image = decode_with_limits(
+            optimized_image.png_bytes,
+            PngDecoderOptions {
+                fail_fast: true,
+                output_format: PixelFormat::Rgb8,
+                color_conversion: ColorConversion::Srgb,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(decoded_image.errors.is_empty());
+        assert_eq!(
+            decoded_image.header.color_type,
+            ColorType::Truecolor
+        );
+        assert_pixels_near(
+            &decoded_image.pixels,
+            &rgb8_pixels,
+        );
+    }
+
+    #[test]
+    fn encoder_ancillary_chunks_test() {
+        use core::{
+            chunk_helpers::ColorType,
+            encoder::{ChunkPosition, PngEncoder},
+            errors::PngDecodeErrorCode,
+        };
+
+        let mut encoder = PngEncoder::new(
+            2,
+            2,
+            ColorType::IndexedColor,
+            1,
+            &Default::default(),
+        );
+        encoder.set_palette(vec![0, 0, 0, 255, 255, 255]);
+        encoder.set_transparency(vec![0]);
+        encoder.add_ancillary_chunk(
+            "tEXt",
+            b"Comment\0after image data".to_vec(),
+            ChunkPosition::AfterImageData,
+        );
+        encoder.add_ancillary_chunk(
+            "gAMA",
+            45455u32.to_be_bytes().to_vec(),
+            ChunkPosition::BeforePalette,
+        );
+        encoder.add_ancillary_chunk(
+            "bKGD",
+            vec![1],
+            ChunkPosition::BeforeImageData,
+        );
+        let png_bytes = encoder.encode(&[0b0100_0000, 0b1000_0000]).unwrap();
+        assert_eq!(
+            read_chunk_types(&png_bytes),
+            vec!["IHDR", "gAMA", "PLTE", "tRNS", "bKGD", "IDAT", "tEXt", "IEND"]
+        );
+        assert_eq!(
+            read_chunk_data(&png_bytes, "bKGD"),
+            Some(vec![1])
+        );
+
+        for chunk_type in ["IDAT", "tRNS", "tEX", "t3XT"] {
+            let mut encoder = PngEncoder::new(
+                1,
+                1,
+                ColorType::Greyscale,
+                8,
+                &Default::default(),
+            );
+            encoder.add_ancillary_chunk(
+                chunk_type,
+                vec![],
+                ChunkPosition::BeforeImageData,
+            );
+            assert!(matches!(
+                encoder.encode(&[0]),
+                Err(PngDecodeErrorCode::_45(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn color_chunks_test() {
+        use core::{
+            color_chunks::{Chromaticities, ColorSpace, RenderingIntent},
+            decoded_image::PixelFormat,
+        };
+
+        let read_metadata = |png_path: &str| {
+            let decoded_image = decode_bytes(
+                std::fs::read(png_path).unwrap(),
+                PixelFormat::Rgba8,
+            );
+            assert!(decoded_image.errors.is_empty());
+            decoded_image.metadata
+        };
+
+        let metadata = read_metadata("../test/png/official/g03n0g16.png");
+        assert_eq!(metadata.gamma, Some(35000));
+        assert_eq!(
+            metadata.color_space(),
+            ColorSpace::Calibrated {
+                gamma: Some(35000),
+                chromaticities: None
+            }
+        );
+
+        let metadata = read_metadata("../test/png/official/ccwn2c08.png");
+        let chromaticities = Chromaticities {
+            white_point_x: 31270,
+            white_point_y: 32900,
+            red_x: 64000,
+            red_y: 33000,
+            green_x: 30000,
+            green_y: 60000,
+            blue_x: 15000,
+            blue_y: 6000,
+        };
+        assert_eq!(
+            metadata.chromaticities,
+            Some(chromaticities)
+        );
+        assert_eq!(
+            metadata.color_space(),
+            ColorSpace::Calibrated {
+                gamma: Some(100000),
+                chromaticities: Some(chromaticities)
+            }
+        );
+
+        // sRGB chunk takes precedence over gAMA chunk
+        let metadata = read_metadata("../test/png/samples/3.png");
+        assert_eq!(metadata.gamma, Some(45455));
+        assert_eq!(
+            metadata.color_space(),
+            ColorSpace::Srgb(RenderingIntent::Perceptual)
+        );
+
+        let metadata = read_metadata("../test/png/samples/1.png");
+        let icc_profile = metadata.icc_profile.as_ref().unwrap();
+        assert_eq!(
+            icc_profile.name,
+            "ICC Profile"
+        );
+        assert_eq!(
+            icc_profile.profile.len(),
+            4064
+        );
+        // every ICC profile has this signature at byte 36
+        assert_eq!(
+            &icc_profile.profile[36..40],
+            b"acsp"
+        );
+        assert_eq!(
+            metadata.color_space(),
+            ColorSpace::IccProfile(icc_profile)
+        );
+
+        let metadata = read_metadata("../test/png/official/basn3p08.png");
+        assert_eq!(
+            metadata.color_space(),
+            ColorSpace::Calibrated {
+                gamma: Some(100000),
+                chromaticities: None
+            }
+        );
+        let mut byte_reader = byte_reader::ByteReader::new(
+            Some("../test/png/official/cs3n2c16.png"),
+            byte_reader::ByteReaderMode::FILE,
+            None,
+        );
+        byte_reader.read_image().unwrap();
+        let probed_image = core::decoder::PngDecoder::new(
+            &mut byte_reader,
+            &Default::default(),
+        )
+        .probe_chunks()
+        .unwrap();
+        assert_eq!(
+            probed_image.metadata.gamma,
+            Some(100000)
+        );
+    }
+
+    /// Encodes a 1x1 palette image with `chunks` added to it
+    fn encode_with_ancillary_chunks(
+        chunks: &[(
+            &str,
+            Vec<u8>,
+            core::encoder::ChunkPosition,
+        )],
+    ) -> Vec<u8> {
+        let mut encoder = core::encoder::PngEncoder::new(
+            1,
+            1,
+            core::chunk_helpers::ColorType::IndexedColor,
+            8,
+            &Default::default(),
+        );
+        encoder.set_palette(vec![10, 20, 30]);
+        for (chunk_type, chunk_data, position) in chunks {
+            encoder.add_ancillary_chunk(
+                chunk_type,
+                chunk_data.clone(),
+                *position,
+            );
+        }
+        encoder.encode(&[0]).unwrap()
+    }
+
+    /// Decodes an image with the default options, which ignore invalid ancillary chunks,
+    /// and checks that exactly one error has been reported for them
+    fn decode_with_chunk_error(
+        png_bytes: Vec<u8>,
+        is_expected_code: fn(&core::errors::PngDecodeErrorCode) -> bool,
+    ) -> core::decoded_image::DecodedImage {
+        let decoded_image = decode_with_limits(png_bytes, Default::default()).unwrap();
+        assert!(
+            matches!(&decoded_image.errors[..], [error] if is_expected_code(&error.code)),
+            "{:?}",
+            decoded_image.errors
+        );
+        decoded_image
+    }
+
+    fn is_wrong_length(code: &core::errors::PngDecodeErrorCode) -> bool {
+        matches!(
+            code,
+            core::errors::PngDecodeErrorCode::_47(_, _, _)
+        )
+    }
+
+    fn is_misplaced(code: &core::errors::PngDecodeErrorCode) -> bool {
+        matches!(
+            code,
+            core::errors::PngDecodeErrorCode::_48(_, _)
+        )
+    }
+
+    fn is_duplicate(code: &core::errors::PngDecodeErrorCode) -> bool {
+        matches!(
+            code,
+            core::errors::PngDecodeErrorCode::_49(_)
+        )
+    }
+
+    fn is_invalid_data(code: &core::errors::PngDecodeErrorCode) -> bool {
+        matches!(
+            code,
+            core::errors::PngDecodeErrorCode::_51(_, _)
+        )
+    }
+
+    /// zlib stream with a single stored (uncompressed) deflate block
+    fn compress_stored(data: &[u8]) -> Vec<u8> {
+        let length = data.len() as u16;
+        let (mut a, mut b) = (1u32, 0u32);
+        for byte in data {
+            a = (a + *byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        [
+            &[0x78, 0x01, 0x01][..],
+            &length.to_le_bytes(),
+            &(!length).to_le_bytes(),
+            data,
+            &((b << 16) | a).to_be_bytes(),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn invalid_color_chunks_test() {
+        use core::{
+            color_chunks::{ColorSpace, RenderingIntent},
+            decoded_image::PixelFormat,
+            encoder::ChunkPosition::{AfterImageData, BeforeImageData, BeforePalette},
+            errors::PngDecodeErrorCode,
+        };
+
+        let iccp_chunk = |name: &[u8], compression_method: u8, profile: &[u8]| {
+            [name, &[0, compression_method], &compress_stored(profile)].concat()
+        };
+        let gamma = |gamma: u32| gamma.to_be_bytes().to_vec();
+        let cases: Vec<(
+            _,
+            fn(&PngDecodeErrorCode) -> bool,
+        )> = vec![
+            (
+                vec![(
+                    "gAMA",
+                    vec![0, 0, 1],
+                    BeforePalette,
+                )],
+                is_wrong_length,
+            ),
+            (
+                vec![(
+                    "gAMA",
+                    gamma(0),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "gAMA",
+                    gamma(45455),
+                    BeforeImageData,
+                )],
+                is_misplaced,
+            ),
+            (
+                vec![(
+                    "gAMA",
+                    gamma(45455),
+                    AfterImageData,
+                )],
+                is_misplaced,
+            ),
+            (
+                vec![(
+                    "cHRM",
+                    vec![0; 31],
+                    BeforePalette,
+                )],
+                is_wrong_length,
+            ),
+            (
+                vec![("sRGB", vec![4], BeforePalette)],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sRGB",
+                    vec![0, 0],
+                    BeforePalette,
+                )],
+                is_wrong_length,
+            ),
+            (
+                vec![(
+                    "iCCP",
+                    iccp_chunk(b"Profile", 1, b"profile"),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "iCCP",
+                    iccp_chunk(b" Profile", 0, b"profile"),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "iCCP",
+                    iccp_chunk(b"", 0, b"profile"),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "iCCP",
+                    b"Profile\0\0not zlib".to_vec(),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "iCCP",
+                    b"Profile".to_vec(),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+        ];
+        for (chunks, is_expected_code) in cases {
+            let png_bytes = encode_with_ancillary_chunks(&chunks);
+            let decoded_image = decode_with_chunk_error(
+                png_bytes.clone(),
+                is_expected_code,
+            );
+            // the invalid chunk is ignored
+            assert_eq!(
+                decoded_image.metadata.color_space(),
+                ColorSpace::Unknown
+            );
+
+            let fail_fast_options = core::decoder::PngDecoderOptions {
+                fail_fast: true,
+                ..Default::default()
+            };
+            assert!(decode_with_limits(png_bytes, fail_fast_options).is_err());
+        }
+
+        // only the first of repeated chunks is used
+        let png_bytes = encode_with_ancillary_chunks(&[
+            (
+                "gAMA",
+                gamma(45455),
+                BeforePalette,
+            ),
+            (
+                "gAMA",
+                gamma(100000),
+                BeforePalette,
+            ),
+        ]);
+        let decoded_image = decode_with_limits(png_bytes, Default::default()).unwrap();
+        assert!(
+            matches!(&decoded_image.errors[..], [error] if matches!(&error.code, PngDecodeErrorCode::_49(chunk_type) if chunk_type == "gAMA"))
+        );
+        assert_eq!(
+            decoded_image.metadata.gamma,
+            Some(45455)
+        );
+
+        // both sRGB and iCCP chunks are kept, and iCCP chunk takes precedence
+        let png_bytes = encode_with_ancillary_chunks(&[
+            ("sRGB", vec![1], BeforePalette),
+            (
+                "iCCP",
+                iccp_chunk(b"Latin-1 \xe9", 0, b"profile"),
+                BeforePalette,
+            ),
+            (
+                "gAMA",
+                gamma(45455),
+                BeforePalette,
+            ),
+        ]);
+        let decoded_image = decode_with_limits(
+            png_bytes.clone(),
+            Default::default(),
+        )
+        .unwrap();
+        assert!(
+            matches!(&decoded_image.errors[..], [error] if matches!(error.code, PngDecodeErrorCode::_50))
+        );
+        let icc_profile = decoded_image.metadata.icc_profile.as_ref().unwrap();
+        assert_eq!(icc_profile.name, "Latin-1 é");
+        assert_eq!(
+            icc_profile.profile,
+            b"profile"
+        );
+        assert_eq!(
+            decoded_image.metadata.srgb_rendering_intent,
+            Some(RenderingIntent::RelativeColorimetric)
+        );
+        assert_eq!(
+            decoded_image.metadata.color_space(),
+            ColorSpace::IccProfile(icc_profile)
+        );
+        // the same metadata comes from decoding without pixels, row by row, and pushing the bytes
+        let mut byte_reader = byte_reader::ByteReader::new(
+            None,
+            byte_reader::ByteReaderMode::RAW,
+            Some(png_bytes.clone()),
+        );
+        byte_reader.read_image().unwrap();
+        let probed_image = core::decoder::PngDecoder::new(
+            &mut byte_reader,
+            &Default::default(),
+        )
+        .probe_chunks()
+        .unwrap();
+        assert_eq!(
+            probed_image.metadata.icc_profile.as_ref(),
+            Some(icc_profile)
+        );
+        let mut byte_reader = byte_reader::ByteReader::new(
+            None,
+            byte_reader::ByteReaderMode::RAW,
+            Some(png_bytes.clone()),
+        );
+        byte_reader.read_image().unwrap();
+        let decoder_options = Default::default();
+        let mut row_decoder = core::row_decoder::PngRowDecoder::new(
+            &mut byte_reader,
+            &decoder_options,
+        );
+        row_decoder.read_header().unwrap();
+        assert_eq!(
+            row_decoder.get_metadata().gamma,
+            Some(45455)
+        );
+        let mut push_decoder = core::push_decoder::PngPushDecoder::new(&Default::default());
+        push_decoder.feed(&png_bytes).unwrap();
+        assert_eq!(
+            push_decoder.get_metadata().srgb_rendering_intent,
+            Some(RenderingIntent::RelativeColorimetric)
+        );
+
+        // profiles that decompress to more than the ancillary limit are ignored
+        // 1000 zero bytes, compressed
+        let compressed_profile = [
+            120, 218, 99, 96, 24, 5, 163, 96, 20, 12, 119, 0, 0, 3, 232, 0, 1,
+        ];
+        let png_bytes = encode_with_ancillary_chunks(&[(
+            "iCCP",
+            [&b"Profile\0\0"[..], &compressed_profile].concat(),
+            BeforePalette,
+        )]);
+        let decoder_options = core::decoder::PngDecoderOptions {
+            max_ancillary_bytes: 999,
+            output_format: PixelFormat::Rgba8,
+            ..Default::default()
+        };
+        let decoded_image = decode_with_limits(png_bytes, decoder_options).unwrap();
+        assert!(decoded_image.metadata.icc_profile.is_none());
+        assert!(
+            matches!(&decoded_image.errors[..], [error] if matches!(error.code, PngDecodeErrorCode::_51(_, _)))
+        );
+    }
+
+    #[test_case("0g16";"Greyscale images of the gamma test set should look the same once converted to sRGB")]
+    #[test_case("2c08";"Truecolor images of the gamma test set should look the same once converted to sRGB")]
+    #[test_case("3p04";"Palette images of the gamma test set should look the same once converted to sRGB")]
+    fn gamma_correction_test(image_kind: &str) {
+        use core::{
+            color_chunks::ColorConversion, decoded_image::PixelFormat, decoder::PngDecoderOptions,
+        };
+
+        // the same picture, encoded with a gamma of 0.35 to 2.5
+        let read_image = |gamma: &str| {
+            std::fs::read(format!(
+                "../test/png/official/g{}n{}.png",
+                gamma, image_kind
+            ))
+            .unwrap()
+        };
+        let srgb_options = PngDecoderOptions {
+            fail_fast: true,
+            output_format: PixelFormat::Rgba8,
+            color_conversion: ColorConversion::Srgb,
+            ..Default::default()
+        };
+        let unconverted_options = PngDecoderOptions {
+            color_conversion: ColorConversion::None,
+            ..srgb_options
+        };
+        let reference_image = decode_with_limits(read_image("10"), srgb_options).unwrap();
+        for gamma in ["03", "04", "05", "07", "25"] {
+            let image = decode_with_limits(
+                read_image(gamma),
+                srgb_options,
+            )
+            .unwrap();
+            let unconverted_image = decode_with_limits(
+                read_image(gamma),
+                unconverted_options,
+            )
+            .unwrap();
+            let unconverted_reference_image = decode_with_limits(
+                read_image("10"),
+                unconverted_options,
+            )
+            .unwrap();
+
+            let difference = mean_absolute_difference(
+                &image.pixels,
+                &reference_image.pixels,
+            );
+            let unconverted_difference = mean_absolute_difference(
+                &unconverted_image.pixels,
+                &unconverted_reference_image.pixels,
+            );
+            // what is left comes from the few levels each image has
+            assert!(
+                difference < 6.0,
+                "gamma {}: {}",
+                gamma,
+                difference
+            );
+            assert!(
+                difference * 2.0 < unconverted_difference,
+                "gamma {}: {} {}",
+                gamma,
+                difference,
+                unconverted_difference
+            );
+        }
+    }
+
+    #[test]
+    fn color_conversion_test() {
+        use core::{
+            chunk_helpers::ColorType,
+            color_chunks::ColorConversion,
+            decoded_image::PixelFormat,
+            decoder::PngDecoderOptions,
+            encoder::{ChunkPosition, PngEncoder},
+        };
+
+        let encode = |color_type: ColorType, pixels: &[u8], chunks: &[(&str, Vec<u8>)]| {
+            let mut encoder = PngEncoder::new(
+                1,
+                1,
+                color_type,
+                8,
+                &Default::default(),
+            );
+            for (chunk_type, chunk_data) in chunks {
+                encoder.add_ancillary_chunk(
+                    chunk_type,
+                    chunk_data.clone(),
+                    ChunkPosition::BeforePalette,
+                );
+            }
+            encoder.encode(pixels).unwrap()
+        };
+        let chrm_chunk = |values: [u32; 8]| {
+            values
+                .iter()
+                .flat_map(|value| value.to_be_bytes())
+                .collect::<Vec<u8>>()
+        };
+        let srgb_primaries = [31270, 32900, 64000, 33000, 30000, 60000, 15000, 6000];
+        let swapped_primaries = [31270, 32900, 30000, 60000, 64000, 33000, 15000, 6000];
+        let srgb_options = PngDecoderOptions {
+            fail_fast: true,
+            color_conversion: ColorConversion::Srgb,
+            ..Default::default()
+        };
+        let linear_options = PngDecoderOptions {
+            color_conversion: ColorConversion::Linear,
+            ..srgb_options
+        };
+
+        // images without any color chunk are treated as sRGB
+        let png_bytes = encode(
+            ColorType::Greyscale,
+            &[128],
+            &[],
+        );
+        let image = decode_with_limits(
+            png_bytes.clone(),
+            PngDecoderOptions {
+                output_format: PixelFormat::Rgba8,
+                ..srgb_options
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            image.pixels,
+            [128, 128, 128, 255]
+        );
+        let image = decode_with_limits(
+            png_bytes.clone(),
+            PngDecoderOptions {
+                output_format: PixelFormat::Rgba16BigEndian,
+                ..linear_options
+            },
+        )
+        .unwrap();
+        // ((128 / 255 + 0.055) / 1.055)^2.4 * 65535
+        assert_eq!(
+            &image.pixels[..2],
+            &14146u16.to_be_bytes()
+        );
+        assert_eq!(
+            &image.pixels[6..],
+            &[255, 255]
+        );
+        // native samples are never converted
+        let image = decode_with_limits(
+            png_bytes,
+            PngDecoderOptions {
+                output_format: PixelFormat::Native,
+                ..linear_options
+            },
+        )
+        .unwrap();
+        assert_eq!(image.pixels, [128]);
+
+        // gamma of 1.0, so the samples are linear already
+        let png_bytes = encode(
+            ColorType::Truecolor,
+            &[0, 128, 255],
+            &[(
+                "gAMA",
+                100000u32.to_be_bytes().to_vec(),
+            )],
+        );
+        let image = decode_with_limits(
+            png_bytes.clone(),
+            PngDecoderOptions {
+                output_format: PixelFormat::Rgb8,
+                ..linear_options
+            },
+        )
+        .unwrap();
+        assert_eq!(image.pixels, [0, 128, 255]);
+        let image = decode_with_limits(
+            png_bytes,
+            PngDecoderOptions {
+                output_format: PixelFormat::Rgb8,
+                ..srgb_options
+            },
+        )
+        .unwrap();
+        assert_eq!(image.pixels, [0, 188, 255]);
+
+        // sRGB chunk takes precedence over gAMA and cHRM chunks
+        let png_bytes = encode(
+            ColorType::Truecolor,
+            &[255, 0, 0],
+            &[
+                ("sRGB", vec![0]),
+                (
+                    "gAMA",
+                    100000u32.to_be_bytes().to_vec(),
+                ),
+                (
+                    "cHRM",
+                    chrm_chunk(swapped_primaries),
+                ),
+            ],
+        );
+        let image = decode_with_limits(
+            png_bytes,
+            PngDecoderOptions {
+                output_format: PixelFormat::Rgb8,
+                ..srgb_options
+            },
+        )
+        .unwrap();
+        assert_eq!(image.pixels, [255, 0, 0]);
+
+        // primaries are converted to the ones of sRGB
+        let png_bytes = encode(
+            ColorType::Truecolor,
+            &[255, 0, 0],
+            &[(
+                "cHRM",
+                chrm_chunk(swapped_primaries),
+            )],
+        );
+        let image = decode_with_limits(
+            png_bytes,
+            PngDecoderOptions {
+                output_format: PixelFormat::Rgb8,
+                ..srgb_options
+            },
+        )
+        .unwrap();
+        assert_eq!(image.pixels, [0, 255, 0]);
+        let png_bytes = encode(
+            ColorType::Truecolor,
+            &[200, 100, 50],
+            &[(
+                "cHRM",
+                chrm_chunk(srgb_primaries),
+            )],
+        );
+        let image = decode_with_limits(
+            png_bytes,
+            PngDecoderOptions {
+                output_format: PixelFormat::Rgb8,
+                ..srgb_options
+            },
+        )
+        .unwrap();
+        assert_eq!(image.pixels, [200, 100, 50]);
+
+        // the row decoder converts the same way
+        let png_bytes = std::fs::read("../test/png/official/g25n2c08.png").unwrap();
+        let image = decode_with_limits(
+            png_bytes.clone(),
+            PngDecoderOptions {
+                output_format: PixelFormat::Rgba8,
+                ..srgb_options
+            },
+        )
+        .unwrap();
+        let mut byte_reader = byte_reader::ByteReader::new(
+            None,
+            byte_reader::ByteReaderMode::RAW,
+            Some(png_bytes),
+        );
+        byte_reader.read_image().unwrap();
+        let decoder_options = PngDecoderOptions {
+            color_conversion: ColorConversion::Srgb,
+            ..Default::default()
+        };
+        let mut row_decoder = core::row_decoder::PngRowDecoder::new(
+            &mut byte_reader,
+            &decoder_options,
+        );
+        row_decoder.read_header().unwrap();
+        let mut pixels = vec![];
+        while let Some(row) = row_decoder.next_row().unwrap() {
+            pixels.extend(row);
+        }
+        assert_eq!(pixels, image.pixels);
+    }
+
+    /// Matrix/TRC ICC profile with `tags`, each given as its signature and data
+    fn build_icc_profile(color_space: &[u8; 4], tags: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let tag_table_length = 4 + tags.len() * 12;
+        let mut header = vec![0u8; 128];
+        header[8] = 4;
+        header[12..16].copy_from_slice(b"mntr");
+        header[16..20].copy_from_slice(color_space);
+        header[20..24].copy_from_slice(b"XYZ ");
+        header[36..40].copy_from_slice(b"acsp");
+        let mut tag_table = (tags.len() as u32).to_be_bytes().to_vec();
+        let mut tag_data = vec![];
+        for (signature, data) in tags {
+            tag_table.extend_from_slice(*signature);
+            tag_table.extend_from_slice(
+                &((128 + tag_table_length + tag_data.len()) as u32).to_be_bytes(),
+            );
+            tag_table.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            tag_data.extend_from_slice(data);
+        }
+        let mut profile = [header, tag_table, tag_data].concat();
+        let profile_length = (profile.len() as u32).to_be_bytes();
+        profile[0..4].copy_from_slice(&profile_length);
+        profile
+    }
+
+    fn xyz_tag(xyz: [f64; 3]) -> Vec<u8> {
+        let values = xyz
+            .iter()
+            .flat_map(|value| ((value * 65536.0).round() as i32).to_be_bytes());
+        [b"XYZ \0\0\0\0".to_vec(), values.collect()].concat()
+    }
+
+    fn curve_tag(entries: &[u16]) -> Vec<u8> {
+        let entry_count = (entries.len() as u32).to_be_bytes();
+        let entries = entries.iter().flat_map(|entry| entry.to_be_bytes());
+        [
+            b"curv\0\0\0\0".to_vec(),
+            entry_count.to_vec(),
+            entries.collect(),
+        ]
+        .concat()
+    }
+
+    /// sRGB curve as parametric function type 3
+    fn srgb_curve_tag() -> Vec<u8> {
+        let parameters = [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045];
+        let parameters = parameters
+            .iter()
+            .flat_map(|value| ((value * 65536.0f64).round() as i32).to_be_bytes());
+        [b"para\0\0\0\0\0\x03\0\0".to_vec(), parameters.collect()].concat()
+    }
+
+    /// RGB profile with the colorants of the primaries, adapted to D50
+    fn build_rgb_icc_profile(colorants: [[f64; 3]; 3], tone_curve: Vec<u8>) -> Vec<u8> {
+        build_icc_profile(
+            b"RGB ",
+            &[
+                (b"rXYZ", xyz_tag(colorants[0])),
+                (b"gXYZ", xyz_tag(colorants[1])),
+                (b"bXYZ", xyz_tag(colorants[2])),
+                (b"rTRC", tone_curve.clone()),
+                (b"gTRC", tone_curve.clone()),
+                (b"bTRC", tone_curve),
+            ],
+        )
+    }
+
+    fn display_p3_icc_profile() -> Vec<u8> {
+        build_rgb_icc_profile(
+            [
+                [0.5151, 0.2412, -0.0011],
+                [0.2920, 0.6922, 0.0419],
+                [0.1571, 0.0666, 0.7841],
+            ],
+            srgb_curve_tag(),
+        )
+    }
+
+    fn adobe_rgb_icc_profile() -> Vec<u8> {
+        // gamma of 563 / 256
+        build_rgb_icc_profile(
+            [
+                [0.6097, 0.3111, 0.0195],
+                [0.2053, 0.6257, 0.0609],
+                [0.1492, 0.0632, 0.7446],
+            ],
+            curve_tag(&[563]),
+        )
+    }
+
+    fn encode_with_icc_profile(
+        color_type: core::chunk_helpers::ColorType,
+        pixels: &[u8],
+        icc_profile: &[u8],
+        chunks: &[(&str, Vec<u8>)],
+    ) -> Vec<u8> {
+        let mut encoder = core::encoder::PngEncoder::new(
+            1,
+            1,
+            color_type,
+            8,
+            &Default::default(),
+        );
+        let iccp_chunk = [&b"Profile\0\0"[..], &compress_stored(icc_profile)].concat();
+        encoder.add_ancillary_chunk(
+            "iCCP",
+            iccp_chunk,
+            core::encoder::ChunkPosition::BeforePalette,
+        );
+        for (chunk_type, chunk_data) in chunks {
+            encoder.add_ancillary_chunk(
+                chunk_type,
+                chunk_data.clone(),
+                core::encoder::ChunkPosition::BeforePalette,
+            );
+        }
+        encoder.encode(pixels).unwrap()
+    }
+
+    fn assert_pixels_near(pixels: &[u8], expected_pixels: &[u8]) {
+        assert_eq!(
+            pixels.len(),
+            expected_pixels.len()
+        );
+        assert!(
+            pixels
+                .iter()
+                .zip(expected_pixels)
+                .all(|(a, b)| a.abs_diff(*b) <= 1),
+            "{:?} {:?}",
+            pixels,
+            expected_pixels
+        );
+    }
+
+    #[test]
+    fn icc_profile_conversion_test() {
+        use core::{
+            chunk_helpers::ColorType, color_chunks::ColorConversion, decoded_image::PixelFormat,
+            decoder::PngDecoderOptions,
+        };
+
+        let srgb_options = PngDecoderOptions {
+            fail_fast: true,
+            output_format: PixelFormat::Rgb8,
+            color_conversion: ColorConversion::Srgb,
+            ..Default::default()
+        };
+
+        // pure red of Display P3 is outside sRGB, and clipped
+        let png_bytes = encode_with_icc_profile(
+            ColorType::Truecolor,
+            &[255, 0, 0],
+            &display_p3_icc_profile(),
+            &[],
+        );
+        let image = decode_with_limits(png_bytes, srgb_options).unwrap();
+        assert_pixels_near(&image.pixels, &[255, 0, 0]);
+        // white and grey stay the same
+        let png_bytes = encode_with_icc_profile(
+            ColorType::Truecolor,
+            &[128, 128, 128],
+            &display_p3_icc_profile(),
+            &[],
+        );
+        let image = decode_with_limits(png_bytes, srgb_options).unwrap();
+        assert_pixels_near(
+            &image.pixels,
+            &[128, 128, 128],
+        );
+        // a gamma of 2.2 is close to the sRGB curve
+        let png_bytes = encode_with_icc_profile(
+            ColorType::Truecolor,
+            &[128, 128, 128],
+            &adobe_rgb_icc_profile(),
+            &[],
+        );
+        let image = decode_with_limits(png_bytes, srgb_options).unwrap();
+        assert_pixels_near(
+            &image.pixels,
+            &[128, 128, 128],
+        );
+
+        // sRGB colors in the destination profile
+        for (icc_profile, expected_pixels) in [
+            (
+                display_p3_icc_profile(),
+                [234, 51, 35],
+            ),
+            (
+                adobe_rgb_icc_profile(),
+                [219, 0, 0],
+            ),
+        ] {
+            let encoder = core::encoder::PngEncoder::new(
+                1,
+                1,
+                ColorType::Truecolor,
+                8,
+                &Default::default(),
+            );
+            let png_bytes = encoder.encode(&[255, 0, 0]).unwrap();
+            let mut byte_reader = byte_reader::ByteReader::new(
+                None,
+                byte_reader::ByteReaderMode::RAW,
+                Some(png_bytes),
+            );
+            byte_reader.read_image().unwrap();
+            let decoder_options = PngDecoderOptions {
+                output_format: PixelFormat::Rgb8,
+                color_conversion: ColorConversion::DestinationProfile,
+                ..Default::default()
+            };
+            let mut decoder = core::decoder::PngDecoder::new(
+                &mut byte_reader,
+                &decoder_options,
+            );
+            decoder.set_destination_profile(&icc_profile).unwrap();
+            assert_pixels_near(
+                &decoder.run().unwrap().pixels,
+                &expected_pixels,
+            );
+        }
+    }
+
+    #[test]
+    fn icc_profile_reduction_test() {
+        use core::{
+            chunk_helpers::ColorType,
+            color_chunks::ColorConversion,
+            decoded_image::PixelFormat,
+            decoder::PngDecoderOptions,
+            encoder::{ChunkPosition, PngEncoder, PngEncoderOptions},
+        };
+
+        let srgb_options = PngDecoderOptions {
+            fail_fast: true,
+            output_format: PixelFormat::Rgb8,
+            color_conversion: ColorConversion::Srgb,
+            ..Default::default()
+        };
+        let encoder_options = PngEncoderOptions {
+            reduce: true,
+            ..Default::default()
+        };
+        // only grey pixels, which are reduced to greyscale without an ICC profile
+        let rgb8_pixels: Vec<u8> = (0..=255).flat_map(|i| [i, i, i]).collect();
+        let mut encoder = PngEncoder::new(
+            16,
+            16,
+            ColorType::Truecolor,
+            8,
+            &encoder_options,
+        );
+        let decoded_image = decode_with_limits(
+            encoder.encode(&rgb8_pixels).unwrap(),
+            srgb_options,
+        )
+        .unwrap();
+        assert_eq!(
+            decoded_image.header.color_type,
+            ColorType::Greyscale
+        );
+
+        // an RGB profile doesn't fit a greyscale image, so the image is kept as it is
+        let iccp_chunk = [
+            &b"Profile\0\0"[..],
+            &compress_stored(&display_p3_icc_profile()),
+        ]
+        .concat();
+        encoder.add_ancillary_chunk(
+            "iCCP",
+            iccp_chunk,
+            ChunkPosition::BeforePalette,
+        );
+        let decoded_image = decode_with_limits(
+            encoder.encode(&rgb8_pixels).unwrap(),
+            srgb_options,
+        )
+        .unwrap();
+        assert!(decoded_image.errors.is_empty());
+        assert_eq!(
+            decoded_image.header.color_type,
+            ColorType::Truecolor
+        );
+        assert!(decoded_image.metadata.icc_profile.is_some());
+        assert_pixels_near(
+            &decoded_image.pixels,
+            &rgb8_pixels,
+        );
+    }
+
+    #[test]
+    fn invalid_icc_profile_test() {
+        use core::{
+            chunk_helpers::ColorType, color_chunks::ColorConversion, decoded_image::PixelFormat,
+            errors::PngDecodeErrorCode,
+        };
+
+        let linear_grey_profile = build_icc_profile(
+            b"GRAY",
+            &[(
+                b"kTRC",
+                curve_tag(&[0, 65535]),
+            )],
+        );
+        let png_bytes = encode_with_icc_profile(
+            ColorType::Greyscale,
+            &[128],
+            &linear_grey_profile,
+            &[],
+        );
+        let decoder_options = core::decoder::PngDecoderOptions {
+            fail_fast: true,
+            output_format: PixelFormat::Gray8,
+            color_conversion: ColorConversion::Srgb,
+            ..Default::default()
+        };
+        let image = decode_with_limits(png_bytes, decoder_options).unwrap();
+        assert_eq!(image.pixels, [188]);
+
+        // the image falls back to its other color chunks, here a gamma of 1.0
+        let gama_chunk = (
+            "gAMA",
+            100000u32.to_be_bytes().to_vec(),
+        );
+        let lookup_table_profile = build_icc_profile(
+            b"RGB ",
+            &[(b"A2B0", b"mft2".to_vec())],
+        );
+        let cases = [
+            (
+                ColorType::Truecolor,
+                lookup_table_profile,
+            ),
+            (
+                ColorType::Truecolor,
+                linear_grey_profile.clone(),
+            ),
+            (
+                ColorType::Greyscale,
+                display_p3_icc_profile(),
+            ),
+            (
+                ColorType::Truecolor,
+                display_p3_icc_profile()[..100].to_vec(),
+            ),
+        ];
+        for (color_type, icc_profile) in cases {
+            let pixels = match color_type {
+                ColorType::Greyscale => vec![128],
+                _ => vec![128, 128, 128],
+            };
+            let png_bytes = encode_with_icc_profile(
+                color_type,
+                &pixels,
+                &icc_profile,
+                &[gama_chunk.clone()],
+            );
+            let decoder_options = core::decoder::PngDecoderOptions {
+                output_format: PixelFormat::Gray8,
+                color_conversion: ColorConversion::Srgb,
+                ..Default::default()
+            };
+            let image = decode_with_limits(
+                png_bytes.clone(),
+                decoder_options,
+            )
+            .unwrap();
+            assert!(
+                matches!(&image.errors[..], [error] if matches!(error.code, PngDecodeErrorCode::_52(_)))
+            );
+            assert_eq!(image.pixels, [188]);
+
+            // the profile is only read when the colors are converted
+            let image = decode_with_limits(png_bytes, Default::default()).unwrap();
+            assert!(image.errors.is_empty());
+        }
+
+        let mut byte_reader = byte_reader::ByteReader::new(
+            Some("../test/png/official/basn2c08.png"),
+            byte_reader::ByteReaderMode::FILE,
+            None,
+        );
+        byte_reader.read_image().unwrap();
+        let decoder_options = core::decoder::PngDecoderOptions {
+            color_conversion: ColorConversion::DestinationProfile,
+            ..Default::default()
+        };
+        let mut decoder = core::decoder::PngDecoder::new(
+            &mut byte_reader,
+            &decoder_options,
+        );
+        assert!(matches!(
+            decoder.set_destination_profile(b"not a profile"),
+            Err(PngDecodeErrorCode::_52(_))
+        ));
+        // colored pixels are converted to their luminance with a greyscale destination profile
+        decoder
+            .set_destination_profile(&linear_grey_profile)
+            .unwrap();
+        let image = decoder.run().unwrap();
+        assert!(image
+            .pixels
+            .chunks_exact(4)
+            .all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]));
+    }
+
+    #[test_case("../test/png/samples/1.png", 2.0;"Colors of ../test/png/samples/1.png should be converted with its ICC profile")]
+    #[test_case("../test/png/samples/2.png", 10.0;"Colors of ../test/png/samples/2.png should be converted with its ICC profile")]
+    fn embedded_icc_profile_test(png_path: &str, max_mean_absolute_difference: f64) {
+        use core::{
+            color_chunks::ColorConversion, decoded_image::PixelFormat, decoder::PngDecoderOptions,
+        };
+
+        let png_bytes = std::fs::read(png_path).unwrap();
+        let srgb_options = PngDecoderOptions {
+            fail_fast: true,
+            output_format: PixelFormat::Rgba8,
+            color_conversion: ColorConversion::Srgb,
+            ..Default::default()
+        };
+        let image = decode_with_limits(
+            png_bytes.clone(),
+            srgb_options,
+        )
+        .unwrap();
+        let unconverted_image = decode_with_limits(
+            png_bytes,
+            PngDecoderOptions {
+                color_conversion: ColorConversion::None,
+                ..srgb_options
+            },
+        )
+        .unwrap();
+        assert!(image.errors.is_empty());
+        let difference = mean_absolute_difference(
+            &image.pixels,
+            &unconverted_image.pixels,
+        );
+        // both profiles describe displays that are close to sRGB
+        assert!(
+            difference > 0.0 && difference < max_mean_absolute_difference,
+            "{}",
+            difference
+        );
+    }
+
+    #[test_case("../test/png/official/ct1n0g04.png", "tEXt";"Text of ../test/png/official/ct1n0g04.png should be read from tEXt chunks")]
+    #[test_case("../test/png/official/ctzn0g04.png", "zTXt";"Text of ../test/png/official/ctzn0g04.png should be read from zTXt chunks")]
+    #[test_case("../test/png/official/cten0g04.png", "iTXt";"Text of ../test/png/official/cten0g04.png should be read from iTXt chunks")]
+    fn text_chunks_test(png_path: &str, last_chunk_type: &str) {
+        let decoded_image = decode_bytes(
+            std::fs::read(png_path).unwrap(),
+            core::decoded_image::PixelFormat::Rgba8,
+        );
+        let text_entries = &decoded_image.metadata.text_entries;
+        let keywords: Vec<&str> = text_entries
+            .iter()
+            .map(|entry| entry.keyword.as_str())
+            .collect();
+        assert_eq!(
+            keywords,
+            [
+                "Title",
+                "Author",
+                "Copyright",
+                "Description",
+                "Software",
+                "Disclaimer"
+            ]
+        );
+        assert_eq!(
+            text_entries[0].text,
+            "PngSuite"
+        );
+        assert_eq!(
+            text_entries[5].text,
+            "Freeware."
+        );
+        assert_eq!(
+            text_entries[5].chunk_type(),
+            last_chunk_type
+        );
+        assert_eq!(
+            text_entries[5].compressed,
+            last_chunk_type == "zTXt"
+        );
+    }
+
+    #[test]
+    fn international_text_test() {
+        use core::{decoded_image::PixelFormat, text_chunks::InternationalText};
+
+        let decoded_image = decode_bytes(
+            std::fs::read("../test/png/official/ctgn0g04.png").unwrap(),
+            PixelFormat::Rgba8,
+        );
+        let copyright = &decoded_image.metadata.text_entries[2];
+        assert_eq!(copyright.keyword, "Copyright");
+        assert_eq!(
+            copyright.text,
+            "Πνευματικά δικαιώματα Schaik van Willem, Καναδάς 2011"
+        );
+        assert_eq!(
+            copyright.international,
+            Some(InternationalText {
+                language_tag: "el".to_string(),
+                translated_keyword: "Πνευματικά δικαιώματα".to_string(),
+            })
+        );
+
+        // tEXt and zTXt chunks are Latin-1, and iTXt chunks may be compressed
+        let png_bytes = encode_with_ancillary_chunks(&[
+            (
+                "tEXt",
+                b"Author\0Fran\xe7ois".to_vec(),
+                core::encoder::ChunkPosition::BeforePalette,
+            ),
+            (
+                "zTXt",
+                [&b"Comment\0\0"[..], &compress_stored(b"caf\xe9")].concat(),
+                core::encoder::ChunkPosition::BeforeImageData,
+            ),
+            (
+                "iTXt",
+                [
+                    &b"Title\0\x01\0ja\0\xe3\x82\xbf\xe3\x82\xa4\xe3\x83\x88\xe3\x83\xab\0"[..],
+                    &compress_stored("写真".as_bytes()),
+                ]
+                .concat(),
+                core::encoder::ChunkPosition::AfterImageData,
+            ),
+            (
+                "iTXt",
+                b"Software\0\0\0\0\0".to_vec(),
+                core::encoder::ChunkPosition::AfterImageData,
+            ),
+        ]);
+        let text_entries = decode_bytes(png_bytes, PixelFormat::Rgba8)
+            .metadata
+            .text_entries;
+        assert_eq!(text_entries.len(), 4);
+        assert_eq!(
+            (
+                text_entries[0].keyword.as_str(),
+                text_entries[0].text.as_str()
+            ),
+            ("Author", "François")
+        );
+        assert_eq!(
+            (
+                text_entries[1].keyword.as_str(),
+                text_entries[1].text.as_str()
+            ),
+            ("Comment", "café")
+        );
+        assert!(text_entries[1].compressed);
+        assert_eq!(text_entries[2].text, "写真");
+        assert!(text_entries[2].compressed);
+        assert_eq!(
+            text_entries[2].international,
+            Some(InternationalText {
+                language_tag: "ja".to_string(),
+                translated_keyword: "タイトル".to_string(),
+            })
+        );
+        assert_eq!(text_entries[3].text, "");
+        assert_eq!(
+            text_entries[3].international.as_ref().unwrap().language_tag,
+            ""
+        );
+    }
+
+    #[test]
+    fn invalid_text_chunks_test() {
+        use core::{encoder::ChunkPosition::BeforeImageData, errors::PngDecodeErrorCode};
+
+        let long_keyword = [&[b'k'; 80][..], b"\0text"].concat();
+        let cases = vec![
+            ("tEXt", b"\0text".to_vec()),
+            ("tEXt", long_keyword),
+            ("tEXt", b"Title".to_vec()),
+            (
+                "tEXt",
+                b"Title\0line\0line".to_vec(),
+            ),
+            (
+                "tEXt",
+                b"Title \0text".to_vec(),
+            ),
+            (
+                "zTXt",
+                [&b"Title\0\x01"[..], &compress_stored(b"text")].concat(),
+            ),
+            ("zTXt", b"Title\0".to_vec()),
+            (
+                "zTXt",
+                b"Title\0\0not zlib".to_vec(),
+            ),
+            (
+                "iTXt",
+                b"Title\0\x02\0\0\0text".to_vec(),
+            ),
+            (
+                "iTXt",
+                b"Title\0\0\x01\0\0text".to_vec(),
+            ),
+            (
+                "iTXt",
+                b"Title\0\0\0en\0".to_vec(),
+            ),
+            (
+                "iTXt",
+                b"Title\0\0\0en_us\0\0text".to_vec(),
+            ),
+            (
+                "iTXt",
+                b"Title\0\0\0\0\0\xe9".to_vec(),
+            ),
+            (
+                "iTXt",
+                b"Title\0\x01\0\0\0text".to_vec(),
+            ),
+        ];
+        for (chunk_type, chunk_data) in cases {
+            let png_bytes = encode_with_ancillary_chunks(&[
+                (
+                    "tEXt",
+                    b"Author\0author".to_vec(),
+                    BeforeImageData,
+                ),
+                (
+                    chunk_type,
+                    chunk_data.clone(),
+                    BeforeImageData,
+                ),
+            ]);
+            let decoded_image = decode_with_limits(
+                png_bytes.clone(),
+                Default::default(),
+            )
+            .unwrap();
+            assert!(
+                matches!(&decoded_image.errors[..], [error] if matches!(&error.code, PngDecodeErrorCode::_51(invalid_chunk_type, _) if invalid_chunk_type == chunk_type)),
+                "{:?}",
+                chunk_data
+            );
+            // only the invalid entry is ignored
+            assert_eq!(
+                decoded_image.metadata.text_entries.len(),
+                1
+            );
+
+            let fail_fast_options = core::decoder::PngDecoderOptions {
+                fail_fast: true,
+                ..Default::default()
+            };
+            assert!(decode_with_limits(png_bytes, fail_fast_options).is_err());
+        }
+
+        // text that decompresses to more than the ancillary limit is ignored
+        // 1000 zero bytes, compressed
+        let compressed_text = [
+            120, 218, 99, 96, 24, 5, 163, 96, 20, 12, 119, 0, 0, 3, 232, 0, 1,
+        ];
+        let png_bytes = encode_with_ancillary_chunks(&[(
+            "zTXt",
+            [&b"Comment\0\0"[..], &compressed_text].concat(),
+            BeforeImageData,
+        )]);
+        let decoder_options = core::decoder::PngDecoderOptions {
+            max_ancillary_bytes: 100,
+            ..Default::default()
+        };
+        let decoded_image = decode_with_limits(png_bytes, decoder_options).unwrap();
+        assert!(
+            matches!(&decoded_image.errors[..], [error] if matches!(error.code, PngDecodeErrorCode::_51(_, _)))
+        );
+        assert!(decoded_image.metadata.text_entries.is_empty());
+    }
+
+    #[test]
+    fn chunk_editor_test() {
+        use core::{
+            chunk_editor::PngChunkEditor, decoded_image::PixelFormat, text_chunks::TextEntry,
+        };
+
+        let png_bytes = std::fs::read("../test/png/official/ctzn0g04.png").unwrap();
+        let long_text = "Licensed under the MIT license. ".repeat(40);
+        let mut chunk_editor = PngChunkEditor::new();
+        chunk_editor.set_text(TextEntry::new(
+            "Copyright",
+            "Copyright 2026",
+        ));
+        chunk_editor.remove_text("Disclaimer");
+        chunk_editor.add_text(TextEntry::new(
+            "Build", "1234",
+        ));
+        chunk_editor.add_text(TextEntry::new(
+            "License", &long_text,
+        ));
+        chunk_editor.add_text(TextEntry::new(
+            "Title", "写真",
+        ));
+        let edited_png_bytes = chunk_editor.apply(&png_bytes).unwrap();
+
+        let original_image = decode_bytes(
+            png_bytes.clone(),
+            PixelFormat::Rgba8,
+        );
+        let edited_image = decode_bytes(
+            edited_png_bytes.clone(),
+            PixelFormat::Rgba8,
+        );
+        assert_eq!(
+            edited_image.pixels,
+            original_image.pixels
+        );
+        assert_eq!(
+            edited_image.metadata.gamma,
+            original_image.metadata.gamma
+        );
+        // image data is copied as it is
+        assert_eq!(
+            chunk_bytes(&edited_png_bytes, "IDAT"),
+            chunk_bytes(&png_bytes, "IDAT")
+        );
+
+        let text_entries = &edited_image.metadata.text_entries;
+        let entries: Vec<(&str, &str)> = text_entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.chunk_type(),
+                    entry.keyword.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("tEXt", "Title"),
+                ("tEXt", "Author"),
+                ("tEXt", "Copyright"),
+                ("zTXt", "Description"),
+                ("zTXt", "Software"),
+                ("tEXt", "Build"),
+                ("zTXt", "License"),
+                ("iTXt", "Title"),
+            ]
+        );
+        assert_eq!(
+            text_entries[2].text,
+            "Copyright 2026"
+        );
+        assert_eq!(
+            text_entries[6].text,
+            long_text
+        );
+        assert_eq!(text_entries[7].text, "写真");
+        assert_eq!(
+            text_entries[7].international.as_ref().unwrap().language_tag,
+            ""
+        );
+
+        // entries that are added and then removed are not written, and the other chunks stay as they are
+        let mut chunk_editor = PngChunkEditor::new();
+        chunk_editor.add_text(TextEntry::new(
+            "Build", "1234",
+        ));
+        chunk_editor.remove_text("Build");
+        assert_eq!(
+            chunk_editor.apply(&png_bytes).unwrap(),
+            png_bytes
+        );
+        chunk_editor.remove_text("Copyright");
+        chunk_editor.set_text(TextEntry::new(
+            "Copyright",
+            "Copyright 2026",
+        ));
+        let edited_png_bytes = chunk_editor.apply(&png_bytes).unwrap();
+        let text_entries = decode_bytes(
+            edited_png_bytes,
+            PixelFormat::Rgba8,
+        )
+        .metadata
+        .text_entries;
+        assert_eq!(text_entries.len(), 6);
+        assert_eq!(
+            text_entries[2].keyword,
+            "Copyright"
+        );
+        assert!(!text_entries[2].compressed);
+    }
+
+    #[test]
+    fn invalid_text_entry_test() {
+        use core::{
+            chunk_editor::PngChunkEditor,
+            errors::PngDecodeErrorCode,
+            text_chunks::{InternationalText, TextEntry},
+        };
+
+        let png_bytes = std::fs::read("../test/png/official/ct1n0g04.png").unwrap();
+        let international_entry = |language_tag: &str, translated_keyword: &str| TextEntry {
+            international: Some(InternationalText {
+                language_tag: language_tag.to_string(),
+                translated_keyword: translated_keyword.to_string(),
+            }),
+            ..TextEntry::new("Title", "text")
+        };
+        let cases = vec![
+            (
+                TextEntry::new("", "text"),
+                "tEXt",
+            ),
+            (
+                TextEntry::new(&"k".repeat(80), "text"),
+                "tEXt",
+            ),
+            (
+                TextEntry::new("Title ", "text"),
+                "tEXt",
+            ),
+            (
+                TextEntry::new("Ti\0tle", "text"),
+                "tEXt",
+            ),
+            (
+                TextEntry::new("題名", "text"),
+                "tEXt",
+            ),
+            (
+                TextEntry::new("Title", "line\0line"),
+                "tEXt",
+            ),
+            (
+                TextEntry {
+                    international: None,
+                    ..TextEntry::new("Title", "写真")
+                },
+                "tEXt",
+            ),
+            (
+                international_entry("en_us", ""),
+                "iTXt",
+            ),
+            (
+                international_entry("en", "Ti\0tle"),
+                "iTXt",
+            ),
+        ];
+        for (text_entry, chunk_type) in cases {
+            let mut chunk_editor = PngChunkEditor::new();
+            chunk_editor.add_text(text_entry.clone());
+            let result = chunk_editor.apply(&png_bytes);
+            assert!(
+                matches!(&result, Err(PngDecodeErrorCode::_53(invalid_chunk_type, _)) if invalid_chunk_type == chunk_type),
+                "{:?}",
+                text_entry
+            );
+        }
+
+        // chunks of a broken image can't be copied as they are
+        let mut broken_png_bytes = png_bytes.clone();
+        let last_byte_index = broken_png_bytes.len() - 1;
+        broken_png_bytes[last_byte_index] ^= 1;
+        assert!(matches!(
+            PngChunkEditor::new().apply(&broken_png_bytes),
+            Err(PngDecodeErrorCode::_7(_, _))
+        ));
+    }
+
+    #[test_case("bgai4a08", None, [0x33, 0x66, 0x99];"../test/png/official/bgai4a08.png without bKGD chunk should be composited onto the given color")]
+    #[test_case("bgan6a16", None, [0x33, 0x66, 0x99];"../test/png/official/bgan6a16.png without bKGD chunk should be composited onto the given color")]
+    #[test_case("bgbn4a08", Some(core::background::BackgroundColor::Grey(0)), [0, 0, 0];"../test/png/official/bgbn4a08.png should be composited onto black")]
+    #[test_case("bggn4a16", Some(core::background::BackgroundColor::Grey(43908)), [171, 171, 171];"../test/png/official/bggn4a16.png should be composited onto grey")]
+    #[test_case("bgwn6a08", Some(core::background::BackgroundColor::Rgb(255, 255, 255)), [255, 255, 255];"../test/png/official/bgwn6a08.png should be composited onto white")]
+    #[test_case("bgyn6a16", Some(core::background::BackgroundColor::Rgb(65535, 65535, 0)), [255, 255, 0];"../test/png/official/bgyn6a16.png should be composited onto yellow")]
+    fn background_color_test(
+        image_name: &str,
+        expected_background_color: Option<core::background::BackgroundColor>,
+        expected_background_rgb: [u8; 3],
+    ) {
+        use core::{background::AlphaFlattening, decoder::PngDecoderOptions};
+
+        let png_bytes = std::fs::read(format!(
+            "../test/png/official/{}.png",
+            image_name
+        ))
+        .unwrap();
+        let image = decode_bytes(
+            png_bytes.clone(),
+            core::decoded_image::PixelFormat::Rgba8,
+        );
+        assert_eq!(
+            image.metadata.background_color,
+            expected_background_color
+        );
+
+        let decoder_options = PngDecoderOptions {
+            alpha_flattening: AlphaFlattening::ImageBackground,
+            background_color: 0x336699,
+            ..Default::default()
+        };
+        let flattened_image = decode_with_limits(
+            png_bytes.clone(),
+            decoder_options,
+        )
+        .unwrap();
+        assert!(flattened_image.errors.is_empty());
+        let expected_pixels: Vec<u8> = image
+            .pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                let alpha = pixel[3] as u32;
+                let blend = |sample: u8, background: u8| {
+                    ((sample as u32 * alpha + background as u32 * (255 - alpha) + 127) / 255) as u8
+                };
+                [
+                    blend(
+                        pixel[0],
+                        expected_background_rgb[0],
+                    ),
+                    blend(
+                        pixel[1],
+                        expected_background_rgb[1],
+                    ),
+                    blend(
+                        pixel[2],
+                        expected_background_rgb[2],
+                    ),
+                    255,
+                ]
+            })
+            .collect();
+        assert_pixels_near(
+            &flattened_image.pixels,
+            &expected_pixels,
+        );
+
+        // the given color is used even when the image has bKGD chunk
+        let decoder_options = PngDecoderOptions {
+            alpha_flattening: AlphaFlattening::BackgroundColor,
+            ..decoder_options
+        };
+        let flattened_image = decode_with_limits(png_bytes, decoder_options).unwrap();
+        let transparent_pixel_index = image
+            .pixels
+            .chunks_exact(4)
+            .position(|pixel| pixel[3] == 0)
+            .unwrap();
+        assert_eq!(
+            flattened_image.pixels[transparent_pixel_index * 4..transparent_pixel_index * 4 + 4],
+            [0x33, 0x66, 0x99, 255]
+        );
+    }
+
+    #[test]
+    fn palette_background_test() {
+        use core::{
+            background::{AlphaFlattening, BackgroundColor},
+            chunk_helpers::ColorType,
+            decoded_image::PixelFormat,
+            encoder::{ChunkPosition, PngEncoder},
+        };
+
+        let mut encoder = PngEncoder::new(
+            3,
+            1,
+            ColorType::IndexedColor,
+            8,
+            &Default::default(),
+        );
+        encoder.set_palette(vec![10, 20, 30, 200, 100, 50]);
+        encoder.set_transparency(vec![0, 255]);
+        encoder.add_ancillary_chunk(
+            "bKGD",
+            vec![1],
+            ChunkPosition::BeforeImageData,
+        );
+        let png_bytes = encoder.encode(&[0, 1, 0]).unwrap();
+
+        let image = decode_bytes(
+            png_bytes.clone(),
+            PixelFormat::Rgba8,
+        );
+        assert_eq!(
+            image.metadata.background_color,
+            Some(BackgroundColor::PaletteIndex(
+                1
+            ))
+        );
+        assert_eq!(
+            image.pixels,
+            [10, 20, 30, 0, 200, 100, 50, 255, 10, 20, 30, 0]
+        );
+        let decoder_options = core::decoder::PngDecoderOptions {
+            alpha_flattening: AlphaFlattening::ImageBackground,
+            ..Default::default()
+        };
+        let flattened_image = decode_with_limits(
+            png_bytes.clone(),
+            decoder_options,
+        )
+        .unwrap();
+        assert_eq!(
+            flattened_image.pixels,
+            [200, 100, 50, 255, 200, 100, 50, 255, 200, 100, 50, 255]
+        );
+        // the row decoder flattens the same way
+        let mut byte_reader = byte_reader::ByteReader::new(
+            None,
+            byte_reader::ByteReaderMode::RAW,
+            Some(png_bytes.clone()),
+        );
+        byte_reader.read_image().unwrap();
+        let decoder_options = core::decoder::PngDecoderOptions {
+            alpha_flattening: AlphaFlattening::BackgroundColor,
+            background_color: 0x010203,
+            ..Default::default()
+        };
+        let mut row_decoder = core::row_decoder::PngRowDecoder::new(
+            &mut byte_reader,
+            &decoder_options,
+        );
+        row_decoder.read_header().unwrap();
+        assert_eq!(
+            row_decoder.next_row().unwrap().unwrap(),
+            [1, 2, 3, 255, 200, 100, 50, 255, 1, 2, 3, 255]
+        );
+        // native output keeps the samples as they are
+        let decoder_options = core::decoder::PngDecoderOptions {
+            alpha_flattening: AlphaFlattening::ImageBackground,
+            output_format: PixelFormat::Native,
+            ..Default::default()
+        };
+        assert_eq!(
+            decode_with_limits(png_bytes, decoder_options)
+                .unwrap()
+                .pixels,
+            [0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn invalid_background_test() {
+        use core::{
+            chunk_helpers::ColorType,
+            encoder::{
+                ChunkPosition::{AfterImageData, BeforeImageData, BeforePalette},
+                PngEncoder,
+            },
+            errors::PngDecodeErrorCode,
+        };
+
+        let cases: Vec<(
+            _,
+            fn(&PngDecodeErrorCode) -> bool,
+        )> = vec![
+            (
+                vec![(
+                    "bKGD",
+                    vec![0, 0],
+                    BeforeImageData,
+                )],
+                is_wrong_length,
+            ),
+            (
+                vec![(
+                    "bKGD",
+                    vec![1],
+                    BeforeImageData,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![("bKGD", vec![0], BeforePalette)],
+                is_misplaced,
+            ),
+            (
+                vec![(
+                    "bKGD",
+                    vec![0],
+                    AfterImageData,
+                )],
+                is_misplaced,
+            ),
+            (
+                vec![
+                    (
+                        "bKGD",
+                        vec![0],
+                        BeforeImageData,
+                    ),
+                    (
+                        "bKGD",
+                        vec![0],
+                        BeforeImageData,
+                    ),
+                ],
+                is_duplicate,
+            ),
+        ];
+        for (chunks, is_expected_code) in cases {
+            let png_bytes = encode_with_ancillary_chunks(&chunks);
+            decode_with_chunk_error(png_bytes, is_expected_code);
+        }
+
+        // truecolor images with a suggested palette must have bKGD chunk after PLTE chunk as well
+        for (position, expected_error_count) in [(BeforePalette, 1), (BeforeImageData, 0)] {
+            let mut encoder = PngEncoder::new(
+                1,
+                1,
+                ColorType::Truecolor,
+                8,
+                &Default::default(),
+            );
+            encoder.set_palette(vec![10, 20, 30]);
+            encoder.add_ancillary_chunk(
+                "bKGD",
+                vec![0, 1, 0, 2, 0, 3],
+                position,
+            );
+            let decoded_image = decode_with_limits(
+                encoder.encode(&[0, 0, 0]).unwrap(),
+                Default::default(),
+            )
+            .unwrap();
+            assert_eq!(
+                decoded_image.errors.len(),
+                expected_error_count
+            );
+            assert_eq!(
+                decoded_image.metadata.background_color.is_some(),
+                expected_error_count == 0
+            );
+        }
+
+        // greyscale values must fit into the bit depth of the image
+        let mut encoder = PngEncoder::new(
+            1,
+            1,
+            ColorType::Greyscale,
+            8,
+            &Default::default(),
+        );
+        encoder.add_ancillary_chunk(
+            "bKGD",
+            vec![1, 0],
+            BeforeImageData,
+        );
+        let decoded_image = decode_with_limits(
+            encoder.encode(&[0]).unwrap(),
+            Default::default(),
+        )
+        .unwrap();
+        assert_eq!(decoded_image.errors.len(), 1);
+        assert!(decoded_image.metadata.background_color.is_none());
+    }
+
+    #[test_case("cdfn2c08", 1, 4, core::physical_chunks::PhysicalUnit::Unknown;"Pixels of ../test/png/official/cdfn2c08.png should be 4 times as wide as they are tall")]
+    #[test_case("cdhn2c08", 4, 1, core::physical_chunks::PhysicalUnit::Unknown;"Pixels of ../test/png/official/cdhn2c08.png should be 4 times as tall as they are wide")]
+    #[test_case("cdsn2c08", 1, 1, core::physical_chunks::PhysicalUnit::Unknown;"Pixels of ../test/png/official/cdsn2c08.png should be square")]
+    #[test_case("cdun2c08", 1000, 1000, core::physical_chunks::PhysicalUnit::Meter;"Pixels of ../test/png/official/cdun2c08.png should be 1 millimeter wide")]
+    fn physical_dimensions_test(
+        image_name: &str,
+        pixels_per_unit_x: u32,
+        pixels_per_unit_y: u32,
+        unit: core::physical_chunks::PhysicalUnit,
+    ) {
+        use core::physical_chunks::PhysicalDimensions;
+
+        let png_bytes = std::fs::read(format!(
+            "../test/png/official/{}.png",
+            image_name
+        ))
+        .unwrap();
+        let metadata = decode_bytes(
+            png_bytes,
+            core::decoded_image::PixelFormat::Rgba8,
+        )
+        .metadata;
+        let expected_physical_dimensions = PhysicalDimensions {
+            pixels_per_unit_x,
+            pixels_per_unit_y,
+            unit,
+        };
+        assert_eq!(
+            metadata.physical_dimensions,
+            Some(expected_physical_dimensions)
+        );
+        match metadata.physical_dimensions.unwrap().dpi() {
+            Some((dpi_x, dpi_y)) => {
+                assert!((dpi_x - 25.4).abs() < 1e-9 && (dpi_y - 25.4).abs() < 1e-9)
+            }
+            None => assert_eq!(
+                unit,
+                core::physical_chunks::PhysicalUnit::Unknown
+            ),
+        }
+    }
+
+    #[test_case("cm0n0g04", 946730096, [2000, 1, 1, 12, 34, 56];"../test/png/official/cm0n0g04.png should have been modified on 2000-01-01")]
+    #[test_case("cm7n0g04", 0, [1970, 1, 1, 0, 0, 0];"../test/png/official/cm7n0g04.png should have been modified on 1970-01-01")]
+    #[test_case("cm9n0g04", 946684799, [1999, 12, 31, 23, 59, 59];"../test/png/official/cm9n0g04.png should have been modified on 1999-12-31")]
+    fn timestamp_test(image_name: &str, unix_time: u64, expected_fields: [u16; 6]) {
+        use core::timestamp::Timestamp;
+
+        let png_bytes = std::fs::read(format!(
+            "../test/png/official/{}.png",
+            image_name
+        ))
+        .unwrap();
+        let timestamp = decode_bytes(
+            png_bytes,
+            core::decoded_image::PixelFormat::Rgba8,
+        )
+        .metadata
+        .timestamp
+        .unwrap();
+        let fields = [
+            timestamp.year,
+            timestamp.month as u16,
+            timestamp.day as u16,
+            timestamp.hour as u16,
+            timestamp.minute as u16,
+            timestamp.second as u16,
+        ];
+        assert_eq!(fields, expected_fields);
+        assert_eq!(
+            Timestamp::from_unix_time(unix_time),
+            Some(timestamp)
+        );
+    }
+
+    #[test]
+    fn write_physical_chunks_test() {
+        use core::{
+            chunk_editor::PngChunkEditor,
+            chunk_helpers::ColorType,
+            decoded_image::PixelFormat,
+            encoder::PngEncoder,
+            physical_chunks::{
+                ImageOffset, OffsetUnit, PhysicalDimensions, PhysicalScale, PhysicalUnit, ScaleUnit,
+            },
+            timestamp::Timestamp,
+        };
+
+        let physical_dimensions = PhysicalDimensions::from_dpi(300.0, 150.0);
+        assert_eq!(
+            (
+                physical_dimensions.pixels_per_unit_x,
+                physical_dimensions.pixels_per_unit_y
+            ),
+            (11811, 5906)
+        );
+        assert_eq!(
+            physical_dimensions.unit,
+            PhysicalUnit::Meter
+        );
+        let (dpi_x, dpi_y) = physical_dimensions.dpi().unwrap();
+        assert_eq!(
+            (dpi_x.round(), dpi_y.round()),
+            (300.0, 150.0)
+        );
+        let image_offset = ImageOffset {
+            x: -20,
+            y: 1500,
+            unit: OffsetUnit::Micrometer,
+        };
+        let physical_scale = PhysicalScale {
+            pixel_width: 0.25,
+            pixel_height: 2.5e-7,
+            unit: ScaleUnit::Meter,
+        };
+        // leap day
+        let timestamp = Timestamp::from_unix_time(951782400).unwrap();
+        assert_eq!(
+            (
+                timestamp.year,
+                timestamp.month,
+                timestamp.day,
+                timestamp.hour
+            ),
+            (2000, 2, 29, 0)
+        );
+
+        let mut encoder = PngEncoder::new(
+            1,
+            1,
+            ColorType::Greyscale,
+            8,
+            &Default::default(),
+        );
+        encoder
+            .set_physical_dimensions(&PhysicalDimensions::from_dpi(
+                72.0, 72.0,
+            ))
+            .unwrap();
+        encoder
+            .set_physical_dimensions(&physical_dimensions)
+            .unwrap();
+        encoder.set_offset(&image_offset);
+        encoder.set_scale(&physical_scale).unwrap();
+        encoder.set_timestamp(&timestamp).unwrap();
+        let png_bytes = encoder.encode(&[128]).unwrap();
+        let decoded_image = decode_bytes(png_bytes, PixelFormat::Rgba8);
+        assert!(decoded_image.errors.is_empty());
+        assert_eq!(
+            decoded_image.metadata.physical_dimensions,
+            Some(physical_dimensions)
+        );
+        assert_eq!(
+            decoded_image.metadata.offset,
+            Some(image_offset)
+        );
+        assert_eq!(
+            decoded_image.metadata.scale,
+            Some(physical_scale)
+        );
+        assert_eq!(
+            decoded_image.metadata.timestamp,
+            Some(timestamp)
+        );
+
+        // the editor replaces the existing tIME chunk, and adds pHYs chunk before the image data
+        let png_bytes = std::fs::read("../test/png/official/cm0n0g04.png").unwrap();
+        let mut chunk_editor = PngChunkEditor::new();
+        chunk_editor.set_timestamp(&timestamp).unwrap();
+        chunk_editor
+            .set_physical_dimensions(&physical_dimensions)
+            .unwrap();
+        let edited_png_bytes = chunk_editor.apply(&png_bytes).unwrap();
+        assert_eq!(
+            chunk_bytes(&edited_png_bytes, "IDAT"),
+            chunk_bytes(&png_bytes, "IDAT")
+        );
+        let decoded_image = decode_bytes(
+            edited_png_bytes,
+            PixelFormat::Rgba8,
+        );
+        assert_eq!(
+            decoded_image.metadata.timestamp,
+            Some(timestamp)
+        );
+        assert_eq!(
+            decoded_image.metadata.physical_dimensions,
+            Some(physical_dimensions)
+        );
+    }
+
+    #[test]
+    fn invalid_physical_chunks_test() {
+        use core::{
+            encoder::{
+                ChunkPosition::{AfterImageData, BeforeImageData, BeforePalette},
+                PngEncoder,
+            },
+            errors::PngDecodeErrorCode,
+            physical_chunks::{PhysicalDimensions, PhysicalScale, PhysicalUnit, ScaleUnit},
+            timestamp::Timestamp,
+        };
+
+        let phys_chunk = |pixels_per_unit: u32, unit: u8| {
+            [
+                &pixels_per_unit.to_be_bytes()[..],
+                &1000u32.to_be_bytes(),
+                &[unit],
+            ]
+            .concat()
+        };
+        let time_chunk = |year: u16, month: u8, day: u8, second: u8| {
+            [&year.to_be_bytes()[..], &[month, day, 12, 0, second]].concat()
+        };
+        let cases: Vec<(
+            _,
+            fn(&PngDecodeErrorCode) -> bool,
+        )> = vec![
+            (
+                vec![(
+                    "pHYs",
+                    vec![0; 8],
+                    BeforePalette,
+                )],
+                is_wrong_length,
+            ),
+            (
+                vec![(
+                    "pHYs",
+                    phys_chunk(1000, 2),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "pHYs",
+                    phys_chunk(0, 1),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "pHYs",
+                    phys_chunk(1 << 31, 1),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "pHYs",
+                    phys_chunk(1000, 1),
+                    AfterImageData,
+                )],
+                is_misplaced,
+            ),
+            (
+                vec![
+                    (
+                        "pHYs",
+                        phys_chunk(1000, 1),
+                        BeforePalette,
+                    ),
+                    (
+                        "pHYs",
+                        phys_chunk(1000, 1),
+                        BeforeImageData,
+                    ),
+                ],
+                is_duplicate,
+            ),
+            (
+                vec![(
+                    "oFFs",
+                    vec![0, 0, 0, 1, 0, 0, 0, 1, 2],
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "oFFs",
+                    vec![0; 9],
+                    AfterImageData,
+                )],
+                is_misplaced,
+            ),
+            (
+                vec![(
+                    "sCAL",
+                    b"\x031\x001".to_vec(),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sCAL",
+                    b"\x011.5".to_vec(),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sCAL",
+                    b"\x010\x001".to_vec(),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sCAL",
+                    b"\x011\x00-1".to_vec(),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sCAL",
+                    b"\x01inf\x001".to_vec(),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sCAL",
+                    b"\x011\x00".to_vec(),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "tIME",
+                    vec![0; 6],
+                    AfterImageData,
+                )],
+                is_wrong_length,
+            ),
+            (
+                vec![(
+                    "tIME",
+                    time_chunk(2023, 13, 1, 0),
+                    AfterImageData,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "tIME",
+                    time_chunk(2023, 2, 29, 0),
+                    AfterImageData,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "tIME",
+                    time_chunk(2023, 1, 0, 0),
+                    AfterImageData,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "tIME",
+                    time_chunk(2023, 1, 1, 61),
+                    AfterImageData,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![
+                    (
+                        "tIME",
+                        time_chunk(2024, 2, 29, 60),
+                        BeforePalette,
+                    ),
+                    (
+                        "tIME",
+                        time_chunk(2024, 3, 1, 0),
+                        AfterImageData,
+                    ),
+                ],
+                is_duplicate,
+            ),
+        ];
+        for (chunks, is_expected_code) in cases {
+            let png_bytes = encode_with_ancillary_chunks(&chunks);
+            let decoded_image = decode_with_chunk_error(png_bytes, is_expected_code);
+            // only the first of repeated chunks is used, and invalid chunks are ignored
+            let metadata = decoded_image.metadata;
+            let is_first_used = chunks.len() == 2;
+            assert_eq!(
+                metadata.physical_dimensions.is_some(),
+                is_first_used && chunks[0].0 == "pHYs"
+            );
+            assert_eq!(
+                metadata.timestamp.is_some(),
+                is_first_used && chunks[0].0 == "tIME"
+            );
+            assert!(metadata.offset.is_none() && metadata.scale.is_none());
+        }
+
+        // values that can't be stored are rejected when they are set
+        let mut encoder = PngEncoder::new(
+            1,
+            1,
+            core::chunk_helpers::ColorType::Greyscale,
+            8,
+            &Default::default(),
+        );
+        let invalid_timestamp = Timestamp {
+            year: 2023,
+            month: 4,
+            day: 31,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        };
+        assert!(matches!(
+            encoder.set_timestamp(&invalid_timestamp),
+            Err(PngDecodeErrorCode::_53(_, _))
+        ));
+        let invalid_scale = PhysicalScale {
+            pixel_width: 0.0,
+            pixel_height: 1.0,
+            unit: ScaleUnit::Radian,
+        };
+        assert!(matches!(
+            encoder.set_scale(&invalid_scale),
+            Err(PngDecodeErrorCode::_53(_, _))
+        ));
+        let invalid_physical_dimensions = PhysicalDimensions {
+            pixels_per_unit_x: 0,
+            pixels_per_unit_y: 1,
+            unit: PhysicalUnit::Unknown,
+        };
+        assert!(matches!(
+            encoder.set_physical_dimensions(&invalid_physical_dimensions),
+            Err(PngDecodeErrorCode::_53(_, _))
+        ));
+        assert!(decode_bytes(
+            encoder.encode(&[0]).unwrap(),
+            core::decoded_image::PixelFormat::Rgba8
+        )
+        .metadata
+        .timestamp
+        .is_none());
+    }
+
+    #[test_case("cs3n2c16", Some(core::significant_bits::SignificantBits::Rgb(13, 13, 13));"../test/png/official/cs3n2c16.png should have 13 significant bits")]
+    #[test_case("cs3n3p08", Some(core::significant_bits::SignificantBits::Rgb(3, 3, 3));"../test/png/official/cs3n3p08.png should have 3 significant bits")]
+    #[test_case("cs5n2c08", Some(core::significant_bits::SignificantBits::Rgb(5, 5, 5));"../test/png/official/cs5n2c08.png should have 5 significant bits")]
+    #[test_case("cs5n3p08", Some(core::significant_bits::SignificantBits::Rgb(5, 5, 5));"../test/png/official/cs5n3p08.png should have 5 significant bits")]
+    #[test_case("cs8n2c08", None;"../test/png/official/cs8n2c08.png should have no sBIT chunk")]
+    #[test_case("cs8n3p08", None;"../test/png/official/cs8n3p08.png should have no sBIT chunk")]
+    fn significant_bits_test(
+        image_name: &str,
+        expected_significant_bits: Option<core::significant_bits::SignificantBits>,
+    ) {
+        use core::decoded_image::PixelFormat;
+
+        let png_bytes = std::fs::read(format!(
+            "../test/png/official/{}.png",
+            image_name
+        ))
+        .unwrap();
+        let image = decode_bytes(
+            png_bytes.clone(),
+            PixelFormat::Rgba8,
+        );
+        assert_eq!(
+            image.metadata.significant_bits,
+            expected_significant_bits
+        );
+
+        // PngSuite replicates the significant bits into the rest of each sample, which is exactly what rescaling gives
+        let decoder_options = core::decoder::PngDecoderOptions {
+            rescale_significant_bits: true,
+            output_format: PixelFormat::Rgba8,
+            ..Default::default()
+        };
+        let rescaled_image = decode_with_limits(png_bytes, decoder_options).unwrap();
+        assert_eq!(
+            rescaled_image.pixels,
+            image.pixels
+        );
+    }
+
+    #[test]
+    fn rescale_significant_bits_test() {
+        use core::{
+            chunk_helpers::ColorType,
+            decoded_image::PixelFormat,
+            decoder::PngDecoderOptions,
+            encoder::{ChunkPosition, PngEncoder},
+            significant_bits::SignificantBits,
+        };
+
+        let rescale_options = PngDecoderOptions {
+            rescale_significant_bits: true,
+            ..Default::default()
+        };
+
+        // 5 significant bits, shifted into the high bits of 8-bit samples without replicating them
+        let mut encoder = PngEncoder::new(
+            3,
+            1,
+            ColorType::GreyscaleAlpha,
+            8,
+            &Default::default(),
+        );
+        encoder.add_ancillary_chunk(
+            "sBIT",
+            vec![5, 8],
+            ChunkPosition::BeforePalette,
+        );
+        let png_bytes = encoder
+            .encode(&[0, 255, 31 << 3, 248, 16 << 3, 128])
+            .unwrap();
+        assert_eq!(
+            decode_bytes(
+                png_bytes.clone(),
+                PixelFormat::GrayAlpha8
+            )
+            .pixels,
+            [0, 255, 248, 248, 128, 128]
+        );
+        let rescaled_image = decode_with_limits(
+            png_bytes,
+            PngDecoderOptions {
+                output_format: PixelFormat::GrayAlpha8,
+                ..rescale_options
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            rescaled_image.metadata.significant_bits,
+            Some(SignificantBits::GreyAlpha(
+                5, 8
+            ))
+        );
+        // alpha has all of its 8 bits, so it stays as it is
+        assert_eq!(
+            rescaled_image.pixels,
+            [0, 255, 255, 248, 132, 128]
+        );
+
+        // 12 significant bits of 16-bit samples, and a different number of bits for each channel
+        let mut encoder = PngEncoder::new(
+            1,
+            1,
+            ColorType::Truecolor,
+            16,
+            &Default::default(),
+        );
+        encoder.add_ancillary_chunk(
+            "sBIT",
+            vec![12, 16, 1],
+            ChunkPosition::BeforePalette,
+        );
+        let png_bytes = encoder
+            .encode(&[0xFF, 0xF0, 0x12, 0x34, 0x80, 0x00])
+            .unwrap();
+        let rescaled_image = decode_with_limits(
+            png_bytes.clone(),
+            PngDecoderOptions {
+                output_format: PixelFormat::Rgba16BigEndian,
+                ..rescale_options
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            rescaled_image.pixels,
+            [0xFF, 0xFF, 0x12, 0x34, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+        // native output keeps the samples as they are
+        assert_eq!(
+            decode_with_limits(
+                png_bytes,
+                PngDecoderOptions {
+                    output_format: PixelFormat::Native,
+                    ..rescale_options
+                }
+            )
+            .unwrap()
+            .pixels,
+            [0xFF, 0xF0, 0x12, 0x34, 0x80, 0x00]
+        );
+    }
+
+    #[test]
+    fn invalid_significant_bits_test() {
+        use core::{
+            encoder::ChunkPosition::{BeforeImageData, BeforePalette},
+            errors::PngDecodeErrorCode,
+        };
+
+        let cases: Vec<(
+            _,
+            fn(&PngDecodeErrorCode) -> bool,
+        )> = vec![
+            (
+                vec![("sBIT", vec![5], BeforePalette)],
+                is_wrong_length,
+            ),
+            (
+                vec![(
+                    "sBIT",
+                    vec![5, 5, 0],
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sBIT",
+                    vec![5, 5, 9],
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sBIT",
+                    vec![5, 5, 5],
+                    BeforeImageData,
+                )],
+                is_misplaced,
+            ),
+            (
+                vec![
+                    (
+                        "sBIT",
+                        vec![5, 5, 5],
+                        BeforePalette,
+                    ),
+                    (
+                        "sBIT",
+                        vec![4, 4, 4],
+                        BeforePalette,
+                    ),
+                ],
+                is_duplicate,
+            ),
+        ];
+        for (chunks, is_expected_code) in cases {
+            let png_bytes = encode_with_ancillary_chunks(&chunks);
+            let decoded_image = decode_with_chunk_error(png_bytes, is_expected_code);
+            // only the first of repeated chunks is used, and invalid chunks are ignored
+            assert_eq!(
+                decoded_image.metadata.significant_bits.is_some(),
+                chunks.len() == 2
+            );
+        }
+    }
+
+    #[test_case("ps1n0g08", Some(8), None;"../test/png/official/ps1n0g08.png should have an 8-bit suggested palette")]
+    #[test_case("ps1n2c16", Some(8), None;"../test/png/official/ps1n2c16.png should have an 8-bit suggested palette")]
+    #[test_case("ps2n0g08", Some(16), None;"../test/png/official/ps2n0g08.png should have a 16-bit suggested palette")]
+    #[test_case("ps2n2c16", Some(16), None;"../test/png/official/ps2n2c16.png should have a 16-bit suggested palette")]
+    #[test_case("ch1n3p04", None, Some(15);"../test/png/official/ch1n3p04.png should have a histogram of 15 entries")]
+    #[test_case("ch2n3p08", None, Some(256);"../test/png/official/ch2n3p08.png should have a histogram of 256 entries")]
+    fn palette_chunks_test(
+        image_name: &str,
+        expected_sample_depth: Option<u8>,
+        expected_histogram_length: Option<usize>,
+    ) {
+        let png_bytes = std::fs::read(format!(
+            "../test/png/official/{}.png",
+            image_name
+        ))
+        .unwrap();
+        let decoded_image = decode_bytes(
+            png_bytes,
+            core::decoded_image::PixelFormat::Rgba8,
+        );
+        let metadata = decoded_image.metadata;
+
+        assert_eq!(
+            metadata.suggested_palettes.len(),
+            expected_sample_depth.is_some() as usize
+        );
+        if let Some(suggested_palette) = metadata.suggested_palettes.first() {
+            // a 6x6x6 color cube
+            assert_eq!(
+                suggested_palette.name,
+                "six-cube"
+            );
+            assert_eq!(
+                suggested_palette.sample_depth,
+                expected_sample_depth.unwrap()
+            );
+            assert_eq!(
+                suggested_palette.entries.len(),
+                216
+            );
+            assert_eq!(
+                suggested_palette.entries[1].blue,
+                51
+            );
+            assert!(suggested_palette
+                .entries
+                .iter()
+                .all(|entry| entry.alpha == 255 && entry.frequency == 0));
+        }
+        assert_eq!(
+            metadata.palette_histogram.as_ref().map(Vec::len),
+            expected_histogram_length
+        );
+    }
+
+    #[test]
+    fn write_palette_chunks_test() {
+        use core::{
+            chunk_helpers::ColorType,
+            decoded_image::PixelFormat,
+            encoder::PngEncoder,
+            errors::PngDecodeErrorCode,
+            palette_chunks::{SuggestedPalette, SuggestedPaletteEntry},
+            quantizer::{self, QuantizerOptions},
+        };
+
+        assert_eq!(
+            core::palette_chunks::scale_frequencies(&[0, 1, 500_000, 1_000_000]),
+            [0, 1, 32767, 65535]
+        );
+
+        // 3 red pixels, 1 half transparent blue pixel
+        let rgba8_pixels = [
+            255, 0, 0, 255, 255, 0, 0, 255, 0, 0, 255, 128, 255, 0, 0, 255,
+        ];
+        let quantized_image = quantizer::quantize(
+            2,
+            2,
+            &rgba8_pixels,
+            &QuantizerOptions::default(),
+        )
+        .unwrap();
+        // transparent colors come first
+        assert_eq!(
+            quantized_image.histogram,
+            [21845, 65535]
+        );
+        let suggested_palette = quantized_image.suggested_palette("quantized");
+        assert_eq!(
+            suggested_palette.entries,
+            [
+                SuggestedPaletteEntry {
+                    red: 255,
+                    green: 0,
+                    blue: 0,
+                    alpha: 255,
+                    frequency: 65535
+                },
+                SuggestedPaletteEntry {
+                    red: 0,
+                    green: 0,
+                    blue: 255,
+                    alpha: 128,
+                    frequency: 21845
+                },
+            ]
+        );
+        let wide_palette = SuggestedPalette {
+            name: "wide gamut".to_string(),
+            sample_depth: 16,
+            entries: vec![SuggestedPaletteEntry {
+                red: 1000,
+                green: 65535,
+                blue: 0,
+                alpha: 65535,
+                frequency: 7,
+            }],
+        };
+
+        let mut encoder = PngEncoder::new(
+            2,
+            2,
+            ColorType::IndexedColor,
+            quantized_image.bit_depth,
+            &Default::default(),
+        );
+        assert!(matches!(
+            encoder.set_palette_histogram(&quantized_image.histogram),
+            Err(PngDecodeErrorCode::_53(_, _))
+        ));
+        encoder.set_palette(quantized_image.palette.clone());
+        encoder.set_transparency(quantized_image.transparency.clone().unwrap());
+        encoder
+            .set_palette_histogram(&quantized_image.histogram)
+            .unwrap();
+        encoder.add_suggested_palette(&suggested_palette).unwrap();
+        encoder.add_suggested_palette(&wide_palette).unwrap();
+        assert!(matches!(
+            encoder.add_suggested_palette(&suggested_palette),
+            Err(PngDecodeErrorCode::_53(_, _))
+        ));
+        let decoded_image = decode_bytes(
+            encoder.encode(&quantized_image.pixels).unwrap(),
+            PixelFormat::Rgba8,
+        );
+        assert!(decoded_image.errors.is_empty());
+        assert_eq!(
+            decoded_image.pixels,
+            rgba8_pixels
+        );
+        assert_eq!(
+            decoded_image.metadata.palette_histogram,
+            Some(quantized_image.histogram)
+        );
+        assert_eq!(
+            decoded_image.metadata.suggested_palettes,
+            [suggested_palette, wide_palette.clone()]
+        );
+
+        // the palette isn't replaced by reduction, so that the histogram still matches it
+        let mut encoder = PngEncoder::new(
+            1,
+            1,
+            ColorType::IndexedColor,
+            8,
+            &core::encoder::PngEncoderOptions {
+                reduce: true,
+                ..Default::default()
+            },
+        );
+        encoder.set_palette(vec![10, 20, 30, 40, 50, 60]);
+        encoder.set_palette_histogram(&[0, 65535]).unwrap();
+        let decoded_image = decode_bytes(
+            encoder.encode(&[1]).unwrap(),
+            PixelFormat::Rgba8,
+        );
+        assert!(decoded_image.errors.is_empty());
+        assert_eq!(
+            decoded_image.header.bit_depth,
+            8
+        );
+        assert_eq!(
+            decoded_image.metadata.palette_histogram,
+            Some(vec![0, 65535])
+        );
+
+        // values that can't be stored are rejected when they are set
+        let invalid_palettes = [
+            SuggestedPalette {
+                name: " leading space".to_string(),
+                sample_depth: 8,
+                entries: vec![],
+            },
+            SuggestedPalette {
+                name: "ok".to_string(),
+                sample_depth: 4,
+                entries: vec![],
+            },
+            SuggestedPalette {
+                name: "ok".to_string(),
+                sample_depth: 8,
+                entries: wide_palette.entries.clone(),
+            },
+        ];
+        for invalid_palette in invalid_palettes {
+            assert!(matches!(
+                encoder.add_suggested_palette(&invalid_palette),
+                Err(PngDecodeErrorCode::_53(_, _))
+            ));
+        }
+        assert!(matches!(
+            encoder.set_palette_histogram(&[1, 2, 3]),
+            Err(PngDecodeErrorCode::_53(_, _))
+        ));
+    }
+
+    #[test]
+    fn invalid_palette_chunks_test() {
+        use core::{
+            encoder::ChunkPosition::{AfterImageData, BeforeImageData, BeforePalette},
+            errors::PngDecodeErrorCode,
+        };
+
+        let splt_chunk = |name: &str, sample_depth: u8, entry_bytes: usize| {
+            [name.as_bytes(), &[0, sample_depth], &vec![0; entry_bytes]].concat()
+        };
+        let cases: Vec<(
+            _,
+            fn(&PngDecodeErrorCode) -> bool,
+        )> = vec![
+            (
+                vec![(
+                    "sPLT",
+                    splt_chunk("", 8, 6),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sPLT",
+                    b"no separator".to_vec(),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sPLT",
+                    b"name\x00".to_vec(),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sPLT",
+                    splt_chunk("name", 4, 6),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sPLT",
+                    splt_chunk("name", 8, 10),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sPLT",
+                    splt_chunk("name", 16, 6),
+                    BeforePalette,
+                )],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "sPLT",
+                    splt_chunk("name", 8, 6),
+                    AfterImageData,
+                )],
+                is_misplaced,
+            ),
+            (
+                vec![
+                    (
+                        "sPLT",
+                        splt_chunk("name", 8, 6),
+                        BeforePalette,
+                    ),
+                    (
+                        "sPLT",
+                        splt_chunk("name", 16, 10),
+                        BeforeImageData,
+                    ),
+                ],
+                is_invalid_data,
+            ),
+            (
+                vec![(
+                    "hIST",
+                    vec![0, 1, 0, 2],
+                    BeforeImageData,
+                )],
+                is_wrong_length,
+            ),
+            (
+                vec![(
+                    "hIST",
+                    vec![0, 1],
+                    BeforePalette,
+                )],
+                is_misplaced,
+            ),
+            (
+                vec![(
+                    "hIST",
+                    vec![0, 1],
+                    AfterImageData,
+                )],
+                is_misplaced,
+            ),
+            (
+                vec![
+                    (
+                        "hIST",
+                        vec![0, 1],
+                        BeforeImageData,
+                    ),
+                    (
+                        "hIST",
+                        vec![0, 2],
+                        BeforeImageData,
+                    ),
+                ],
+                is_duplicate,
+            ),
+        ];
+        for (chunks, is_expected_code) in cases {
+            let png_bytes = encode_with_ancillary_chunks(&chunks);
+            let decoded_image = decode_with_chunk_error(png_bytes, is_expected_code);
+            // only the first of repeated chunks is used, and invalid chunks are ignored
+            let metadata = decoded_image.metadata;
+            let is_first_used = chunks.len() == 2;
+            assert_eq!(
+                metadata.suggested_palettes.len(),
+                (is_first_used && chunks[0].0 == "sPLT") as usize
+            );
+            assert_eq!(
+                metadata.palette_histogram,
+                (is_first_used && chunks[0].0 == "hIST").then(|| vec![1])
+            );
+        }
+
+        // suggested palettes with different names are all kept, with sPLT chunks allowed on either side of PLTE chunk
+        let png_bytes = encode_with_ancillary_chunks(&[
+            (
+                "sPLT",
+                splt_chunk("first", 8, 12),
+                BeforePalette,
+            ),
+            (
+                "sPLT",
+                splt_chunk("second", 16, 0),
+                BeforeImageData,
+            ),
+        ]);
+        let decoded_image = decode_with_limits(png_bytes, Default::default()).unwrap();
+        assert!(decoded_image.errors.is_empty());
+        let names: Vec<&str> = decoded_image
+            .metadata
+            .suggested_palettes
+            .iter()
+            .map(|palette| palette.name.as_str())
+            .collect();
+        assert_eq!(names, ["first", "second"]);
+        assert_eq!(
+            decoded_image.metadata.suggested_palettes[0].entries.len(),
+            2
+        );
+    }
 }
@@ -0,0 +1,34 @@
+//! Run with `cargo fuzz run decode` from the repository root.
+//! Malformed input must only ever make the decoders return an error.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use png_core::{byte_reader, decoder, push_decoder};
+
+fuzz_target!(|data: &[u8]| {
+    // keeps corrupt dimensions from allocating too much
+    let decoder_options = decoder::PngDecoderOptions {
+        max_output_bytes: 1 << 24,
+        max_inflated_bytes: 1 << 26,
+        ..Default::default()
+    };
+
+    let mut byte_reader = byte_reader::ByteReader::new(
+        None,
+        byte_reader::ByteReaderMode::RAW,
+        Some(data.to_vec()),
+    );
+    let _ = decoder::PngDecoder::new(
+        &mut byte_reader,
+        &decoder_options,
+    )
+    .run();
+
+    let mut push_decoder = push_decoder::PngPushDecoder::new(&decoder_options);
+    for piece in data.chunks(1 + data.first().copied().unwrap_or(0) as usize) {
+        if push_decoder.feed(piece).is_err() {
+            break;
+        }
+        while push_decoder.next_row().is_some() {}
+    }
+});
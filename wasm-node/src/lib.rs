@@ -1,4 +1,4 @@
-use core::{byte_reader, decoder};
+use core::{byte_reader, decoder, errors};
 use js_sys::Uint8Array;
 
 use wasm_bindgen::prelude::*;
@@ -7,7 +7,7 @@ use wasm_bindgen::prelude::*;
 pub fn decode_raw_bytes(
     raw_bytes: Vec<u8>,
     decoder_options: &decoder::PngDecoderOptions,
-) -> Uint8Array {
+) -> Result<Uint8Array, JsValue> {
     let mut byte_reader = byte_reader::ByteReader::new(
         None,
         byte_reader::ByteReaderMode::RAW,
@@ -17,5 +17,11 @@ pub fn decode_raw_bytes(
         &mut byte_reader,
         decoder_options,
     );
-    return decoder.run().unwrap().as_slice().into();
+    match decoder.run() {
+        Ok(decoded_image) => Ok(decoded_image.pixels.as_slice().into()),
+        // thrown as an exception on the JS side
+        Err(code) => Err(JsValue::from_str(
+            &errors::PngDecodeError::new(code, 0).to_string(),
+        )),
+    }
 }